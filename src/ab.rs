@@ -0,0 +1,118 @@
+//! In-process A/B comparison of two execution engines: the core (`Machine`) and the
+//! reference interpreter. Both run the same ROM with the same input, one frame at a time;
+//! each engine is timed on its own and their states are compared after every frame. This
+//! keeps optimizations of the core honest: a change that makes it faster has to leave the
+//! results alone.
+//!
+//! ```
+//! use chip8::ab;
+//! use chip8::machine::Machine;
+//!
+//! let mut machine = Machine::new();
+//! // V0 += 1, loop forever
+//! machine.load_rom(&[0x70, 0x01, 0x12, 0x00]);
+//!
+//! let report = ab::compare(&mut machine, 10, None).unwrap();
+//! assert_eq!(report.frames, 10);
+//! assert_eq!(report.divergence, None);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::hardware::{Error, RAM_SIZE};
+use crate::machine::Machine;
+use crate::macros::Playback;
+use crate::reference::{Divergence, Reference};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Report {
+    // frames both engines ran
+    pub frames: u64,
+    // time spent in each engine
+    pub core: Duration,
+    pub reference: Duration,
+    // the first frame after which the states differed, if any. `pc` and `opcode` are the
+    // last instruction of that frame, the difference may have started earlier in it
+    pub divergence: Option<Divergence>,
+}
+
+impl Report {
+    /**
+     * The report as JSON, times in seconds, `divergence` is `null` when the states agreed.
+     */
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\n  \"frames\": {},\n  \"core_secs\": {:.6},\n  \"reference_secs\": {:.6},\n  \"divergence\": {}\n}}\n",
+            self.frames,
+            self.core.as_secs_f64(),
+            self.reference.as_secs_f64(),
+            self.divergence.as_ref().map_or("null".to_string(), Divergence::to_json),
+        )
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let per_frame = |d: Duration| d.as_secs_f64() * 1e6 / self.frames.max(1) as f64;
+        writeln!(f, "{} frames", self.frames)?;
+        writeln!(f, "core:      {:>10.3} ms, {:.2} us/frame", self.core.as_secs_f64() * 1e3, per_frame(self.core))?;
+        writeln!(f, "reference: {:>10.3} ms, {:.2} us/frame", self.reference.as_secs_f64() * 1e3, per_frame(self.reference))?;
+        match &self.divergence {
+            Some(divergence) => write!(f, "diverged: {}", divergence),
+            None => write!(f, "no divergence"),
+        }
+    }
+}
+
+/**
+ * Run `machine` and a `Reference` copied from it for up to `frames` frames, feeding both
+ * the keys from `playback`. Stops early when the program exits or the states differ.
+ */
+pub fn compare(machine: &mut Machine, frames: u64, mut playback: Option<Playback>) -> Result<Report, Error> {
+    let mut reference = Reference::from_chip8(&machine.chip8);
+    let start_frame = machine.frame();
+    let mut report = Report { frames: 0, core: Duration::ZERO, reference: Duration::ZERO, divergence: None };
+    // what `CXNN` drew in the core, handed to the reference in the same order
+    let mut random = VecDeque::new();
+
+    while machine.frame() - start_frame < frames && !machine.chip8.exited() {
+        if playback.as_mut().is_some_and(|p| !p.apply(machine)) {
+            playback = None;
+        }
+        reference.keys = machine.chip8.keys;
+
+        let started = Instant::now();
+        let mut steps = 0;
+        loop {
+            let op = machine.chip8.get_ram()[machine.chip8.pc % RAM_SIZE];
+            let frame_done = machine.step()?;
+            steps += 1;
+            if op >> 4 == 0xC {
+                random.push_back(machine.chip8.v[(op & 0xF) as usize]);
+            }
+            if frame_done {
+                break;
+            }
+        }
+        report.core += started.elapsed();
+
+        let started = Instant::now();
+        for _ in 0..steps {
+            let value = if reference.ram[reference.pc % RAM_SIZE] >> 4 == 0xC { random.pop_front().unwrap_or(0) } else { 0 };
+            reference.step(value);
+        }
+        reference.tick_timers();
+        report.reference += started.elapsed();
+
+        if let Some((what, core, reference)) = reference.compare(&machine.chip8) {
+            let (pc, opcode) = machine.history.iter().last().unwrap_or_default();
+            report.divergence = Some(Divergence { frame: machine.frame(), pc, opcode, what, core, reference });
+            break;
+        }
+    }
+
+    report.frames = machine.frame() - start_frame;
+    Ok(report)
+}
@@ -0,0 +1,198 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use chip8::text;
+
+use crate::i18n::Lang;
+
+/**
+ * Something the user can have the emulator do, from a hotkey or the command palette. The
+ * frontend runs them all in one place, whichever way they were asked for.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    NextPalette,
+    PaletteCycle,
+    Grid,
+    DebugOverlay,
+    Keypad,
+    BeepFlash,
+    // switch the running program to the next platform's quirks, see `Platform::after`
+    NextQuirks,
+    // the last warnings and errors, see `log_file::Log::recent`
+    Messages,
+    // brightness, contrast and gamma, see `picture_menu::PictureMenu`
+    Picture,
+    // reveal sprites row by row, see `slow_draw::SlowDraw`
+    SlowDraw,
+    SaveState,
+    LoadState,
+    // pause or continue, like the debugger's `pause` and `continue`
+    Pause,
+    Step,
+    // run a call to completion, or until the current subroutine returns
+    StepOver,
+    StepOut,
+    Reset,
+    // ask for each keypad key's host key or button, see `keymap::RemapWizard`
+    RemapKeys,
+    // list the connected controllers and change their player numbers, see `controllers::ControllerMenu`
+    Controllers,
+    // print the frame and `savestate::checksum`, for verifying runs
+    StateChecksum,
+    // the egui debugger window, see `debug_ui`
+    #[cfg(feature = "gui-debug")]
+    Debugger,
+    Quit,
+}
+
+// every action with its message id and hotkey, in the order the command palette lists them
+pub const ACTIONS: &[(Action, &str, Option<Keycode>)] = &[
+    (Action::SaveState, "action.save_state", Some(Keycode::F9)),
+    (Action::LoadState, "action.load_state", Some(Keycode::F10)),
+    (Action::NextPalette, "action.next_palette", Some(Keycode::F2)),
+    (Action::PaletteCycle, "action.palette_cycle", Some(Keycode::F3)),
+    (Action::Grid, "action.grid", Some(Keycode::F1)),
+    (Action::DebugOverlay, "action.debug_overlay", Some(Keycode::F4)),
+    (Action::Keypad, "action.keypad", Some(Keycode::F11)),
+    (Action::BeepFlash, "action.beep_flash", None),
+    (Action::NextQuirks, "action.next_quirks", None),
+    (Action::Messages, "action.messages", None),
+    (Action::Picture, "action.picture", None),
+    (Action::SlowDraw, "action.slow_draw", None),
+    (Action::Pause, "action.pause", None),
+    (Action::Step, "action.step", None),
+    (Action::StepOver, "action.step_over", None),
+    (Action::StepOut, "action.step_out", None),
+    (Action::Reset, "action.reset", None),
+    (Action::RemapKeys, "action.remap_keys", None),
+    (Action::Controllers, "action.controllers", None),
+    (Action::StateChecksum, "action.state_checksum", Some(Keycode::F12)),
+    #[cfg(feature = "gui-debug")]
+    (Action::Debugger, "action.debugger", None),
+    (Action::Quit, "action.quit", None),
+];
+
+// matches the command palette shows at once
+const PALETTE_ROWS: usize = 8;
+
+impl Action {
+    pub fn for_key(keycode: Keycode) -> Option<Action> {
+        ACTIONS.iter().find(|(_, _, key)| *key == Some(keycode)).map(|(action, _, _)| *action)
+    }
+}
+
+/**
+ * How well `query` matches `name`: its characters have to appear in `name` in order, ignoring
+ * case. Matches at the start of a word and runs of consecutive characters score higher.
+ * `None` if it doesn't match at all.
+ */
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut last: Option<usize> = None;
+
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = next + name[next..].iter().position(|n| *n == c)?;
+        score += 1;
+        if found == 0 || !name[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        if last.is_some_and(|last| last + 1 == found) {
+            score += 2;
+        }
+        last = Some(found);
+        next = found + 1;
+    }
+
+    Some(score)
+}
+
+/**
+ * What the command palette wants after an event.
+ */
+pub enum PaletteInput {
+    Typing,
+    Close,
+    Run(Action),
+}
+
+/**
+ * Ctrl+P: type part of an action's name, pick one of the matches with the arrow keys and
+ * run it with Enter.
+ */
+#[derive(Default)]
+pub struct CommandPalette {
+    query: String,
+    selected: usize,
+}
+
+impl CommandPalette {
+    /**
+     * Actions matching what was typed, the best match first. All of them for an empty query.
+     * Names are matched as the overlay shows them, so `ä` and `ae` both find "nächste".
+     */
+    pub fn matches(&self, lang: Lang) -> Vec<Action> {
+        let query = text::spell_out(&self.query);
+        let mut scored: Vec<(i32, usize, Action)> = ACTIONS
+            .iter()
+            .enumerate()
+            .filter_map(|(n, (action, id, _))| fuzzy_score(&query, &text::spell_out(lang.tr(id))).map(|score| (score, n, *action)))
+            .collect();
+        // ties keep the order of `ACTIONS`
+        scored.sort_by_key(|(score, n, _)| (-score, *n));
+        scored.into_iter().map(|(_, _, action)| action).collect()
+    }
+
+    pub fn handle(&mut self, event: &Event, lang: Lang) -> PaletteInput {
+        match event {
+            Event::TextInput { text, .. } => {
+                self.query.push_str(text);
+                self.selected = 0;
+            }
+            Event::KeyDown { keycode: Some(keycode), .. } => match keycode {
+                Keycode::Escape => return PaletteInput::Close,
+                Keycode::Return | Keycode::KpEnter => {
+                    return match self.matches(lang).get(self.selected) {
+                        Some(action) => PaletteInput::Run(*action),
+                        None => PaletteInput::Close,
+                    };
+                }
+                Keycode::Backspace => {
+                    self.query.pop();
+                    self.selected = 0;
+                }
+                Keycode::Up => self.selected = self.selected.saturating_sub(1),
+                Keycode::Down => {
+                    let count = self.matches(lang).len().min(PALETTE_ROWS);
+                    self.selected = (self.selected + 1).min(count.saturating_sub(1));
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        PaletteInput::Typing
+    }
+
+    /**
+     * The palette as overlay text: the query, then the best matches with their hotkeys.
+     */
+    pub fn text(&self, lang: Lang) -> String {
+        let mut out = format!("> {}_", self.query);
+        let matches = self.matches(lang);
+        if matches.is_empty() {
+            out.push('\n');
+            out.push_str(lang.tr("msg.no_action"));
+        }
+        for (n, action) in matches.iter().take(PALETTE_ROWS).enumerate() {
+            let Some((_, id, key)) = ACTIONS.iter().find(|(a, _, _)| a == action) else {
+                continue;
+            };
+            let marker = if n == self.selected { '>' } else { ' ' };
+            let key = key.map_or(String::new(), |key| format!("  [{}]", key.name()));
+            out.push_str(&format!("\n{} {}{}", marker, lang.tr(id), key));
+        }
+        out
+    }
+}
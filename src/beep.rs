@@ -0,0 +1,46 @@
+use std::time::{Duration, Instant};
+
+/**
+ * When the beep sounds, in real time. Beeps are scheduled when the program sets the sound
+ * timer rather than by sampling the timer once a frame, which misses a timer of 1 that runs
+ * out within the frame it was set in. Every beep lasts at least `floor`, since a tick or two
+ * is too short to be heard on most systems.
+ */
+pub struct BeepSchedule {
+    floor: Duration,
+    started: Instant,
+    until: Instant,
+}
+
+impl BeepSchedule {
+    pub fn new(floor: Duration) -> Self {
+        let now = Instant::now();
+        BeepSchedule { floor, started: now, until: now }
+    }
+
+    /**
+     * The program set the sound timer to last `duration`. Zero stops the beep, but not
+     * before it has sounded for `floor`.
+     */
+    pub fn request(&mut self, duration: Duration) {
+        let now = Instant::now();
+        if duration.is_zero() {
+            self.until = self.until.min(now.max(self.started + self.floor));
+        } else {
+            self.started = now;
+            self.until = now + duration.max(self.floor);
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        Instant::now() < self.until
+    }
+
+    /**
+     * How much longer the beep sounds, for the controller rumble.
+     */
+    #[cfg(feature = "gui")]
+    pub fn remaining(&self) -> Duration {
+        self.until.saturating_duration_since(Instant::now())
+    }
+}
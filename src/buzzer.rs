@@ -0,0 +1,219 @@
+#[cfg(any(feature = "audio", feature = "cpal"))]
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(any(feature = "audio", feature = "cpal"))]
+use std::sync::Arc;
+
+#[cfg(feature = "audio")]
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+use crate::config::AudioBackend;
+
+// pitch and loudness of the beep, the volume out of 1.0
+#[cfg(any(feature = "audio", feature = "cpal"))]
+const PITCH_HZ: f32 = 440.0;
+#[cfg(any(feature = "audio", feature = "cpal"))]
+const VOLUME: f32 = 0.25;
+
+/**
+ * Something that can play the beep. The frontend only turns it on and off, backends keep
+ * their device open for as long as they live.
+ */
+pub trait Buzzer {
+    fn set_beep(&mut self, on: bool);
+
+    /**
+     * Sample frames the device has asked for so far, `None` without a device. Grows in
+     * steps of the device's buffer size, every time it runs low.
+     */
+    fn samples(&self) -> Option<u64> {
+        None
+    }
+}
+
+/**
+ * What a backend shares with its audio callback.
+ */
+#[cfg(any(feature = "audio", feature = "cpal"))]
+#[derive(Default)]
+struct Gate {
+    // set from the emulation thread while the beep sounds
+    on: AtomicBool,
+    // sample frames the callback has filled in
+    samples: AtomicU64,
+}
+
+/**
+ * Open `backend`. SDL audio needs the window's SDL context, see `open_sdl`.
+ */
+pub fn open(backend: AudioBackend) -> Result<Box<dyn Buzzer>, String> {
+    match backend {
+        #[cfg(feature = "cpal")]
+        AudioBackend::Cpal => {
+            let gate = Arc::new(Gate::default());
+            Ok(Box::new(CpalBuzzer { stream: open_cpal(gate.clone())?, gate }))
+        }
+        AudioBackend::Off => Ok(Box::new(Silent)),
+        _ => Err(format!("audio backend {:?} isn't available here", backend)),
+    }
+}
+
+/**
+ * Open SDL audio on `context`, the context of the window the beep belongs to.
+ */
+#[cfg(feature = "audio")]
+pub fn open_sdl(context: &sdl2::Sdl) -> Result<Box<dyn Buzzer>, String> {
+    let gate = Arc::new(Gate::default());
+    Ok(Box::new(SdlBuzzer { device: open_sdl_device(context, gate.clone())?, gate }))
+}
+
+/**
+ * Square wave that keeps running whether or not it is audible, so the pitch and duty cycle
+ * don't depend on how often (or how slowly) the emulator toggles the beep.
+ */
+#[cfg(any(feature = "audio", feature = "cpal"))]
+struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    gate: Arc<Gate>,
+}
+
+#[cfg(any(feature = "audio", feature = "cpal"))]
+impl SquareWave {
+    fn new(sample_rate: u32, gate: Arc<Gate>) -> Self {
+        SquareWave { phase_inc: PITCH_HZ / sample_rate as f32, phase: 0.0, gate }
+    }
+
+    /**
+     * Fill `out` with frames of `channels` samples each, every channel the same.
+     */
+    fn fill(&mut self, out: &mut [f32], channels: usize) {
+        let volume = if self.gate.on.load(Ordering::Relaxed) { VOLUME } else { 0.0 };
+        for frame in out.chunks_mut(channels) {
+            frame.fill(if self.phase <= 0.5 { volume } else { -volume });
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+        self.gate.samples.fetch_add(out.len().div_ceil(channels) as u64, Ordering::Relaxed);
+    }
+}
+
+// `Off`, for systems without a working audio device
+struct Silent;
+
+impl Buzzer for Silent {
+    fn set_beep(&mut self, _on: bool) {}
+}
+
+#[cfg(feature = "audio")]
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.fill(out, 1);
+    }
+}
+
+#[cfg(feature = "audio")]
+struct SdlBuzzer {
+    // only held so the device (and its callback) stays open
+    #[allow(dead_code)]
+    device: AudioDevice<SquareWave>,
+    gate: Arc<Gate>,
+}
+
+#[cfg(feature = "audio")]
+impl Buzzer for SdlBuzzer {
+    fn set_beep(&mut self, on: bool) {
+        self.gate.on.store(on, Ordering::Relaxed);
+    }
+
+    fn samples(&self) -> Option<u64> {
+        Some(self.gate.samples.load(Ordering::Relaxed))
+    }
+}
+
+/**
+ * Open the default playback device with a mono square wave, silent until `gate` is set.
+ */
+#[cfg(feature = "audio")]
+fn open_sdl_device(context: &sdl2::Sdl, gate: Arc<Gate>) -> Result<AudioDevice<SquareWave>, String> {
+    let audio_subsystem = context.audio()?;
+
+    let desired_spec = AudioSpecDesired {
+        freq: Some(44100),
+        channels: Some(1),  // mono
+        samples: None       // default sample size
+    };
+
+    let device = audio_subsystem.open_playback(None, &desired_spec, |spec| SquareWave::new(spec.freq as u32, gate))?;
+    device.resume();
+
+    Ok(device)
+}
+
+#[cfg(feature = "cpal")]
+struct CpalBuzzer {
+    // only held so the stream keeps playing
+    #[allow(dead_code)]
+    stream: cpal::Stream,
+    gate: Arc<Gate>,
+}
+
+#[cfg(feature = "cpal")]
+impl Buzzer for CpalBuzzer {
+    fn set_beep(&mut self, on: bool) {
+        self.gate.on.store(on, Ordering::Relaxed);
+    }
+
+    fn samples(&self) -> Option<u64> {
+        Some(self.gate.samples.load(Ordering::Relaxed))
+    }
+}
+
+/**
+ * Open the default output device of the default host in its own format, playing the square
+ * wave on every channel.
+ */
+#[cfg(feature = "cpal")]
+fn open_cpal(gate: Arc<Gate>) -> Result<cpal::Stream, String> {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+    let device = cpal::default_host().default_output_device().ok_or("no audio output device")?;
+    let supported = device.default_output_config().map_err(|e| e.to_string())?;
+    let format = supported.sample_format();
+    let config: cpal::StreamConfig = supported.into();
+    let wave = SquareWave::new(config.sample_rate.0, gate);
+
+    let stream = match format {
+        cpal::SampleFormat::F32 => cpal_stream::<f32>(&device, &config, wave),
+        cpal::SampleFormat::I16 => cpal_stream::<i16>(&device, &config, wave),
+        cpal::SampleFormat::U16 => cpal_stream::<u16>(&device, &config, wave),
+        _ => return Err(format!("unsupported sample format {}", format)),
+    }
+    .map_err(|e| e.to_string())?;
+    stream.play().map_err(|e| e.to_string())?;
+
+    Ok(stream)
+}
+
+#[cfg(feature = "cpal")]
+fn cpal_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig, mut wave: SquareWave) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    use cpal::traits::DeviceTrait;
+
+    let channels = config.channels as usize;
+    let mut buffer = Vec::new();
+    device.build_output_stream(
+        config,
+        move |out: &mut [T], _| {
+            buffer.resize(out.len(), 0.0);
+            wave.fill(&mut buffer, channels);
+            for (sample, value) in out.iter_mut().zip(&buffer) {
+                *sample = T::from_sample(*value);
+            }
+        },
+        |e| eprintln!("audio error: {}", e),
+        None,
+    )
+}
@@ -0,0 +1,85 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use chip8::machine::Machine;
+use chip8::patch;
+
+use crate::crash_report;
+
+// first line of a new report file
+const HEADER: &str = "# rust8 compatibility report: version crc32 bytes profile result frames faults\n";
+
+/**
+ * How a run of a ROM went, for `--compat-report`. Nothing leaves the machine: the report is
+ * a local file the user can look over and send in by hand, one line per run:
+ *
+ * ```text
+ * 0.1.0 0a1b2c3d 246 default pass 3600 -
+ * 0.1.0 0a1b2c3d 246 strict fail 12 illegal opcode 00FD at 2F0
+ * ```
+ *
+ * It names the ROM only by checksum and size, never by file name.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub crc: u32,
+    pub size: usize,
+    // behavior the core ran with, see `crash_report::profile`
+    pub profile: &'static str,
+    pub passed: bool,
+    pub frames: u64,
+    // every fault hit, including the ones skipped past in the debugger
+    pub faults: Vec<String>,
+}
+
+impl Entry {
+    /**
+     * The entry for a run of `rom` that ended with `result`. A run passes if it ended
+     * without an error and never faulted.
+     */
+    pub fn new(rom: &[u8], machine: &Machine, faults: &[String], result: &Result<(), String>) -> Entry {
+        let mut faults = faults.to_vec();
+        // errors that aren't faults, like a failed `check` or a divergence
+        if let (Err(e), true) = (result, faults.is_empty()) {
+            faults.push(e.clone());
+        }
+
+        Entry {
+            crc: patch::crc32(rom),
+            size: rom.len(),
+            profile: crash_report::profile(&machine.chip8),
+            passed: faults.is_empty(),
+            frames: machine.frame(),
+            faults,
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        let faults = if self.faults.is_empty() { "-".to_string() } else { self.faults.join("; ") };
+        format!(
+            "{} {:08x} {} {} {} {} {}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.crc,
+            self.size,
+            self.profile,
+            if self.passed { "pass" } else { "fail" },
+            self.frames,
+            faults.replace('\n', " "),
+        )
+    }
+
+    /**
+     * Add the entry to the report at `path`, starting one if there is none yet.
+     */
+    pub fn append(&self, path: &str) -> Result<(), String> {
+        let new = !Path::new(path).exists();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("can't open compatibility report {}: {}", path, e))?;
+        let text = if new { format!("{}{}", HEADER, self.to_line()) } else { self.to_line() };
+        file.write_all(text.as_bytes()).map_err(|e| format!("can't write compatibility report {}: {}", path, e))
+    }
+}
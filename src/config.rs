@@ -0,0 +1,868 @@
+use chip8::hardware::KEY_COUNT;
+use chip8::palette::{Palette, Picture, PRESETS};
+use chip8::platform::Platform;
+use chip8::quirks::Quirks;
+use chip8::random::{Algorithm, ALGORITHMS};
+
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::thread;
+
+use crate::crash_report;
+use crate::i18n::{Lang, LANGS};
+use crate::paths;
+use crate::play_log;
+use crate::recent;
+use crate::routing::Routing;
+use crate::rom_settings;
+use crate::save_ram;
+
+pub const REFRESH_NTSC: f64 = 60.0;
+pub const REFRESH_PAL: f64 = 50.0;
+
+// frames to run in headless mode when --frames isn't given, 10 seconds at 60Hz
+pub const HEADLESS_FRAMES: u64 = 600;
+
+pub const MACRO_DIR: &str = "macros";
+pub const DEBUG_DIR: &str = "debug";
+pub const KEYS_FILE: &str = "keys.txt";
+pub const THUMBNAIL_DIR: &str = "thumbnails";
+// frames run before a ROM's thumbnail is taken, 2 seconds at 60Hz. by then most ROMs show
+// their title screen
+pub const THUMBNAIL_FRAME: u64 = 120;
+
+pub const SOAK_HOURS: f64 = 1.0;
+// most ROMs `split` runs at once
+pub const SPLIT_MAX: usize = 4;
+
+// shortest beep, a sound timer of 1 (17ms) is barely a click
+pub const MIN_BEEP_MS: f64 = 50.0;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Command {
+    // run the ROM, windowed or headless
+    Run,
+    // run the core in lockstep with the reference interpreter, report the first divergence
+    Verify,
+    // time the core against the reference interpreter, see `chip8::ab`
+    Ab,
+    // compare two trace files and report the first mismatch
+    CompareTrace(String, String),
+    // run a test ROM headless and report the result screen, see `headless::check`
+    Check,
+    // run many headless instances of a ROM for a long time, see `soak::run`
+    Soak(String),
+    // run several ROMs at once in one window, see `split_screen::run`
+    Split(Vec<String>),
+    // print a ROM's disassembly, see `chip8::disasm::analyze`
+    Disasm(String),
+    // print a save state's contents, see `state_tool::dump`
+    StateDump(String),
+    // write a save state's display as a PNG, see `state_tool::extract_vram`
+    StateExtractVram(String),
+    // strip a ROM's trailing zeros and guess where it starts, see `rom_tool::Normalized`
+    RomNormalize(String),
+    // write a ROM that tests an opcode family, see `chip8::tutorial`
+    RomTutorial(String),
+    // make a palette from the colors of a screenshot, see `palette_tool::extract`
+    PaletteExtract(String),
+    // list how long each ROM has been played, see `play_log`
+    PlayTime,
+    // list the favorite and recently started ROMs, see `recent`
+    Recent,
+    // mark a ROM file as a favorite or unmark it
+    Favorite(String),
+}
+
+/**
+ * Output format of the tools: `state dump`, `rom normalize`, `palette extract`, `disasm`,
+ * `ab`, `verify` and headless runs.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+/**
+ * What draws the window contents, see `display`. Defaults to SDL, or to minifb when that
+ * is the only frontend built in.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderBackend {
+    // the SDL2 canvas
+    Sdl,
+    // wgpu, the `wgpu` feature: HiDPI output and a CRT filter
+    Wgpu,
+    // the minifb frontend, the `minifb` feature: no SDL at all, see `minifb_frontend`
+    Minifb,
+}
+
+// names accepted by --backend
+pub const RENDER_BACKENDS: [(&str, RenderBackend); 3] =
+    [("sdl", RenderBackend::Sdl), ("wgpu", RenderBackend::Wgpu), ("minifb", RenderBackend::Minifb)];
+
+impl RenderBackend {
+    pub fn by_name(name: &str) -> Result<RenderBackend, String> {
+        let backend = RENDER_BACKENDS.iter().find(|(n, _)| *n == name).map(|(_, b)| *b).ok_or_else(|| {
+            let names: Vec<&str> = RENDER_BACKENDS.iter().map(|(n, _)| *n).collect();
+            format!("unknown backend '{}', expected one of: {}", name, names.join(", "))
+        })?;
+        let built = match backend {
+            RenderBackend::Sdl => cfg!(feature = "gui"),
+            RenderBackend::Wgpu => cfg!(feature = "wgpu"),
+            RenderBackend::Minifb => cfg!(feature = "minifb"),
+        };
+        if !built {
+            let feature = if backend == RenderBackend::Sdl { "gui" } else { name };
+            return Err(format!("backend '{}' isn't built in, rebuild with --features {}", name, feature));
+        }
+        Ok(backend)
+    }
+}
+
+impl Default for RenderBackend {
+    fn default() -> Self {
+        if cfg!(feature = "minifb") && !cfg!(feature = "gui") {
+            RenderBackend::Minifb
+        } else {
+            RenderBackend::Sdl
+        }
+    }
+}
+
+/**
+ * What plays the beep, see `buzzer`. Defaults to the first one built in.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AudioBackend {
+    // SDL2 audio, the `audio` feature
+    Sdl,
+    // cpal, the `cpal` feature, for systems where SDL audio doesn't work
+    Cpal,
+    // no sound, the beep flash and rumble still work
+    Off,
+}
+
+// names accepted by --audio
+pub const AUDIO_BACKENDS: [(&str, AudioBackend); 3] =
+    [("sdl", AudioBackend::Sdl), ("cpal", AudioBackend::Cpal), ("off", AudioBackend::Off)];
+
+impl AudioBackend {
+    pub fn by_name(name: &str) -> Result<AudioBackend, String> {
+        let backend = AUDIO_BACKENDS.iter().find(|(n, _)| *n == name).map(|(_, b)| *b).ok_or_else(|| {
+            let names: Vec<&str> = AUDIO_BACKENDS.iter().map(|(n, _)| *n).collect();
+            format!("unknown audio backend '{}', expected one of: {}", name, names.join(", "))
+        })?;
+        if !backend.is_built() {
+            return Err(format!("audio backend '{}' isn't built in, rebuild with --features {}", name, backend.feature()));
+        }
+        Ok(backend)
+    }
+
+    pub fn is_built(self) -> bool {
+        match self {
+            AudioBackend::Sdl => cfg!(feature = "audio"),
+            AudioBackend::Cpal => cfg!(feature = "cpal"),
+            AudioBackend::Off => true,
+        }
+    }
+
+    fn feature(self) -> &'static str {
+        match self {
+            AudioBackend::Sdl => "audio",
+            AudioBackend::Cpal => "cpal",
+            AudioBackend::Off => "",
+        }
+    }
+}
+
+impl Default for AudioBackend {
+    fn default() -> Self {
+        [AudioBackend::Sdl, AudioBackend::Cpal].into_iter().find(|b| b.is_built()).unwrap_or(AudioBackend::Off)
+    }
+}
+
+/**
+ * How the emulated screen is scaled up to the window.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Scaling {
+    // largest whole multiple that fits, every pixel the same size
+    #[default]
+    PixelPerfect,
+    // as large as fits while keeping the aspect ratio, pixels may differ by one
+    Fit,
+    // fill the window, for variants like 64x48 whose pixels weren't square
+    Stretch,
+}
+
+// names accepted by --scaling and in the ROM settings file
+pub const SCALINGS: [(&str, Scaling); 3] =
+    [("pixel-perfect", Scaling::PixelPerfect), ("fit", Scaling::Fit), ("stretch", Scaling::Stretch)];
+
+impl Scaling {
+    pub fn by_name(name: &str) -> Result<Scaling, String> {
+        SCALINGS.iter().find(|(n, _)| *n == name).map(|(_, s)| *s).ok_or_else(|| {
+            let names: Vec<&str> = SCALINGS.iter().map(|(n, _)| *n).collect();
+            format!("unknown scaling '{}', expected one of: {}", name, names.join(", "))
+        })
+    }
+}
+
+/**
+ * What an analog stick pushed diagonally does.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Diagonals {
+    // hold the keys for both directions
+    Both,
+    // only the direction the stick is pushed furthest in, for games that read one key at a time
+    Dominant,
+}
+
+/**
+ * Keypad keys an analog stick holds, see `input::stick_keys`.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StickMap {
+    pub up: usize,
+    pub down: usize,
+    pub left: usize,
+    pub right: usize,
+    // fraction of the stick's travel that is ignored around the center
+    pub deadzone: f64,
+    pub diagonals: Diagonals,
+}
+
+impl Default for StickMap {
+    // 2/8/4/6 are the arrows on the keypad, what most games use
+    fn default() -> Self {
+        StickMap { up: 0x2, down: 0x8, left: 0x4, right: 0x6, deadzone: 0.25, diagonals: Diagonals::Dominant }
+    }
+}
+
+pub struct Config {
+    pub command: Command,
+    // rate in Hz at which the timers tick and the display refreshes
+    pub refresh_rate: f64,
+    // emulation speed relative to the refresh rate, below 1 for slow motion
+    pub speed: f64,
+    // `--speed` was given, it wins over the ROM's settings
+    pub speed_given: bool,
+    // breakpoints set before the program starts, besides the ones `debug_session` restores
+    pub breakpoints: Vec<usize>,
+    // start with the debugger paused on the first instruction
+    pub paused: bool,
+    // pause at the start of the frame after this many, see `Debugger::execute`'s `runto-frame`
+    pub break_at_frame: Option<u64>,
+    // start from the state F9 saved for this ROM
+    pub load_state: bool,
+    // run without a window, forced when built without the `gui` feature
+    pub headless: bool,
+    // number of frames to run in headless mode
+    pub frames: u64,
+    pub palette: Palette,
+    // `--palette` was given, it wins over the colors in Octo's options
+    pub palette_given: bool,
+    // animate the background color, see `Render::palette_cycle`
+    pub palette_cycle: bool,
+    // brightness, contrast and gamma for the display, see `Picture`
+    pub picture: Picture,
+    // fade pixels out instead of clearing them, see `Render::phosphor`
+    pub phosphor: bool,
+    pub backend: RenderBackend,
+    pub audio: AudioBackend,
+    // shortest beep in milliseconds, however short the sound timer was set
+    pub min_beep_ms: f64,
+    // flash a border while the beep sounds, see `render::Layer::Flash`
+    pub beep_flash: bool,
+    // IPS/BPS patch applied to the ROM before loading
+    pub patch: Option<String>,
+    // open the sprite editor instead of running the ROM
+    pub sprite_editor: bool,
+    // reveal sprites row by row, see `slow_draw::SlowDraw`
+    pub slow_draw: bool,
+    // accept debugger commands on stdin
+    pub repl: bool,
+    // open the egui debugger window, see `debug_ui`
+    pub debug_ui: bool,
+    // write an execution trace to this file
+    pub trace: Option<String>,
+    // print every executed instruction to stdout
+    pub print_instructions: bool,
+    // directory input macros are stored in, one file per ROM
+    pub macro_dir: String,
+    // macro slot replayed by `ab`
+    pub replay: Option<usize>,
+    // dump performance counters to this file on exit
+    pub stats_json: Option<String>,
+    // write a row of timings per pass of the main loop to this file, see `Stats::log_frames`
+    pub frame_times: Option<String>,
+    // add how the run went to this file on exit, see `compat::Entry`
+    pub compat_report: Option<String>,
+    // write an annotated disassembly with execution counts to this file on exit
+    pub coverage: Option<String>,
+    // write the beep as a WAV file on exit, see `chip8::wav`
+    pub record_audio: Option<String>,
+    // enable non-standard hardware such as the real-time clock, off to keep ROMs honest
+    pub extensions: bool,
+    // most sprites drawn per frame, see `Machine::draw_budget`
+    pub draw_budget: Option<u32>,
+    // most instructions run per frame, see `Machine::cycles_per_frame`
+    pub cycles_per_frame: Option<u32>,
+    // instructions without drawing or waiting before a loop counts as stuck, see
+    // `Machine::runaway_limit`
+    pub runaway: Option<u64>,
+    // pause in the debugger when that happens
+    pub break_on_runaway: bool,
+    // how CXNN picks its numbers, see `chip8::random`
+    pub rng: Algorithm,
+    // fault on arithmetic the core would let wrap, see `Chip8::hardened`
+    pub harden: bool,
+    // only the original CHIP-8's instructions and behavior, see `Chip8::strict`
+    pub strict: bool,
+    // how the instructions interpreters disagree on behave, see `chip8::quirks`
+    pub quirks: Quirks,
+    // `--quirks` was given, it wins over the VIP's with `--strict`, the platform's and Octo's
+    // options
+    pub quirks_given: bool,
+    // sets `strict`, `xo_chip`, the quirks and the font together, see `chip8::platform`
+    pub platform: Option<Platform>,
+    // XO-CHIP's instructions, 64 KiB of RAM and second bitplane, see `Chip8::set_xo_chip`
+    pub xo_chip: bool,
+    // keep `save_ram::ADDR..+LEN` across sessions, in a file per ROM under `save_dir`
+    pub save_ram: bool,
+    pub save_dir: String,
+    // a PNG of the screen per ROM, taken at `thumbnail_frame` of its first run, 0 for never
+    pub thumbnail_dir: String,
+    pub thumbnail_frame: u64,
+    // extra glyphs for `check`, see `chip8::ocr`
+    pub glyphs: Option<String>,
+    // parallel instances and duration of a soak test
+    pub instances: usize,
+    pub hours: f64,
+    // append a leveled log of ROM loads, faults, saves and setting changes to this file
+    pub log_file: Option<String>,
+    // language of messages and overlays, defaults to the one set in the environment
+    pub lang: Lang,
+    // overrides the ROM's entry in `rom_settings`, pixel perfect if neither is set
+    pub scaling: Option<Scaling>,
+    // per-ROM settings file, see `rom_settings`
+    pub rom_settings: String,
+    // where a report is written when the program faults, see `crash_report`
+    pub crash_dir: String,
+    // where breakpoints are kept between runs, one file per ROM, see `debug_session`
+    pub debug_dir: String,
+    // launches and play time of each ROM, see `play_log`
+    pub play_log: String,
+    // ROM file given to `run`: a path, a number from the `recent` list or `--last`.
+    // without one the built-in ROM runs
+    pub rom: Option<String>,
+    // favorite and recently started ROMs, see `recent`
+    pub recent_file: String,
+    // host keys and controller buttons of the keypad, see `keymap::KeyMap`
+    pub keys_file: String,
+    // which device may press which keypad keys, for two players
+    pub routing: Routing,
+    pub stick: StickMap,
+    // `disasm` prints a Graphviz control flow graph instead of a listing
+    pub cfg: bool,
+    // output format of the tools, see `Format`
+    pub format: Format,
+    // where the `state`, `rom` and `palette` tools write to
+    pub out: Option<String>,
+    // `rom normalize` pads odd ROMs to an even length
+    pub pad: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            command: Command::Run,
+            refresh_rate: REFRESH_NTSC,
+            speed: 1.0,
+            speed_given: false,
+            breakpoints: Vec::new(),
+            paused: false,
+            break_at_frame: None,
+            load_state: false,
+            headless: !cfg!(feature = "gui") && !cfg!(feature = "minifb"),
+            frames: HEADLESS_FRAMES,
+            palette: Palette::default(),
+            palette_given: false,
+            palette_cycle: false,
+            picture: Picture::default(),
+            backend: RenderBackend::default(),
+            audio: AudioBackend::default(),
+            min_beep_ms: MIN_BEEP_MS,
+            beep_flash: false,
+            phosphor: false,
+            patch: None,
+            sprite_editor: false,
+            slow_draw: false,
+            repl: false,
+            debug_ui: false,
+            trace: None,
+            print_instructions: false,
+            macro_dir: paths::locate(paths::data_dir(), MACRO_DIR),
+            replay: None,
+            stats_json: None,
+            frame_times: None,
+            compat_report: None,
+            coverage: None,
+            record_audio: None,
+            extensions: false,
+            draw_budget: None,
+            cycles_per_frame: None,
+            runaway: None,
+            break_on_runaway: false,
+            rng: Algorithm::Host,
+            harden: false,
+            strict: false,
+            quirks: Quirks::default(),
+            quirks_given: false,
+            platform: None,
+            xo_chip: false,
+            save_ram: false,
+            save_dir: paths::locate(paths::data_dir(), save_ram::SAVE_DIR),
+            thumbnail_dir: paths::locate(paths::data_dir(), THUMBNAIL_DIR),
+            thumbnail_frame: THUMBNAIL_FRAME,
+            glyphs: None,
+            instances: thread::available_parallelism().map_or(1, |n| n.get()),
+            hours: SOAK_HOURS,
+            log_file: None,
+            lang: Lang::from_env(),
+            scaling: None,
+            rom_settings: paths::locate(paths::config_dir(), rom_settings::ROM_SETTINGS),
+            crash_dir: paths::locate(paths::data_dir(), crash_report::CRASH_DIR),
+            debug_dir: paths::locate(paths::config_dir(), DEBUG_DIR),
+            play_log: paths::locate(paths::data_dir(), play_log::PLAY_LOG),
+            rom: None,
+            recent_file: paths::locate(paths::data_dir(), recent::RECENT_FILE),
+            keys_file: paths::locate(paths::config_dir(), KEYS_FILE),
+            routing: Routing::default(),
+            stick: StickMap::default(),
+            cfg: false,
+            format: Format::Text,
+            out: None,
+            pad: false,
+        }
+    }
+}
+
+impl Config {
+    /**
+     * Build a config from command line arguments (without the program name).
+     * `verify`: check the core against the reference interpreter for `--frames` frames
+     * `ab`: time the core and the reference interpreter on the ROM, see `--replay`
+     * `compare-trace <a> <b>`: compare two trace files
+     * `check`: run a test ROM for `--frames` frames and report its result screen
+     * `soak <rom>`: endurance test, see `--instances` and `--hours`
+     * `split <rom>,<rom>[,..]`: run 2 to 4 ROMs side by side in one window
+     * `disasm [--cfg] <rom>`: print the ROM's disassembly, or its control flow graph
+     * `state dump <file>`: print a save state, see `--format` and `--out`
+     * `state extract-vram <file>`: write a save state's display to the PNG given by `--out`
+     * `rom normalize <rom>`: report on a ROM, and write it without trailing zeros to `--out`
+     * `rom tutorial <family>`: describe a ROM testing one of the `chip8::tutorial::FAMILIES`, write it to `--out`
+     * `palette extract <png>`: print a screenshot's colors as a palette, and write it to `--out`
+     * `playtime`: list the ROMs played in the window, with launches and play time
+     * `run <rom>|<n>|--last`: run a ROM file, the nth ROM `recent` lists or the last one run
+     * `recent`: list the favorite and recently run ROM files
+     * `favorite <rom>`: mark a ROM file as a favorite, or unmark it
+     * `--refresh <hz>`: timer/display rate in Hz
+     * `--pal`: shorthand for `--refresh 50`, for ROMs tuned on European VIPs
+     * `--ntsc`: shorthand for `--refresh 60` (default)
+     * `--speed <factor>`: run faster or slower than real time, e.g. 0.25 for slow motion
+     * `--break <addr>`: set a breakpoint before starting, can be given more than once
+     * `--paused`: start with the debugger paused
+     * `--break-at-frame <n>`: run n frames and pause, or stop there when headless
+     * `--load-state`: start from the state saved with F9
+     * `--headless`: run without a window and print the final screen
+     * `--frames <n>`: number of frames to run in headless mode
+     * `--palette <name>|<file>`: one of the `palette::PRESETS`, or a palette file
+     * `--palette-cycle`: slowly animate the background color
+     * `--brightness <b>`: brighten (up to 1) or darken (down to -1) the picture
+     * `--contrast <factor>`: more contrast above 1, less below, 0 to 4
+     * `--gamma <g>`: lift the dark colors above 1, deepen them below, 0.1 to 10
+     * `--backend <name>`: one of the `RENDER_BACKENDS`
+     * `--audio <backend>`: one of the `AUDIO_BACKENDS`
+     * `--min-beep <ms>`: shortest beep, so a sound timer of 1 or 2 can be heard
+     * `--beep-flash`: flash a border around the screen while the beep sounds
+     * `--patch <file>`: apply an IPS or BPS patch to the ROM
+     * `--sprite-editor`: open the sprite editor
+     * `--slow-draw`: draw sprites one row at a time, to see how they are built
+     * `--repl`: read debugger commands from stdin while running
+     * `--debug-ui`: open the debugger window (needs the `gui-debug` feature)
+     * `--trace <file>`: write an execution trace (see `chip8::trace`)
+     * `--print-instructions`: print every executed instruction to stdout
+     * `--macro-dir <dir>`: where input macros are stored (see `chip8::macros`)
+     * `--replay <slot>`: input macro `ab` feeds to both engines
+     * `--stats-json <file>`: write performance counters as JSON on exit
+     * `--frame-times <file>`: write per-frame timings as CSV while running
+     * `--compat-report <file>`: add how the run went to a compatibility report on exit
+     * `--coverage <file>`: write a coverage map on exit, see `chip8::coverage`
+     * `--record-audio <file>`: write the session's beep as a WAV file on exit
+     * `--extensions`: enable emulator-specific hardware, see `chip8::peripheral`
+     * `--draw-budget <n>`: draw at most n sprites per frame, for the VIP's flicker
+     * `--phosphor`: let pixels fade out, which smooths flicker
+     * `--cycles <n>`: run at most n instructions per frame
+     * `--runaway <n>`: slow down a program that runs n instructions without drawing or waiting
+     * `--break-on-runaway`: pause in the debugger instead, needs `--runaway`
+     * `--rng <name>`: one of the `random::ALGORITHMS` for CXNN
+     * `--harden`: stop on arithmetic overflow in the core instead of wrapping
+     * `--strict`: fault on instructions the original CHIP-8 didn't have
+     * `--quirks <list>`: the quirks that are on, comma-separated, see `chip8::quirks`
+     * `--platform <name>`: run as `vip`, `schip` or `xochip`, see `chip8::platform`
+     * `--xo-chip`: run XO-CHIP programs, with 64 KiB of RAM and two bitplanes
+     * `--save-ram`: persist 0xE00-0xEFF across sessions
+     * `--save-dir <dir>`: where save RAM is stored
+     * `--thumbnail-dir <dir>`: where ROM thumbnails are stored
+     * `--thumbnail-frame <n>`: frame of a ROM's first run its thumbnail is taken at, 0 for never
+     * `--glyphs <file>`: extra glyphs `check` should recognize
+     * `--instances <n>`: parallel instances for `soak`, defaults to the number of CPUs
+     * `--hours <h>`: how long to soak for
+     * `--log-file <file>`: keep a log for diagnosing issues later, see `log_file::Log`
+     * `--lang <code>`: language of messages and overlays, one of the `i18n::LANGS`
+     * `--scaling <mode>`: how the screen fills the window, one of the `SCALINGS`
+     * `--rom-settings <file>`: per-ROM settings, see `rom_settings`
+     * `--crash-dir <dir>`: where crash reports are written
+     * `--debug-dir <dir>`: where breakpoints are kept between runs
+     * `--play-log <file>`: where play time is recorded
+     * `--recent-file <file>`: where favorite and recent ROMs are kept
+     * `--keys-file <file>`: which host keys and buttons press the keypad keys
+     * `--route <keys=device,..>`: which device may press which keypad keys, see `routing::Routing`
+     * `--stick <up,down,left,right>`: keypad keys (hex) the left analog stick holds
+     * `--deadzone <fraction>`: part of the stick's travel around the center that is ignored
+     * `--diagonals both|dominant`: whether a diagonal holds two keys or one
+     * `--format text|json`: output format of `state dump`, `rom normalize`, `palette extract`, `disasm`, `ab`, `verify` and `--headless`
+     * `--out <file>`: where the `state`, `rom` and `palette` tools write to
+     * `--pad`: have `rom normalize` pad an odd ROM with a zero byte
+     */
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "verify" => config.command = Command::Verify,
+                "ab" => config.command = Command::Ab,
+                "--replay" => {
+                    let slot = args.next().ok_or("--replay needs a macro slot")?;
+                    config.replay = Some(slot.parse::<usize>().map_err(|_| format!("invalid macro slot '{}'", slot))?);
+                }
+                "compare-trace" => {
+                    let a = args.next().ok_or("compare-trace needs two files")?;
+                    let b = args.next().ok_or("compare-trace needs two files")?;
+                    config.command = Command::CompareTrace(a, b);
+                }
+                "check" => config.command = Command::Check,
+                "playtime" => config.command = Command::PlayTime,
+                "run" => config.rom = Some(args.next().ok_or("run needs a ROM file, a number from 'recent' or --last")?),
+                "recent" => config.command = Command::Recent,
+                "favorite" => config.command = Command::Favorite(args.next().ok_or("favorite needs a ROM file")?),
+                "disasm" => {
+                    let mut path = args.next().ok_or("disasm needs a ROM file")?;
+                    if path == "--cfg" {
+                        config.cfg = true;
+                        path = args.next().ok_or("disasm needs a ROM file")?;
+                    }
+                    config.command = Command::Disasm(path);
+                }
+                "--cfg" => config.cfg = true,
+                "state" => {
+                    let tool = args.next().ok_or("state needs a tool, dump or extract-vram")?;
+                    let path = args.next().ok_or_else(|| format!("state {} needs a save state file", tool))?;
+                    config.command = match tool.as_str() {
+                        "dump" => Command::StateDump(path),
+                        "extract-vram" => Command::StateExtractVram(path),
+                        _ => return Err(format!("unknown state tool '{}', expected dump or extract-vram", tool)),
+                    };
+                }
+                "rom" => {
+                    let tool = args.next().ok_or("rom needs a tool, normalize or tutorial")?;
+                    config.command = match tool.as_str() {
+                        "normalize" => Command::RomNormalize(args.next().ok_or("rom normalize needs a ROM file")?),
+                        "tutorial" => Command::RomTutorial(args.next().ok_or("rom tutorial needs an opcode family")?),
+                        _ => return Err(format!("unknown rom tool '{}', expected normalize or tutorial", tool)),
+                    };
+                }
+                "--pad" => config.pad = true,
+                "palette" => {
+                    let tool = args.next().ok_or("palette needs a tool, extract")?;
+                    let path = args.next().ok_or_else(|| format!("palette {} needs an image file", tool))?;
+                    config.command = match tool.as_str() {
+                        "extract" => Command::PaletteExtract(path),
+                        _ => return Err(format!("unknown palette tool '{}', expected extract", tool)),
+                    };
+                }
+                "--format" => {
+                    config.format = match args.next().ok_or("--format needs a value")?.as_str() {
+                        "text" => Format::Text,
+                        "json" => Format::Json,
+                        other => return Err(format!("unknown format '{}', expected text or json", other)),
+                    };
+                }
+                "--out" => config.out = Some(args.next().ok_or("--out needs a file")?),
+                "--glyphs" => config.glyphs = Some(args.next().ok_or("--glyphs needs a file")?),
+                "soak" => config.command = Command::Soak(args.next().ok_or("soak needs a ROM file")?),
+                "split" => {
+                    let roms: Vec<String> = args.next().ok_or("split needs ROM files")?.split(',').map(String::from).collect();
+                    if !(2..=SPLIT_MAX).contains(&roms.len()) {
+                        return Err(format!("split needs 2 to {} ROM files, separated by commas", SPLIT_MAX));
+                    }
+                    config.command = Command::Split(roms);
+                }
+                "--instances" => {
+                    let n = args.next().ok_or("--instances needs a value")?;
+                    config.instances = n
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("invalid instance count '{}'", n))?;
+                }
+                "--hours" => {
+                    let hours = args.next().ok_or("--hours needs a value")?;
+                    config.hours = hours
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|h| *h > 0.0)
+                        .ok_or_else(|| format!("invalid duration '{}'", hours))?;
+                }
+                "--extensions" => config.extensions = true,
+                "--harden" => config.harden = true,
+                "--strict" => config.strict = true,
+                "--quirks" => {
+                    config.quirks = Quirks::parse(&args.next().ok_or("--quirks needs a value")?)?;
+                    config.quirks_given = true;
+                }
+                "--platform" => config.platform = Some(Platform::by_name(&args.next().ok_or("--platform needs a name")?)?),
+                "--xo-chip" => config.xo_chip = true,
+                "--draw-budget" => {
+                    let n = args.next().ok_or("--draw-budget needs a value")?;
+                    config.draw_budget = Some(n
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("invalid draw budget '{}'", n))?);
+                }
+                "--cycles" => {
+                    let n = args.next().ok_or("--cycles needs a value")?;
+                    config.cycles_per_frame = Some(n
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("invalid cycles per frame '{}'", n))?);
+                }
+                "--runaway" => {
+                    let n = args.next().ok_or("--runaway needs a value")?;
+                    config.runaway = Some(n
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("invalid runaway limit '{}'", n))?);
+                }
+                "--break-on-runaway" => config.break_on_runaway = true,
+                "--rng" => {
+                    let name = args.next().ok_or("--rng needs a value")?;
+                    config.rng = Algorithm::by_name(&name).ok_or_else(|| {
+                        let names: Vec<&str> = ALGORITHMS.iter().map(|(n, _)| *n).collect();
+                        format!("unknown RNG '{}', expected one of: {}", name, names.join(", "))
+                    })?;
+                }
+                "--save-ram" => config.save_ram = true,
+                "--save-dir" => config.save_dir = args.next().ok_or("--save-dir needs a directory")?,
+                "--thumbnail-dir" => config.thumbnail_dir = args.next().ok_or("--thumbnail-dir needs a directory")?,
+                "--thumbnail-frame" => {
+                    let n = args.next().ok_or("--thumbnail-frame needs a frame")?;
+                    config.thumbnail_frame = n.parse().map_err(|_| format!("invalid frame '{}'", n))?;
+                }
+                "--trace" => config.trace = Some(args.next().ok_or("--trace needs a file")?),
+                "--print-instructions" => config.print_instructions = true,
+                "--refresh" => {
+                    let hz = args.next().ok_or("--refresh needs a value")?;
+                    config.refresh_rate = hz
+                        .parse::<f64>()
+                        .map_err(|_| format!("invalid refresh rate '{}'", hz))?;
+                    if config.refresh_rate <= 0.0 {
+                        return Err(format!("refresh rate must be positive, got {}", hz));
+                    }
+                }
+                "--speed" => {
+                    config.speed = parse_speed(&args.next().ok_or("--speed needs a value")?)?;
+                    config.speed_given = true;
+                }
+                "--break" => config.breakpoints.push(parse_addr(&args.next().ok_or("--break needs an address")?)?),
+                "--paused" => config.paused = true,
+                "--break-at-frame" => {
+                    let n = args.next().ok_or("--break-at-frame needs a frame number")?;
+                    config.break_at_frame = Some(n
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("invalid frame number '{}'", n))?);
+                }
+                "--load-state" => config.load_state = true,
+                "--pal" => config.refresh_rate = REFRESH_PAL,
+                "--ntsc" => config.refresh_rate = REFRESH_NTSC,
+                "--headless" => config.headless = true,
+                "--frames" => {
+                    let frames = args.next().ok_or("--frames needs a value")?;
+                    config.frames = frames
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid frame count '{}'", frames))?;
+                }
+                "--palette" => {
+                    let name = args.next().ok_or("--palette needs a value")?;
+                    config.palette = match Palette::by_name(&name) {
+                        Some(palette) => palette,
+                        // a file written by `palette extract`, or by hand
+                        None if Path::new(&name).is_file() => {
+                            let text = fs::read_to_string(&name).map_err(|e| format!("can't read {}: {}", name, e))?;
+                            Palette::parse(&text).map_err(|e| format!("{}: {}", name, e))?
+                        }
+                        None => {
+                            let names: Vec<&str> = PRESETS.iter().map(|(n, _)| *n).collect();
+                            return Err(format!("unknown palette '{}', expected a palette file or one of: {}", name, names.join(", ")));
+                        }
+                    };
+                    config.palette_given = true;
+                }
+                "--palette-cycle" => config.palette_cycle = true,
+                "--brightness" => config.picture.brightness = picture_setting("--brightness", args.next(), Picture::BRIGHTNESS)?,
+                "--contrast" => config.picture.contrast = picture_setting("--contrast", args.next(), Picture::CONTRAST)?,
+                "--gamma" => config.picture.gamma = picture_setting("--gamma", args.next(), Picture::GAMMA)?,
+                "--beep-flash" => config.beep_flash = true,
+                "--phosphor" => config.phosphor = true,
+                "--backend" => config.backend = RenderBackend::by_name(&args.next().ok_or("--backend needs a value")?)?,
+                "--audio" => config.audio = AudioBackend::by_name(&args.next().ok_or("--audio needs a value")?)?,
+                "--min-beep" => {
+                    let ms = args.next().ok_or("--min-beep needs a value")?;
+                    config.min_beep_ms = ms
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|ms| *ms >= 0.0)
+                        .ok_or_else(|| format!("invalid beep duration '{}'", ms))?;
+                }
+                "--sprite-editor" => config.sprite_editor = true,
+                "--slow-draw" => config.slow_draw = true,
+                "--repl" => config.repl = true,
+                "--debug-ui" if cfg!(feature = "gui-debug") => config.debug_ui = true,
+                "--debug-ui" => return Err("--debug-ui isn't built in, rebuild with --features gui-debug".to_string()),
+                "--macro-dir" => config.macro_dir = args.next().ok_or("--macro-dir needs a directory")?,
+                "--stats-json" => config.stats_json = Some(args.next().ok_or("--stats-json needs a file")?),
+                "--frame-times" => config.frame_times = Some(args.next().ok_or("--frame-times needs a file")?),
+                "--compat-report" => config.compat_report = Some(args.next().ok_or("--compat-report needs a file")?),
+                "--coverage" => config.coverage = Some(args.next().ok_or("--coverage needs a file")?),
+                "--record-audio" => config.record_audio = Some(args.next().ok_or("--record-audio needs a file")?),
+                "--log-file" => config.log_file = Some(args.next().ok_or("--log-file needs a file")?),
+                "--lang" => {
+                    let code = args.next().ok_or("--lang needs a value")?;
+                    config.lang = Lang::by_code(&code).ok_or_else(|| {
+                        let codes: Vec<&str> = LANGS.iter().map(|(c, _)| *c).collect();
+                        format!("unknown language '{}', expected one of: {}", code, codes.join(", "))
+                    })?;
+                }
+                "--scaling" => config.scaling = Some(Scaling::by_name(&args.next().ok_or("--scaling needs a value")?)?),
+                "--rom-settings" => config.rom_settings = args.next().ok_or("--rom-settings needs a file")?,
+                "--crash-dir" => config.crash_dir = args.next().ok_or("--crash-dir needs a directory")?,
+                "--debug-dir" => config.debug_dir = args.next().ok_or("--debug-dir needs a directory")?,
+                "--play-log" => config.play_log = args.next().ok_or("--play-log needs a file")?,
+                "--recent-file" => config.recent_file = args.next().ok_or("--recent-file needs a file")?,
+                "--keys-file" => config.keys_file = args.next().ok_or("--keys-file needs a file")?,
+                "--route" => config.routing = Routing::parse(&args.next().ok_or("--route needs keys and devices")?)?,
+                "--stick" => {
+                    let keys = args.next().ok_or("--stick needs four keys")?;
+                    let parsed: Vec<usize> = keys
+                        .split(',')
+                        .map(|k| usize::from_str_radix(k.trim(), 16).ok().filter(|k| *k < KEY_COUNT))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| format!("invalid keys '{}', expected up,down,left,right like 2,8,4,6", keys))?;
+                    let [up, down, left, right] = parsed[..] else {
+                        return Err(format!("--stick needs four keys, got '{}'", keys));
+                    };
+                    config.stick = StickMap { up, down, left, right, ..config.stick };
+                }
+                "--deadzone" => {
+                    let deadzone = args.next().ok_or("--deadzone needs a value")?;
+                    config.stick.deadzone = deadzone
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|d| (0.0..1.0).contains(d))
+                        .ok_or_else(|| format!("invalid deadzone '{}', expected 0 to 0.99", deadzone))?;
+                }
+                "--diagonals" => {
+                    config.stick.diagonals = match args.next().ok_or("--diagonals needs a value")?.as_str() {
+                        "both" => Diagonals::Both,
+                        "dominant" => Diagonals::Dominant,
+                        other => return Err(format!("unknown diagonals policy '{}', expected both or dominant", other)),
+                    };
+                }
+                "--patch" => config.patch = Some(args.next().ok_or("--patch needs a file")?),
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        if let Some(platform) = config.platform {
+            if config.strict && !platform.strict() {
+                return Err(format!("--platform {} can't be combined with --strict", platform.name()));
+            }
+            if config.xo_chip && !platform.xo_chip() {
+                return Err(format!("--platform {} can't be combined with --xo-chip", platform.name()));
+            }
+            config.strict = platform.strict();
+            config.xo_chip = platform.xo_chip();
+            if !config.quirks_given {
+                config.quirks = platform.quirks();
+            }
+        }
+        if config.strict && config.extensions {
+            return Err("--strict and --extensions can't be used together".to_string());
+        }
+        if config.strict && config.xo_chip {
+            return Err("--strict and --xo-chip can't be used together".to_string());
+        }
+        if config.strict && !config.quirks_given {
+            config.quirks = Quirks::VIP;
+        }
+        if config.break_on_runaway && config.runaway.is_none() {
+            return Err("--break-on-runaway needs --runaway".to_string());
+        }
+
+        Ok(config)
+    }
+}
+
+/**
+ * A speed factor, see `--speed`.
+ */
+pub fn parse_speed(factor: &str) -> Result<f64, String> {
+    let speed = factor.parse::<f64>().map_err(|_| format!("invalid speed '{}'", factor))?;
+    if speed <= 0.0 {
+        return Err(format!("speed must be positive, got {}", factor));
+    }
+    Ok(speed)
+}
+
+/**
+ * An address, hex with a `0x` prefix and decimal otherwise like in the debugger.
+ */
+pub fn parse_addr(addr: &str) -> Result<usize, String> {
+    let parsed = match addr.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => addr.parse::<usize>(),
+    };
+    parsed.map_err(|_| format!("invalid address '{}'", addr))
+}
+
+/**
+ * The value of `--brightness`, `--contrast` or `--gamma`, which has to be in `range`.
+ */
+fn picture_setting(flag: &str, value: Option<String>, range: RangeInclusive<f32>) -> Result<f32, String> {
+    let value = value.ok_or_else(|| format!("{} needs a value", flag))?;
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|v| range.contains(v))
+        .ok_or_else(|| format!("invalid {} '{}', expected {} to {}", &flag[2..], value, range.start(), range.end()))
+}
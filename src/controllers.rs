@@ -0,0 +1,70 @@
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+
+use crate::i18n::Lang;
+use crate::render::Render;
+
+/**
+ * What the controller menu wants after an event.
+ */
+pub enum MenuInput {
+    Open,
+    Close,
+}
+
+/**
+ * Lists the connected controllers by player number, see `Render::player`. Up and Down pick
+ * one, Shift+Up and Shift+Down move it to another player number, Escape or Enter close the
+ * menu. Controllers plugged in or out while it is open show up or disappear right away.
+ */
+#[derive(Default)]
+pub struct ControllerMenu {
+    selected: usize,
+}
+
+impl ControllerMenu {
+    pub fn handle(&mut self, event: &Event, renderer: &mut Render) -> MenuInput {
+        let count = renderer.controller_names().len();
+        self.selected = self.selected.min(count.saturating_sub(1));
+
+        let Event::KeyDown { keycode: Some(keycode), keymod, .. } = event else {
+            return MenuInput::Open;
+        };
+        let moving = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+        match keycode {
+            Keycode::Escape | Keycode::Return | Keycode::KpEnter => return MenuInput::Close,
+            Keycode::Up if self.selected > 0 => {
+                if moving {
+                    renderer.swap_controllers(self.selected, self.selected - 1);
+                }
+                self.selected -= 1;
+            }
+            Keycode::Down if self.selected + 1 < count => {
+                if moving {
+                    renderer.swap_controllers(self.selected, self.selected + 1);
+                }
+                self.selected += 1;
+            }
+            _ => {}
+        }
+        MenuInput::Open
+    }
+
+    /**
+     * The menu as overlay text, for the controllers in `names` by player number.
+     */
+    pub fn text(&self, names: &[String], lang: Lang) -> String {
+        let mut out = lang.tr("menu.controllers").to_string();
+        if names.is_empty() {
+            out.push('\n');
+            out.push_str(lang.tr("msg.no_controllers"));
+        }
+        for (n, name) in names.iter().enumerate() {
+            let marker = if n == self.selected.min(names.len() - 1) { '>' } else { ' ' };
+            out.push_str(&format!("\n{} {} {}", marker, n + 1, name));
+        }
+        out.push('\n');
+        out.push_str(lang.tr("menu.controllers_keys"));
+        out
+    }
+}
@@ -0,0 +1,64 @@
+//! Screen coordinate math in one place. Sprites start at a position that wraps around the
+//! screen (`V0 = 70` on a 64 pixel wide screen draws at X 6) and are then either clipped at
+//! the edge, like on the VIP, or wrap around to the other side, as some interpreters do.
+//! Everything here takes the screen size as an argument so it works for the 64x32, 128x64
+//! and any other resolution alike.
+//!
+//! ```
+//! use chip8::coords::{self, Edge};
+//!
+//! // starting positions always wrap
+//! assert_eq!(coords::wrap(70, 64), 6);
+//! assert_eq!(coords::wrap(63, 64), 63);
+//!
+//! // the 8 pixels of a sprite row starting at X 60
+//! let clipped: Vec<_> = (0..8).filter_map(|n| coords::offset(60, n, 64, Edge::Clip)).collect();
+//! assert_eq!(clipped, [60, 61, 62, 63]);
+//! let wrapped: Vec<_> = (0..8).filter_map(|n| coords::offset(60, n, 64, Edge::Wrap)).collect();
+//! assert_eq!(wrapped, [60, 61, 62, 63, 0, 1, 2, 3]);
+//!
+//! // a sprite at (60, 30) on the 128x64 screen, 8 wide and 5 tall
+//! assert_eq!(coords::clip_rect(60, 30, 8, 5, 128, 64), Some((60, 30, 8, 5)));
+//! assert_eq!(coords::clip_rect(124, 62, 8, 5, 128, 64), Some((124, 62, 4, 2)));
+//! assert_eq!(coords::clip_rect(0, 0, 8, 0, 64, 32), None);
+//! ```
+
+/**
+ * What happens to the part of a sprite that runs past the edge of the screen.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Edge {
+    // it isn't drawn
+    Clip,
+    // it is drawn on the other side
+    Wrap,
+}
+
+/**
+ * `pos` moved onto a screen axis of `size` pixels, the way sprite positions wrap.
+ */
+pub fn wrap(pos: usize, size: usize) -> usize {
+    pos % size.max(1)
+}
+
+/**
+ * The pixel `offset` pixels past `start` on an axis of `size` pixels, or None if it is
+ * clipped. `start` has to be on screen already, see `wrap`.
+ */
+pub fn offset(start: usize, offset: usize, size: usize, edge: Edge) -> Option<usize> {
+    let pos = start + offset;
+    match edge {
+        Edge::Clip => (pos < size).then_some(pos),
+        Edge::Wrap => Some(wrap(pos, size)),
+    }
+}
+
+/**
+ * The on-screen part of a `w` by `h` area whose top left corner is at X/Y (wrapped first)
+ * as (x, y, width, height), or None if nothing of it is on screen.
+ */
+pub fn clip_rect(x: usize, y: usize, w: usize, h: usize, width: usize, height: usize) -> Option<(usize, usize, usize, usize)> {
+    let (x, y) = (wrap(x, width), wrap(y, height));
+    let (w, h) = (w.min(width - x), h.min(height - y));
+    (w > 0 && h > 0).then_some((x, y, w, h))
+}
@@ -0,0 +1,138 @@
+//! Code coverage for a session: which addresses were executed and which way every
+//! conditional skip went, exported as an annotated disassembly of the ROM:
+//!
+//! ```text
+//! ; 6 of 8 ROM bytes executed, 0 of 1 branches went both ways
+//!   0x200  6005  LD V0, 0x05          1x
+//!   0x202  3005  SE V0, 0x05          1x  skipped 1, fell through 0
+//! - 0x204  00E0  CLS
+//!   0x206  1206  JP 0x206             6x
+//! ```
+//!
+//! Lines starting with `-` were never executed. Handy for checking that a test ROM really
+//! ran all of its checks.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::disasm::{self, ROM_ADDR};
+use crate::hardware::RAM_SIZE;
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Branch {
+    // the next instruction was skipped
+    pub taken: u32,
+    pub not_taken: u32,
+}
+
+/**
+ * Execution counts per address and outcomes per conditional skip, see `Machine::coverage`.
+ *
+ * ```
+ * use chip8::coverage::Coverage;
+ * use chip8::machine::Machine;
+ *
+ * let mut machine = Machine::new();
+ * machine.coverage = Some(Coverage::new(machine.chip8.get_ram().len()));
+ * // V0 = 5, skip if V0 == 5, clear the screen, loop forever
+ * let rom = [0x60, 0x05, 0x30, 0x05, 0x00, 0xE0, 0x12, 0x06];
+ * machine.load_rom(&rom);
+ * machine.run_frame().unwrap();
+ *
+ * let coverage = machine.coverage.as_ref().unwrap();
+ * assert_eq!(coverage.hits(0x202), 1);
+ * assert_eq!(coverage.hits(0x204), 0);
+ * assert_eq!(coverage.branches[&0x202].taken, 1);
+ * assert!(coverage.annotate(machine.chip8.get_ram(), rom.len()).contains("- 0x204  00E0  CLS"));
+ * ```
+ */
+#[derive(Clone)]
+pub struct Coverage {
+    hits: Vec<u32>,
+    // keyed by the address of the skip instruction
+    pub branches: BTreeMap<usize, Branch>,
+}
+
+impl Coverage {
+    /**
+     * Coverage of `ram_size` bytes of RAM, `chip8.get_ram().len()`. Switching XO-CHIP on
+     * later grows it.
+     */
+    pub fn new(ram_size: usize) -> Self {
+        Coverage { hits: vec![0; ram_size], branches: BTreeMap::new() }
+    }
+
+    /**
+     * Record the instruction at `pc` having run, leaving the PC at `next_pc`.
+     */
+    pub fn record(&mut self, pc: usize, opcode: u16, next_pc: usize) {
+        if pc >= self.hits.len() {
+            self.hits.resize(pc + 1, 0);
+        }
+        self.hits[pc] = self.hits[pc].saturating_add(1);
+
+        if disasm::is_skip(opcode) {
+            let branch = self.branches.entry(pc).or_default();
+            // past the next instruction, which is 4 bytes for XO-CHIP's long load
+            if next_pc > pc + 2 {
+                branch.taken += 1;
+            } else {
+                branch.not_taken += 1;
+            }
+        }
+    }
+
+    /**
+     * How often the instruction at `addr` ran.
+     */
+    pub fn hits(&self, addr: usize) -> u32 {
+        self.hits.get(addr).copied().unwrap_or(0)
+    }
+
+    /**
+     * Disassemble the `rom_len` bytes of ROM in `ram` with the execution counts and branch
+     * outcomes. Executed instructions are disassembled where they ran, even at odd
+     * addresses; everything else is shown two bytes at a time.
+     */
+    pub fn annotate(&self, ram: &[u8], rom_len: usize) -> String {
+        let end = (ROM_ADDR + rom_len).min(ram.len());
+        let mut lines = String::new();
+        let mut executed = 0;
+
+        let mut addr = ROM_ADDR;
+        while addr < end {
+            let hits = self.hits(addr);
+            // an instruction starts at the next byte, keep in step with it
+            if hits == 0 && addr + 1 < end && self.hits(addr + 1) > 0 {
+                writeln!(lines, "- {:#05x}  {:02X}    DB {:#04x}", addr, ram[addr], ram[addr]).unwrap();
+                addr += 1;
+                continue;
+            }
+
+            let opcode = (ram[addr] as u16) << 8 | ram[(addr + 1) % ram.len()] as u16;
+            let text = format!("{:#05x}  {:04X}  {}", addr, opcode, disasm::mnemonic(opcode));
+
+            if hits == 0 {
+                writeln!(lines, "- {}", text).unwrap();
+            } else {
+                executed += 2.min(end - addr);
+                write!(lines, "  {:<34}{}x", text, hits).unwrap();
+                if let Some(branch) = self.branches.get(&addr) {
+                    write!(lines, "  skipped {}, fell through {}", branch.taken, branch.not_taken).unwrap();
+                }
+                lines.push('\n');
+            }
+            addr += 2;
+        }
+
+        let both_ways = self.branches.iter().filter(|(a, b)| (ROM_ADDR..end).contains(*a) && b.taken > 0 && b.not_taken > 0).count();
+        let branches = self.branches.keys().filter(|a| (ROM_ADDR..end).contains(*a)).count();
+        format!("; {} of {} ROM bytes executed, {} of {} branches went both ways\n{}", executed, end - ROM_ADDR, both_ways, branches, lines)
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Coverage::new(RAM_SIZE)
+    }
+}
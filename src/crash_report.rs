@@ -0,0 +1,52 @@
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chip8::debugger::{self, Debugger};
+use chip8::hardware::Chip8;
+use chip8::machine::Machine;
+use chip8::patch;
+use chip8::trace::HISTORY_CAPACITY;
+
+pub const CRASH_DIR: &str = "crashes";
+
+/**
+ * Write a report on the fault `debugger` stopped at into `dir` and return its path. It holds
+ * what is needed to reproduce the fault: the emulator version, which ROM, its settings,
+ * the post-mortem and the last instructions executed, so users can attach it to bug reports.
+ */
+pub fn write(dir: &str, rom: &[u8], machine: &Machine, debugger: &Debugger) -> Result<String, String> {
+    let crc = patch::crc32(rom);
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let path = format!("{}/crash-{:08x}-{}.txt", dir, crc, secs);
+
+    let report = format!(
+        "rust8 {} crash report\nrom: crc32 {:08x}, {} bytes\nquirks: {} ({})\nframe: {}\n\n{}\n\nexecution history, oldest first:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        crc,
+        rom.len(),
+        profile(&machine.chip8),
+        machine.chip8.quirks,
+        machine.frame(),
+        debugger.post_mortem(machine),
+        debugger::history(machine, HISTORY_CAPACITY),
+    );
+
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&path, report))
+        .map_err(|e| format!("can't write crash report {}: {}", path, e))?;
+    Ok(path)
+}
+
+/**
+ * Name of the behavior the core runs with: `strict` for `Chip8::strict`, `xo-chip` in
+ * XO-CHIP mode, `default` otherwise.
+ */
+pub fn profile(chip8: &Chip8) -> &'static str {
+    if chip8.strict {
+        "strict"
+    } else if chip8.xo_chip() {
+        "xo-chip"
+    } else {
+        "default"
+    }
+}
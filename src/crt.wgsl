@@ -0,0 +1,50 @@
+// CRT filter for the wgpu backend, see `wgpu_display`: draws the window contents over the
+// whole surface, with dark gaps between the emulated screen's rows and darker corners.
+
+struct Params {
+    // size of the window contents and of the surface, in pixels
+    source: vec2<f32>,
+    output: vec2<f32>,
+    // emulated screen within the window contents: left, top, width, height
+    screen: vec4<f32>,
+    // emulated screen rows, one scanline each
+    rows: f32,
+};
+
+@group(0) @binding(0) var frame: texture_2d<f32>;
+@group(0) @binding(1) var frame_sampler: sampler;
+@group(0) @binding(2) var<uniform> params: Params;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// one triangle twice the size of the surface, so the visible part covers it exactly
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOut {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOut;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    var color = textureSample(frame, frame_sampler, in.uv).rgb;
+
+    // scanlines, only where a row is tall enough on the surface to show a gap
+    let pos = in.uv * params.source - params.screen.xy;
+    let row_height = params.screen.w / max(params.rows, 1.0) * params.output.y / params.source.y;
+    if (all(pos >= vec2<f32>(0.0)) && all(pos < params.screen.zw) && row_height >= 3.0) {
+        let row = fract(pos.y / params.screen.w * params.rows);
+        color = color * mix(1.0, 0.6, smoothstep(0.7, 1.0, row));
+    }
+
+    // vignette
+    let edge = in.uv * (1.0 - in.uv);
+    color = color * clamp(pow(edge.x * edge.y * 16.0, 0.2), 0.0, 1.0);
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use chip8::debugger::Label;
+use chip8::hardware::XO_RAM_SIZE;
+use chip8::patch;
+
+/**
+ * What a debugging session leaves behind for the next run of the same ROM: its breakpoints,
+ * watches, RAM labels and whether the debug overlay was open. Stored as text, one entry per
+ * line, a label with its address and length:
+ *
+ * ```text
+ * break 0x2a4
+ * break 0x31c
+ * watch ram[i + 1]
+ * label 0x3f0 3 score BCD
+ * overlay
+ * ```
+ */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Session {
+    pub breakpoints: BTreeSet<usize>,
+    // the expressions, as typed, see `chip8::expr`
+    pub watches: Vec<String>,
+    // see `Debugger::labels`
+    pub labels: Vec<Label>,
+    // the F4 overlay, see `render::Layer::Debug`
+    pub overlay: bool,
+}
+
+/**
+ * Session file for a ROM, keyed by its checksum like the save files.
+ */
+pub fn path(dir: &str, rom: &[u8]) -> String {
+    format!("{}/{:08x}.txt", dir, patch::crc32(rom))
+}
+
+impl Session {
+    pub fn parse(text: &str) -> Result<Session, String> {
+        let mut session = Session::default();
+
+        for (n, line) in text.lines().enumerate() {
+            match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [] => {}
+                ["break", addr] => {
+                    let hex = addr.strip_prefix("0x").unwrap_or(addr);
+                    let addr = usize::from_str_radix(hex, 16).map_err(|_| format!("line {}: invalid address '{}'", n + 1, addr))?;
+                    session.breakpoints.insert(addr);
+                }
+                ["label", addr, len, name @ ..] if !name.is_empty() => {
+                    let invalid = |what, text| format!("line {}: invalid {} '{}'", n + 1, what, text);
+                    let hex = addr.strip_prefix("0x").unwrap_or(addr);
+                    let addr = usize::from_str_radix(hex, 16).map_err(|_| invalid("address", addr))?;
+                    // no RAM is bigger than XO-CHIP's
+                    let fits = |len: &usize| *len > 0 && addr.checked_add(*len).is_some_and(|end| end <= XO_RAM_SIZE);
+                    let len = len.parse().ok().filter(fits).ok_or_else(|| invalid("length", len))?;
+                    session.labels.push(Label { addr, len, name: name.join(" ") });
+                }
+                ["watch", _, ..] => session.watches.push(line.trim_start().trim_start_matches("watch").trim().to_string()),
+                ["overlay"] => session.overlay = true,
+                _ => return Err(format!("line {}: unknown entry '{}'", n + 1, line.trim())),
+            }
+        }
+
+        Ok(session)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out: String = self.breakpoints.iter().map(|addr| format!("break {:#05x}\n", addr)).collect();
+        out.extend(self.watches.iter().map(|text| format!("watch {}\n", text)));
+        out.extend(self.labels.iter().map(|label| format!("label {:#05x} {} {}\n", label.addr, label.len, label.name)));
+        if self.overlay {
+            out.push_str("overlay\n");
+        }
+        out
+    }
+
+    /**
+     * The session saved at `path`, empty if there is none yet.
+     */
+    pub fn load(path: &str) -> Result<Session, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Session::parse(&text).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Session::default()),
+            Err(e) => Err(format!("can't read {}: {}", path, e)),
+        }
+    }
+
+    /**
+     * Save the session to `path`. An empty session removes the file instead, so ROMs that
+     * were never debugged don't leave one behind.
+     */
+    pub fn store(&self, path: &str) -> Result<(), String> {
+        if *self == Session::default() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(format!("can't remove {}: {}", path, e)),
+                _ => Ok(()),
+            };
+        }
+
+        let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir)
+            .and_then(|_| fs::write(path, self.to_text()))
+            .map_err(|e| format!("can't write {}: {}", path, e))
+    }
+}
@@ -0,0 +1,472 @@
+use std::time::Instant;
+
+use egui::load::SizedTexture;
+use egui::text::{LayoutJob, TextFormat};
+use egui::{pos2, vec2, Color32, ColorImage, Context, Key, Modifiers, PointerButton, RawInput, Rect, RichText, Sense, Stroke, TextureHandle, TextureOptions, ViewportId};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::VideoSubsystem;
+
+use chip8::debugger::{Debugger, Label};
+use chip8::disasm;
+use chip8::hardware::{HaltReason, State, V_REG_COUNT};
+use chip8::machine::Machine;
+use chip8::palette::Palette;
+
+use crate::i18n::Lang;
+use crate::ui_painter::Painter;
+
+// instructions the disassembly shows before and after PC
+const DISASM_BEFORE: usize = 8;
+const DISASM_AFTER: usize = 24;
+// bytes per line of the memory panel
+const MEMORY_ROW: usize = 16;
+// registers and bytes the last stepped instruction changed
+const CHANGED: Color32 = Color32::YELLOW;
+// the sprite row slow drawing is showing, see `slow_draw`
+const SPRITE_ROW: Color32 = Color32::from_rgb(0xFF, 0x80, 0x00);
+// names of RAM labels in the memory panel
+const LABEL: Color32 = Color32::GRAY;
+
+/**
+ * The debugger as a second window of egui panels: registers, memory, disassembly,
+ * breakpoints, watches and the framebuffer. It drives the same `Debugger` as the REPL, so
+ * the two can be used side by side.
+ */
+pub struct DebugUi {
+    ctx: Context,
+    painter: Painter,
+    lang: Lang,
+    palette: Palette,
+    started: Instant,
+    // input since the last `draw`
+    events: Vec<egui::Event>,
+    modifiers: Modifiers,
+    focused: bool,
+    // which panels are open, toggled from the menu bar
+    registers: bool,
+    memory: bool,
+    disassembly: bool,
+    breakpoints: bool,
+    watches: bool,
+    framebuffer: bool,
+    // what's typed into the breakpoints, watches and memory panels
+    new_break: String,
+    new_watch: String,
+    new_label: String,
+    // output of the last debugger command
+    status: String,
+    vram: Option<TextureHandle>,
+    // address of the sprite row being drawn, highlighted in the memory panel
+    pub sprite_row: Option<usize>,
+    // set when the window is closed, the frontend then drops it
+    pub closed: bool,
+}
+
+impl DebugUi {
+    pub fn open(video: &VideoSubsystem, lang: Lang, palette: Palette) -> Result<Self, String> {
+        let window = video
+            .window(lang.tr("ui.title"), 800, 600)
+            .resizable()
+            .allow_highdpi()
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(DebugUi {
+            ctx: Context::default(),
+            painter: Painter::new(window)?,
+            lang,
+            palette,
+            started: Instant::now(),
+            events: Vec::new(),
+            modifiers: Modifiers::default(),
+            focused: true,
+            registers: true,
+            memory: false,
+            disassembly: true,
+            breakpoints: true,
+            watches: true,
+            framebuffer: false,
+            new_break: String::new(),
+            new_watch: String::new(),
+            new_label: String::new(),
+            status: String::new(),
+            vram: None,
+            sprite_row: None,
+            closed: false,
+        })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.painter.window().id()
+    }
+
+    /**
+     * Pass on an SDL event for the debugger window.
+     */
+    pub fn handle_event(&mut self, event: &Event) {
+        let modifiers = self.modifiers;
+        let event = match *event {
+            Event::MouseMotion { x, y, .. } => egui::Event::PointerMoved(pos2(x as f32, y as f32)),
+            Event::MouseButtonDown { mouse_btn, x, y, .. } | Event::MouseButtonUp { mouse_btn, x, y, .. } => {
+                let Some(button) = pointer_button(mouse_btn) else {
+                    return;
+                };
+                let pressed = matches!(event, Event::MouseButtonDown { .. });
+                egui::Event::PointerButton { pos: pos2(x as f32, y as f32), button, pressed, modifiers }
+            }
+            Event::MouseWheel { x, y, .. } => egui::Event::Scroll(vec2(x as f32, y as f32) * 24.0),
+            Event::TextInput { ref text, .. } => egui::Event::Text(text.clone()),
+            Event::KeyDown { keycode, keymod, repeat, .. } | Event::KeyUp { keycode, keymod, repeat, .. } => {
+                self.modifiers = egui_modifiers(keymod);
+                let Some(key) = keycode.and_then(egui_key) else {
+                    return;
+                };
+                let pressed = matches!(event, Event::KeyDown { .. });
+                egui::Event::Key { key, physical_key: None, pressed, repeat, modifiers: self.modifiers }
+            }
+            Event::Window { win_event: WindowEvent::Leave, .. } => egui::Event::PointerGone,
+            Event::Window { win_event: WindowEvent::FocusGained | WindowEvent::FocusLost, .. } => {
+                self.focused = matches!(event, Event::Window { win_event: WindowEvent::FocusGained, .. });
+                return;
+            }
+            Event::Window { win_event: WindowEvent::Close, .. } => {
+                self.closed = true;
+                return;
+            }
+            _ => return,
+        };
+        self.events.push(event);
+    }
+
+    /**
+     * Draw the panels and run whatever they were clicked to do. Returns whether a
+     * debugger command ran, which can change the machine and the screen.
+     */
+    pub fn draw(&mut self, machine: &mut Machine, debugger: &mut Debugger) -> Result<bool, String> {
+        let window = self.painter.window();
+        let (width, height) = window.size();
+        let pixels_per_point = self.painter.pixels_per_point();
+        let mut input = RawInput {
+            screen_rect: Some(Rect::from_min_size(pos2(0.0, 0.0), vec2(width as f32, height as f32))),
+            time: Some(self.started.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            focused: self.focused,
+            ..RawInput::default()
+        };
+        input.viewports.entry(ViewportId::ROOT).or_default().native_pixels_per_point = Some(pixels_per_point);
+
+        let rgba = machine.chip8.to_rgba(&self.palette);
+        let size = [machine.chip8.get_vram().width(), machine.chip8.get_vram().height()];
+        let image = ColorImage::from_rgba_unmultiplied(size, &rgba);
+        match &mut self.vram {
+            Some(texture) => texture.set(image, TextureOptions::NEAREST),
+            None => self.vram = Some(self.ctx.load_texture("vram", image, TextureOptions::NEAREST)),
+        }
+
+        let ctx = self.ctx.clone();
+        let mut commands: Vec<String> = Vec::new();
+        let output = ctx.run(input, |ctx| self.panels(ctx, machine, debugger, &mut commands));
+
+        let ran = !commands.is_empty();
+        for command in commands {
+            self.status = debugger.execute(machine, &command);
+        }
+
+        let primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        self.painter.paint(output.textures_delta, &primitives, output.pixels_per_point)?;
+        Ok(ran)
+    }
+
+    /**
+     * Lay out one frame. Commands for the debugger go to `commands` rather than running
+     * right away, so everything in a frame shows the same machine state.
+     */
+    fn panels(&mut self, ctx: &Context, machine: &Machine, debugger: &mut Debugger, commands: &mut Vec<String>) {
+        let lang = self.lang;
+        egui::TopBottomPanel::top("menu").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button(lang.tr("ui.panels"), |ui| {
+                    ui.checkbox(&mut self.registers, lang.tr("ui.registers"));
+                    ui.checkbox(&mut self.memory, lang.tr("ui.memory"));
+                    ui.checkbox(&mut self.disassembly, lang.tr("ui.disassembly"));
+                    ui.checkbox(&mut self.breakpoints, lang.tr("ui.breakpoints"));
+                    ui.checkbox(&mut self.watches, lang.tr("ui.watches"));
+                    ui.checkbox(&mut self.framebuffer, lang.tr("ui.framebuffer"));
+                });
+                ui.separator();
+                if debugger.paused {
+                    if ui.button(lang.tr("ui.continue")).clicked() {
+                        commands.push("continue".to_string());
+                    }
+                } else if ui.button(lang.tr("ui.pause")).clicked() {
+                    commands.push("pause".to_string());
+                }
+                if ui.button(lang.tr("ui.step")).clicked() {
+                    commands.push("step".to_string());
+                }
+                if ui.button(lang.tr("ui.step_over")).clicked() {
+                    commands.push("over".to_string());
+                }
+                if !machine.chip8.stack.is_empty() && ui.button(lang.tr("ui.step_out")).clicked() {
+                    commands.push("out".to_string());
+                }
+                if debugger.fault.is_some() && ui.button(lang.tr("ui.skip")).clicked() {
+                    commands.push("nop".to_string());
+                }
+            });
+        });
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            let status = match debugger.fault {
+                Some(fault) => RichText::new(fault.to_string()).color(Color32::LIGHT_RED),
+                None => RichText::new(self.status.lines().last().unwrap_or_default()),
+            };
+            ui.label(status);
+        });
+        egui::CentralPanel::default().show(ctx, |_| {});
+
+        let chip8 = &machine.chip8;
+        let changes = debugger.changes.clone().unwrap_or_default();
+        // highlighted if the last stepped instruction changed it
+        let value = |text: String, changed: bool| {
+            let text = RichText::new(text).monospace();
+            if changed {
+                text.color(CHANGED)
+            } else {
+                text
+            }
+        };
+        egui::Window::new(lang.tr("ui.registers")).open(&mut self.registers).show(ctx, |ui| {
+            egui::Grid::new("v").striped(true).show(ui, |ui| {
+                for n in 0..V_REG_COUNT {
+                    ui.label(value(format!("V{:X} {:02X}", n, chip8.v[n]), changes.v_changed(n)));
+                    if n % 4 == 3 {
+                        ui.end_row();
+                    }
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.monospace(format!("PC {:#05x}", chip8.pc));
+                ui.label(value(format!("I {:#05x}", chip8.i), changes.i.is_some()));
+                ui.label(value(format!("SP {}", chip8.sp), changes.sp.is_some()));
+            });
+            ui.horizontal(|ui| {
+                ui.label(value(format!("DT {:#04x}", chip8.tim_delay), changes.tim_delay.is_some()));
+                ui.label(value(format!("ST {:#04x}", chip8.tim_snd), changes.tim_snd.is_some()));
+                ui.monospace(format!("frame {}", machine.frame()));
+            });
+            let stack: Vec<String> = chip8.stack.iter().map(|a| format!("{:#05x}", a)).collect();
+            ui.monospace(format!("stack [{}]", stack.join(", ")));
+            let state = match chip8.state {
+                State::Running if debugger.paused => lang.tr("ui.paused").to_string(),
+                State::Running => lang.tr("ui.running").to_string(),
+                State::Halted(HaltReason::WaitKey(reg)) => lang.format("ui.wait_key", &[&format!("{:X}", reg)]),
+                State::Halted(_) => lang.tr("menu.exited").to_string(),
+            };
+            ui.label(state);
+        });
+
+        egui::Window::new(lang.tr("ui.memory")).open(&mut self.memory).default_width(420.0).show(ctx, |ui| {
+            ui.collapsing(lang.tr("ui.labels"), |ui| {
+                let mut removed = None;
+                for label in &debugger.labels {
+                    ui.horizontal(|ui| {
+                        ui.monospace(format!("{:<13} {}", label.range(), label.name));
+                        if ui.small_button("x").clicked() {
+                            removed = Some(label.addr);
+                        }
+                    });
+                }
+                if let Some(addr) = removed {
+                    commands.push(format!("unlabel {:#05x}", addr));
+                }
+                ui.horizontal(|ui| {
+                    let field = ui.add(egui::TextEdit::singleline(&mut self.new_label).hint_text(lang.tr("ui.label_hint")).desired_width(160.0));
+                    let entered = field.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+                    if (ui.button(lang.tr("ui.add")).clicked() || entered) && !self.new_label.trim().is_empty() {
+                        commands.push(format!("label {}", self.new_label.trim()));
+                        self.new_label.clear();
+                    }
+                });
+            });
+            let ram = chip8.get_ram();
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+            egui::ScrollArea::vertical().show_rows(ui, row_height, ram.len() / MEMORY_ROW, |ui, rows| {
+                for row in rows {
+                    let addr = row * MEMORY_ROW;
+                    // the row PC and I point into stand out, and the bytes just written
+                    let color = match () {
+                        _ if (addr..addr + MEMORY_ROW).contains(&chip8.pc) => Color32::LIGHT_GREEN,
+                        _ if (addr..addr + MEMORY_ROW).contains(&chip8.i) => Color32::LIGHT_BLUE,
+                        _ => ui.visuals().text_color(),
+                    };
+                    let format = |color| TextFormat { font_id: font_id.clone(), color, ..Default::default() };
+                    let mut job = LayoutJob::default();
+                    job.append(&format!("{:03X} ", addr), 0.0, format(color));
+                    for (a, byte) in ram[addr..addr + MEMORY_ROW].iter().enumerate().map(|(n, b)| (addr + n, b)) {
+                        let color = match () {
+                            _ if self.sprite_row == Some(a) => SPRITE_ROW,
+                            _ if changes.ram_changed(a) => CHANGED,
+                            _ => color,
+                        };
+                        job.append(" ", 0.0, format(color));
+                        // labelled bytes are underlined, the row tells what they are
+                        let underline = match debugger.label_at(a) {
+                            Some(_) => Stroke::new(1.0, color),
+                            None => Stroke::NONE,
+                        };
+                        job.append(&format!("{:02X}", byte), 0.0, TextFormat { underline, ..format(color) });
+                    }
+                    // the names of the labels starting on the row after it, all of the row's on hover
+                    let labels: Vec<&Label> =
+                        debugger.labels.iter().filter(|l| l.addr < addr + MEMORY_ROW && addr < l.addr + l.len).collect();
+                    let starting: Vec<&str> =
+                        labels.iter().filter(|l| l.addr >= addr).map(|l| l.name.as_str()).collect();
+                    if !starting.is_empty() {
+                        job.append(&format!("  {}", starting.join(", ")), 0.0, format(LABEL));
+                    }
+                    let row = ui.label(job);
+                    if !labels.is_empty() {
+                        let text: Vec<String> = labels.iter().map(|l| format!("{} {}", l.range(), l.name)).collect();
+                        row.on_hover_text(text.join("\n"));
+                    }
+                }
+            });
+        });
+
+        egui::Window::new(lang.tr("ui.disassembly")).open(&mut self.disassembly).show(ctx, |ui| {
+            ui.label(lang.tr("ui.disassembly_hint"));
+            let ram = chip8.get_ram();
+            let start = chip8.pc.saturating_sub(2 * DISASM_BEFORE);
+            for addr in (start..chip8.pc + 2 * DISASM_AFTER).step_by(2).filter(|a| a + 1 < ram.len()) {
+                let opcode = (ram[addr] as u16) << 8 | ram[addr + 1] as u16;
+                let mark = if debugger.breakpoints.contains(&addr) { '*' } else { ' ' };
+                let arrow = if addr == chip8.pc { '>' } else { ' ' };
+                let text = format!("{}{} {:03X}: {:04X}  {}", mark, arrow, addr, opcode, disasm::mnemonic(opcode));
+                let text = RichText::new(text).monospace();
+                if ui.selectable_label(addr == chip8.pc, text).clicked() && !debugger.breakpoints.remove(&addr) {
+                    debugger.breakpoints.insert(addr);
+                }
+            }
+        });
+
+        egui::Window::new(lang.tr("ui.breakpoints")).open(&mut self.breakpoints).show(ctx, |ui| {
+            let mut removed = None;
+            for &addr in &debugger.breakpoints {
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("{:#05x}", addr));
+                    if ui.small_button("x").clicked() {
+                        removed = Some(addr);
+                    }
+                });
+            }
+            if let Some(addr) = removed {
+                debugger.breakpoints.remove(&addr);
+            }
+            ui.horizontal(|ui| {
+                let field = ui.add(egui::TextEdit::singleline(&mut self.new_break).hint_text("0x200").desired_width(80.0));
+                let entered = field.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+                if (ui.button(lang.tr("ui.add")).clicked() || entered) && !self.new_break.trim().is_empty() {
+                    commands.push(format!("break {}", self.new_break.trim()));
+                    self.new_break.clear();
+                }
+            });
+        });
+
+        egui::Window::new(lang.tr("ui.watches")).open(&mut self.watches).show(ctx, |ui| {
+            let mut removed = None;
+            for (n, line) in debugger.watch_lines(machine).iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.monospace(line);
+                    if ui.small_button("x").clicked() {
+                        removed = Some(n + 1);
+                    }
+                });
+            }
+            if let Some(n) = removed {
+                commands.push(format!("unwatch {}", n));
+            }
+            ui.horizontal(|ui| {
+                let field = ui.add(egui::TextEdit::singleline(&mut self.new_watch).hint_text("ram[i + 1]").desired_width(160.0));
+                let entered = field.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+                if (ui.button(lang.tr("ui.add")).clicked() || entered) && !self.new_watch.trim().is_empty() {
+                    commands.push(format!("watch {}", self.new_watch.trim()));
+                    self.new_watch.clear();
+                }
+            });
+        });
+
+        let vram = chip8.get_vram();
+        let texture = self.vram.as_ref().map(|t| t.id());
+        egui::Window::new(lang.tr("ui.framebuffer")).open(&mut self.framebuffer).show(ctx, |ui| {
+            let Some(texture) = texture else {
+                return;
+            };
+            // whole pixels per cell, 4 or more
+            let scale = ((ui.available_width() / vram.width() as f32).floor()).max(4.0);
+            let size = vec2(vram.width() as f32, vram.height() as f32) * scale;
+            let response = ui.add(egui::Image::new(SizedTexture::new(texture, size)).sense(Sense::hover()));
+            let hovered = response.hover_pos().map(|pos| ((pos - response.rect.min) / scale).floor());
+            let text = match hovered {
+                Some(cell) => {
+                    let (x, y) = (cell.x as usize, cell.y as usize);
+                    let key = if vram.get(x, y) == Some(true) { "msg.pixel_on" } else { "msg.pixel_off" };
+                    lang.format(key, &[&x, &y])
+                }
+                None => format!("{}x{}", vram.width(), vram.height()),
+            };
+            ui.label(text);
+        });
+    }
+}
+
+fn pointer_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        _ => None,
+    }
+}
+
+fn egui_modifiers(keymod: Mod) -> Modifiers {
+    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    Modifiers {
+        alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        ctrl,
+        shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        mac_cmd: false,
+        command: ctrl,
+    }
+}
+
+/**
+ * The keys text fields and scrolling need, typing itself comes in as text input.
+ */
+fn egui_key(keycode: Keycode) -> Option<Key> {
+    let key = match keycode {
+        Keycode::Left => Key::ArrowLeft,
+        Keycode::Right => Key::ArrowRight,
+        Keycode::Up => Key::ArrowUp,
+        Keycode::Down => Key::ArrowDown,
+        Keycode::Home => Key::Home,
+        Keycode::End => Key::End,
+        Keycode::PageUp => Key::PageUp,
+        Keycode::PageDown => Key::PageDown,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Delete => Key::Delete,
+        Keycode::Return | Keycode::KpEnter => Key::Enter,
+        Keycode::Tab => Key::Tab,
+        Keycode::Escape => Key::Escape,
+        Keycode::A => Key::A,
+        Keycode::C => Key::C,
+        Keycode::V => Key::V,
+        Keycode::X => Key::X,
+        Keycode::Z => Key::Z,
+        _ => return None,
+    };
+    Some(key)
+}
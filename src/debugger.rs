@@ -0,0 +1,784 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::{self, Write};
+
+use crate::coords;
+use crate::expr::Expr;
+use crate::hardware::{Chip8, Error, HaltReason, State, V_REG_COUNT};
+use crate::machine::Machine;
+use crate::savestate;
+use crate::trace::TraceRecord;
+
+// instructions kept for the post-mortem, see `Debugger::fault`
+pub const HISTORY_LEN: usize = 32;
+// instructions `hist` shows without a count
+const HIST_DEFAULT: usize = 16;
+
+/**
+ * Breakpoints and run control on top of a `Machine`, driven by text commands.
+ *
+ * ```
+ * use chip8::debugger::Debugger;
+ * use chip8::machine::Machine;
+ *
+ * let mut machine = Machine::new();
+ * // V0 += 1, loop forever
+ * machine.load_rom(&[0x70, 0x01, 0x12, 0x00]);
+ *
+ * let mut debugger = Debugger::new();
+ * debugger.execute(&mut machine, "break 0x202");
+ * debugger.run_frame(&mut machine);
+ * assert!(debugger.paused);
+ * assert_eq!(machine.chip8.pc, 0x202);
+ *
+ * debugger.execute(&mut machine, "step 3");
+ * assert_eq!(machine.chip8.v[0], 2);
+ * ```
+ *
+ * When the core faults, execution pauses instead of stopping the program, and the fault
+ * stays in `fault` until it is dealt with:
+ *
+ * ```
+ * use chip8::debugger::Debugger;
+ * use chip8::hardware::Error;
+ * use chip8::machine::Machine;
+ *
+ * let mut machine = Machine::new();
+ * // V0 = 1, then an instruction that doesn't exist
+ * machine.load_rom(&[0x60, 0x01, 0x80, 0x0F, 0x12, 0x00]);
+ *
+ * let mut debugger = Debugger::new();
+ * let report = debugger.run_frame(&mut machine).unwrap();
+ * assert!(report.contains("illegal opcode 800F"));
+ * assert_eq!(debugger.fault, Some(Error::IllegalOpcode { pc: 0x202, opcode: 0x800F }));
+ *
+ * // skip it and carry on
+ * debugger.execute(&mut machine, "nop");
+ * assert_eq!(debugger.fault, None);
+ * assert_eq!(machine.chip8.pc, 0x204);
+ * ```
+ */
+pub struct Debugger {
+    pub breakpoints: BTreeSet<usize>,
+    pub paused: bool,
+    // set by `continue` so we don't immediately stop on the breakpoint we're sitting on
+    skip_break: bool,
+    // where `over`, `out`, `draw`, `cls` and `input` pause
+    until: Option<Until>,
+    // the error that paused execution, until `nop` skips the instruction or the frontend resets
+    pub fault: Option<Error>,
+    // the last `HISTORY_LEN` instructions, oldest first
+    history: VecDeque<TraceRecord>,
+    // what the last instruction `step` ran changed, until execution continues
+    pub changes: Option<StateDiff>,
+    // pause when the program looks stuck, see `Machine::runaway_limit`
+    pub break_on_runaway: bool,
+    // expressions shown with their values while the program runs, see `watch_lines`
+    pub watches: Vec<Expr>,
+    // names for ranges of RAM, ordered by address, see `add_label`
+    pub labels: Vec<Label>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            paused: false,
+            skip_break: false,
+            until: None,
+            fault: None,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            changes: None,
+            break_on_runaway: false,
+            watches: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    /**
+     * Run the rest of the current frame unless paused, stopping at breakpoints and faults.
+     * Returns a message when execution stopped, the post-mortem report for a fault.
+     */
+    pub fn run_frame(&mut self, machine: &mut Machine) -> Option<String> {
+        if self.paused {
+            return None;
+        }
+        self.changes = None;
+
+        loop {
+            let pc = machine.chip8.pc;
+            if let Some(until) = self.until.filter(|until| until.reached(machine) && !self.skip_break) {
+                self.until = None;
+                self.paused = true;
+                let at = format!("{:#05x}: {:04X}", pc, opcode_at(machine, pc));
+                return Some(match until {
+                    Until::Frame(frame) => format!("paused at frame {}, {}", frame, at),
+                    _ => format!("paused at {}", at),
+                });
+            }
+            if self.breakpoints.contains(&pc) && !self.skip_break {
+                self.paused = true;
+                self.until = None;
+                return Some(format!("breakpoint at {:#05x}", pc));
+            }
+            self.skip_break = false;
+
+            let stuck = machine.runaway.is_some();
+            match self.step_one(machine) {
+                Ok(_) if self.break_on_runaway && !stuck && machine.runaway.is_some() => {
+                    self.paused = true;
+                    self.until = None;
+                    return Some(format!(
+                        "probable infinite loop at {:#05x}: {} instructions without drawing or waiting",
+                        pc,
+                        machine.runaway_limit.unwrap_or_default()
+                    ));
+                }
+                Ok(true) => return None,
+                Ok(false) => {}
+                Err(e) => return Some(self.fail(machine, e)),
+            }
+        }
+    }
+
+    /**
+     * Step the machine, remembering the instruction for the post-mortem.
+     */
+    fn step_one(&mut self, machine: &mut Machine) -> Result<bool, Error> {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(TraceRecord::from_chip8(&machine.chip8));
+        machine.step()
+    }
+
+    /**
+     * Pause on a fault and describe it.
+     */
+    fn fail(&mut self, machine: &Machine, e: Error) -> String {
+        self.paused = true;
+        self.until = None;
+        self.fault = Some(e);
+        format!("{}\n'nop' skips the instruction and continues", self.post_mortem(machine))
+    }
+
+    /**
+     * The fault with the faulting instruction, the registers and the instructions leading up
+     * to it, oldest first. Empty without a fault.
+     */
+    pub fn post_mortem(&self, machine: &Machine) -> String {
+        let Some(fault) = self.fault else {
+            return String::new();
+        };
+        let mut out = String::new();
+
+        writeln!(out, "fault: {}", fault).unwrap();
+        writeln!(out, "{:#05x}: {:04X}", machine.chip8.pc, opcode_at(machine, machine.chip8.pc)).unwrap();
+        writeln!(out, "{}", registers(machine)).unwrap();
+        writeln!(out, "last {} instructions:", self.history.len()).unwrap();
+        for record in &self.history {
+            writeln!(out, "{}", record).unwrap();
+        }
+
+        out.trim_end().to_string()
+    }
+
+
+    /**
+     * Execute a single debugger command and return its output.
+     *
+     * ```
+     * use chip8::debugger::Debugger;
+     * use chip8::machine::Machine;
+     *
+     * let mut machine = Machine::new();
+     * let mut debugger = Debugger::new();
+     * assert_eq!(debugger.execute(&mut machine, "mem 0xFFE 4"), "0xffe: 00 00");
+     * // nothing past the end of RAM, however far
+     * assert_eq!(debugger.execute(&mut machine, "mem 0xFFFFFFFFFFFFFFFF 16"), "");
+     * ```
+     */
+    pub fn execute(&mut self, machine: &mut Machine, line: &str) -> String {
+        let args: Vec<&str> = line.split_whitespace().collect();
+
+        let result = match args.as_slice() {
+            [] => Ok(String::new()),
+            ["help" | "h"] => Ok(HELP.to_string()),
+            ["reg" | "r"] => Ok(registers(machine)),
+            ["mem" | "m", addr] => parse_num(addr).map(|a| memory(machine, &self.labels, a, 16)),
+            ["mem" | "m", addr, len] => {
+                parse_num(addr).and_then(|a| parse_num(len).map(|l| memory(machine, &self.labels, a, l)))
+            }
+            ["step" | "s"] => self.step(machine, 1),
+            ["step" | "s", n] => parse_num(n).and_then(|n| self.step(machine, n)),
+            ["over" | "o"] => self.step_over(machine),
+            ["out"] => self.step_out(machine),
+            ["draw"] => Ok(self.run_until(Until::Draw, "running until the next draw")),
+            ["cls"] => Ok(self.run_until(Until::Clear, "running until the screen is cleared")),
+            ["input"] => Ok(self.run_until(Until::Input, "running until the keys are read")),
+            ["runto-frame", n] => parse_num(n).and_then(|n| self.run_to_frame(machine, n as u64)),
+            ["break" | "b", addr] => parse_num(addr).map(|a| {
+                self.breakpoints.insert(a);
+                format!("breakpoint set at {:#05x}", a)
+            }),
+            ["delete" | "d", addr] => parse_num(addr).map(|a| {
+                if self.breakpoints.remove(&a) {
+                    format!("breakpoint at {:#05x} removed", a)
+                } else {
+                    format!("no breakpoint at {:#05x}", a)
+                }
+            }),
+            ["breaks"] => Ok(self
+                .breakpoints
+                .iter()
+                .map(|a| format!("{:#05x}", a))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ["continue" | "c"] => {
+                self.paused = false;
+                self.skip_break = true;
+                Ok("continuing".to_string())
+            }
+            ["nop"] => match self.fault.take() {
+                Some(_) => {
+                    machine.chip8.pc += machine.chip8.instruction_len(machine.chip8.pc);
+                    self.paused = false;
+                    Ok(format!("skipped, continuing at {:#05x}", machine.chip8.pc))
+                }
+                None => Err("no fault to skip".to_string()),
+            },
+            ["watch", _, ..] => {
+                let text = line.trim_start().trim_start_matches("watch");
+                Expr::parse(text).map(|expr| {
+                    self.watches.push(expr);
+                    format!("watch {} added", self.watches.len())
+                })
+            }
+            ["watches" | "watch"] => Ok(self.watch_lines(machine).join("\n")),
+            ["unwatch", n] => parse_num(n).and_then(|n| match n {
+                n if (1..=self.watches.len()).contains(&n) => Ok(format!("watch {} removed", self.watches.remove(n - 1))),
+                n => Err(format!("no watch {}", n)),
+            }),
+            ["label", addr, len, name @ ..] if !name.is_empty() => parse_num(addr).and_then(|addr| match parse_num(len)? {
+                0 => Err("a label needs a length of at least 1".to_string()),
+                len if addr.checked_add(len).is_none_or(|end| end > machine.chip8.get_ram().len()) => {
+                    Err(format!("{} bytes from {:#05x} don't fit in RAM", len, addr))
+                }
+                len => {
+                    let label = Label { addr, len, name: name.join(" ") };
+                    let text = format!("{} labelled '{}'", label.range(), label.name);
+                    self.add_label(label);
+                    Ok(text)
+                }
+            }),
+            ["unlabel", addr] => parse_num(addr).map(|a| match self.labels.iter().position(|l| l.addr == a) {
+                Some(n) => format!("label '{}' removed", self.labels.remove(n).name),
+                None => format!("no label at {:#05x}", a),
+            }),
+            ["labels"] => Ok(self.labels.iter().map(|l| format!("{:<13} {}", l.range(), l.name)).collect::<Vec<_>>().join("\n")),
+            ["pm"] => Ok(self.post_mortem(machine)),
+            ["checksum"] => Ok(format!("frame {}, state checksum {:08x}", machine.frame(), savestate::checksum(machine))),
+            ["hist"] => Ok(history(machine, HIST_DEFAULT)),
+            ["hist", n] => parse_num(n).map(|n| history(machine, n)),
+            ["pause" | "p"] => {
+                self.paused = true;
+                self.until = None;
+                Ok(format!("paused at {:#05x}", machine.chip8.pc))
+            }
+            _ => Err(format!("unknown command '{}', try 'help'", line.trim())),
+        };
+
+        result.unwrap_or_else(|e| e)
+    }
+
+    /**
+     * Name a range of RAM, replacing the label that started at the same address. Labels
+     * are shown next to the bytes in `mem` dumps and the memory panel, and next to watches
+     * that read a labelled byte.
+     *
+     * ```
+     * use chip8::debugger::Debugger;
+     * use chip8::machine::Machine;
+     *
+     * let mut machine = Machine::new();
+     * // VA = 0x2A, I = 0x300, store VA's decimal digits at I
+     * machine.load_rom(&[0x6A, 0x2A, 0xA3, 0x00, 0xFA, 0x33]);
+     *
+     * let mut debugger = Debugger::new();
+     * assert_eq!(debugger.execute(&mut machine, "label 0x300 3 score BCD"), "0x300-0x302 labelled 'score BCD'");
+     * assert_eq!(debugger.label_at(0x301).unwrap().name, "score BCD");
+     * assert_eq!(debugger.label_at(0x303), None);
+     * assert_eq!(debugger.execute(&mut machine, "label 0xFFE 3 past the end"), "3 bytes from 0xffe don't fit in RAM");
+     *
+     * debugger.execute(&mut machine, "step 3");
+     * assert_eq!(debugger.execute(&mut machine, "mem 0x300 4"), "0x300: 00 04 02 00  score BCD");
+     * debugger.execute(&mut machine, "watch ram[0x301]");
+     * assert_eq!(debugger.watch_lines(&machine), ["1: ram[0x301] = 4  [score BCD]"]);
+     * ```
+     */
+    pub fn add_label(&mut self, label: Label) {
+        self.labels.retain(|l| l.addr != label.addr);
+        let at = self.labels.partition_point(|l| l.addr < label.addr);
+        self.labels.insert(at, label);
+    }
+
+    /**
+     * The label `addr` is in; where labels overlap, the one that starts closest before it.
+     */
+    pub fn label_at(&self, addr: usize) -> Option<&Label> {
+        self.labels.iter().rev().find(|l| l.contains(addr))
+    }
+
+    /**
+     * The watches, numbered for `unwatch`, with their values as of now.
+     *
+     * ```
+     * use chip8::debugger::Debugger;
+     * use chip8::machine::Machine;
+     *
+     * let mut machine = Machine::new();
+     * // VA = 0x2A
+     * machine.load_rom(&[0x6A, 0x2A]);
+     *
+     * let mut debugger = Debugger::new();
+     * debugger.execute(&mut machine, "watch v[0xA]");
+     * debugger.execute(&mut machine, "watch ram[0x5000]");
+     * debugger.execute(&mut machine, "step");
+     * assert_eq!(debugger.watch_lines(&machine), ["1: v[0xA] = 0x2a (42)", "2: ram[0x5000] = ram[0x5000] is outside RAM"]);
+     * ```
+     */
+    pub fn watch_lines(&self, machine: &Machine) -> Vec<String> {
+        let value = |expr: &Expr| match expr.eval(machine) {
+            Ok(n) if (0..10).contains(&n) => n.to_string(),
+            Ok(n) if n > 0 => format!("{:#x} ({})", n, n),
+            Ok(n) => n.to_string(),
+            Err(e) => e,
+        };
+        // the labels of the bytes it reads, if it reads any at a fixed address
+        let labels = |expr: &Expr| {
+            let mut names: Vec<&str> = expr.ram_addrs().into_iter().filter_map(|a| self.label_at(a)).map(|l| l.name.as_str()).collect();
+            names.dedup();
+            if names.is_empty() {
+                String::new()
+            } else {
+                format!("  [{}]", names.join(", "))
+            }
+        };
+        self.watches.iter().enumerate().map(|(n, expr)| format!("{}: {} = {}{}", n + 1, expr, value(expr), labels(expr))).collect()
+    }
+
+    /**
+     * Pause and run `count` instructions, printing each one.
+     */
+    fn step(&mut self, machine: &mut Machine, count: usize) -> Result<String, String> {
+        let mut out = String::new();
+        self.paused = true;
+
+        for _ in 0..count {
+            let pc = machine.chip8.pc;
+            let before = Before::of(&machine.chip8);
+            write!(out, "{:#05x}: {:04X}", pc, opcode_at(machine, pc)).unwrap();
+            if let Err(e) = self.step_one(machine) {
+                return Err(format!("{}\n{}", out, self.fail(machine, e)));
+            }
+            let changes = StateDiff::between(&before, &machine.chip8);
+            writeln!(out, "  {}", changes).unwrap();
+            self.changes = Some(changes);
+        }
+
+        Ok(out.trim_end().to_string())
+    }
+
+    /**
+     * Run the `2NNN` call at PC to completion and pause after it, a plain `step` for any
+     * other instruction. Breakpoints and faults inside the subroutine still stop it.
+     *
+     * ```
+     * use chip8::debugger::Debugger;
+     * use chip8::machine::Machine;
+     *
+     * let mut machine = Machine::new();
+     * // call 0x206, loop forever; 0x206: V0 = 7, return
+     * machine.load_rom(&[0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x60, 0x07, 0x00, 0xEE]);
+     *
+     * let mut debugger = Debugger::new();
+     * debugger.execute(&mut machine, "pause");
+     * debugger.execute(&mut machine, "over");
+     * assert_eq!(debugger.run_frame(&mut machine).unwrap(), "paused at 0x202: 1202");
+     * assert_eq!(machine.chip8.v[0], 7);
+     * ```
+     */
+    fn step_over(&mut self, machine: &mut Machine) -> Result<String, String> {
+        let pc = machine.chip8.pc;
+        if opcode_at(machine, pc) >> 12 != 0x2 {
+            return self.step(machine, 1);
+        }
+        let depth = machine.chip8.stack.len();
+        Ok(self.run_until(Until::Return { addr: pc + 2, depth }, &format!("stepping over the call at {:#05x}", pc)))
+    }
+
+    /**
+     * Run until the subroutine PC is in returns, and pause after the call that got there.
+     */
+    fn step_out(&mut self, machine: &mut Machine) -> Result<String, String> {
+        let Some(&ret) = machine.chip8.stack.last() else {
+            return Err("not in a subroutine".to_string());
+        };
+        let depth = machine.chip8.stack.len() - 1;
+        Ok(self.run_until(Until::Return { addr: ret, depth }, &format!("running until the return to {:#05x}", ret)))
+    }
+
+    /**
+     * Run until `frame` frames have completed, and pause before the first instruction of
+     * the next one. Frames count instructions, not time, so with the same input (e.g. a
+     * replayed macro) and `Chip8::seed` for `CXNN`, every run pauses on the same state.
+     *
+     * ```
+     * use chip8::debugger::Debugger;
+     * use chip8::machine::Machine;
+     *
+     * let mut machine = Machine::new();
+     * // V0 += 1, loop forever
+     * machine.load_rom(&[0x70, 0x01, 0x12, 0x00]);
+     *
+     * let mut debugger = Debugger::new();
+     * debugger.execute(&mut machine, "runto-frame 5");
+     * while !debugger.paused {
+     *     debugger.run_frame(&mut machine);
+     * }
+     * assert_eq!(machine.frame(), 5);
+     * // 8 instructions a frame, half of them adds
+     * assert_eq!(machine.chip8.v[0], 20);
+     * assert_eq!(machine.chip8.pc, 0x200);
+     * assert_eq!(debugger.execute(&mut machine, "runto-frame 3"), "already past frame 3, at frame 5");
+     * ```
+     */
+    fn run_to_frame(&mut self, machine: &Machine, frame: u64) -> Result<String, String> {
+        if frame <= machine.frame() {
+            return Err(format!("already past frame {}, at frame {}", frame, machine.frame()));
+        }
+        Ok(self.run_until(Until::Frame(frame), &format!("running until frame {}", frame)))
+    }
+
+    /**
+     * Continue until `until` is reached, not counting the instruction at PC. Returns `msg`.
+     */
+    fn run_until(&mut self, until: Until, msg: &str) -> String {
+        self.until = Some(until);
+        self.paused = false;
+        self.skip_break = true;
+        msg.to_string()
+    }
+}
+
+/**
+ * Where a `Debugger::run_until` pauses, before the instruction runs.
+ *
+ * ```
+ * use chip8::debugger::Debugger;
+ * use chip8::machine::Machine;
+ *
+ * let mut machine = Machine::new();
+ * // V0 = 1, V1 = 2, clear the screen, draw, wait for a key
+ * machine.load_rom(&[0x60, 0x01, 0x61, 0x02, 0x00, 0xE0, 0xD0, 0x15, 0xF0, 0x0A]);
+ *
+ * let mut debugger = Debugger::new();
+ * debugger.execute(&mut machine, "pause");
+ * debugger.execute(&mut machine, "draw");
+ * assert_eq!(debugger.run_frame(&mut machine).unwrap(), "paused at 0x206: D015");
+ * debugger.execute(&mut machine, "input");
+ * assert_eq!(debugger.run_frame(&mut machine).unwrap(), "paused at 0x208: F00A");
+ * ```
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Until {
+    // an address, with this many return addresses on the stack so a recursive call passing
+    // by doesn't count
+    Return { addr: usize, depth: usize },
+    // DXYN
+    Draw,
+    // 00E0
+    Clear,
+    // EX9E, EXA1 and FX0A
+    Input,
+    // the start of the frame after this many have run
+    Frame(u64),
+}
+
+impl Until {
+    fn reached(self, machine: &Machine) -> bool {
+        let (pc, opcode) = (machine.chip8.pc, opcode_at(machine, machine.chip8.pc));
+        match self {
+            Until::Return { addr, depth } => pc == addr && machine.chip8.stack.len() == depth,
+            Until::Draw => opcode >> 12 == 0xD,
+            Until::Clear => opcode == 0x00E0,
+            Until::Input => matches!((opcode >> 12, opcode & 0xFF), (0xE, 0x9E | 0xA1) | (0xF, 0x0A)),
+            Until::Frame(frame) => machine.frame() >= frame && machine.cycle == 0,
+        }
+    }
+}
+
+/**
+ * A name for a range of RAM, like "score BCD" for the three bytes a game keeps its score's
+ * digits in, see `Debugger::add_label`.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Label {
+    pub addr: usize,
+    pub len: usize,
+    pub name: String,
+}
+
+impl Label {
+    pub fn contains(&self, addr: usize) -> bool {
+        (self.addr..self.addr + self.len).contains(&addr)
+    }
+
+    /**
+     * The addresses it covers, like `0x300-0x302`, or just `0x300` for a single byte.
+     */
+    pub fn range(&self) -> String {
+        match self.len {
+            1 => format!("{:#05x}", self.addr),
+            len => format!("{:#05x}-{:#05x}", self.addr, self.addr + len - 1),
+        }
+    }
+}
+
+/**
+ * What one instruction changed: the V registers, I, the stack pointer, the timers and RAM
+ * (not what went to a peripheral), each with its new value. PC changes with every
+ * instruction and isn't listed.
+ *
+ * ```
+ * use chip8::debugger::Debugger;
+ * use chip8::machine::Machine;
+ *
+ * let mut machine = Machine::new();
+ * // V0 = 5, I = 0x300, store V0's decimal digits at I
+ * machine.load_rom(&[0x60, 0x05, 0xA3, 0x00, 0xF0, 0x33]);
+ *
+ * let mut debugger = Debugger::new();
+ * debugger.execute(&mut machine, "step 2");
+ * assert_eq!(debugger.changes.as_ref().unwrap().i, Some(0x300));
+ * debugger.execute(&mut machine, "step");
+ * let changes = debugger.changes.as_ref().unwrap();
+ * // 0 and 0 were there already
+ * assert_eq!(changes.ram, vec![(0x302, 5)]);
+ * assert_eq!(changes.to_string(), "[0x302]=05");
+ * ```
+ */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct StateDiff {
+    pub v: Vec<(usize, u8)>,
+    pub i: Option<usize>,
+    pub sp: Option<usize>,
+    pub tim_delay: Option<u8>,
+    pub tim_snd: Option<u8>,
+    pub ram: Vec<(usize, u8)>,
+}
+
+// what `StateDiff` compares with, taken before the instruction runs
+struct Before {
+    v: [u8; V_REG_COUNT],
+    i: usize,
+    sp: usize,
+    tim_delay: u8,
+    tim_snd: u8,
+    ram: Box<[u8]>,
+}
+
+impl Before {
+    fn of(chip8: &Chip8) -> Before {
+        Before {
+            v: chip8.v,
+            i: chip8.i,
+            sp: chip8.sp,
+            tim_delay: chip8.tim_delay,
+            tim_snd: chip8.tim_snd,
+            ram: chip8.get_ram().into(),
+        }
+    }
+}
+
+impl StateDiff {
+    fn between(before: &Before, chip8: &Chip8) -> StateDiff {
+        let ram = chip8.get_ram();
+
+        StateDiff {
+            v: (0..V_REG_COUNT).filter(|n| before.v[*n] != chip8.v[*n]).map(|n| (n, chip8.v[n])).collect(),
+            i: (before.i != chip8.i).then_some(chip8.i),
+            sp: (before.sp != chip8.sp).then_some(chip8.sp),
+            tim_delay: (before.tim_delay != chip8.tim_delay).then_some(chip8.tim_delay),
+            tim_snd: (before.tim_snd != chip8.tim_snd).then_some(chip8.tim_snd),
+            ram: (0..ram.len().min(before.ram.len())).filter(|a| before.ram[*a] != ram[*a]).map(|a| (a, ram[a])).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == StateDiff::default()
+    }
+
+    pub fn v_changed(&self, n: usize) -> bool {
+        self.v.iter().any(|(reg, _)| *reg == n)
+    }
+
+    pub fn ram_changed(&self, addr: usize) -> bool {
+        self.ram.iter().any(|(a, _)| *a == addr)
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts: Vec<String> = self.v.iter().map(|(n, value)| format!("V{:X}={:02X}", n, value)).collect();
+        parts.extend(self.i.map(|i| format!("I={:#05x}", i)));
+        parts.extend(self.sp.map(|sp| format!("SP={}", sp)));
+        parts.extend(self.tim_delay.map(|t| format!("DT={:02X}", t)));
+        parts.extend(self.tim_snd.map(|t| format!("ST={:02X}", t)));
+        parts.extend(self.ram.iter().map(|(a, value)| format!("[{:#05x}]={:02X}", a, value)));
+        if parts.is_empty() {
+            write!(f, "no changes")
+        } else {
+            write!(f, "{}", parts.join(" "))
+        }
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger::new()
+    }
+}
+
+const HELP: &str = "\
+reg                 show registers, timers and stack
+mem <addr> [len]    hex dump memory
+step [n]            pause and execute n instructions (default 1)
+over                run a call to completion, or step if it isn't one
+out                 run until the current subroutine returns
+draw                run until the next DXYN
+cls                 run until the next 00E0
+input               run until the next EX9E, EXA1 or FX0A
+runto-frame <n>     run until n frames have run, and pause at the start of the next
+break <addr>        set a breakpoint
+delete <addr>       remove a breakpoint
+breaks              list breakpoints
+watch <expr>        show an expression's value as the program runs, e.g. ram[i + 1]
+watches             list the watches with their values
+unwatch <n>         remove the nth watch
+label <addr> <len> <name>
+                    name len bytes of RAM, shown in mem dumps and next to watches
+unlabel <addr>      remove the label starting at addr
+labels              list labels
+continue            resume execution
+pause               pause execution
+nop                 after a fault, skip the faulting instruction and continue
+pm                  show the post-mortem of the last fault
+hist [n]            show the last n executed instructions (default 16, up to 1024)
+checksum            show the frame and state checksum, see savestate::checksum
+reset               restart the program (window only)
+quit                quit (window only)";
+
+/**
+ * Parse a number as hex with a `0x` prefix, decimal otherwise.
+ */
+fn parse_num(s: &str) -> Result<usize, String> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse::<usize>(),
+    };
+    parsed.map_err(|_| format!("invalid number '{}'", s))
+}
+
+/**
+ * If the instruction at PC is a `DXYN`, the screen area it will draw to as
+ * (x, y, width, height), clipped to the screen.
+ *
+ * ```
+ * use chip8::debugger;
+ * use chip8::machine::Machine;
+ *
+ * let mut machine = Machine::new();
+ * // V0 = 60, V1 = 2, draw 5 rows at (V0, V1)
+ * machine.load_rom(&[0x60, 0x3C, 0x61, 0x02, 0xD0, 0x15]);
+ * assert_eq!(debugger::next_sprite(&machine.chip8), None);
+ *
+ * machine.step().unwrap();
+ * machine.step().unwrap();
+ * assert_eq!(debugger::next_sprite(&machine.chip8), Some((60, 2, 4, 5)));
+ * ```
+ */
+pub fn next_sprite(chip8: &Chip8) -> Option<(usize, usize, usize, usize)> {
+    let ram = chip8.get_ram();
+    let op = (ram[chip8.pc % ram.len()] as u16) << 8 | ram[(chip8.pc + 1) % ram.len()] as u16;
+    if op >> 12 != 0xD {
+        return None;
+    }
+
+    let vram = chip8.get_vram();
+    let x = chip8.v[((op >> 8) & 0xF) as usize] as usize;
+    let y = chip8.v[((op >> 4) & 0xF) as usize] as usize;
+
+    coords::clip_rect(x, y, 8, (op & 0xF) as usize, vram.width(), vram.height())
+}
+
+fn opcode_at(machine: &Machine, addr: usize) -> u16 {
+    let ram = machine.chip8.get_ram();
+    (ram[addr % ram.len()] as u16) << 8 | ram[(addr + 1) % ram.len()] as u16
+}
+
+fn registers(machine: &Machine) -> String {
+    let c = &machine.chip8;
+    let mut out = String::new();
+
+    writeln!(out, "PC {:#05x}  I {:#05x}  SP {}  DT {:#04x}  ST {:#04x}  frame {}",
+        c.pc, c.i, c.sp, c.tim_delay, c.tim_snd, machine.frame()).unwrap();
+    for n in 0..V_REG_COUNT {
+        write!(out, "V{:X} {:02X}{}", n, c.v[n], if n % 8 == 7 { "\n" } else { "  " }).unwrap();
+    }
+    let stack: Vec<String> = c.stack.iter().map(|a| format!("{:#05x}", a)).collect();
+    write!(out, "stack [{}]", stack.join(", ")).unwrap();
+    match c.state {
+        State::Halted(HaltReason::WaitKey(reg)) => write!(out, "\nwaiting for a key (FX0A into V{:X})", reg).unwrap(),
+        State::Halted(HaltReason::Exit) => write!(out, "\nexited").unwrap(),
+        State::Running => {}
+    }
+
+    out
+}
+
+/**
+ * The last `count` instructions the machine executed, oldest first, from its `History`.
+ */
+pub fn history(machine: &Machine, count: usize) -> String {
+    let skip = machine.history.len().saturating_sub(count);
+    machine
+        .history
+        .iter()
+        .skip(skip)
+        .map(|(pc, opcode)| format!("{:#05x}: {:04X}", pc, opcode))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn memory(machine: &Machine, labels: &[Label], addr: usize, len: usize) -> String {
+    let ram = machine.chip8.get_ram();
+    let end = addr.saturating_add(len).min(ram.len());
+    // labels line up after the longest line
+    let width = 3 * end.saturating_sub(addr).min(16);
+
+    (addr..end)
+        .step_by(16)
+        .map(|line| {
+            let line_end = (line + 16).min(end);
+            let bytes: Vec<String> = ram[line..line_end].iter().map(|b| format!("{:02X}", b)).collect();
+            // the labels of the bytes on the line after them
+            let names: Vec<&str> =
+                labels.iter().filter(|l| l.addr < line_end && line < l.addr + l.len).map(|l| l.name.as_str()).collect();
+            match names.is_empty() {
+                true => format!("{:#05x}: {}", line, bytes.join(" ")),
+                false => format!("{:#05x}: {:<width$} {}", line, bytes.join(" "), names.join(", ")),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
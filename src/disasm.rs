@@ -0,0 +1,382 @@
+//! Disassembly into the mnemonics of Cowgod's CHIP-8 reference, e.g. `LD V0, 0x05` for
+//! `6005` and `DRW V0, V1, 5` for `D015`. Anything the core doesn't execute comes out as a
+//! `DW` with the raw word, so data mixed in with code still reads sensibly. XO-CHIP's
+//! instructions are decoded too, the 16-bit address after `F000` shows up as data.
+//!
+//! `analyze` goes further and separates code from data by following the program's control
+//! flow from its entry point, see `Analysis`, and splits it into basic blocks for a control
+//! flow graph.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+// where ROMs are loaded and start executing
+pub const ROM_ADDR: usize = 0x200;
+// data bytes per line of a listing
+const DATA_PER_LINE: usize = 8;
+
+/**
+ * Mnemonic for a single instruction.
+ *
+ * ```
+ * use chip8::disasm;
+ *
+ * assert_eq!(disasm::mnemonic(0x00E0), "CLS");
+ * assert_eq!(disasm::mnemonic(0x6A05), "LD VA, 0x05");
+ * assert_eq!(disasm::mnemonic(0xD015), "DRW V0, V1, 5");
+ * assert_eq!(disasm::mnemonic(0x800F), "DW 0x800f");
+ * assert_eq!(disasm::mnemonic(0x5132), "LD [I], V1-V3");
+ * assert_eq!(disasm::mnemonic(0xF201), "PLANE 2");
+ * assert_eq!(disasm::mnemonic(0x00C4), "SCD 4");
+ * ```
+ */
+pub fn mnemonic(opcode: u16) -> String {
+    let x = (opcode >> 8) & 0xF;
+    let y = (opcode >> 4) & 0xF;
+    let n = opcode & 0xF;
+    let nn = opcode & 0xFF;
+    let nnn = opcode & 0xFFF;
+
+    match opcode >> 12 {
+        0x0 => match nnn {
+            0x0E0 => "CLS".to_string(),
+            0x0EE => "RET".to_string(),
+            0x0C0..=0x0CF => format!("SCD {}", n),
+            0x0FB => "SCR".to_string(),
+            0x0FC => "SCL".to_string(),
+            0x0FD => "EXIT".to_string(),
+            0x0FE => "LOW".to_string(),
+            0x0FF => "HIGH".to_string(),
+            _ => format!("SYS {:#05x}", nnn),
+        },
+        0x1 => format!("JP {:#05x}", nnn),
+        0x2 => format!("CALL {:#05x}", nnn),
+        0x3 => format!("SE V{:X}, {:#04x}", x, nn),
+        0x4 => format!("SNE V{:X}, {:#04x}", x, nn),
+        0x5 => match n {
+            0x0 => format!("SE V{:X}, V{:X}", x, y),
+            0x2 => format!("LD [I], V{:X}-V{:X}", x, y),
+            0x3 => format!("LD V{:X}-V{:X}, [I]", x, y),
+            _ => data(opcode),
+        },
+        0x6 => format!("LD V{:X}, {:#04x}", x, nn),
+        0x7 => format!("ADD V{:X}, {:#04x}", x, nn),
+        0x8 => match n {
+            0x0 => format!("LD V{:X}, V{:X}", x, y),
+            0x1 => format!("OR V{:X}, V{:X}", x, y),
+            0x2 => format!("AND V{:X}, V{:X}", x, y),
+            0x3 => format!("XOR V{:X}, V{:X}", x, y),
+            0x4 => format!("ADD V{:X}, V{:X}", x, y),
+            0x5 => format!("SUB V{:X}, V{:X}", x, y),
+            0x6 => format!("SHR V{:X}, V{:X}", x, y),
+            0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X}, V{:X}", x, y),
+            _ => data(opcode),
+        },
+        0x9 if n == 0 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA => format!("LD I, {:#05x}", nnn),
+        0xB => format!("JP V0, {:#05x}", nnn),
+        0xC => format!("RND V{:X}, {:#04x}", x, nn),
+        0xD => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE => match nn {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => data(opcode),
+        },
+        0xF => match nn {
+            0x00 if x == 0 => "LD I, LONG".to_string(),
+            0x01 if x <= 3 => format!("PLANE {}", x),
+            0x02 if x == 0 => "AUDIO".to_string(),
+            0x3A => format!("PITCH V{:X}", x),
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x30 => format!("LD HF, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            _ => data(opcode),
+        },
+        _ => data(opcode),
+    }
+}
+
+fn data(opcode: u16) -> String {
+    format!("DW {:#06x}", opcode)
+}
+
+/**
+ * Whether the instruction conditionally skips the next one (`SE`, `SNE`, `SKP`, `SKNP`).
+ */
+pub fn is_skip(opcode: u16) -> bool {
+    match opcode >> 12 {
+        0x3 | 0x4 => true,
+        0x5 | 0x9 => opcode & 0xF == 0,
+        0xE => matches!(opcode & 0xFF, 0x9E | 0xA1),
+        _ => false,
+    }
+}
+
+/**
+ * Where control can go after the instruction at `addr`. Jumps with a target only known at
+ * run time (`BNNN`), returns and exits lead nowhere that can be followed.
+ */
+pub fn successors(addr: usize, opcode: u16) -> Vec<usize> {
+    let nnn = (opcode & 0xFFF) as usize;
+
+    match opcode >> 12 {
+        0x0 => match opcode & 0xFFF {
+            0x0E0 | 0x0C0..=0x0CF | 0x0FB | 0x0FC | 0x0FE | 0x0FF => vec![addr + 2],
+            0x0EE | 0x0FD => vec![],
+            // the core jumps to NNN, see `Chip8::op_0xxx`
+            _ => vec![nnn],
+        },
+        0x1 => vec![nnn],
+        0x2 => vec![nnn, addr + 2],
+        0xB => vec![],
+        // past the address
+        0xF if opcode == 0xF000 => vec![addr + 4],
+        _ if is_skip(opcode) => vec![addr + 2, addr + 4],
+        _ => vec![addr + 2],
+    }
+}
+
+/**
+ * Code found by following every jump, call and skip from the entry point of a ROM loaded at
+ * `ROM_ADDR`. Whatever can't be reached that way is treated as data, which keeps sprites
+ * and tables from being decoded as nonsense instructions.
+ *
+ * ```
+ * use chip8::disasm;
+ *
+ * // jump over a sprite, then loop forever
+ * let rom = [0x12, 0x04, 0xF0, 0x90, 0x12, 0x04];
+ * let analysis = disasm::analyze(&rom);
+ * assert!(analysis.is_code(0x200) && analysis.is_code(0x204));
+ * assert!(!analysis.is_code(0x202));
+ * assert!(analysis.listing(&rom).contains("0x202  DB 0xf0, 0x90"));
+ * ```
+ */
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Analysis {
+    // addresses instructions start at
+    pub code: BTreeSet<usize>,
+    // jump and call targets, labelled in the listing
+    pub labels: BTreeSet<usize>,
+    // where control goes after each instruction in `code`, see `successors`
+    pub successors: BTreeMap<usize, Vec<usize>>,
+}
+
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let end = ROM_ADDR + rom.len();
+    let opcode_at = |addr: usize| (rom[addr - ROM_ADDR] as u16) << 8 | rom[addr - ROM_ADDR + 1] as u16;
+    let mut analysis = Analysis::default();
+    let mut pending = vec![ROM_ADDR];
+
+    while let Some(addr) = pending.pop() {
+        // outside the ROM, or the last byte which can't hold an instruction
+        if addr < ROM_ADDR || addr + 1 >= end || !analysis.code.insert(addr) {
+            continue;
+        }
+
+        let opcode = opcode_at(addr);
+        if mnemonic(opcode).starts_with("DW") {
+            // not an instruction after all, the program never gets here or faults if it does
+            analysis.code.remove(&addr);
+            continue;
+        }
+        if matches!(opcode >> 12, 0x1 | 0x2) || (opcode >> 12 == 0 && !matches!(opcode & 0xFFF, 0x0C0..=0x0CF | 0x0E0 | 0x0EE | 0x0FB..=0x0FF)) {
+            analysis.labels.insert((opcode & 0xFFF) as usize);
+        }
+        let mut next = successors(addr, opcode);
+        // a skip over `F000 NNNN` skips its address too
+        if is_skip(opcode) && addr + 3 < end && opcode_at(addr + 2) == 0xF000 {
+            next = vec![addr + 2, addr + 6];
+        }
+        pending.extend(&next);
+        analysis.successors.insert(addr, next);
+    }
+
+    analysis
+}
+
+impl Analysis {
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.code.contains(&addr)
+    }
+
+    /**
+     * Disassemble `rom` with labels on jump and call targets and data as `DB` lines.
+     */
+    pub fn listing(&self, rom: &[u8]) -> String {
+        let mut out = String::new();
+
+        for line in self.lines(rom) {
+            match line {
+                Line::Label(addr) => writeln!(out, "L{:03x}:", addr).unwrap(),
+                Line::Code(addr, opcode) => writeln!(out, "{:#05x}  {:04X}  {}", addr, opcode, mnemonic(opcode)).unwrap(),
+                Line::Data(addr, bytes) => {
+                    let bytes: Vec<String> = bytes.iter().map(|b| format!("{:#04x}", b)).collect();
+                    writeln!(out, "{:#05x}  DB {}", addr, bytes.join(", ")).unwrap();
+                }
+            }
+        }
+
+        out
+    }
+
+    /**
+     * The listing and the basic blocks as JSON, for scripts. Addresses and opcodes are
+     * numbers, each line is an instruction with its mnemonic or a run of data bytes.
+     *
+     * ```
+     * use chip8::disasm;
+     *
+     * // V0 = 5, jump to itself, then a data byte
+     * let rom = [0x60, 0x05, 0x12, 0x02, 0xFF];
+     * let json = disasm::analyze(&rom).to_json(&rom);
+     * assert!(json.contains("{\"addr\": 512, \"opcode\": 24581, \"text\": \"LD V0, 0x05\"}"));
+     * assert!(json.contains("{\"addr\": 516, \"data\": [255]}"));
+     * assert!(json.contains("\"labels\": [514]"));
+     * ```
+     */
+    pub fn to_json(&self, rom: &[u8]) -> String {
+        let list = |items: Vec<String>| items.join(", ");
+        let lines: Vec<String> = self
+            .lines(rom)
+            .into_iter()
+            .filter_map(|line| match line {
+                Line::Label(_) => None,
+                Line::Code(addr, opcode) => Some(format!("{{\"addr\": {}, \"opcode\": {}, \"text\": {:?}}}", addr, opcode, mnemonic(opcode))),
+                Line::Data(addr, bytes) => Some(format!("{{\"addr\": {}, \"data\": [{}]}}", addr, list(bytes.iter().map(|b| b.to_string()).collect()))),
+            })
+            .collect();
+        let blocks: Vec<String> = self
+            .blocks()
+            .iter()
+            .map(|block| {
+                let successors = list(block.successors.iter().map(|a| a.to_string()).collect());
+                format!("{{\"start\": {}, \"end\": {}, \"successors\": [{}]}}", block.start, block.end, successors)
+            })
+            .collect();
+
+        format!(
+            "{{\n  \"labels\": [{}],\n  \"lines\": [\n    {}\n  ],\n  \"blocks\": [\n    {}\n  ]\n}}\n",
+            list(self.labels.iter().map(|a| a.to_string()).collect()),
+            lines.join(",\n    "),
+            blocks.join(",\n    "),
+        )
+    }
+
+    // what the listing shows, in address order
+    fn lines<'a>(&self, rom: &'a [u8]) -> Vec<Line<'a>> {
+        let end = ROM_ADDR + rom.len();
+        let mut lines = Vec::new();
+
+        let mut addr = ROM_ADDR;
+        while addr < end {
+            if self.labels.contains(&addr) {
+                lines.push(Line::Label(addr));
+            }
+
+            if self.is_code(addr) {
+                let opcode = (rom[addr - ROM_ADDR] as u16) << 8 | rom[addr - ROM_ADDR + 1] as u16;
+                lines.push(Line::Code(addr, opcode));
+                addr += 2;
+                continue;
+            }
+
+            // data runs up to the next instruction or label
+            let run = (addr..end)
+                .take(DATA_PER_LINE)
+                .take_while(|a| *a == addr || !(self.is_code(*a) || self.labels.contains(a)))
+                .count();
+            lines.push(Line::Data(addr, &rom[addr - ROM_ADDR..addr - ROM_ADDR + run]));
+            addr += run;
+        }
+
+        lines
+    }
+
+    /**
+     * Split the code into basic blocks: runs of instructions that are only entered at the
+     * top and only branch at the bottom. Blocks are ordered by address.
+     *
+     * ```
+     * use chip8::disasm;
+     *
+     * // V0 = 0, skip if V0 == 1, V0 += 1, jump back to the skip
+     * let rom = [0x60, 0x00, 0x30, 0x01, 0x70, 0x01, 0x12, 0x02];
+     * let blocks = disasm::analyze(&rom).blocks();
+     * let starts: Vec<usize> = blocks.iter().map(|b| b.start).collect();
+     * assert_eq!(starts, [0x200, 0x202, 0x204, 0x206]);
+     * assert_eq!(blocks[1].successors, [0x204, 0x206]);
+     * ```
+     */
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut leaders: BTreeSet<usize> = self.labels.iter().copied().filter(|a| self.is_code(*a)).collect();
+        leaders.insert(ROM_ADDR);
+        // whatever follows a branch starts a block of its own
+        for &addr in &self.code {
+            if self.successors[&addr] != [addr + 2] {
+                leaders.extend(self.successors[&addr].iter().filter(|a| self.is_code(**a)));
+            }
+        }
+
+        let mut blocks = Vec::new();
+        for &start in leaders.iter().filter(|a| self.is_code(**a)) {
+            let mut end = start;
+            while self.successors[&end] == [end + 2] && self.is_code(end + 2) && !leaders.contains(&(end + 2)) {
+                end += 2;
+            }
+            let successors = self.successors[&end].iter().copied().filter(|a| self.is_code(*a)).collect();
+            blocks.push(Block { start, end: end + 2, successors });
+        }
+        blocks
+    }
+
+    /**
+     * The basic blocks and the edges between them as a Graphviz digraph, each block labelled
+     * with its instructions.
+     */
+    pub fn to_dot(&self, rom: &[u8]) -> String {
+        let mut out = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+
+        for block in self.blocks() {
+            let lines: Vec<String> = (block.start..block.end)
+                .step_by(2)
+                .map(|addr| {
+                    let opcode = (rom[addr - ROM_ADDR] as u16) << 8 | rom[addr - ROM_ADDR + 1] as u16;
+                    format!("{:#05x}  {}", addr, mnemonic(opcode))
+                })
+                .collect();
+            // \l left-aligns each line in Graphviz
+            writeln!(out, "    b{:03x} [label=\"{}\\l\"];", block.start, lines.join("\\l")).unwrap();
+            for target in &block.successors {
+                writeln!(out, "    b{:03x} -> b{:03x};", block.start, target).unwrap();
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+// one line of a listing
+enum Line<'a> {
+    Label(usize),
+    Code(usize, u16),
+    Data(usize, &'a [u8]),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Block {
+    pub start: usize,
+    // first address after the block's last instruction
+    pub end: usize,
+    // blocks control can continue in
+    pub successors: Vec<usize>,
+}
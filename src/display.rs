@@ -0,0 +1,91 @@
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::Canvas;
+use sdl2::video::{Window, WindowBuilder};
+
+use crate::config::RenderBackend;
+
+/**
+ * What `Render` draws on: the window and a few drawing primitives, all in window
+ * coordinates. Drawing only shows up after `present`.
+ */
+pub trait Display {
+    fn window(&self) -> &Window;
+    fn window_mut(&mut self) -> &mut Window;
+    fn set_draw_color(&mut self, color: Color);
+    fn clear(&mut self);
+    fn fill_rect(&mut self, rect: Rect) -> Result<(), String>;
+    fn draw_rect(&mut self, rect: Rect) -> Result<(), String>;
+    fn draw_line(&mut self, from: Point, to: Point) -> Result<(), String>;
+    fn present(&mut self) -> Result<(), String>;
+
+    /**
+     * Where the emulated screen is and how many rows it has, for backends that filter it
+     * (like the CRT scanlines of the wgpu backend). Others ignore it.
+     */
+    fn set_screen(&mut self, _area: Rect, _rows: usize) {}
+
+    fn fill_rects(&mut self, rects: &[Rect]) -> Result<(), String> {
+        rects.iter().try_for_each(|rect| self.fill_rect(*rect))
+    }
+}
+
+/**
+ * Build the window described by `window` and a display for it.
+ */
+pub fn open(backend: RenderBackend, window: &mut WindowBuilder) -> Result<Box<dyn Display>, String> {
+    match backend {
+        RenderBackend::Sdl => {
+            let window = window.opengl().build().map_err(|e| e.to_string())?;
+            Ok(Box::new(window.into_canvas().build().map_err(|e| e.to_string())?))
+        }
+        #[cfg(feature = "wgpu")]
+        RenderBackend::Wgpu => {
+            let window = window.allow_highdpi().build().map_err(|e| e.to_string())?;
+            Ok(Box::new(crate::wgpu_display::WgpuDisplay::new(window)?))
+        }
+        #[cfg(not(feature = "wgpu"))]
+        RenderBackend::Wgpu => Err("backend 'wgpu' isn't built in".to_string()),
+        // its own frontend rather than a display, see `minifb_frontend`
+        RenderBackend::Minifb => Err("minifb can't draw an SDL window".to_string()),
+    }
+}
+
+impl Display for Canvas<Window> {
+    fn window(&self) -> &Window {
+        Canvas::window(self)
+    }
+
+    fn window_mut(&mut self) -> &mut Window {
+        Canvas::window_mut(self)
+    }
+
+    fn set_draw_color(&mut self, color: Color) {
+        Canvas::set_draw_color(self, color)
+    }
+
+    fn clear(&mut self) {
+        Canvas::clear(self)
+    }
+
+    fn fill_rect(&mut self, rect: Rect) -> Result<(), String> {
+        Canvas::fill_rect(self, rect)
+    }
+
+    fn fill_rects(&mut self, rects: &[Rect]) -> Result<(), String> {
+        Canvas::fill_rects(self, rects)
+    }
+
+    fn draw_rect(&mut self, rect: Rect) -> Result<(), String> {
+        Canvas::draw_rect(self, rect)
+    }
+
+    fn draw_line(&mut self, from: Point, to: Point) -> Result<(), String> {
+        Canvas::draw_line(self, from, to)
+    }
+
+    fn present(&mut self) -> Result<(), String> {
+        Canvas::present(self);
+        Ok(())
+    }
+}
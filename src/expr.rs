@@ -0,0 +1,336 @@
+//! Expressions over the machine state for the debugger, like `v[0xA]`, `ram[i + 1]` or
+//! `stack.len() > 2 && dt == 0`, used by watches (see `Debugger::watches`).
+//!
+//! Values are signed 64-bit integers, comparisons and `&&`, `||`, `!` give 1 or 0. What
+//! can be read:
+//!
+//! ```text
+//! v[n], v0 ... vf    V registers
+//! i, pc, sp          I, the program counter, the stack depth
+//! dt, st             delay and sound timer
+//! ram[addr]          a byte of RAM
+//! stack[n]           a return address, 0 is the oldest
+//! stack.len()        the number of return addresses
+//! frame              frames run so far
+//! ```
+//!
+//! Operators, loosest binding first: `||`, `&&`, `==` `!=`, `<` `<=` `>` `>=`, `|`, `^`, `&`,
+//! `<<` `>>`, `+` `-`, `*` `/` `%`, and unary `-` `!` `~`. Numbers are decimal, or hex and
+//! binary with `0x` and `0b`.
+//!
+//! ```
+//! use chip8::expr::Expr;
+//! use chip8::machine::Machine;
+//!
+//! let mut machine = Machine::new();
+//! // VA = 0x2A, I = 0x300, store VA's decimal digits at I
+//! machine.load_rom(&[0x6A, 0x2A, 0xA3, 0x00, 0xFA, 0x33]);
+//! (0..3).for_each(|_| machine.chip8.cycle().unwrap());
+//!
+//! let eval = |text: &str| Expr::parse(text).unwrap().eval(&machine);
+//! assert_eq!(eval("v[0xA]"), Ok(42));
+//! assert_eq!(eval("ram[i + 1] * 10 + ram[i + 2] == va"), Ok(1));
+//! assert_eq!(eval("stack.len()"), Ok(0));
+//! assert_eq!(eval("stack[0]"), Err("stack[0] is empty".to_string()));
+//! assert!(Expr::parse("v[1 +").is_err());
+//! ```
+
+use std::fmt;
+
+use crate::hardware::V_REG_COUNT;
+use crate::machine::Machine;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Expr {
+    // as typed, for showing the expression back
+    text: String,
+    node: Node,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Node {
+    Num(i64),
+    // a plain name, e.g. `pc`
+    Var(Var),
+    Index(Array, Box<Node>),
+    StackLen,
+    Unary(&'static str, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Var {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    Frame,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Array {
+    V,
+    Ram,
+    Stack,
+}
+
+// binary operators by precedence, loosest first
+const LEVELS: [&[&str]; 10] =
+    [&["||"], &["&&"], &["==", "!="], &["<=", ">=", "<", ">"], &["|"], &["^"], &["&"], &["<<", ">>"], &["+", "-"], &["*", "/", "%"]];
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0 };
+        let node = parser.binary(0)?;
+        match parser.peek() {
+            None => Ok(Expr { text: text.trim().to_string(), node }),
+            Some(token) => Err(format!("unexpected '{}'", token)),
+        }
+    }
+
+    pub fn eval(&self, machine: &Machine) -> Result<i64, String> {
+        eval(&self.node, machine)
+    }
+
+    /**
+     * The addresses the expression reads with a constant `ram[addr]`, in order, for
+     * naming what a watch looks at. Computed addresses like `ram[i + 1]` aren't known.
+     *
+     * ```
+     * use chip8::expr::Expr;
+     *
+     * let expr = Expr::parse("ram[0x300] * 10 + ram[0x301] + ram[i]").unwrap();
+     * assert_eq!(expr.ram_addrs(), [0x300, 0x301]);
+     * ```
+     */
+    pub fn ram_addrs(&self) -> Vec<usize> {
+        let mut addrs = Vec::new();
+        ram_addrs(&self.node, &mut addrs);
+        addrs
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        let start = pos;
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+        if c.is_ascii_alphanumeric() || c == '_' {
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+        } else {
+            let two: String = chars[pos..chars.len().min(pos + 2)].iter().collect();
+            pos += match two.as_str() {
+                "||" | "&&" | "==" | "!=" | "<=" | ">=" | "<<" | ">>" => 2,
+                _ if "+-*/%&|^<>!~()[].".contains(c) => 1,
+                _ => return Err(format!("unexpected '{}'", c)),
+            };
+        }
+        tokens.push(chars[start..pos].iter().collect::<String>().to_ascii_lowercase());
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn next(&mut self) -> Result<&str, String> {
+        let token = self.tokens.get(self.pos).ok_or("unexpected end")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        match self.next()? {
+            t if t == token => Ok(()),
+            t => Err(format!("expected '{}', found '{}'", token, t)),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut node = self.binary(level + 1)?;
+        while let Some(op) = self.peek().and_then(|t| LEVELS[level].iter().find(|op| **op == t)) {
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.binary(level + 1)?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        let op = match self.peek() {
+            Some("-") => "-",
+            Some("!") => "!",
+            Some("~") => "~",
+            _ => return self.primary(),
+        };
+        self.pos += 1;
+        Ok(Node::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        let token = self.next()?.to_string();
+        if token == "(" {
+            let node = self.binary(0)?;
+            self.expect(")")?;
+            return Ok(node);
+        }
+        if token.starts_with(|c: char| c.is_ascii_digit()) {
+            return number(&token).map(Node::Num);
+        }
+
+        let array = match token.as_str() {
+            "v" => Array::V,
+            "ram" => Array::Ram,
+            "stack" if self.peek() == Some(".") => {
+                for part in [".", "len", "(", ")"] {
+                    self.expect(part)?;
+                }
+                return Ok(Node::StackLen);
+            }
+            "stack" => Array::Stack,
+            name => return variable(name).map(Node::Var),
+        };
+        self.expect("[")?;
+        let index = self.binary(0)?;
+        self.expect("]")?;
+        Ok(Node::Index(array, Box::new(index)))
+    }
+}
+
+fn number(token: &str) -> Result<i64, String> {
+    let parsed = match (token.strip_prefix("0x"), token.strip_prefix("0b")) {
+        (Some(hex), _) => i64::from_str_radix(hex, 16),
+        (_, Some(bin)) => i64::from_str_radix(bin, 2),
+        _ => token.parse(),
+    };
+    parsed.map_err(|_| format!("invalid number '{}'", token))
+}
+
+fn variable(name: &str) -> Result<Var, String> {
+    let var = match name {
+        "i" => Var::I,
+        "pc" => Var::Pc,
+        "sp" => Var::Sp,
+        "dt" => Var::Dt,
+        "st" => Var::St,
+        "frame" => Var::Frame,
+        _ => match name.strip_prefix('v').map(|n| usize::from_str_radix(n, 16)) {
+            Some(Ok(n)) if n < V_REG_COUNT && name.len() == 2 => Var::V(n),
+            _ => return Err(format!("unknown name '{}'", name)),
+        },
+    };
+    Ok(var)
+}
+
+fn ram_addrs(node: &Node, addrs: &mut Vec<usize>) {
+    match node {
+        Node::Index(Array::Ram, index) => match **index {
+            Node::Num(n) if n >= 0 => addrs.push(n as usize),
+            ref index => ram_addrs(index, addrs),
+        },
+        Node::Index(_, index) | Node::Unary(_, index) => ram_addrs(index, addrs),
+        Node::Binary(_, a, b) => {
+            ram_addrs(a, addrs);
+            ram_addrs(b, addrs);
+        }
+        Node::Num(_) | Node::Var(_) | Node::StackLen => {}
+    }
+}
+
+fn eval(node: &Node, machine: &Machine) -> Result<i64, String> {
+    let chip8 = &machine.chip8;
+    let value = match node {
+        Node::Num(n) => *n,
+        Node::Var(var) => match var {
+            Var::V(n) => chip8.v[*n] as i64,
+            Var::I => chip8.i as i64,
+            Var::Pc => chip8.pc as i64,
+            Var::Sp => chip8.sp as i64,
+            Var::Dt => chip8.tim_delay as i64,
+            Var::St => chip8.tim_snd as i64,
+            Var::Frame => machine.frame() as i64,
+        },
+        Node::StackLen => chip8.stack.len() as i64,
+        Node::Index(array, index) => {
+            let n = eval(index, machine)?;
+            let found = match array {
+                Array::V => usize::try_from(n).ok().filter(|n| *n < V_REG_COUNT).map(|n| chip8.v[n] as i64),
+                Array::Ram => usize::try_from(n).ok().filter(|n| *n < chip8.get_ram().len()).map(|n| chip8.get_ram()[n] as i64),
+                Array::Stack => usize::try_from(n).ok().and_then(|n| chip8.stack.get(n)).map(|a| *a as i64),
+            };
+            match (found, array) {
+                (Some(value), _) => value,
+                (None, Array::V) => return Err(format!("no register v[{:#x}]", n)),
+                (None, Array::Ram) => return Err(format!("ram[{:#x}] is outside RAM", n)),
+                (None, Array::Stack) => return Err(format!("stack[{}] is empty", n)),
+            }
+        }
+        Node::Unary(op, node) => {
+            let n = eval(node, machine)?;
+            match *op {
+                "-" => n.wrapping_neg(),
+                "!" => (n == 0) as i64,
+                _ => !n,
+            }
+        }
+        Node::Binary(op, a, b) => {
+            let a = eval(a, machine)?;
+            // short-circuit, so `sp > 0 && stack[sp - 1] == 0x200` is fine with an empty stack
+            match (*op, a != 0) {
+                ("&&", false) => return Ok(0),
+                ("||", true) => return Ok(1),
+                _ => {}
+            }
+            let b = eval(b, machine)?;
+            match *op {
+                "&&" | "||" => (b != 0) as i64,
+                "==" => (a == b) as i64,
+                "!=" => (a != b) as i64,
+                "<" => (a < b) as i64,
+                "<=" => (a <= b) as i64,
+                ">" => (a > b) as i64,
+                ">=" => (a >= b) as i64,
+                "|" => a | b,
+                "^" => a ^ b,
+                "&" => a & b,
+                "<<" => a.wrapping_shl(b as u32),
+                ">>" => a.wrapping_shr(b as u32),
+                "+" => a.wrapping_add(b),
+                "-" => a.wrapping_sub(b),
+                "*" => a.wrapping_mul(b),
+                _ if b == 0 => return Err("division by zero".to_string()),
+                "/" => a.wrapping_div(b),
+                _ => a.wrapping_rem(b),
+            }
+        }
+    };
+    Ok(value)
+}
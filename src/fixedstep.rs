@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+// how many steps update() will catch up on before dropping the backlog
+const MAX_BACKLOG: u32 = 5;
+
+/**
+ * Fixed timestep helper: `update()` returns true once for every step that is due,
+ * so `while fixedstep.update() { .. }` runs the step at the requested rate.
+ * After a long stall (e.g. the window being dragged) at most `MAX_BACKLOG` steps are
+ * caught up on instead of running a burst of frames.
+ */
+pub struct FixedStep {
+    step: Duration,
+    last: Instant,
+    accumulator: Duration,
+}
+
+impl FixedStep {
+    pub fn start(hz: f64) -> Self {
+        FixedStep {
+            step: Duration::from_secs_f64(1.0 / hz),
+            last: Instant::now(),
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    pub fn update(&mut self) -> bool {
+        let now = Instant::now();
+        self.accumulator += now - self.last;
+        self.last = now;
+
+        if self.accumulator > self.step * MAX_BACKLOG {
+            self.accumulator = self.step * MAX_BACKLOG;
+        }
+
+        if self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            true
+        } else {
+            false
+        }
+    }
+
+    /**
+     * Time until the next step is due, for sleeping in between.
+     */
+    pub fn until_next(&self) -> Duration {
+        self.step.saturating_sub(self.accumulator + self.last.elapsed())
+    }
+}
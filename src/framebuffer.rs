@@ -0,0 +1,164 @@
+use crate::hardware::Error;
+use crate::palette::Palette;
+
+/**
+ * Monochrome display memory, stored row-major with one `bool` per pixel.
+ * Coordinates are always given as (x, y), with (0, 0) the top-left pixel.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    /**
+     * CRC-32 of the size and the pixels, for comparing screens without keeping them around.
+     *
+     * ```
+     * use chip8::framebuffer::Framebuffer;
+     *
+     * let mut fb = Framebuffer::new(64, 32);
+     * let blank = fb.checksum();
+     * fb.set(3, 4, true).unwrap();
+     * assert_ne!(fb.checksum(), blank);
+     * // a blank screen of another size is another screen
+     * assert_ne!(Framebuffer::new(128, 64).checksum(), blank);
+     * ```
+     */
+    pub fn checksum(&self) -> u32 {
+        let mut data = Vec::with_capacity(8 + self.pixels.len() / 8 + 1);
+        data.extend_from_slice(&(self.width as u32).to_le_bytes());
+        data.extend_from_slice(&(self.height as u32).to_le_bytes());
+        // 8 pixels to a byte, leftmost in the high bit
+        data.extend(self.pixels.chunks(8).map(|bits| bits.iter().fold(0u8, |byte, &on| byte << 1 | on as u8)));
+        crate::patch::crc32(&data)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /**
+     * Pixel at X/Y, `None` when outside the framebuffer.
+     */
+    pub fn get(&self, x: usize, y: usize) -> Option<bool> {
+        if x < self.width && y < self.height {
+            Some(self.pixels[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, val: bool) -> Result<(), Error> {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = val;
+            Ok(())
+        } else {
+            Err(Error::OutOfBounds { x, y })
+        }
+    }
+
+    /**
+     * Turn off every pixel.
+     */
+    pub fn clear(&mut self) {
+        self.pixels.fill(false);
+    }
+
+    /**
+     * Move every pixel `dx` to the right and `dy` down, negative for left and up. Pixels
+     * moved off the edge are lost, the ones moved in are off.
+     *
+     * ```
+     * use chip8::framebuffer::Framebuffer;
+     *
+     * let mut fb = Framebuffer::new(8, 4);
+     * fb.set(1, 0, true).unwrap();
+     * fb.set(7, 3, true).unwrap();
+     * fb.scroll(4, 1);
+     * assert_eq!(fb.iter_set_pixels().collect::<Vec<_>>(), vec![(5, 1)]);
+     * fb.scroll(-4, 0);
+     * assert_eq!(fb.iter_set_pixels().collect::<Vec<_>>(), vec![(1, 1)]);
+     * ```
+     */
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        let old = std::mem::replace(&mut self.pixels, vec![false; self.width * self.height]);
+        for (n, _) in old.iter().enumerate().filter(|(_, px)| **px) {
+            let x = (n % self.width) as isize + dx;
+            let y = (n / self.width) as isize + dy;
+            if (0..self.width as isize).contains(&x) && (0..self.height as isize).contains(&y) {
+                self.pixels[y as usize * self.width + x as usize] = true;
+            }
+        }
+    }
+
+    /**
+     * Rows from top to bottom, each `width` pixels long.
+     */
+    pub fn rows(&self) -> impl Iterator<Item = &[bool]> {
+        self.pixels.chunks(self.width)
+    }
+
+    /**
+     * Every pixel as (x, y, value), row by row.
+     */
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize, bool)> + '_ {
+        self.pixels.iter().enumerate().map(|(n, px)| (n % self.width, n / self.width, *px))
+    }
+
+    /**
+     * Coordinates of the pixels that are on.
+     *
+     * ```
+     * use chip8::framebuffer::Framebuffer;
+     *
+     * let mut fb = Framebuffer::new(4, 2);
+     * fb.set(3, 0, true).unwrap();
+     * fb.set(1, 1, true).unwrap();
+     *
+     * assert_eq!(fb.iter_set_pixels().collect::<Vec<_>>(), vec![(3, 0), (1, 1)]);
+     * assert_eq!(fb.rows().nth(1), Some(&[false, true, false, false][..]));
+     * ```
+     */
+    pub fn iter_set_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.pixels().filter(|(_, _, px)| *px).map(|(x, y, _)| (x, y))
+    }
+
+    /**
+     * Convert to a `width * height` RGBA image (4 bytes per pixel, row-major, opaque).
+     *
+     * ```
+     * use chip8::framebuffer::Framebuffer;
+     * use chip8::palette::Palette;
+     *
+     * let mut fb = Framebuffer::new(2, 1);
+     * fb.set(1, 0, true).unwrap();
+     *
+     * assert_eq!(fb.to_rgba(&Palette::GREEN), vec![0, 0, 0, 255, 0, 255, 0, 255]);
+     * ```
+     */
+    pub fn to_rgba(&self, palette: &Palette) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.pixels.len() * 4);
+
+        for px in &self.pixels {
+            let [r, g, b] = palette.color(*px);
+            out.extend_from_slice(&[r, g, b, 0xFF]);
+        }
+
+        out
+    }
+}
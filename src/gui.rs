@@ -0,0 +1,867 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sdl2::controller::Axis;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+
+use chip8::debugger::{self, Debugger};
+use chip8::expr::Expr;
+use chip8::hardware;
+use chip8::machine::Machine;
+use chip8::macros::{MacroSet, Playback, Recorder};
+use chip8::palette::PRESETS;
+use chip8::patch;
+use chip8::platform::Platform;
+use chip8::savestate;
+
+use crate::actions::{Action, CommandPalette, PaletteInput};
+use crate::beep::BeepSchedule;
+use crate::config::Config;
+use crate::controllers::{ControllerMenu, MenuInput};
+use crate::crash_report;
+use crate::debug_session::{self, Session};
+#[cfg(feature = "gui-debug")]
+use crate::debug_ui::DebugUi;
+use crate::fixedstep::FixedStep;
+use crate::i18n::Lang;
+use crate::input;
+use crate::keymap::{Binding, KeyMap, RemapInput, RemapWizard};
+use crate::log_file::Log;
+use crate::picture_menu::PictureMenu;
+use crate::render::{self, Render};
+use crate::repl::Repl;
+use crate::routing::Device;
+use crate::slow_draw::SlowDraw;
+use crate::stats::Stats;
+use crate::thumbnail;
+
+// frame at which the screen is captured for the window icon
+const ICON_FRAME: u64 = 60;
+// longest wait for input while the program can't change anything on its own
+const IDLE_WAIT_MS: u32 = 250;
+
+/**
+ * Run the program in a window until it is closed or quit, then keep the debugger session
+ * for the next run of the ROM.
+ */
+pub fn run(config: &Config, machine: &mut Machine, rom: &[u8], stats: &mut Stats, log: &mut Log) -> Result<(), String> {
+    let mut gui = Gui::open(config, machine, rom, stats, log)?;
+    while !gui.quit {
+        gui.pass()?;
+    }
+    gui.close()
+}
+
+/**
+ * The window and everything the main loop keeps from one pass to the next: the debugger,
+ * the menus and overlays, macros and the state of the input devices.
+ */
+struct Gui<'a> {
+    config: &'a Config,
+    machine: &'a mut Machine,
+    rom: &'a [u8],
+    stats: &'a mut Stats,
+    log: &'a mut Log,
+    lang: Lang,
+    renderer: Render,
+    debugger: Debugger,
+    // breakpoints and the overlay carry over from the last run of this ROM
+    session_path: String,
+    repl: Option<Repl>,
+    #[cfg(feature = "gui-debug")]
+    debug_ui: Option<DebugUi>,
+    palette_index: usize,
+    // macros are stored per ROM, keyed by its checksum
+    macro_path: String,
+    macros: MacroSet,
+    recording: Option<(usize, Recorder)>,
+    playback: Option<Playback>,
+    // F9/F10 save and load a state, kept next to the save RAM
+    state_path: String,
+    // slow motion just runs frames less often, the beep keeps its pitch since the audio
+    // callback generates it independently
+    fixedstep: FixedStep,
+    halt_reported: bool,
+    // the program looks stuck and was slowed down, see `Machine::runaway`
+    runaway_reported: bool,
+    // `Chip8::warnings` already logged, back to 0 when a state is loaded, which has none
+    warnings_reported: usize,
+    // R restarts from here after a fault
+    initial_state: Vec<u8>,
+    beep: BeepSchedule,
+    icon_set: bool,
+    thumbnail: Option<String>,
+    // left stick position and the keypad keys it holds, see `input::stick_keys`
+    stick: (i16, i16),
+    stick_held: Vec<usize>,
+    // event that woke us up from an idle wait
+    pending: Option<Event>,
+    // open while Ctrl+P is searching for an action
+    command_palette: Option<CommandPalette>,
+    // open while the controllers are listed
+    controller_menu: Option<ControllerMenu>,
+    // open while brightness, contrast and gamma are tuned
+    picture_menu: Option<PictureMenu>,
+    // sprites appear a row at a time while set
+    slow_draw: Option<SlowDraw>,
+    keymap: KeyMap,
+    // open while keys are being remapped, with whether the keypad layer was shown before
+    remap: Option<(RemapWizard, bool)>,
+    // something on screen changed during this pass
+    redraw: bool,
+    // the window was closed or Quit was chosen, no more passes
+    quit: bool,
+}
+
+impl<'a> Gui<'a> {
+    fn open(config: &'a Config, machine: &'a mut Machine, rom: &'a [u8], stats: &'a mut Stats, log: &'a mut Log) -> Result<Self, String> {
+        let mut renderer = Render::new("Chip8", hardware::CHIP8_WIDTH * hardware::MULTIPLIER, hardware::CHIP8_HEIGHT * hardware::MULTIPLIER, true, config.backend)?;
+        renderer.open_buzzer(config.audio)?;
+
+        renderer.palette = config.palette;
+        renderer.palette_cycle = config.palette_cycle;
+        renderer.picture = config.picture;
+        renderer.phosphor = config.phosphor;
+        renderer.scaling = config.scaling.unwrap_or_default();
+        renderer.set_visible(render::Layer::Flash, config.beep_flash);
+
+        let lang = config.lang;
+        let mut debugger = Debugger::new();
+        let session_path = debug_session::path(&config.debug_dir, rom);
+        let session = Session::load(&session_path)?;
+        if !session.breakpoints.is_empty() {
+            println!("{}", lang.format("msg.session_restored", &[&session.breakpoints.len(), &session_path]));
+        }
+        debugger.breakpoints = session.breakpoints;
+        debugger.breakpoints.extend(&config.breakpoints);
+        debugger.paused = config.paused;
+        debugger.break_on_runaway = config.break_on_runaway;
+        if let Some(frame) = config.break_at_frame {
+            log.info(&debugger.execute(machine, &format!("runto-frame {}", frame)));
+        }
+        for text in &session.watches {
+            match Expr::parse(text) {
+                Ok(expr) => debugger.watches.push(expr),
+                Err(e) => log.warn(&format!("{}: dropped watch '{}': {}", session_path, text, e)),
+            }
+        }
+        for label in session.labels {
+            debugger.add_label(label);
+        }
+        if session.overlay {
+            renderer.set_visible(render::Layer::Debug, true);
+        }
+        let repl = if config.repl {
+            // the per-instruction trace would drown out the REPL output
+            machine.chip8.trace = false;
+            println!("{}", lang.tr("msg.debugger_ready"));
+            Some(Repl::start())
+        } else {
+            None
+        };
+        #[cfg(feature = "gui-debug")]
+        let debug_ui = if config.debug_ui {
+            machine.chip8.trace = false;
+            let video = renderer.display.window().subsystem().clone();
+            Some(DebugUi::open(&video, lang, config.palette)?)
+        } else {
+            None
+        };
+
+        let macro_path = format!("{}/{:08x}.txt", config.macro_dir, patch::crc32(rom));
+        let macros = match fs::read_to_string(&macro_path) {
+            Ok(text) => MacroSet::parse(&text).map_err(|e| format!("{}: {}", macro_path, e))?,
+            Err(_) => MacroSet::default(),
+        };
+
+        Ok(Gui {
+            config,
+            rom,
+            stats,
+            lang,
+            debugger,
+            session_path,
+            repl,
+            #[cfg(feature = "gui-debug")]
+            debug_ui,
+            palette_index: PRESETS.iter().position(|(_, p)| *p == config.palette).unwrap_or(0),
+            macro_path,
+            macros,
+            recording: None,
+            playback: None,
+            state_path: crate::state_path(&config.save_dir, rom),
+            fixedstep: FixedStep::start(config.refresh_rate * config.speed),
+            halt_reported: false,
+            runaway_reported: false,
+            warnings_reported: 0,
+            initial_state: savestate::save(machine),
+            beep: BeepSchedule::new(Duration::from_secs_f64(config.min_beep_ms / 1000.0)),
+            icon_set: false,
+            thumbnail: thumbnail::pending(config, rom),
+            stick: (0, 0),
+            stick_held: Vec::new(),
+            pending: None,
+            command_palette: None,
+            controller_menu: None,
+            picture_menu: None,
+            slow_draw: config.slow_draw.then(|| SlowDraw::attach(machine)),
+            keymap: KeyMap::load(&config.keys_file)?,
+            remap: None,
+            redraw: false,
+            quit: false,
+            renderer,
+            machine,
+            log,
+        })
+    }
+
+    /**
+     * One pass of the main loop: run the frames that are due, handle input, bring the
+     * overlays up to date and draw, then sleep until the next frame.
+     */
+    fn pass(&mut self) -> Result<(), String> {
+        self.redraw = false;
+
+        let emulate_started = Instant::now();
+        while self.fixedstep.update() {
+            self.run_frame();
+        }
+        self.stats.emulate += emulate_started.elapsed();
+
+        let events: Vec<Event> = self.pending.take().into_iter().chain(self.renderer.event_pump.poll_iter()).collect();
+        let mut actions: Vec<Action> = Vec::new();
+        for event in events {
+            self.handle_event(event, &mut actions)?;
+            if self.quit {
+                return Ok(());
+            }
+        }
+        for action in actions {
+            self.run_action(action)?;
+            if self.quit {
+                return Ok(());
+            }
+        }
+
+        self.poll_repl()?;
+        if self.quit {
+            return Ok(());
+        }
+        #[cfg(feature = "gui-debug")]
+        self.update_debug_ui()?;
+
+        self.report()?;
+        self.capture()?;
+        self.update_overlays();
+        self.present()?;
+        self.wait()
+    }
+
+    /**
+     * Run a frame of the program, or reveal the next sprite row while slow drawing.
+     */
+    fn run_frame(&mut self) {
+        if let Some(slow) = &mut self.slow_draw {
+            if slow.busy() {
+                self.redraw |= slow.advance(self.machine.chip8.get_vram());
+                return;
+            }
+            slow.before_frame(self.machine);
+        }
+        if self.playback.as_mut().is_some_and(|p| !p.apply(self.machine)) {
+            self.playback = None;
+        }
+        if let Some(msg) = self.debugger.run_frame(self.machine) {
+            println!("{}", msg);
+            // a fault pauses with a post-mortem instead of ending the program
+            if let Some(fault) = self.debugger.fault {
+                self.stats.faults.push(fault.to_string());
+                self.log.error(&format!("fault at frame {}: {}", self.machine.frame(), fault));
+                let mut menu = self.lang.format("menu.fault", &[&fault]);
+                match crash_report::write(&self.config.crash_dir, self.rom, self.machine, &self.debugger) {
+                    Ok(path) => {
+                        println!("{}", self.lang.format("msg.crash_report", &[&path]));
+                        self.log.info(&format!("crash report written to {}", path));
+                        menu = format!("{}\n{}", menu, self.lang.format("msg.crash_report", &[&path]));
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        self.log.warn(&e);
+                    }
+                }
+                self.renderer.menu = Some(menu);
+                self.redraw = true;
+            }
+        }
+        if let Some(ticks) = self.machine.chip8.sound_request.take() {
+            self.beep.request(Duration::from_secs_f64(ticks as f64 / (self.config.refresh_rate * self.config.speed)));
+            // rumble for as long as the beep will last, in case it can't be heard
+            if ticks > 0 {
+                self.renderer.rumble(self.beep.remaining());
+            }
+        }
+        // the background animation and fading pixels need a fresh frame every tick
+        self.redraw |= self.renderer.palette_cycle || self.renderer.fading();
+        if let Some(slow) = &mut self.slow_draw {
+            self.redraw |= slow.after_frame();
+        }
+    }
+
+    /**
+     * Hand `event` to whichever menu or window wants it, or act on it. Hotkeys and the
+     * command palette add to `actions`, which run once all events are in.
+     */
+    fn handle_event(&mut self, event: Event, actions: &mut Vec<Action>) -> Result<(), String> {
+        let lang = self.lang;
+
+        #[cfg(feature = "gui-debug")]
+        if let Some(ui) = self.debug_ui.as_mut().filter(|ui| event.get_window_id() == Some(ui.window_id())) {
+            ui.handle_event(&event);
+            return Ok(());
+        }
+        // the remap wizard takes every key and button press until it is done
+        if let Some((wizard, keypad_shown)) = &mut self.remap {
+            let input = wizard.handle(&event);
+            if !matches!(input, RemapInput::Waiting) {
+                self.renderer.set_visible(render::Layer::Keypad, *keypad_shown);
+                self.remap = None;
+            }
+            if let RemapInput::Done(map) = input {
+                let keys_file = &self.config.keys_file;
+                match map.store(keys_file) {
+                    Ok(_) => {
+                        println!("{}", lang.format("msg.keys_saved", &[keys_file]));
+                        self.log.info(&format!("key map saved to {}", keys_file));
+                    }
+                    Err(e) => {
+                        eprintln!("{}", lang.format("err.keys_save", &[keys_file, &e]));
+                        self.log.warn(&e);
+                    }
+                }
+                self.keymap = map;
+            }
+            if matches!(event, Event::KeyDown { .. } | Event::TextInput { .. } | Event::ControllerButtonDown { .. }) {
+                self.redraw = true;
+                return Ok(());
+            }
+        }
+        // the command palette takes the typing, releasing keys still reaches the keypad
+        if let Some(commands) = &mut self.command_palette {
+            match commands.handle(&event, lang) {
+                PaletteInput::Typing => {}
+                PaletteInput::Close => self.command_palette = None,
+                PaletteInput::Run(action) => {
+                    actions.push(action);
+                    self.command_palette = None;
+                }
+            }
+            if matches!(event, Event::KeyDown { .. } | Event::TextInput { .. }) {
+                self.redraw = true;
+                return Ok(());
+            }
+        }
+        // so does the controller menu, plugging controllers in and out still gets through
+        if let Some(menu) = &mut self.controller_menu {
+            if let MenuInput::Close = menu.handle(&event, &mut self.renderer) {
+                self.controller_menu = None;
+            }
+            if matches!(event, Event::KeyDown { .. } | Event::TextInput { .. }) {
+                self.redraw = true;
+                return Ok(());
+            }
+        }
+        if let Some(menu) = &mut self.picture_menu {
+            if let MenuInput::Close = menu.handle(&event, &mut self.renderer.picture) {
+                self.picture_menu = None;
+                let picture = self.renderer.picture;
+                self.log.info(&format!("picture set to brightness {:+.2}, contrast {:.2}, gamma {:.2}", picture.brightness, picture.contrast, picture.gamma));
+            }
+            if matches!(event, Event::KeyDown { .. } | Event::TextInput { .. }) {
+                self.redraw = true;
+                return Ok(());
+            }
+        }
+
+        let config = self.config;
+        let routing = &config.routing;
+        match event {
+            // with the debugger window open, closing this one doesn't quit on its own
+            Event::Quit { .. } | Event::Window { win_event: WindowEvent::Close, .. } => self.quit = true,
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.renderer.add_controller(which) {
+                    Ok(name) => {
+                        println!("{}", lang.format("msg.controller", &[&name]));
+                        self.log.info(&format!("controller connected: {}", name));
+                    }
+                    Err(e) => eprintln!("{}", lang.format("err.controller", &[&e])),
+                }
+                self.redraw |= self.controller_menu.is_some();
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(name) = self.renderer.remove_controller(which) {
+                    println!("{}", lang.format("msg.controller_removed", &[&name]));
+                    self.log.info(&format!("controller disconnected: {}", name));
+                }
+                self.redraw |= self.controller_menu.is_some();
+            },
+            Event::Window { win_event: WindowEvent::Exposed | WindowEvent::SizeChanged(..) | WindowEvent::Restored, .. } => {
+                self.redraw = true;
+            },
+            // F1-F4, F9-F12, see `actions::ACTIONS`
+            Event::KeyDown { keycode: Some(keycode), repeat: false, .. } if Action::for_key(keycode).is_some() => {
+                actions.extend(Action::for_key(keycode));
+            },
+            Event::KeyDown { keycode: Some(Keycode::P), keymod, repeat: false, .. } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                self.command_palette = Some(CommandPalette::default());
+                self.redraw = true;
+            },
+            // with the debugger open, clicking the screen tells which pixel it is
+            Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } if self.repl.is_some() => {
+                if let Some((cx, cy)) = self.renderer.screen_to_cell(x, y) {
+                    let lit = self.machine.chip8.get_vram().get(cx, cy) == Some(true);
+                    println!("{}", lang.format(if lit { "msg.pixel_on" } else { "msg.pixel_off" }, &[&cx, &cy]));
+                }
+            },
+            // after a fault: N skips the instruction, R restarts the program, Q quits
+            Event::KeyDown { keycode: Some(keycode @ (Keycode::N | Keycode::R | Keycode::Q)), repeat: false, .. }
+                if self.debugger.fault.is_some() =>
+            {
+                match keycode {
+                    Keycode::N => println!("{}", self.debugger.execute(self.machine, "nop")),
+                    Keycode::R => self.restart()?,
+                    _ => return Err(self.debugger.fault.map(|f| f.to_string()).unwrap_or_default()),
+                }
+                self.renderer.menu = None;
+                self.redraw = true;
+            },
+            // F5-F8: play a macro, Ctrl+F5-F8: start/stop recording one
+            Event::KeyDown { keycode: Some(keycode), keymod, repeat: false, .. } => {
+                if let Some(slot) = input::macro_slot(keycode) {
+                    self.macro_key(slot, keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD));
+                } else if let Some(key) = self.keymap.lookup(Binding::Key(keycode)).filter(|k| routing.allows(Device::Keyboard, *k)) {
+                    self.keypad_input(key, true);
+                }
+            },
+            Event::KeyUp { keycode: Some(keycode), .. } => {
+                if let Some(key) = self.keymap.lookup(Binding::Key(keycode)).filter(|k| routing.allows(Device::Keyboard, *k)) {
+                    self.keypad_input(key, false);
+                }
+            },
+            Event::ControllerButtonDown { button, which, .. } | Event::ControllerButtonUp { button, which, .. } => {
+                let player = self.renderer.player(which).unwrap_or(1);
+                let key = self.keymap.lookup_button(player, button).filter(|k| routing.allows(Device::Controller(player), *k));
+                if let Some(key) = key {
+                    self.keypad_input(key, matches!(event, Event::ControllerButtonDown { .. }));
+                }
+            },
+            Event::ControllerAxisMotion { axis, value, which, .. } => {
+                match axis {
+                    Axis::LeftX => self.stick.0 = value,
+                    Axis::LeftY => self.stick.1 = value,
+                    _ => return Ok(()),
+                }
+                let device = Device::Controller(self.renderer.player(which).unwrap_or(1));
+                let mut held = input::stick_keys(&config.stick, self.stick.0, self.stick.1);
+                held.retain(|k| routing.allows(device, *k));
+                let released: Vec<usize> = self.stick_held.iter().copied().filter(|k| !held.contains(k)).collect();
+                let pressed: Vec<usize> = held.iter().copied().filter(|k| !self.stick_held.contains(k)).collect();
+                for key in released {
+                    self.keypad_input(key, false);
+                }
+                for key in pressed {
+                    self.keypad_input(key, true);
+                }
+                self.stick_held = held;
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /**
+     * F5-F8 plays the macro in `slot`. With Ctrl it starts recording one, or stops the
+     * recording and stores it.
+     */
+    fn macro_key(&mut self, slot: usize, ctrl: bool) {
+        let lang = self.lang;
+        if !ctrl {
+            match self.macros.slots.get(&slot) {
+                Some(events) => self.playback = Some(Playback::start(self.machine, events.clone())),
+                None => println!("{}", lang.format("msg.macro_empty", &[&slot])),
+            }
+        } else if let Some((slot, recorder)) = self.recording.take() {
+            self.macros.slots.insert(slot, recorder.finish());
+            let macro_path = &self.macro_path;
+            match fs::create_dir_all(&self.config.macro_dir).and_then(|_| fs::write(macro_path, self.macros.to_text())) {
+                Ok(_) => {
+                    println!("{}", lang.format("msg.macro_saved", &[&slot, macro_path]));
+                    self.log.info(&format!("macro {} saved to {}", slot, macro_path));
+                }
+                Err(e) => {
+                    eprintln!("{}", lang.format("err.macro_save", &[macro_path, &e]));
+                    self.log.warn(&format!("can't save macros to {}: {}", macro_path, e));
+                }
+            }
+        } else {
+            println!("{}", lang.format("msg.macro_recording", &[&slot, &(slot + 4)]));
+            self.recording = Some((slot, Recorder::start(self.machine)));
+        }
+    }
+
+    /**
+     * A keypad key went down or up on the host, from the keyboard or a controller.
+     */
+    fn keypad_input(&mut self, key: usize, down: bool) {
+        self.machine.chip8.set_input(key, down);
+        if let Some((_, recorder)) = &mut self.recording {
+            recorder.record(self.machine, key, down);
+        }
+        self.renderer.keys[key] = down;
+        self.redraw |= self.renderer.is_visible(render::Layer::Keypad);
+    }
+
+    /**
+     * Do what a hotkey or the command palette asked for.
+     */
+    fn run_action(&mut self, action: Action) -> Result<(), String> {
+        let lang = self.lang;
+        let state_path = &self.state_path;
+        match action {
+            Action::NextPalette => {
+                self.palette_index = (self.palette_index + 1) % PRESETS.len();
+                let (name, palette) = PRESETS[self.palette_index];
+                self.renderer.palette = palette;
+                println!("{}", lang.format("msg.palette", &[&name]));
+                self.log.info(&format!("palette changed to {}", name));
+            }
+            Action::NextQuirks => {
+                // RAM, display mode and fonts stay as they are, the program keeps running
+                let platform = Platform::after(self.machine.chip8.quirks);
+                self.machine.chip8.quirks = platform.quirks();
+                println!("{}", lang.format("msg.quirks", &[&platform.name(), &self.machine.chip8.quirks]));
+                self.log.info(&format!("quirks changed to {}'s: {}", platform.name(), self.machine.chip8.quirks));
+            }
+            Action::PaletteCycle => {
+                self.renderer.palette_cycle = !self.renderer.palette_cycle;
+                self.log.info(&format!("palette cycling {}", if self.renderer.palette_cycle { "on" } else { "off" }));
+            }
+            Action::Grid => self.renderer.toggle(render::Layer::Grid),
+            // with an outline of where the next DXYN will draw
+            Action::DebugOverlay => self.renderer.toggle(render::Layer::Debug),
+            Action::Keypad => self.renderer.toggle(render::Layer::Keypad),
+            Action::BeepFlash => self.renderer.toggle(render::Layer::Flash),
+            Action::Messages => self.renderer.toggle(render::Layer::Messages),
+            Action::SaveState => {
+                match fs::create_dir_all(&self.config.save_dir).and_then(|_| fs::write(state_path, savestate::save(self.machine))) {
+                    Ok(_) => {
+                        println!("{}", lang.format("msg.state_saved", &[state_path]));
+                        self.log.info(&format!("state saved to {} at frame {}", state_path, self.machine.frame()));
+                    }
+                    Err(e) => {
+                        eprintln!("{}", lang.format("err.state_save", &[state_path, &e]));
+                        self.log.warn(&format!("can't save state to {}: {}", state_path, e));
+                    }
+                }
+            }
+            Action::LoadState => {
+                let loaded = fs::read(state_path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|data| savestate::load(self.machine, &data).map_err(|e| e.to_string()));
+                match loaded {
+                    Ok(_) => {
+                        self.warnings_reported = 0;
+                        println!("{}", lang.format("msg.state_loaded", &[state_path]));
+                        self.log.info(&format!("state loaded from {}", state_path));
+                    }
+                    Err(e) => {
+                        eprintln!("{}", lang.format("err.state_load", &[state_path, &e]));
+                        self.log.warn(&format!("can't load state from {}: {}", state_path, e));
+                    }
+                }
+            }
+            Action::Pause => println!("{}", self.debugger.execute(self.machine, if self.debugger.paused { "continue" } else { "pause" })),
+            Action::Step => println!("{}", self.debugger.execute(self.machine, "step")),
+            Action::StepOver => println!("{}", self.debugger.execute(self.machine, "over")),
+            Action::StepOut => println!("{}", self.debugger.execute(self.machine, "out")),
+            Action::Reset => {
+                self.reset()?;
+                self.renderer.menu = None;
+            }
+            Action::StateChecksum => {
+                let checksum = format!("{:08x}", savestate::checksum(self.machine));
+                println!("{}", lang.format("msg.state_checksum", &[&self.machine.frame(), &checksum]));
+                self.log.info(&format!("state checksum {} at frame {}", checksum, self.machine.frame()));
+            }
+            Action::Controllers => self.controller_menu = Some(ControllerMenu::default()),
+            Action::Picture => self.picture_menu = Some(PictureMenu::default()),
+            Action::SlowDraw => {
+                self.slow_draw = match self.slow_draw {
+                    Some(_) => {
+                        SlowDraw::detach(self.machine);
+                        None
+                    }
+                    None => Some(SlowDraw::attach(self.machine)),
+                };
+                self.log.info(&format!("slow drawing {}", if self.slow_draw.is_some() { "on" } else { "off" }));
+            }
+            Action::RemapKeys => {
+                self.remap = Some((RemapWizard::start(&self.keymap), self.renderer.is_visible(render::Layer::Keypad)));
+                self.renderer.set_visible(render::Layer::Keypad, true);
+            }
+            #[cfg(feature = "gui-debug")]
+            Action::Debugger => {
+                if self.debug_ui.is_none() {
+                    self.machine.chip8.trace = false;
+                    let video = self.renderer.display.window().subsystem().clone();
+                    self.debug_ui = Some(DebugUi::open(&video, lang, self.config.palette)?);
+                }
+            }
+            Action::Quit => self.quit = true,
+        }
+        self.redraw = true;
+        Ok(())
+    }
+
+    /**
+     * Go back to the state the program started in and resume, after a fault.
+     */
+    fn restart(&mut self) -> Result<(), String> {
+        savestate::load(self.machine, &self.initial_state).map_err(|e| e.to_string())?;
+        self.debugger.fault = None;
+        self.debugger.paused = false;
+        self.warnings_reported = 0;
+        Ok(())
+    }
+
+    /**
+     * Restart the program at any time, also after it exited.
+     */
+    fn reset(&mut self) -> Result<(), String> {
+        self.restart()?;
+        self.renderer.display.window_mut().set_title("Chip8").map_err(|e| e.to_string())?;
+        self.halt_reported = false;
+        Ok(())
+    }
+
+    /**
+     * Run the debugger commands typed since the last pass.
+     */
+    fn poll_repl(&mut self) -> Result<(), String> {
+        let lines: Vec<String> = self.repl.as_ref().map_or(Vec::new(), |repl| repl.lines().collect());
+        for line in lines {
+            // the debugger can't restart or quit on its own, those need the frontend
+            match line.trim() {
+                "reset" => self.reset()?,
+                "quit" => {
+                    self.quit = true;
+                    return Ok(());
+                }
+                _ => println!("{}", self.debugger.execute(self.machine, &line)),
+            }
+            self.dismiss_menu();
+            self.redraw = true;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "gui-debug")]
+    fn update_debug_ui(&mut self) -> Result<(), String> {
+        let Some(ui) = &mut self.debug_ui else {
+            return Ok(());
+        };
+        ui.sprite_row = self.slow_draw.as_ref().and_then(|slow| slow.current).map(|row| row.addr);
+        let changed = ui.draw(self.machine, &mut self.debugger)?;
+        if ui.closed {
+            self.debug_ui = None;
+        }
+        if changed {
+            self.dismiss_menu();
+            self.redraw = true;
+        }
+        Ok(())
+    }
+
+    /**
+     * Take down the fault or exit menu once the debugger got the program going again.
+     */
+    fn dismiss_menu(&mut self) {
+        if self.debugger.fault.is_none() && self.renderer.menu.is_some() && !self.machine.chip8.exited() {
+            self.renderer.menu = None;
+        }
+    }
+
+    /**
+     * Tell the user about the beep, the program exiting or getting stuck and the warnings
+     * the core carried on past.
+     */
+    fn report(&mut self) -> Result<(), String> {
+        let lang = self.lang;
+
+        // checked on every pass rather than per frame, a short beep can end between frames
+        let beeping = self.machine.chip8.tim_snd > 0 || self.beep.is_on();
+        self.redraw |= beeping != self.renderer.is_beeping() && self.renderer.is_visible(render::Layer::Flash);
+        self.renderer.set_beep(beeping);
+
+        if self.machine.chip8.exited() && !self.halt_reported {
+            println!("{}", lang.tr("msg.exited"));
+            self.log.info(&format!("program exited (00FD) at frame {}", self.machine.frame()));
+            self.renderer.display.window_mut().set_title(lang.tr("window.halted")).map_err(|e| e.to_string())?;
+            self.renderer.set_beep(false);
+            self.renderer.rumble(Duration::ZERO);
+            self.renderer.menu = Some(lang.tr("menu.exited").to_string());
+            self.redraw = true;
+            self.halt_reported = true;
+        }
+
+        match (self.machine.runaway, self.runaway_reported) {
+            (Some(pc), false) => {
+                let pc = format!("{:#05x}", pc);
+                println!("{}", lang.format("msg.runaway", &[&pc, &self.machine.runaway_limit.unwrap_or_default()]));
+                self.log.warn(&format!("probable infinite loop at {} at frame {}", pc, self.machine.frame()));
+                self.runaway_reported = true;
+            }
+            (None, true) => self.runaway_reported = false,
+            _ => {}
+        }
+
+        // the core carried on past these, they only show up in the log and the message layer
+        for warning in self.machine.chip8.warnings.iter().skip(self.warnings_reported) {
+            self.log.warn(&format!("{} at frame {}, carried on", warning, self.machine.frame()));
+        }
+        self.warnings_reported = self.machine.chip8.warnings.len();
+        Ok(())
+    }
+
+    /**
+     * Take the window icon and the thumbnail once the program is far enough along.
+     */
+    fn capture(&mut self) -> Result<(), String> {
+        // by now most ROMs show their title screen, which makes a recognizable icon
+        if !self.icon_set && self.machine.frame() >= ICON_FRAME {
+            self.renderer.set_icon(self.machine.chip8.get_vram())?;
+            self.icon_set = true;
+        }
+        if let Some(path) = self.thumbnail.take_if(|_| self.machine.frame() >= self.config.thumbnail_frame) {
+            match thumbnail::store(self.config, self.machine, &path) {
+                Ok(()) => self.log.info(&format!("thumbnail written to {}", path)),
+                Err(e) => self.log.warn(&e),
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Bring the menus, the message layer and the debug overlay up to date.
+     */
+    fn update_overlays(&mut self) {
+        let lang = self.lang;
+        self.renderer.commands = self
+            .command_palette
+            .as_ref()
+            .map(|commands| commands.text(lang))
+            .or_else(|| self.remap.as_ref().map(|(wizard, _)| wizard.text(lang)))
+            .or_else(|| self.controller_menu.as_ref().map(|menu| menu.text(&self.renderer.controller_names(), lang)))
+            .or_else(|| self.picture_menu.as_ref().map(|menu| menu.text(&self.renderer.picture, lang)));
+        self.renderer.highlight = self.remap.as_ref().map(|(wizard, _)| wizard.key());
+
+        if self.renderer.is_visible(render::Layer::Messages) {
+            let recent = self.log.recent();
+            let messages = format!("{}\n{}", lang.tr("menu.messages"), if recent.is_empty() { lang.tr("msg.no_messages") } else { &recent });
+            self.redraw |= messages != self.renderer.messages;
+            self.renderer.messages = messages;
+        }
+
+        if self.renderer.is_visible(render::Layer::Debug) {
+            let machine = &*self.machine;
+            let mut sprite_box = debugger::next_sprite(&machine.chip8);
+            let mut text = format!("PC {:03X}  I {:03X}", machine.chip8.pc, machine.chip8.i);
+            // the sprite row being revealed, and the byte in memory it came from
+            if let Some(row) = self.slow_draw.as_ref().and_then(|slow| slow.current) {
+                sprite_box = Some((row.x, row.y, 8, 1));
+                text = format!("{}\nrow {:03X}: {:08b}{}", text, row.addr, row.byte, if row.collided { "  VF=1" } else { "" });
+            }
+            for watch in self.debugger.watch_lines(machine) {
+                text = format!("{}\n{}", text, watch);
+            }
+            // while single-stepping
+            let changes = self.debugger.changes.as_ref().map_or(String::new(), |changes| changes.to_string());
+            if sprite_box != self.renderer.sprite_box || text != self.renderer.debug_text || changes != self.renderer.debug_changes {
+                self.renderer.sprite_box = sprite_box;
+                self.renderer.debug_text = text;
+                self.renderer.debug_changes = changes;
+                self.redraw = true;
+            }
+        }
+    }
+
+    /**
+     * Draw the window if anything on it changed.
+     */
+    fn present(&mut self) -> Result<(), String> {
+        let machine = &mut *self.machine;
+        // the renderer fits whatever size the VRAM has, nothing to do but log it
+        if machine.chip8.resolution_changed {
+            let vram = machine.chip8.get_vram();
+            println!("{}", self.lang.format("msg.resolution", &[&vram.width(), &vram.height()]));
+            machine.chip8.resolution_changed = false;
+        }
+
+        if machine.chip8.vram_changed || self.redraw {
+            let render_started = Instant::now();
+            let plane2 = machine.chip8.xo_chip().then(|| machine.chip8.get_plane2());
+            self.renderer.update(self.slow_draw.as_ref().and_then(SlowDraw::screen).unwrap_or(machine.chip8.get_vram()), plane2)?;
+            machine.chip8.vram_changed = false;
+            self.stats.frames_rendered += 1;
+            self.stats.render += render_started.elapsed();
+        }
+        Ok(())
+    }
+
+    /**
+     * Sleep until the next frame is due, or until there is input if the program is idle.
+     */
+    fn wait(&mut self) -> Result<(), String> {
+        // idle with the timers run out (FX0A, 00FD or a jump to itself), nothing changes
+        // until the user does something, so block on the event queue instead of ticking frames
+        // the debugger window keeps drawing, like the REPL keeps being polled
+        #[cfg(feature = "gui-debug")]
+        let debug_ui_open = self.debug_ui.is_some();
+        #[cfg(not(feature = "gui-debug"))]
+        let debug_ui_open = false;
+        let idle = self.machine.idle()
+            && self.machine.chip8.tim_delay == 0
+            && self.machine.chip8.tim_snd == 0
+            && !self.beep.is_on()
+            && self.playback.is_none()
+            && self.repl.is_none()
+            && !debug_ui_open
+            && !self.renderer.palette_cycle
+            && !self.renderer.fading()
+            && !self.slow_draw.as_ref().is_some_and(SlowDraw::busy);
+
+        let sleep_started = Instant::now();
+        if idle {
+            self.pending = self.renderer.event_pump.wait_event_timeout(IDLE_WAIT_MS);
+        } else {
+            thread::sleep(self.fixedstep.until_next());
+        }
+        self.stats.sleep += sleep_started.elapsed();
+        self.stats.end_pass(self.machine, self.renderer.audio_samples())
+    }
+
+    /**
+     * Store the breakpoints, watches, labels and overlay for the next run of this ROM.
+     */
+    fn close(self) -> Result<(), String> {
+        let session = Session {
+            breakpoints: self.debugger.breakpoints,
+            watches: self.debugger.watches.iter().map(|expr| expr.to_string()).collect(),
+            labels: self.debugger.labels,
+            overlay: self.renderer.is_visible(render::Layer::Debug),
+        };
+        session.store(&self.session_path).inspect_err(|e| self.log.error(e))
+    }
+}
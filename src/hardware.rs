@@ -1,26 +1,140 @@
-use rand::Rng;
 
-use array2d::{Array2D, Error};
-use sdl2::keyboard::Keycode;
+use crate::coords::{self, Edge};
+use crate::font::{BIG_FONT_SET, FONT_SET};
+
+use crate::framebuffer::Framebuffer;
+use crate::palette::Palette;
+use crate::peripheral::Peripheral;
+use crate::quirks::Quirks;
+use crate::random::{Host, Random};
+
+use std::fmt;
+use std::ops::Range;
 
 pub const CHIP8_WIDTH: u32 = 64;
 pub const CHIP8_HEIGHT: u32 = 32;
+// SCHIP's hires mode, see `00FF`
+pub const SCHIP_WIDTH: u32 = 128;
+pub const SCHIP_HEIGHT: u32 = 64;
 pub const MULTIPLIER: u32 = 20;
 
 pub const RAM_SIZE: usize = 4096;
+// XO-CHIP's address space, see `Chip8::set_xo_chip`
+pub const XO_RAM_SIZE: usize = 65536;
+// distinct `Chip8::warnings` kept
+pub const MAX_WARNINGS: usize = 64;
 
 pub const V_REG_COUNT: usize = 16;
 pub const STACK_SIZE: usize = 16;
 
 pub const KEY_COUNT: usize = 16;
 
+// default load addresses of the built-in fonts
+pub const FONT_ADDR: usize = 0x50;
+pub const BIG_FONT_ADDR: usize = 0xA0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Error {
+    // pixel access outside the framebuffer
+    OutOfBounds { x: usize, y: usize },
+    // peripheral range outside RAM or overlapping one that is already mapped
+    InvalidMapping { start: usize, end: usize },
+    // the instruction at PC isn't one the core knows
+    IllegalOpcode { pc: usize, opcode: u16 },
+    // 00EE with nothing to return to, or 2NNN with `STACK_SIZE` calls already nested
+    StackUnderflow { pc: usize },
+    StackOverflow { pc: usize },
+    // the instruction at PC (or PC itself) reaches outside RAM
+    MemoryOutOfBounds { pc: usize, addr: usize },
+    // `a + b` in the instruction at PC doesn't fit, a fault when `Chip8::hardened` is set and
+    // one of `Chip8::warnings` otherwise
+    Overflow { pc: usize, opcode: u16, a: usize, b: usize },
+    // `len` bytes loaded at `addr` don't fit in RAM
+    LoadOutOfBounds { addr: usize, len: usize },
+    // a program loaded at `start`-`end` would overwrite the font at `font`
+    FontOverlap { start: usize, end: usize, font: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::OutOfBounds { x, y } => write!(f, "pixel {},{} is off screen", x, y),
+            Error::InvalidMapping { start, end } => write!(f, "can't map a peripheral at {:03X}-{:03X}", start, end),
+            Error::IllegalOpcode { pc, opcode } => write!(f, "illegal opcode {:04X} at {:03X}", opcode, pc),
+            Error::StackUnderflow { pc } => write!(f, "return with an empty stack at {:03X}", pc),
+            Error::StackOverflow { pc } => write!(f, "stack overflow at {:03X}", pc),
+            Error::MemoryOutOfBounds { pc, addr } => write!(f, "access to {:04X} outside RAM at {:03X}", addr, pc),
+            Error::Overflow { pc, opcode, a, b } => write!(f, "{:X} + {:X} overflows in {:04X} at {:03X}", a, b, opcode, pc),
+            Error::LoadOutOfBounds { addr, len } => write!(f, "{} bytes at {:03X} don't fit in RAM", len, addr),
+            Error::FontOverlap { start, end, font } => write!(f, "program at {:03X}-{:03X} overlaps the font at {:03X}", start, end, font),
+        }
+    }
+}
+
+/**
+ * One row of a sprite, right after `DXYN` drew it, for frontends that show the drawing as
+ * it happens. Rows below the bottom edge aren't drawn and aren't reported. The 16 pixel
+ * rows of `DXY0` come as two, one per byte, and a right half past the right edge isn't
+ * reported either.
+ *
+ * ```
+ * use std::sync::{Arc, Mutex};
+ * use chip8::hardware::{Chip8, DrawRow};
+ *
+ * let mut chip8 = Chip8::new();
+ * let rows = Arc::new(Mutex::new(Vec::new()));
+ * let sink = rows.clone();
+ * chip8.draw_hook = Some(Box::new(move |row: &DrawRow| sink.lock().unwrap().push(*row)));
+ * // I = 0x206, draw 2 rows at V0,V1 = 0,0, then the sprite
+ * chip8.load_ram(&[0xA2, 0x06, 0xD0, 0x12, 0x12, 0x04, 0xF0, 0x90], 0x200).unwrap();
+ * chip8.start();
+ * chip8.cycle().unwrap();
+ * chip8.cycle().unwrap();
+ *
+ * let rows = rows.lock().unwrap();
+ * assert_eq!(rows[0], DrawRow { addr: 0x206, byte: 0xF0, x: 0, y: 0, collided: false });
+ * assert_eq!(rows[1], DrawRow { addr: 0x207, byte: 0x90, x: 0, y: 1, collided: false });
+ * ```
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DrawRow {
+    // where the row came from, I plus the row number
+    pub addr: usize,
+    pub byte: u8,
+    // screen position of the row's leftmost pixel
+    pub x: usize,
+    pub y: usize,
+    // the row turned a pixel off, which sets VF
+    pub collided: bool,
+}
+
+// see `Chip8::draw_hook`
+pub type DrawHook = Box<dyn FnMut(&DrawRow) + Send>;
+
 pub enum PC {
     // keep current PC value
     Keep = 0,
     // increment PC with 2
     Step = 1,
-    // increment PC with 4
+    // skip the following instruction, 4 bytes for XO-CHIP's `F000 NNNN` and 2 otherwise
     Skip = 2,
+    // increment PC with 4, past the address of `F000 NNNN`
+    Long = 3,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum State {
+    Running,
+    // cycle() is a no-op while halted, timers keep running
+    Halted(HaltReason),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HaltReason {
+    // program exited through 00FD, only start() resumes it
+    Exit,
+    // FX0A: waiting for a key to be pressed and released, which is then stored in VX
+    WaitKey(usize),
 }
 
 pub struct Chip8 {
@@ -31,10 +145,48 @@ pub struct Chip8 {
     pub v: [u8; V_REG_COUNT],
     pub tim_delay: u8,
     pub tim_snd: u8,
-    ram: [u8; RAM_SIZE],
-    vram: Array2D<bool>,
+    // `RAM_SIZE` bytes, `XO_RAM_SIZE` in XO-CHIP mode
+    ram: Vec<u8>,
+    pub(crate) vram: Framebuffer,
+    // XO-CHIP's second bitplane, the size of `vram` and blank unless a program draws to it
+    pub(crate) vram2: Framebuffer,
+    // bitplanes `DXYN` and `00E0` work on, bit 0 for `vram` and bit 1 for `vram2`, see `FN01`
+    pub planes: u8,
+    // the XO-CHIP instructions and address space, see `set_xo_chip`
+    xo_chip: bool,
     pub vram_changed: bool,
+    // set when the display size changes, see `set_resolution`; frontends clear it
+    pub resolution_changed: bool,
+    // value last written to the sound timer by FX18, for frontends that schedule the beep
+    // themselves; they take it
+    pub sound_request: Option<u8>,
     pub keys: [bool; KEY_COUNT],
+    pub font_addr: usize,
+    pub big_font_addr: usize,
+    // the 4x5 digits `load_fonts` puts at `font_addr`, see `chip8::platform`
+    pub font_set: &'static [u8; 80],
+    pub state: State,
+    // print every executed instruction, off unless the frontend asks for it
+    pub trace: bool,
+    // fault on arithmetic that silently wraps or leaves RAM, see `check_overflow`
+    pub hardened: bool,
+    // only the original CHIP-8 of 1977: later instructions fault, see `validate`
+    pub strict: bool,
+    // how the instructions interpreters disagree on behave
+    pub quirks: Quirks,
+    // key pressed during FX0A, the wait ends when it is released
+    pub(crate) wait_key: Option<usize>,
+    // address ranges claimed by peripherals, see `map_peripheral`
+    pub(crate) peripherals: Vec<(Range<usize>, Box<dyn Peripheral>)>,
+    // source of CXNN's random numbers, seeded from the OS unless `seed` is called
+    pub random: Box<dyn Random>,
+    // timer ticks so far, wrapping, for `Random`s that go by the display interrupt
+    ticks: u8,
+    // called after every sprite row `DXYN` draws, twice per row for `DXY0`'s 16 pixels
+    pub draw_hook: Option<DrawHook>,
+    // what `check_overflow` found while not hardened, the instructions ran anyway; each one
+    // only once, and only the first `MAX_WARNINGS`, for frontends to show
+    pub warnings: Vec<Error>,
 }
 
 impl Chip8 {
@@ -45,66 +197,309 @@ impl Chip8 {
             stack: Vec::new(),
             i: 0x0,
             v: [0x0; V_REG_COUNT],
-            tim_delay: 255,
-            tim_snd: 255,
-            ram: [0x0; RAM_SIZE],
-            vram: Array2D::filled_with(false, CHIP8_HEIGHT as usize, CHIP8_WIDTH as usize),
+            tim_delay: 0,
+            tim_snd: 0,
+            ram: vec![0x0; RAM_SIZE],
+            vram: Framebuffer::new(CHIP8_WIDTH as usize, CHIP8_HEIGHT as usize),
+            vram2: Framebuffer::new(CHIP8_WIDTH as usize, CHIP8_HEIGHT as usize),
+            planes: 1,
+            xo_chip: false,
             vram_changed: false,
+            resolution_changed: false,
+            sound_request: None,
             keys: [false; KEY_COUNT],
+            font_addr: FONT_ADDR,
+            big_font_addr: BIG_FONT_ADDR,
+            font_set: &FONT_SET,
+            state: State::Running,
+            trace: false,
+            hardened: false,
+            strict: false,
+            quirks: Quirks::default(),
+            wait_key: None,
+            peripherals: Vec::new(),
+            random: Box::new(Host::new()),
+            ticks: 0,
+            draw_hook: None,
+            warnings: Vec::new(),
         }
     }
 
-    pub fn load_ram(&mut self, data: &[u8], addr: usize) {
+    /**
+     * Make CXNN's random numbers repeatable: the same seed gives the same sequence.
+     */
+    pub fn seed(&mut self, seed: u64) {
+        self.random.seed(seed);
+    }
+
+    /**
+     * Copy `data` into RAM at `addr`, all of it or nothing.
+     *
+     * ```
+     * use chip8::hardware::{Chip8, Error, RAM_SIZE};
+     *
+     * let mut chip8 = Chip8::new();
+     * assert_eq!(chip8.load_ram(&[1, 2], RAM_SIZE - 2), Ok(()));
+     * assert_eq!(chip8.load_ram(&[1, 2, 3], RAM_SIZE - 2), Err(Error::LoadOutOfBounds { addr: 0xFFE, len: 3 }));
+     * assert_eq!(chip8.get_ram()[RAM_SIZE - 2..], [1, 2]);
+     * ```
+     */
+    pub fn load_ram(&mut self, data: &[u8], addr: usize) -> Result<(), Error> {
         let len = data.len();
-        self.ram[addr..addr + len].copy_from_slice(data);
+        match addr.checked_add(len).filter(|end| *end <= self.ram.len()) {
+            Some(end) => {
+                self.ram[addr..end].copy_from_slice(data);
+                Ok(())
+            }
+            None => Err(Error::LoadOutOfBounds { addr, len }),
+        }
+    }
+
+    /**
+     * Load the small 4x5 font and the big 8x10 font at `font_addr` and `big_font_addr`.
+     */
+    pub fn load_fonts(&mut self) -> Result<(), Error> {
+        self.load_ram(self.font_set, self.font_addr)?;
+        self.load_ram(&BIG_FONT_SET, self.big_font_addr)
+    }
+
+    /**
+     * Where the two fonts are in RAM, the small one first.
+     */
+    pub fn font_ranges(&self) -> [Range<usize>; 2] {
+        [
+            self.font_addr..self.font_addr + self.font_set.len(),
+            self.big_font_addr..self.big_font_addr + BIG_FONT_SET.len(),
+        ]
     }
 
     pub fn start(&mut self) {
         self.pc = 0x200;
+        self.state = State::Running;
+        self.wait_key = None;
     }
 
-    pub fn get_vram(&mut self) -> &Array2D<bool> {
+    /**
+     * Whether the program has exited through 00FD.
+     */
+    pub fn exited(&self) -> bool {
+        self.state == State::Halted(HaltReason::Exit)
+    }
+
+    pub fn get_vram(&self) -> &Framebuffer {
         return &self.vram;
     }
 
+    /**
+     * XO-CHIP's second bitplane, `get_vram` is the first.
+     */
+    pub fn get_plane2(&self) -> &Framebuffer {
+        &self.vram2
+    }
+
+    /**
+     * The display as a `width * height` RGBA image with both bitplanes composed in
+     * `palette`'s four colors, see `Palette::plane_color`. The same as
+     * `get_vram().to_rgba(palette)` while the second plane is blank.
+     *
+     * ```
+     * use chip8::hardware::Chip8;
+     * use chip8::palette::Palette;
+     *
+     * let mut chip8 = Chip8::new();
+     * chip8.set_xo_chip(true);
+     * // select plane 2, I = 0x208, draw 1 row at V0,V0 = 0,0
+     * chip8.load_ram(&[0xF2, 0x01, 0xA2, 0x08, 0xD0, 0x01, 0x12, 0x06, 0x80], 0x200).unwrap();
+     * chip8.start();
+     * (0..3).for_each(|_| chip8.cycle().unwrap());
+     *
+     * assert_eq!(chip8.get_vram().iter_set_pixels().count(), 0);
+     * assert_eq!(chip8.get_plane2().get(0, 0), Some(true));
+     * let rgba = chip8.to_rgba(&Palette::GREEN);
+     * assert_eq!(rgba[..3], Palette::GREEN.plane2);
+     * assert_eq!(rgba[4..7], Palette::GREEN.off);
+     * ```
+     */
+    pub fn to_rgba(&self, palette: &Palette) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.vram.width() * self.vram.height() * 4);
+
+        for ((_, _, px), (_, _, px2)) in self.vram.pixels().zip(self.vram2.pixels()) {
+            let [r, g, b] = palette.plane_color(px as u8 | (px2 as u8) << 1);
+            out.extend_from_slice(&[r, g, b, 0xFF]);
+        }
+
+        out
+    }
+
+    /**
+     * Switch to a blank display of a different size, e.g. for the SCHIP hires mode.
+     */
+    pub fn set_resolution(&mut self, width: usize, height: usize) {
+        if (width, height) != (self.vram.width(), self.vram.height()) {
+            self.vram = Framebuffer::new(width, height);
+            self.vram2 = Framebuffer::new(width, height);
+            self.resolution_changed = true;
+            self.vram_changed = true;
+        }
+    }
+
+    /**
+     * Turn XO-CHIP mode on or off. On, RAM grows to `XO_RAM_SIZE` and the XO-CHIP
+     * instructions run: `FN01` selecting the bitplanes, `F000 NNNN` loading a 16-bit
+     * address into I, and `5XY2`/`5XY3` saving and loading a range of registers. Its sound
+     * instructions, `F002` and `FX3A`, are accepted and ignored, the beep stays the same.
+     * Switching off drops the RAM past `RAM_SIZE`.
+     *
+     * ```
+     * use chip8::hardware::{Chip8, XO_RAM_SIZE};
+     *
+     * let mut chip8 = Chip8::new();
+     * chip8.set_xo_chip(true);
+     * assert_eq!(chip8.get_ram().len(), XO_RAM_SIZE);
+     * // I = 0xC000, V2 = 7, store V2 and V3 at I
+     * chip8.load_ram(&[0xF0, 0x00, 0xC0, 0x00, 0x62, 0x07, 0x52, 0x32], 0x200).unwrap();
+     * chip8.start();
+     * (0..3).for_each(|_| chip8.cycle().unwrap());
+     *
+     * assert_eq!(chip8.i, 0xC000);
+     * assert_eq!(chip8.get_ram()[0xC000..0xC002], [0x07, 0x00]);
+     * ```
+     */
+    pub fn set_xo_chip(&mut self, on: bool) {
+        self.xo_chip = on;
+        self.ram.resize(if on { XO_RAM_SIZE } else { RAM_SIZE }, 0);
+    }
+
+    pub fn xo_chip(&self) -> bool {
+        self.xo_chip
+    }
+
+    pub fn get_ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /**
+     * Hand the addresses in `range` to `peripheral`: data reads and writes by the program
+     * (`DXYN`, `FX33`, `FX55`, `FX65`) go to it instead of RAM. Instructions are always
+     * fetched from RAM.
+     */
+    pub fn map_peripheral(&mut self, range: Range<usize>, peripheral: Box<dyn Peripheral>) -> Result<(), Error> {
+        let invalid = range.is_empty()
+            || range.end > self.ram.len()
+            || self.peripherals.iter().any(|(r, _)| r.start < range.end && range.start < r.end);
+        if invalid {
+            return Err(Error::InvalidMapping { start: range.start, end: range.end });
+        }
+
+        self.peripherals.push((range, peripheral));
+        Ok(())
+    }
+
+    /**
+     * Read a byte as the program sees it, through any mapped peripheral.
+     */
+    pub fn read(&mut self, addr: usize) -> u8 {
+        match self.peripherals.iter_mut().find(|(r, _)| r.contains(&addr)) {
+            Some((range, peripheral)) => peripheral.read(addr - range.start),
+            None => self.ram[addr],
+        }
+    }
+
+    /**
+     * Write a byte as the program does, through any mapped peripheral.
+     */
+    pub fn write(&mut self, addr: usize, value: u8) {
+        match self.peripherals.iter_mut().find(|(r, _)| r.contains(&addr)) {
+            Some((range, peripheral)) => peripheral.write(addr - range.start, value),
+            None => self.ram[addr] = value,
+        }
+    }
+
     pub fn decrease_timers(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
         if self.tim_delay > 0 {
-            self.tim_delay -= self.tim_delay;
+            self.tim_delay -= 1;
         }
         if self.tim_snd > 0 {
-            self.tim_snd -= self.tim_snd;
-        }
-    }
-
-    pub fn set_input(&mut self, key: Keycode, key_down: bool) {
-        let index = match key {
-            Keycode::Num1 => Some(0x1),
-            Keycode::Num2 => Some(0x2),
-            Keycode::Num3 => Some(0x3),
-            Keycode::Num4 => Some(0xc),
-            Keycode::Q => Some(0x4),
-            Keycode::W => Some(0x5),
-            Keycode::E => Some(0x6),
-            Keycode::R => Some(0xd),
-            Keycode::A => Some(0x7),
-            Keycode::S => Some(0x8),
-            Keycode::D => Some(0x9),
-            Keycode::F => Some(0xe),
-            Keycode::Z => Some(0xa),
-            Keycode::X => Some(0x0),
-            Keycode::C => Some(0xb),
-            Keycode::V => Some(0xf),
-            _ => None,
-        };
+            self.tim_snd -= 1;
+        }
+    }
 
-        if index != None {
-            self.keys[index.unwrap()] = key_down;
+    /**
+     * Set the state of keypad key `key` (0x0-0xF). Frontends map their own key codes onto this.
+     * Also ends an `FX0A` wait, which like on the VIP completes when the key is released.
+     *
+     * ```
+     * use chip8::hardware::{Chip8, HaltReason, State};
+     *
+     * let mut chip8 = Chip8::new();
+     * // V3 = key, exit
+     * chip8.load_ram(&[0xF3, 0x0A, 0x00, 0xFD], 0x200).unwrap();
+     * chip8.start();
+     * chip8.cycle().unwrap();
+     * assert_eq!(chip8.state, State::Halted(HaltReason::WaitKey(3)));
+     *
+     * chip8.set_input(0xB, true);
+     * assert_eq!(chip8.state, State::Halted(HaltReason::WaitKey(3)));
+     * chip8.set_input(0xB, false);
+     * assert_eq!(chip8.state, State::Running);
+     * assert_eq!(chip8.v[3], 0xB);
+     * ```
+     */
+    pub fn set_input(&mut self, key: usize, key_down: bool) {
+        self.keys[key] = key_down;
+
+        if let State::Halted(HaltReason::WaitKey(reg)) = self.state {
+            match self.wait_key {
+                None if key_down => self.wait_key = Some(key),
+                Some(pressed) if pressed == key && !key_down => {
+                    self.v[reg] = key as u8;
+                    self.wait_key = None;
+                    self.state = State::Running;
+                }
+                _ => {}
+            }
         }
     }
 
 
+    /**
+     * Fetch, decode and execute a single instruction.
+     *
+     * ```
+     * use chip8::hardware::Chip8;
+     *
+     * let mut chip8 = Chip8::new();
+     * // V0 = 0xFF, V1 = 0x02, V0 += V1 (carries into VF), exit
+     * chip8.load_ram(&[0x60, 0xFF, 0x61, 0x02, 0x80, 0x14, 0x00, 0xFD], 0x200).unwrap();
+     * chip8.start();
+     *
+     * for _ in 0..4 {
+     *     chip8.cycle().unwrap();
+     * }
+     * assert_eq!(chip8.v[0x0], 0x01);
+     * assert_eq!(chip8.v[0xF], 0x01);
+     * assert!(chip8.exited());
+     * ```
+     */
     pub fn cycle(&mut self) -> Result<(), Error> {
+        if let State::Halted(_) = self.state {
+            return Ok(());
+        }
+
+        if self.pc + 1 >= self.ram.len() {
+            return Err(Error::MemoryOutOfBounds { pc: self.pc, addr: self.pc });
+        }
         let opcode = (self.ram[self.pc] as usize) << 8 | (self.ram[self.pc + 1] as usize);
+        self.validate(opcode)?;
+        match self.check_overflow(opcode) {
+            Err(e) if self.hardened => return Err(e),
+            // counters wrapping around with 7XNN are business as usual
+            Err(e) if opcode >> 12 != 0x7 && self.warnings.len() < MAX_WARNINGS && !self.warnings.contains(&e) => {
+                self.warnings.push(e)
+            }
+            _ => {}
+        }
 
         let mut nibs: Vec<usize> = Vec::new();
 
@@ -112,7 +507,9 @@ impl Chip8 {
             nibs.push((opcode & (0xF000 >> (n * 4))) >> (12 - (n * 4)));
         }
 
-        println!("executing {:#0x} @ ROM {:#0x}", opcode, self.pc - 0x200);
+        if self.trace {
+            println!("executing {:#06x} at {:#05x}", opcode, self.pc);
+        }
 
         let step_pc = match nibs[0] {
             0x0 => self.op_0xxx(opcode),
@@ -135,13 +532,13 @@ impl Chip8 {
                 self.i = (opcode & 0xFFF) as usize;
                 PC::Step
             }
-            0xB => { // Jump to address NNN + V0
-                self.pc = (opcode & 0xFFF) + self.v[0] as usize;
+            0xB => { // Jump to address NNN + V0, or XNN + VX with the jump quirk
+                self.pc = (opcode & 0xFFF) + self.v[self.jump_register(opcode)] as usize;
                 PC::Keep
             }
             0xC => { // Set VX to a random number with a mask of NN
                 self.v[nibs[1]] =
-                    rand::thread_rng().gen_range(0..=255) & (((nibs[2] << 4) | nibs[3]) as u8);
+                    self.random.next(&self.ram, self.ticks) & (((nibs[2] << 4) | nibs[3]) as u8);
                 PC::Step
             }
             0xD => self.op_Dxxx(&nibs),
@@ -152,26 +549,180 @@ impl Chip8 {
 
         match step_pc {
             PC::Step => self.pc += 2,
-            PC::Skip => self.pc += 4,
+            PC::Skip => self.pc += 2 + self.instruction_len(self.pc + 2),
+            PC::Long => self.pc += 4,
             PC::Keep => {}
-            _ => panic!("Unhandled PC option"),
         }
 
         Ok(())
     }
 
+    /**
+     * Bytes the instruction at `addr` takes, 4 for XO-CHIP's `F000 NNNN`, 2 for the rest.
+     *
+     * ```
+     * use chip8::hardware::Chip8;
+     *
+     * let mut chip8 = Chip8::new();
+     * chip8.load_ram(&[0xF0, 0x00, 0xE0, 0x00], 0x200).unwrap();
+     * assert_eq!(chip8.instruction_len(0x200), 2);
+     * chip8.set_xo_chip(true);
+     * assert_eq!(chip8.instruction_len(0x200), 4);
+     * ```
+     */
+    pub fn instruction_len(&self, addr: usize) -> usize {
+        match self.ram.get(addr..addr + 2) {
+            Some([0xF0, 0x00]) if self.xo_chip => 4,
+            _ => 2,
+        }
+    }
+
+    /**
+     * Catch the instructions that would fault before any of them runs, so the state is left
+     * as it was and the PC still points at the culprit.
+     *
+     * With `strict` set, the instructions the COSMAC VIP's interpreter didn't have are
+     * illegal too: SCHIP's `00CN`, `00FB`, `00FC`, `00FD`, `00FE`, `00FF` and `FX30`, `0NNN`
+     * machine code calls, and `5XY0`/`9XY0` with anything but 0 in the last nibble. `DXY0` is an empty sprite then
+     * rather than SCHIP's 16x16 one. The XO-CHIP instructions are only legal in XO-CHIP
+     * mode, see `set_xo_chip`.
+     *
+     * ```
+     * use chip8::hardware::{Chip8, Error};
+     *
+     * let mut chip8 = Chip8::new();
+     * chip8.strict = true;
+     * // skip if V0 == V1, with a stray 1 at the end
+     * chip8.load_ram(&[0x50, 0x11], 0x200).unwrap();
+     * chip8.start();
+     * assert_eq!(chip8.cycle(), Err(Error::IllegalOpcode { pc: 0x200, opcode: 0x5011 }));
+     * ```
+     */
+    fn validate(&self, opcode: usize) -> Result<(), Error> {
+        let (pc, x, y, nn) = (self.pc, (opcode >> 8) & 0xF, (opcode >> 4) & 0xF, opcode & 0xFF);
+        let illegal = Err(Error::IllegalOpcode { pc, opcode: opcode as u16 });
+
+        match opcode >> 12 {
+            0x0 if opcode == 0x00EE && self.stack.is_empty() => Err(Error::StackUnderflow { pc }),
+            0x0 if self.strict && opcode != 0x00E0 && opcode != 0x00EE => illegal,
+            0x5 | 0x9 if self.strict && opcode & 0xF != 0 => illegal,
+            0x5 if self.xo_chip && matches!(opcode & 0xF, 0x2 | 0x3) => self.check_span(x.abs_diff(y) + 1),
+            0x2 if self.stack.len() >= STACK_SIZE => Err(Error::StackOverflow { pc }),
+            0x8 => match opcode & 0xF {
+                0x0..=0x7 | 0xE => Ok(()),
+                _ => illegal,
+            },
+            0xD => {
+                let (rows, row_bytes) = self.sprite_size(opcode & 0xF);
+                self.check_span(rows * row_bytes * self.planes.count_ones() as usize)
+            }
+            0xE => match nn {
+                0x9E | 0xA1 => Ok(()),
+                _ => illegal,
+            },
+            0xF if self.strict && nn == 0x30 => illegal,
+            0xF => match nn {
+                0x00 if self.xo_chip && x == 0 && pc + 3 >= self.ram.len() => Err(Error::MemoryOutOfBounds { pc, addr: pc + 3 }),
+                0x00 if self.xo_chip && x == 0 => Ok(()),
+                0x01 if self.xo_chip && x <= 3 => Ok(()),
+                0x02 if self.xo_chip && x == 0 => self.check_span(16),
+                0x3A if self.xo_chip => Ok(()),
+                0x33 => self.check_span(3),
+                0x55 | 0x65 => self.check_span(x + 1),
+                0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x30 => Ok(()),
+                _ => illegal,
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /**
+     * For contributors chasing core bugs: the additions that wrap around or produce an
+     * address past the end of RAM without the program being told, checked before the
+     * instruction runs. The carries of `8XY4` and friends are left alone, they set VF.
+     *
+     * ```
+     * use chip8::hardware::{Chip8, Error};
+     *
+     * let mut chip8 = Chip8::new();
+     * chip8.hardened = true;
+     * // V0 = 0xFF, V0 += 2
+     * chip8.load_ram(&[0x60, 0xFF, 0x70, 0x02], 0x200).unwrap();
+     * chip8.start();
+     * chip8.cycle().unwrap();
+     * assert_eq!(chip8.cycle(), Err(Error::Overflow { pc: 0x202, opcode: 0x7002, a: 0xFF, b: 0x02 }));
+     * assert_eq!(chip8.v[0], 0xFF);
+     *
+     * // not hardened: I = 0xFFF, V0 = 2, I += V0 runs and leaves a warning
+     * let mut chip8 = Chip8::new();
+     * chip8.load_ram(&[0xAF, 0xFF, 0x60, 0x02, 0xF0, 0x1E], 0x200).unwrap();
+     * chip8.start();
+     * (0..3).for_each(|_| chip8.cycle().unwrap());
+     * assert_eq!(chip8.warnings, vec![Error::Overflow { pc: 0x204, opcode: 0xF01E, a: 0xFFF, b: 0x02 }]);
+     * ```
+     */
+    fn check_overflow(&self, opcode: usize) -> Result<(), Error> {
+        let (x, nn, nnn) = ((opcode >> 8) & 0xF, opcode & 0xFF, opcode & 0xFFF);
+
+        match (opcode >> 12, nn) {
+            (0x7, _) => self.checked_add(opcode, self.v[x] as usize, nn, u8::MAX as usize),
+            (0xB, _) => self.checked_add(opcode, nnn, self.v[self.jump_register(opcode)] as usize, self.ram.len() - 2),
+            (0xF, 0x1E) => self.checked_add(opcode, self.i, self.v[x] as usize, self.ram.len() - 1),
+            (0xF, 0x55 | 0x65) if !self.quirks.load_store => self.checked_add(opcode, self.i, x + 1, self.ram.len() - 1),
+            _ => Ok(0),
+        }
+        .map(|_| ())
+    }
+
+    // `a + b` for the instruction at PC, an error if the sum is above `max`
+    fn checked_add(&self, opcode: usize, a: usize, b: usize, max: usize) -> Result<usize, Error> {
+        Some(a + b)
+            .filter(|sum| *sum <= max)
+            .ok_or(Error::Overflow { pc: self.pc, opcode: opcode as u16, a, b })
+    }
+
+    // the register `BNNN` adds to its address: V0, or VX with the jump quirk
+    fn jump_register(&self, opcode: usize) -> usize {
+        if self.quirks.jump {
+            (opcode >> 8) & 0xF
+        } else {
+            0
+        }
+    }
+
+    // the `len` bytes from I onwards have to be in RAM
+    fn check_span(&self, len: usize) -> Result<(), Error> {
+        if self.i + len > self.ram.len() {
+            Err(Error::MemoryOutOfBounds { pc: self.pc, addr: self.i + len - 1 })
+        } else {
+            Ok(())
+        }
+    }
+
     /**
      * `0NNN`: Execute machine language subroutine at address NNN
-     * `00E0`: Clear the screen
+     * `00E0`: Clear the screen, the selected bitplanes of it in XO-CHIP mode
      * `00EE`: Return from a subroutine
+     * `00CN`: Scroll the display down N pixels (SCHIP)
+     * `00FB`: Scroll the display right 4 pixels (SCHIP)
+     * `00FC`: Scroll the display left 4 pixels (SCHIP)
+     * `00FD`: Exit the interpreter (SCHIP)
+     * `00FE`: Switch to the 64x32 display (SCHIP)
+     * `00FF`: Switch to the 128x64 display (SCHIP)
+     *
+     * Scrolling goes by pixels of the current resolution, half as far across the screen in
+     * hires as in lores, like Octo rather than the HP 48 which always scrolled by hires
+     * pixels. In XO-CHIP mode it moves the selected bitplanes.
      */
     fn op_0xxx(&mut self, opcode: usize) -> PC {
         let mut ret = PC::Step;
 
         match opcode {
             0xE0 => {
-                self.vram =
-                    Array2D::filled_with(false, CHIP8_HEIGHT as usize, CHIP8_WIDTH as usize);
+                let planes = self.planes;
+                for plane in [1, 2].into_iter().filter(|plane| planes & plane != 0) {
+                    self.plane_mut(plane).clear();
+                }
                 self.vram_changed = true;
             }
             0xEE => {
@@ -179,6 +730,16 @@ impl Chip8 {
                 ret = PC::Keep;
                 self.sp -= 1;
             }
+            0xC0..=0xCF => self.scroll(0, (opcode & 0xF) as isize),
+            0xFB => self.scroll(4, 0),
+            0xFC => self.scroll(-4, 0),
+            0xFD => {
+                self.state = State::Halted(HaltReason::Exit);
+                ret = PC::Keep;
+            }
+            // both blank the screen, like `set_resolution` does
+            0xFE => self.set_resolution(CHIP8_WIDTH as usize, CHIP8_HEIGHT as usize),
+            0xFF => self.set_resolution(SCHIP_WIDTH as usize, SCHIP_HEIGHT as usize),
             _ => {
                 self.pc = opcode;
                 ret = PC::Keep;
@@ -188,6 +749,15 @@ impl Chip8 {
         return ret;
     }
 
+    // `00CN`, `00FB` and `00FC` on the selected planes
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let planes = self.planes;
+        for plane in [1, 2].into_iter().filter(|plane| planes & plane != 0) {
+            self.plane_mut(plane).scroll(dx, dy);
+        }
+        self.vram_changed = true;
+    }
+
     /**
      * `2NNN`: Execute subroutine starting at address NNN
      */
@@ -203,11 +773,23 @@ impl Chip8 {
      * `3XNN`: Skip the following instruction if the value of register VX equals NN
      * `4XNN`: Skip the following instruction if the value of register VX is not equal to NN
      * `5XY0`: Skip the following instruction if the value of register VX is equal to the value of register VY
+     * `5XY2`: Store registers VX to VY in memory starting at address I, in that order even when X > Y; I is unchanged (XO-CHIP)
+     * `5XY3`: Load registers VX to VY from memory starting at address I; I is unchanged (XO-CHIP)
      */
     fn op_3xxx_4xxx_5xxx(&mut self, nibs: &Vec<usize>) -> PC {
         let mut ret = PC::Step;
 
-        if nibs[0] == 0x3 && (self.v[nibs[1]] == ((nibs[2] << 4) | nibs[3]) as u8) {
+        if nibs[0] == 0x5 && self.xo_chip && (nibs[3] == 0x2 || nibs[3] == 0x3) {
+            let (x, y) = (nibs[1], nibs[2]);
+            for n in 0..=x.abs_diff(y) {
+                let reg = if x <= y { x + n } else { x - n };
+                if nibs[3] == 0x2 {
+                    self.write(self.i + n, self.v[reg]);
+                } else {
+                    self.v[reg] = self.read(self.i + n);
+                }
+            }
+        } else if nibs[0] == 0x3 && (self.v[nibs[1]] == ((nibs[2] << 4) | nibs[3]) as u8) {
             ret = PC::Skip;
         } else if nibs[0] == 0x4 && (self.v[nibs[1]] != ((nibs[2] << 4) | nibs[3]) as u8) {
             ret = PC::Skip;
@@ -252,6 +834,8 @@ impl Chip8 {
     * `8XYE` Store the value of register VY shifted left one bit in register VX¹
       - Set register VF to the most significant bit prior to the shift
       - VY is unchanged
+    * ¹ VX itself is shifted with the shift quirk, see `Quirks`. OR, AND and XOR clear VF
+      with the logic quirk
     */
     fn op_8xxx(&mut self, nibs: &Vec<usize>) -> PC {
         match nibs[3] {
@@ -279,8 +863,9 @@ impl Chip8 {
             }
 
             6 => {
-                self.v[15] = self.v[nibs[2]] & 1;
-                self.v[nibs[1]] = self.v[nibs[2]] >> 1;
+                let src = self.v[self.shift_source(nibs)];
+                self.v[15] = src & 1;
+                self.v[nibs[1]] = src >> 1;
             }
 
             7 => {
@@ -292,57 +877,104 @@ impl Chip8 {
                 self.v[nibs[1]] = val as u8;
             }
             0xE => {
-                self.v[15] = (self.v[nibs[2]] >> 7) & 1;
-                self.v[nibs[1]] = self.v[nibs[2]] << 1;
+                let src = self.v[self.shift_source(nibs)];
+                self.v[15] = (src >> 7) & 1;
+                self.v[nibs[1]] = src << 1;
             }
             _ => panic!("invalid instruction {:#0x} for 0x8xxx", nibs[3]),
         }
 
+        // the VIP's interpreter left VF cleared after OR, AND and XOR
+        if self.quirks.logic && (1..=3).contains(&nibs[3]) {
+            self.v[15] = 0x00;
+        }
+
         return PC::Step;
     }
 
+    // the register `8XY6` and `8XYE` shift: VY, or VX with the shift quirk
+    fn shift_source(&self, nibs: &[usize]) -> usize {
+        if self.quirks.shift {
+            nibs[1]
+        } else {
+            nibs[2]
+        }
+    }
+
     /**
     * `DXYN`: Draw a sprite at position VX, VY with N bytes of sprite data starting at the address stored in I
     - The corresponding graphic on the screen will be eight pixels wide (bits in 1 byte) and N pixels high
     - Set VF to 01 if any set pixels are changed to unset, and 00 otherwise
+    * `DXY0`: Draw a 16x16 sprite, two bytes per row, 32 bytes starting at I (SCHIP)
+    - VF is set the same way, not to SCHIP's count of rows that collided
+    - Pixels past the edge of the screen are cut off, or wrap around to the other side
+      without the clip quirk
+    - In XO-CHIP mode the sprite goes to each selected bitplane, the first plane's data
+      followed by the second's when both are selected. VF is set by a collision in either
     */
     fn op_Dxxx(&mut self, nibs: &Vec<usize>) -> PC {
-        let x = usize::from(self.v[nibs[1]]);
-        let y = usize::from(self.v[nibs[2]]);
-
-        let mut sprite_height = nibs[3];
-        let mut row_count = 0;
+        let (width, height) = (self.vram.width(), self.vram.height());
+        let x = coords::wrap(usize::from(self.v[nibs[1]]), width);
+        let y = coords::wrap(usize::from(self.v[nibs[2]]), height);
+        let (rows, row_bytes) = self.sprite_size(nibs[3]);
+        let edge = if self.quirks.clip { Edge::Clip } else { Edge::Wrap };
 
         self.v[15] = 0x0; // VF == 0
         self.vram_changed = true;
 
         // do some unpacking. each byte corresponds to 8 pixels
-        while sprite_height > 0 {
-            for n in 0..8 {
-                if x + n >= CHIP8_WIDTH as usize {
-                    break;
-                } 
-                
-                if y + row_count >= CHIP8_HEIGHT as usize {
-                    break;
-                }
-
-                // take endianness into account :)
-                let px_val = (self.ram[self.i + row_count] & (1 << 7 - n)) != 0;
-
-                if self.v[15] != 0x01 && *self.vram.get(y + row_count, x + n).unwrap() && px_val {
-                    self.v[15] = 0x01; // VF == 1 when a pixel has been turned off
+        let (mut addr, planes) = (self.i, self.planes);
+        for plane in [1, 2].into_iter().filter(|plane| planes & plane != 0) {
+            for row in 0..rows {
+                for part in 0..row_bytes {
+                    let byte = self.read(addr);
+                    let left = 8 * part;
+                    let mut collided = false;
+                    for n in 0..8 {
+                        let (Some(px), Some(py)) = (coords::offset(x, left + n, width, edge), coords::offset(y, row, height, edge)) else {
+                            break;
+                        };
+
+                        // take endianness into account :)
+                        let px_val = (byte & (1 << 7 - n)) != 0;
+
+                        let screen = self.plane_mut(plane);
+                        let lit = screen.get(px, py).unwrap();
+                        if lit && px_val {
+                            collided = true; // VF == 1 when a pixel has been turned off
+                        }
+                        screen.set(px, py, lit ^ px_val).unwrap();
+                    }
+                    if collided {
+                        self.v[15] = 0x01;
+                    }
+                    let (row_x, row_y) = (coords::offset(x, left, width, edge), coords::offset(y, row, height, edge));
+                    if let (Some(hook), Some(row_x), Some(row_y)) = (&mut self.draw_hook, row_x, row_y) {
+                        hook(&DrawRow { addr, byte, x: row_x, y: row_y, collided });
+                    }
+                    addr += 1;
                 }
-                self.vram.set(y + row_count, x + n, px_val).unwrap();
             }
+        }
 
-            row_count += 1;
-            sprite_height -= 1;
+        return PC::Step;
+    }
 
-            
+    // bitplane 1 (`vram`) or 2 (`vram2`)
+    fn plane_mut(&mut self, plane: u8) -> &mut Framebuffer {
+        if plane == 2 {
+            &mut self.vram2
+        } else {
+            &mut self.vram
         }
+    }
 
-        return PC::Step;
+    // rows and bytes per row of the sprite `DXYN` draws: N by 1, or SCHIP's 16 by 2 for N = 0
+    fn sprite_size(&self, n: usize) -> (usize, usize) {
+        match n {
+            0 if !self.strict => (16, 2),
+            n => (n, 1),
+        }
     }
 
     /**
@@ -350,61 +982,52 @@ impl Chip8 {
      * `EXA1` Skip the following instruction if the key corresponding to the hex value currently stored in register VX is not pressed
      */
     fn op_Exxx(&mut self, nibs: &Vec<usize>) -> PC {
-        let mut ret = PC::Step;
+        // only the low nibble of VX names a key
+        let pressed = self.keys[self.v[nibs[1]] as usize & 0xF];
 
-        for (x, key) in self.keys.into_iter().enumerate() {
-            if key {
-                match ((nibs[2] << 4) | nibs[3]) as u8 {
-                    0x9E => {
-                        if self.v[nibs[1]] as usize == x {
-                            ret = PC::Skip;
-                        }
-                    },
-                    0xA1 => {
-                        if self.v[nibs[1]] as usize != x {
-                            ret = PC::Skip;
-                        }
-                    },
-                    _ => panic!("invalid instruction"),
-                }
-            }
+        match ((nibs[2] << 4) | nibs[3]) as u8 {
+            0x9E if pressed => PC::Skip,
+            0xA1 if !pressed => PC::Skip,
+            0x9E | 0xA1 => PC::Step,
+            _ => panic!("invalid instruction"),
         }
-
-        self.keys.fill(false);
-
-        return ret;
     }
 
     /**
      * `FXxx` Misc register operations.
      */
     fn op_Fxxx(&mut self, nibs: &Vec<usize>) -> PC {
-        let mut ret = PC::Step;
-
         match ((nibs[2] << 4) | nibs[3]) as u8 {
+            // Load the 16-bit address in the next two bytes into I (XO-CHIP `F000 NNNN`)
+            0x00 => {
+                self.i = (self.ram[self.pc + 2] as usize) << 8 | self.ram[self.pc + 3] as usize;
+                return PC::Long;
+            }
+
+            // Select the bitplanes to draw to and clear, 0-3 (XO-CHIP `FN01`)
+            0x01 => self.planes = nibs[1] as u8,
+
+            // Load the audio pattern from I and set the pitch to VX (XO-CHIP `F002` and `FX3A`),
+            // the beep doesn't change
+            0x02 | 0x3A => {}
+
             // Store the current value of the delay timer in register VX
             0x07 => self.v[nibs[1]] = self.tim_delay,
 
-            // Wait for a keypress and store the result in register VX
+            // Wait for a keypress and store the result in register VX, see `set_input`
             0x0A => {
-                ret = PC::Keep;
-
-                for (x, key) in self.keys.into_iter().enumerate() {
-                    if key {
-                        self.v[nibs[1]] = x as u8;
-                        ret = PC::Step;
-                        break;
-                    }
-                }
-
-                self.keys.fill(false);
+                self.state = State::Halted(HaltReason::WaitKey(nibs[1]));
+                self.wait_key = None;
             }
 
             // Set the delay timer to the value of register VX
             0x15 => self.tim_delay = self.v[nibs[1]],
 
             // Set the sound timer to the value of register VX
-            0x18 => self.tim_snd = self.v[nibs[1]],
+            0x18 => {
+                self.tim_snd = self.v[nibs[1]];
+                self.sound_request = Some(self.tim_snd);
+            }
 
             // Add the value stored in register VX to register I
             0x1E => {
@@ -416,7 +1039,10 @@ impl Chip8 {
             }
 
             // Set I to the memory address of the sprite data corresponding to the hexadecimal digit stored in register VX
-            0x29 => self.i = (self.v[nibs[1]] * 0x5) as usize,
+            0x29 => self.i = self.font_addr + self.v[nibs[1]] as usize * 5,
+
+            // Set I to the memory address of the 8x10 sprite data corresponding to the hexadecimal digit stored in register VX
+            0x30 => self.i = self.big_font_addr + self.v[nibs[1]] as usize * 10,
 
             // Store the BCD equivalent of the value stored in register VX at addresses I, I + 1, and I + 2
             0x33 => {
@@ -430,27 +1056,31 @@ impl Chip8 {
                     count += 1;
                 }
 
-                self.ram[self.i + 2] = (res & 0xF) as u8;
-                self.ram[self.i + 1] = ((res & 0xF0) >> 4) as u8;
-                self.ram[self.i] = ((res & 0xF00) >> 8) as u8;
+                self.write(self.i + 2, (res & 0xF) as u8);
+                self.write(self.i + 1, ((res & 0xF0) >> 4) as u8);
+                self.write(self.i, ((res & 0xF00) >> 8) as u8);
             }
 
             // Store the values of registers V0 to VX inclusive in memory starting at address I.
-            // I is set to I + X + 1 after operation
+            // I is set to I + X + 1 after operation, unless the load/store quirk leaves it
             0x55 => {
                 for n in 0..=nibs[1] as usize {
-                    self.ram[self.i + n] = self.v[n];
+                    self.write(self.i + n, self.v[n]);
+                }
+                if !self.quirks.load_store {
+                    self.i += nibs[1] + 1;
                 }
-                self.i += (self.v[nibs[1]] + 1) as usize;
             }
 
             // Fill registers V0 to VX inclusive with the values stored in memory starting at address I
-            // I is set to I + X + 1 after operation
+            // I is set to I + X + 1 after operation, unless the load/store quirk leaves it
             0x65 => {
                 for n in 0..=nibs[1] as usize {
-                    self.v[n] = self.ram[self.i + n];
+                    self.v[n] = self.read(self.i + n);
+                }
+                if !self.quirks.load_store {
+                    self.i += nibs[1] + 1;
                 }
-                self.i += (self.v[nibs[1]] + 1) as usize;
             }
             _ => panic!(
                 "invalid instruction {:#0x} for 0xFxxx",
@@ -458,6 +1088,6 @@ impl Chip8 {
             ),
         }
 
-        return ret;
+        return PC::Step;
     }
 }
@@ -0,0 +1,171 @@
+use chip8::ab;
+use chip8::debugger::Debugger;
+use chip8::framebuffer::Framebuffer;
+use chip8::machine::Machine;
+use chip8::macros::{MacroSet, Playback};
+use chip8::ocr;
+use chip8::patch;
+use chip8::reference;
+
+use std::fs;
+use std::time::Instant;
+
+use crate::config::{Config, Format};
+use crate::crash_report;
+use crate::stats::Stats;
+
+/**
+ * Run without a window: execute `config.frames` frames (or until the program exits, or
+ * `config.break_at_frame`) as fast as possible, then print the screen to stdout, or a JSON
+ * report of the same with `--format json`. A fault stops the run and leaves a crash report.
+ */
+pub fn run(config: &Config, machine: &mut Machine, rom: &[u8], stats: &mut Stats) -> Result<(), String> {
+    // only used to catch faults and stop at a frame, there are no breakpoints
+    let mut debugger = Debugger::new();
+    if let Some(frame) = config.break_at_frame {
+        debugger.execute(machine, &format!("runto-frame {}", frame));
+    }
+    // the trace would end up in the middle of the JSON
+    if config.format == Format::Json {
+        machine.chip8.trace = false;
+    }
+    while machine.frame() < config.frames && !machine.chip8.exited() && !debugger.paused {
+        let started = Instant::now();
+        debugger.run_frame(machine);
+        stats.emulate += started.elapsed();
+        stats.end_pass(machine, None)?;
+    }
+
+    stats.frames_rendered += 1;
+    match config.format {
+        Format::Text => {
+            print!("{}", vram_to_text(machine.chip8.get_vram()));
+            println!("{} frames, PC {:#05x}, state {:?}", machine.frame(), machine.chip8.pc, machine.chip8.state);
+            if let Some(pc) = machine.runaway {
+                println!("probable infinite loop at {:#05x}", pc);
+            }
+        }
+        Format::Json => print!("{}", run_json(machine, &debugger)),
+    }
+
+    match debugger.fault {
+        Some(fault) => {
+            stats.faults.push(fault.to_string());
+            if config.format == Format::Text {
+                println!("{}", debugger.post_mortem(machine));
+            }
+            let path = crash_report::write(&config.crash_dir, rom, machine, &debugger)?;
+            Err(format!("{}, crash report written to {}", fault, path))
+        }
+        None => Ok(()),
+    }
+}
+
+fn run_json(machine: &Machine, debugger: &Debugger) -> String {
+    let rows: Vec<String> = vram_to_text(machine.chip8.get_vram()).lines().map(|row| format!("\"{}\"", row)).collect();
+    let or_null = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+
+    format!(
+        "{{\n  \"frames\": {},\n  \"pc\": {},\n  \"state\": \"{:?}\",\n  \"runaway\": {},\n  \"fault\": {},\n  \"display\": [\n    {}\n  ]\n}}\n",
+        machine.frame(),
+        machine.chip8.pc,
+        machine.chip8.state,
+        or_null(machine.runaway.map(|pc| pc.to_string())),
+        or_null(debugger.fault.map(|fault| format!("{:?}", fault.to_string()))),
+        rows.join(",\n    "),
+    )
+}
+
+/**
+ * Run the core in lockstep with the reference interpreter for `config.frames` frames and
+ * report the first point where they disagree. With `--format json` the report goes to
+ * stdout either way, the error only sets the exit code.
+ */
+pub fn verify(config: &Config, machine: &mut Machine) -> Result<(), String> {
+    let divergence = reference::lockstep(machine, config.frames).map_err(|e| e.to_string())?;
+    if config.format == Format::Json {
+        let divergence_json = divergence.as_ref().map_or("null".to_string(), |d| d.to_json());
+        println!("{{\"frames\": {}, \"divergence\": {}}}", machine.frame(), divergence_json);
+    }
+    match divergence {
+        Some(divergence) => Err(config.lang.format("err.divergence", &[&divergence])),
+        None => {
+            if config.format == Format::Text {
+                println!("{}", config.lang.format("msg.no_divergence", &[&machine.frame()]));
+            }
+            Ok(())
+        }
+    }
+}
+
+/**
+ * Run the ROM for `config.frames` frames in the core and in the reference interpreter, with
+ * the input macro in slot `config.replay`, and print how long each took. Fails when their
+ * states differ.
+ */
+pub fn ab(config: &Config, machine: &mut Machine, rom: &[u8]) -> Result<(), String> {
+    let playback = match config.replay {
+        Some(slot) => {
+            let path = format!("{}/{:08x}.txt", config.macro_dir, patch::crc32(rom));
+            let text = fs::read_to_string(&path).map_err(|e| config.lang.format("err.read", &[&path, &e]))?;
+            let mut macros = MacroSet::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+            let events = macros.slots.remove(&slot).ok_or_else(|| config.lang.format("msg.macro_empty", &[&slot]))?;
+            Some(Playback::start(machine, events))
+        }
+        None => None,
+    };
+
+    let report = ab::compare(machine, config.frames, playback).map_err(|e| e.to_string())?;
+    match config.format {
+        Format::Text => println!("{}", report),
+        Format::Json => print!("{}", report.to_json()),
+    }
+    match report.divergence {
+        Some(divergence) => Err(config.lang.format("err.divergence", &[&divergence])),
+        None => Ok(()),
+    }
+}
+
+/**
+ * Run a test ROM headless, read its result screen back with `chip8::ocr` and print a JSON
+ * report. Glyphs labelled `x` mark failed checks; the run passes when any text was read
+ * and none of it is an `x`.
+ */
+pub fn check(config: &Config, machine: &mut Machine) -> Result<(), String> {
+    let mut glyphs = ocr::font_glyphs();
+    if let Some(path) = &config.glyphs {
+        let text = fs::read_to_string(path).map_err(|e| config.lang.format("err.read", &[path, &e]))?;
+        // user glyphs go first so they win over font digits of the same shape
+        glyphs.splice(0..0, ocr::parse_glyphs(&text).map_err(|e| format!("{}: {}", path, e))?);
+    }
+
+    while machine.frame() < config.frames && !machine.chip8.exited() {
+        machine.run_frame().map_err(|e| e.to_string())?;
+    }
+
+    let lines = ocr::read_lines(machine.chip8.get_vram(), &glyphs);
+    let passed = !lines.is_empty() && lines.iter().all(|line| !line.contains('x'));
+    let quoted: Vec<String> = lines.iter().map(|l| format!("{:?}", l)).collect();
+
+    println!("{{\"passed\": {}, \"frames\": {}, \"lines\": [{}]}}", passed, machine.frame(), quoted.join(", "));
+
+    if passed {
+        Ok(())
+    } else {
+        Err(config.lang.tr("err.test_failed").to_string())
+    }
+}
+
+/**
+ * Render VRAM as text, one line per row: `#` for a set pixel, `.` otherwise.
+ */
+pub fn vram_to_text(vram: &Framebuffer) -> String {
+    let mut out = String::with_capacity((vram.width() + 1) * vram.height());
+
+    for row in vram.rows() {
+        out.extend(row.iter().map(|px| if *px { '#' } else { '.' }));
+        out.push('\n');
+    }
+
+    out
+}
@@ -0,0 +1,250 @@
+use std::env;
+use std::fmt::Display;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Lang {
+    En,
+    De,
+}
+
+// language codes accepted by --lang, in the same order as `Lang`
+pub const LANGS: [(&str, Lang); 2] = [("en", Lang::En), ("de", Lang::De)];
+
+// translation tables, keyed by message id. `{}` marks where `Lang::format` puts its
+// arguments, in order. a message missing from a table falls back to English. log files
+// aren't translated, they are for whoever has to debug the machine. German keeps its
+// umlauts, the overlays spell them out with `text::spell_out` since their font has none
+const EN: &[(&str, &str)] = &[
+    ("window.halted", "Chip8 (halted)"),
+    ("window.split", "Chip8 - {} ({} of {})"),
+    ("menu.exited", "program exited"),
+    ("menu.fault", "{}\nN: skip  R: reset  Q: quit"),
+    ("menu.controllers", "controllers"),
+    ("menu.controllers_keys", "Up/Down: pick  Shift+Up/Down: move  Esc: close"),
+    ("menu.messages", "recent warnings and errors"),
+    ("menu.picture", "picture"),
+    ("menu.picture_keys", "Up/Down: pick  Left/Right: change  Backspace: normal  Esc: close"),
+    ("menu.brightness", "brightness"),
+    ("menu.contrast", "contrast"),
+    ("menu.gamma", "gamma"),
+    ("msg.crash_report", "crash report: {}"),
+    ("msg.exited", "program exited (00FD)"),
+    ("msg.runaway", "probable infinite loop at {}: {} instructions without drawing or waiting, slowed down"),
+    ("msg.debugger_ready", "debugger ready, type 'help' for commands"),
+    ("msg.session_restored", "{} breakpoints restored from {}"),
+    ("msg.palette", "palette: {}"),
+    ("msg.pixel_on", "pixel {},{} is on"),
+    ("msg.pixel_off", "pixel {},{} is off"),
+    ("msg.resolution", "resolution changed to {}x{}"),
+    ("msg.controller", "controller connected: {}"),
+    ("msg.controller_removed", "controller disconnected: {}"),
+    ("msg.macro_empty", "macro {} is empty"),
+    ("msg.macro_recording", "recording macro {}, press Ctrl+F{} again to stop"),
+    ("msg.macro_saved", "macro {} saved to {}"),
+    ("err.macro_save", "can't save macros to {}: {}"),
+    ("err.controller", "can't open controller: {}"),
+    ("msg.state_saved", "state saved to {}"),
+    ("msg.state_loaded", "state loaded from {}"),
+    ("err.state_save", "can't save state to {}: {}"),
+    ("err.state_load", "can't load state from {}: {}"),
+    ("err.read", "can't read {}: {}"),
+    ("err.test_failed", "test ROM reported a failure"),
+    ("err.divergence", "divergence: {}"),
+    ("msg.no_divergence", "no divergence in {} frames"),
+    ("msg.play_time", "launch {} of this ROM, played {} so far"),
+    ("msg.compat_report", "compatibility report added to {}, nothing was sent"),
+    ("msg.no_action", "no matching action"),
+    ("msg.no_controllers", "no controllers connected"),
+    ("msg.no_messages", "none so far"),
+    ("msg.remap", "press the key for keypad key {} ({}/{})\nBackspace: keep  Esc: cancel"),
+    ("msg.keys_saved", "key map saved to {}"),
+    ("msg.state_checksum", "frame {}, state checksum {}"),
+    ("msg.quirks", "{} quirks: {}"),
+    ("err.keys_save", "can't save the key map to {}: {}"),
+    ("action.save_state", "save state"),
+    ("action.load_state", "load state"),
+    ("action.next_palette", "next palette"),
+    ("action.next_quirks", "next platform's quirks"),
+    ("action.palette_cycle", "toggle background cycling"),
+    ("action.grid", "toggle pixel grid"),
+    ("action.debug_overlay", "toggle debug overlay"),
+    ("action.keypad", "toggle keypad overlay"),
+    ("action.beep_flash", "toggle beep flash"),
+    ("action.messages", "toggle message log"),
+    ("action.picture", "picture: brightness, contrast, gamma"),
+    ("action.slow_draw", "toggle slow sprite drawing"),
+    ("action.pause", "pause / continue"),
+    ("action.step", "step one instruction"),
+    ("action.step_over", "step over call"),
+    ("action.step_out", "step out of subroutine"),
+    ("action.reset", "reset"),
+    ("action.debugger", "open debugger window"),
+    ("action.remap_keys", "remap keys"),
+    ("action.controllers", "controllers"),
+    ("action.state_checksum", "show state checksum"),
+    ("action.quit", "quit"),
+    ("ui.title", "Chip8 debugger"),
+    ("ui.panels", "Panels"),
+    ("ui.registers", "Registers"),
+    ("ui.memory", "Memory"),
+    ("ui.disassembly", "Disassembly"),
+    ("ui.disassembly_hint", "click an instruction to toggle a breakpoint"),
+    ("ui.breakpoints", "Breakpoints"),
+    ("ui.watches", "Watches"),
+    ("ui.framebuffer", "Framebuffer"),
+    ("ui.add", "Add"),
+    ("ui.labels", "Labels"),
+    ("ui.label_hint", "0x300 3 score"),
+    ("ui.pause", "Pause"),
+    ("ui.continue", "Continue"),
+    ("ui.step", "Step"),
+    ("ui.step_over", "Step over"),
+    ("ui.step_out", "Step out"),
+    ("ui.skip", "Skip fault"),
+    ("ui.running", "running"),
+    ("ui.paused", "paused"),
+    ("ui.wait_key", "waiting for a key (FX0A into V{})"),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("window.halted", "Chip8 (angehalten)"),
+    ("window.split", "Chip8 - {} ({} von {})"),
+    ("menu.exited", "Programm beendet"),
+    ("menu.fault", "{}\nN: weiter  R: neu starten  Q: beenden"),
+    ("menu.controllers", "Controller"),
+    ("menu.controllers_keys", "Hoch/Runter: wählen  Shift+Hoch/Runter: verschieben  Esc: schließen"),
+    ("menu.messages", "letzte Warnungen und Fehler"),
+    ("menu.picture", "Bild"),
+    ("menu.picture_keys", "Hoch/Runter: wählen  Links/Rechts: ändern  Rücktaste: normal  Esc: schließen"),
+    ("menu.brightness", "Helligkeit"),
+    ("menu.contrast", "Kontrast"),
+    ("menu.gamma", "Gamma"),
+    ("msg.crash_report", "Absturzbericht: {}"),
+    ("msg.exited", "Programm beendet (00FD)"),
+    ("msg.runaway", "vermutlich Endlosschleife bei {}: {} Befehle ohne Zeichnen oder Warten, verlangsamt"),
+    ("msg.debugger_ready", "Debugger bereit, 'help' zeigt die Befehle"),
+    ("msg.session_restored", "{} Haltepunkte aus {} wiederhergestellt"),
+    ("msg.palette", "Palette: {}"),
+    ("msg.pixel_on", "Pixel {},{} ist an"),
+    ("msg.pixel_off", "Pixel {},{} ist aus"),
+    ("msg.resolution", "Auflösung auf {}x{} geändert"),
+    ("msg.controller", "Controller verbunden: {}"),
+    ("msg.controller_removed", "Controller getrennt: {}"),
+    ("msg.macro_empty", "Makro {} ist leer"),
+    ("msg.macro_recording", "Makro {} wird aufgenommen, Strg+F{} beendet die Aufnahme"),
+    ("msg.macro_saved", "Makro {} in {} gespeichert"),
+    ("err.macro_save", "Makros können nicht in {} gespeichert werden: {}"),
+    ("err.controller", "Controller kann nicht geöffnet werden: {}"),
+    ("msg.state_saved", "Spielstand in {} gespeichert"),
+    ("msg.state_loaded", "Spielstand aus {} geladen"),
+    ("err.state_save", "Spielstand kann nicht in {} gespeichert werden: {}"),
+    ("err.state_load", "Spielstand aus {} kann nicht geladen werden: {}"),
+    ("err.read", "{} kann nicht gelesen werden: {}"),
+    ("err.test_failed", "Test-ROM meldet einen Fehler"),
+    ("err.divergence", "Abweichung: {}"),
+    ("msg.no_divergence", "keine Abweichung in {} Frames"),
+    ("msg.play_time", "{}. Start dieses ROMs, bisher {} gespielt"),
+    ("msg.compat_report", "Kompatibilitätsbericht in {} ergänzt, nichts wurde gesendet"),
+    ("msg.no_action", "keine passende Aktion"),
+    ("msg.no_controllers", "keine Controller verbunden"),
+    ("msg.no_messages", "bisher keine"),
+    ("msg.remap", "Taste für Tastenfeld-Taste {} drücken ({}/{})\nRücktaste: behalten  Esc: abbrechen"),
+    ("msg.keys_saved", "Tastenbelegung in {} gespeichert"),
+    ("msg.state_checksum", "Frame {}, Zustandsprüfsumme {}"),
+    ("msg.quirks", "Quirks von {}: {}"),
+    ("err.keys_save", "Tastenbelegung kann nicht in {} gespeichert werden: {}"),
+    ("action.save_state", "Spielstand speichern"),
+    ("action.load_state", "Spielstand laden"),
+    ("action.next_palette", "nächste Palette"),
+    ("action.next_quirks", "Quirks der nächsten Plattform"),
+    ("action.palette_cycle", "Hintergrundanimation an/aus"),
+    ("action.grid", "Pixelraster an/aus"),
+    ("action.debug_overlay", "Debug-Anzeige an/aus"),
+    ("action.keypad", "Tastenfeld-Anzeige an/aus"),
+    ("action.beep_flash", "Piepblitz an/aus"),
+    ("action.messages", "Meldungsprotokoll an/aus"),
+    ("action.picture", "Bild: Helligkeit, Kontrast, Gamma"),
+    ("action.slow_draw", "Sprites langsam zeichnen an/aus"),
+    ("action.pause", "anhalten / weiter"),
+    ("action.step", "einen Befehl ausführen"),
+    ("action.step_over", "Aufruf überspringen"),
+    ("action.step_out", "bis zum Rücksprung ausführen"),
+    ("action.reset", "neu starten"),
+    ("action.debugger", "Debugger-Fenster öffnen"),
+    ("action.remap_keys", "Tasten neu belegen"),
+    ("action.controllers", "Controller"),
+    ("action.state_checksum", "Zustandsprüfsumme zeigen"),
+    ("action.quit", "beenden"),
+    ("ui.title", "Chip8-Debugger"),
+    ("ui.panels", "Ansicht"),
+    ("ui.registers", "Register"),
+    ("ui.memory", "Speicher"),
+    ("ui.disassembly", "Disassembly"),
+    ("ui.disassembly_hint", "Klick auf einen Befehl setzt oder entfernt einen Haltepunkt"),
+    ("ui.breakpoints", "Haltepunkte"),
+    ("ui.watches", "Beobachtete Ausdrücke"),
+    ("ui.framebuffer", "Bildspeicher"),
+    ("ui.add", "Hinzufügen"),
+    ("ui.labels", "Bezeichnungen"),
+    ("ui.label_hint", "0x300 3 Punkte"),
+    ("ui.pause", "Anhalten"),
+    ("ui.continue", "Weiter"),
+    ("ui.step", "Schritt"),
+    ("ui.step_over", "Prozedurschritt"),
+    ("ui.step_out", "Bis Rücksprung"),
+    ("ui.skip", "Fehler überspringen"),
+    ("ui.running", "läuft"),
+    ("ui.paused", "angehalten"),
+    ("ui.wait_key", "wartet auf eine Taste (FX0A nach V{})"),
+];
+
+impl Lang {
+    pub fn by_code(code: &str) -> Option<Lang> {
+        LANGS.iter().find(|(c, _)| *c == code).map(|(_, lang)| *lang)
+    }
+
+    /**
+     * Language from the environment (`LC_ALL`, `LC_MESSAGES`, `LANG`), e.g. `de_DE.UTF-8`,
+     * English if it isn't one we have.
+     */
+    pub fn from_env() -> Lang {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Lang::by_code(value.get(..2)?))
+            .unwrap_or(Lang::En)
+    }
+
+    fn table(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::En => EN,
+            Lang::De => DE,
+        }
+    }
+
+    /**
+     * The message with id `key`, or the id itself if no table has it.
+     */
+    pub fn tr(self, key: &'static str) -> &'static str {
+        [self.table(), EN]
+            .iter()
+            .find_map(|table| table.iter().find(|(k, _)| *k == key))
+            .map_or(key, |(_, text)| text)
+    }
+
+    /**
+     * `tr` with each `{}` replaced by the next argument.
+     */
+    pub fn format(self, key: &'static str, args: &[&dyn Display]) -> String {
+        let mut parts = self.tr(key).split("{}");
+        let mut out = parts.next().unwrap_or_default().to_string();
+        for (n, part) in parts.enumerate() {
+            if let Some(arg) = args.get(n) {
+                out.push_str(&arg.to_string());
+            }
+            out.push_str(part);
+        }
+        out
+    }
+}
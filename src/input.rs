@@ -0,0 +1,66 @@
+use sdl2::keyboard::Keycode;
+
+use crate::config::{Diagonals, StickMap};
+
+/**
+ * Map a host key onto the CHIP-8 keypad, using the usual 1234/QWER/ASDF/ZXCV layout:
+ *
+ * ```text
+ * 1 2 3 C      1 2 3 4
+ * 4 5 6 D  <-  Q W E R
+ * 7 8 9 E      A S D F
+ * A 0 B F      Z X C V
+ * ```
+ */
+pub fn keypad_index(key: Keycode) -> Option<usize> {
+    match key {
+        Keycode::Num1 => Some(0x1),
+        Keycode::Num2 => Some(0x2),
+        Keycode::Num3 => Some(0x3),
+        Keycode::Num4 => Some(0xc),
+        Keycode::Q => Some(0x4),
+        Keycode::W => Some(0x5),
+        Keycode::E => Some(0x6),
+        Keycode::R => Some(0xd),
+        Keycode::A => Some(0x7),
+        Keycode::S => Some(0x8),
+        Keycode::D => Some(0x9),
+        Keycode::F => Some(0xe),
+        Keycode::Z => Some(0xa),
+        Keycode::X => Some(0x0),
+        Keycode::C => Some(0xb),
+        Keycode::V => Some(0xf),
+        _ => None,
+    }
+}
+
+/**
+ * Macro slot for a hotkey: F5 to F8 are slots 1 to 4.
+ */
+pub fn macro_slot(key: Keycode) -> Option<usize> {
+    match key {
+        Keycode::F5 => Some(1),
+        Keycode::F6 => Some(2),
+        Keycode::F7 => Some(3),
+        Keycode::F8 => Some(4),
+        _ => None,
+    }
+}
+
+/**
+ * Keypad keys held by an analog stick at X/Y (SDL axis values, right and down are positive).
+ * Within the deadzone an axis holds nothing; outside it, `map.diagonals` decides whether both
+ * axes count or only the one pushed furthest.
+ */
+pub fn stick_keys(map: &StickMap, x: i16, y: i16) -> Vec<usize> {
+    let threshold = (map.deadzone * i16::MAX as f64) as i32;
+    let (x, y) = (x as i32, y as i32);
+    let horizontal = (x.abs() > threshold).then_some(if x < 0 { map.left } else { map.right });
+    let vertical = (y.abs() > threshold).then_some(if y < 0 { map.up } else { map.down });
+
+    match map.diagonals {
+        Diagonals::Both => horizontal.into_iter().chain(vertical).collect(),
+        Diagonals::Dominant if x.abs() >= y.abs() => horizontal.into_iter().collect(),
+        Diagonals::Dominant => vertical.into_iter().collect(),
+    }
+}
@@ -0,0 +1,219 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use chip8::hardware::KEY_COUNT;
+
+use crate::i18n::Lang;
+use crate::input;
+
+// host keys of the default layout, see `input::keypad_index`
+const DEFAULT_KEYS: [Keycode; KEY_COUNT] = [
+    Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4,
+    Keycode::Q, Keycode::W, Keycode::E, Keycode::R,
+    Keycode::A, Keycode::S, Keycode::D, Keycode::F,
+    Keycode::Z, Keycode::X, Keycode::C, Keycode::V,
+];
+
+// controller buttons of the default layout: the d-pad like the default stick, A in the middle
+const DEFAULT_BUTTONS: [(Button, usize); 5] = [
+    (Button::DPadUp, 0x2),
+    (Button::DPadDown, 0x8),
+    (Button::DPadLeft, 0x4),
+    (Button::DPadRight, 0x6),
+    (Button::A, 0x5),
+];
+
+// the order the remap wizard asks for the keys in, row by row like the COSMAC VIP's keypad
+const WIZARD_ORDER: [usize; KEY_COUNT] = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF];
+
+/**
+ * Something on the host that can press a keypad key.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Binding {
+    Key(Keycode),
+    // a button on any controller
+    Button(Button),
+    // a button on one controller, by player number, see `Render::player`
+    PadButton(usize, Button),
+}
+
+/**
+ * Which host keys and controller buttons press which keypad keys. A host key or button
+ * presses at most one keypad key, a keypad key can have several. Stored as text, one
+ * binding per line with the keypad key in hex and SDL's name for the host key or button:
+ *
+ * ```text
+ * 1 key 1
+ * c key Return
+ * 5 button a
+ * ```
+ *
+ * `pad<n>` instead of `button` binds a button on the nth controller only, for two players
+ * with a controller each. It wins over a `button` binding of the same button:
+ *
+ * ```text
+ * 1 pad1 dpup
+ * c pad2 dpup
+ * ```
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyMap {
+    pub bindings: Vec<(Binding, usize)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let keys = DEFAULT_KEYS.iter().filter_map(|&k| input::keypad_index(k).map(|key| (Binding::Key(k), key)));
+        let buttons = DEFAULT_BUTTONS.iter().map(|&(b, key)| (Binding::Button(b), key));
+        KeyMap { bindings: keys.chain(buttons).collect() }
+    }
+}
+
+impl KeyMap {
+    pub fn parse(text: &str) -> Result<KeyMap, String> {
+        let mut map = KeyMap { bindings: Vec::new() };
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || format!("line {}: invalid binding '{}'", n + 1, line);
+            let (key, rest) = line.split_once(' ').ok_or_else(invalid)?;
+            let key = usize::from_str_radix(key, 16).ok().filter(|k| *k < KEY_COUNT).ok_or_else(invalid)?;
+            let binding = match rest.split_once(' ').ok_or_else(invalid)? {
+                ("key", name) => Binding::Key(Keycode::from_name(name).ok_or_else(|| format!("line {}: unknown key '{}'", n + 1, name))?),
+                ("button", name) => {
+                    Binding::Button(Button::from_string(name).ok_or_else(|| format!("line {}: unknown button '{}'", n + 1, name))?)
+                }
+                (kind, name) if kind.starts_with("pad") => {
+                    let player = kind[3..].parse::<usize>().ok().filter(|p| *p > 0).ok_or_else(invalid)?;
+                    let button = Button::from_string(name).ok_or_else(|| format!("line {}: unknown button '{}'", n + 1, name))?;
+                    Binding::PadButton(player, button)
+                }
+                _ => return Err(invalid()),
+            };
+            map.bind(binding, key);
+        }
+
+        Ok(map)
+    }
+
+    pub fn to_text(&self) -> String {
+        self.bindings
+            .iter()
+            .map(|(binding, key)| match binding {
+                Binding::Key(k) => format!("{:x} key {}\n", key, k.name()),
+                Binding::Button(b) => format!("{:x} button {}\n", key, b.string()),
+                Binding::PadButton(player, b) => format!("{:x} pad{} {}\n", key, player, b.string()),
+            })
+            .collect()
+    }
+
+    /**
+     * The map saved at `path`, the default layout if there is none yet.
+     */
+    pub fn load(path: &str) -> Result<KeyMap, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => KeyMap::parse(&text).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(KeyMap::default()),
+            Err(e) => Err(format!("can't read {}: {}", path, e)),
+        }
+    }
+
+    pub fn store(&self, path: &str) -> Result<(), String> {
+        let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir)
+            .and_then(|_| fs::write(path, self.to_text()))
+            .map_err(|e| format!("can't write {}: {}", path, e))
+    }
+
+    /**
+     * Have `binding` press `key`, instead of whatever it pressed before.
+     */
+    pub fn bind(&mut self, binding: Binding, key: usize) {
+        self.bindings.retain(|(b, _)| *b != binding);
+        self.bindings.push((binding, key));
+    }
+
+    pub fn lookup(&self, binding: Binding) -> Option<usize> {
+        self.bindings.iter().find(|(b, _)| *b == binding).map(|(_, key)| *key)
+    }
+
+    /**
+     * The keypad key `button` presses on the controller of `player`.
+     */
+    pub fn lookup_button(&self, player: usize, button: Button) -> Option<usize> {
+        self.lookup(Binding::PadButton(player, button)).or_else(|| self.lookup(Binding::Button(button)))
+    }
+}
+
+/**
+ * What the remap wizard wants after an event.
+ */
+pub enum RemapInput {
+    Waiting,
+    Cancel,
+    Done(KeyMap),
+}
+
+/**
+ * Asks for each keypad key in turn and binds the next host key or controller button pressed
+ * to it. A new key replaces the keypad key's old key, a new button its old button, so the
+ * keyboard and a controller can be set up one after the other. Backspace keeps the current
+ * binding, Escape gives up without changing anything.
+ */
+pub struct RemapWizard {
+    map: KeyMap,
+    // position in `WIZARD_ORDER`
+    next: usize,
+}
+
+impl RemapWizard {
+    pub fn start(map: &KeyMap) -> RemapWizard {
+        RemapWizard { map: map.clone(), next: 0 }
+    }
+
+    /**
+     * The keypad key asked for now.
+     */
+    pub fn key(&self) -> usize {
+        WIZARD_ORDER[self.next]
+    }
+
+    pub fn handle(&mut self, event: &Event) -> RemapInput {
+        let binding = match event {
+            Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return RemapInput::Cancel,
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => None,
+            Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => Some(Binding::Key(*keycode)),
+            Event::ControllerButtonDown { button, .. } => Some(Binding::Button(*button)),
+            _ => return RemapInput::Waiting,
+        };
+
+        if let Some(binding) = binding {
+            let key = self.key();
+            // the keypad key's old binding of the same kind makes way
+            let same_kind = |b: &Binding| matches!((b, binding), (Binding::Key(_), Binding::Key(_)) | (Binding::Button(_), Binding::Button(_)));
+            self.map.bindings.retain(|(b, k)| *k != key || !same_kind(b));
+            self.map.bind(binding, key);
+        }
+        self.next += 1;
+        if self.next == WIZARD_ORDER.len() {
+            return RemapInput::Done(self.map.clone());
+        }
+        RemapInput::Waiting
+    }
+
+    /**
+     * The wizard's prompt, for the menu layer.
+     */
+    pub fn text(&self, lang: Lang) -> String {
+        lang.format("msg.remap", &[&format!("{:X}", self.key()), &(self.next + 1), &WIZARD_ORDER.len()])
+    }
+}
@@ -0,0 +1,45 @@
+//! CHIP-8 emulator core.
+//!
+//! `hardware::Chip8` is the interpreter itself, `machine::Machine` wraps it with frame
+//! scheduling. Frontends only need these two and the VRAM they expose.
+//!
+//! ```
+//! use chip8::machine::Machine;
+//!
+//! let mut machine = Machine::new();
+//! // V0 = 0, I = font sprite for V0, draw it at (V0, V0), loop forever
+//! machine.load_rom(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06]);
+//! machine.run_frame().unwrap();
+//!
+//! // the "0" glyph starts with a full row of 4 pixels
+//! let vram = machine.chip8.get_vram();
+//! assert!((0..4).all(|x| vram.get(x, 0) == Some(true)));
+//! assert_eq!(vram.get(4, 0), Some(false));
+//! assert!(machine.chip8.vram_changed);
+//! ```
+
+pub mod ab;
+pub mod coords;
+pub mod coverage;
+pub mod debugger;
+pub mod disasm;
+pub mod expr;
+pub mod font;
+pub mod framebuffer;
+pub mod hardware;
+pub mod lz4;
+pub mod machine;
+pub mod macros;
+pub mod ocr;
+pub mod palette;
+pub mod patch;
+pub mod peripheral;
+pub mod platform;
+pub mod quirks;
+pub mod random;
+pub mod reference;
+pub mod savestate;
+pub mod text;
+pub mod trace;
+pub mod tutorial;
+pub mod wav;
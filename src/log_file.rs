@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// the log is rotated once it would grow past this
+pub const MAX_BYTES: u64 = 1024 * 1024;
+// rotated logs kept as <path>.1 (newest) to <path>.<KEEP>
+pub const KEEP: usize = 3;
+// warnings and errors kept for `Log::recent`
+pub const RECENT: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        })
+    }
+}
+
+/**
+ * Leveled, timestamped log for finding out what happened on an unattended machine after the
+ * fact. Without a path nothing is written, so callers don't need to check whether logging is
+ * on. Lines look like `2024-05-01T12:00:00Z INFO  loaded ROM ...`.
+ *
+ * The last `RECENT` warnings and errors are also kept in memory, file or not, for the
+ * message overlay.
+ */
+pub struct Log {
+    path: Option<String>,
+    file: Option<File>,
+    size: u64,
+    // newest last, with how often each came in a row
+    recent: VecDeque<(Level, String, usize)>,
+}
+
+impl Log {
+    pub fn open(path: Option<&str>) -> Result<Self, String> {
+        let mut log = Log { path: path.map(str::to_string), file: None, size: 0, recent: VecDeque::new() };
+        if let Some(path) = path {
+            log.file = Some(append(path)?);
+            log.size = fs::metadata(path).map_or(0, |m| m.len());
+        }
+        Ok(log)
+    }
+
+    pub fn info(&mut self, msg: &str) {
+        self.write(Level::Info, msg);
+    }
+
+    pub fn warn(&mut self, msg: &str) {
+        self.write(Level::Warn, msg);
+    }
+
+    pub fn error(&mut self, msg: &str) {
+        self.write(Level::Error, msg);
+    }
+
+    pub fn write(&mut self, level: Level, msg: &str) {
+        if level != Level::Info {
+            self.remember(level, msg);
+        }
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+
+        let line = format!("{} {:5} {}\n", timestamp(), level, msg);
+        if self.size > 0 && self.size + line.len() as u64 > MAX_BYTES {
+            if let Err(e) = self.rotate(&path) {
+                eprintln!("{}, logging stopped", e);
+                self.path = None;
+                return;
+            }
+        }
+
+        match self.file.as_mut().map(|f| f.write_all(line.as_bytes())) {
+            Some(Ok(())) => self.size += line.len() as u64,
+            _ => {
+                eprintln!("writing the log {} failed, logging stopped", path);
+                self.path = None;
+            }
+        }
+    }
+
+    fn remember(&mut self, level: Level, msg: &str) {
+        match self.recent.back_mut() {
+            Some((last, text, count)) if *last == level && text == msg => *count += 1,
+            _ => {
+                if self.recent.len() == RECENT {
+                    self.recent.pop_front();
+                }
+                self.recent.push_back((level, msg.to_string(), 1));
+            }
+        }
+    }
+
+    /**
+     * The last warnings and errors, oldest first, one per line like `WARN  can't save (x3)`.
+     */
+    #[cfg(feature = "gui")]
+    pub fn recent(&self) -> String {
+        self.recent
+            .iter()
+            .map(|(level, msg, count)| match count {
+                1 => format!("{:5} {}", level, msg),
+                _ => format!("{:5} {} (x{})", level, msg, count),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn rotate(&mut self, path: &str) -> Result<(), String> {
+        self.file = None;
+        for n in (1..KEEP).rev() {
+            // older files may not exist yet
+            let _ = fs::rename(format!("{}.{}", path, n), format!("{}.{}", path, n + 1));
+        }
+        fs::rename(path, format!("{}.1", path)).map_err(|e| format!("can't rotate log {}: {}", path, e))?;
+
+        self.file = Some(append(path)?);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn append(path: &str) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("can't open log {}: {}", path, e))
+}
+
+/**
+ * Current time in UTC as ISO 8601, e.g. `2024-05-01T12:00:00Z`.
+ */
+fn timestamp() -> String {
+    format_time(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()))
+}
+
+/**
+ * Seconds since the Unix epoch in UTC as ISO 8601.
+ */
+pub fn format_time(secs: u64) -> String {
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // days since 1970-01-01 to a civil date, from Howard Hinnant's date algorithms
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+}
@@ -0,0 +1,161 @@
+//! LZ4 block compression, the format without the frame around it, for save states. Fast
+//! enough to run every frame and good at what states are mostly made of: runs of zeros in
+//! RAM and on the display.
+//!
+//! A block is a series of sequences: a token byte holding the number of literals (high
+//! nibble) and the match length minus 4 (low nibble), 15 meaning more length bytes follow,
+//! then the literals, a 2-byte little endian offset back into the output and the rest of
+//! the match length. The last sequence only has literals. The size of the data isn't part
+//! of the block, the caller keeps it.
+//!
+//! ```
+//! use chip8::lz4;
+//!
+//! let mut data = vec![0u8; 4096];
+//! data[0x200..0x208].copy_from_slice(&[0x60, 0x05, 0xF0, 0x29, 0xD0, 0x15, 0x12, 0x06]);
+//! let block = lz4::compress(&data);
+//! assert!(block.len() < 64);
+//! assert_eq!(lz4::decompress(&block, data.len()), Ok(data));
+//!
+//! // cut short
+//! assert_eq!(lz4::decompress(&block[..block.len() - 3], 4096), Err(lz4::Error::Truncated));
+//! ```
+
+use std::fmt;
+
+// shortest match worth a sequence
+const MIN_MATCH: usize = 4;
+// the format wants the last 5 bytes as literals, and no match starting in the last 12
+const LAST_LITERALS: usize = 5;
+const MATCH_LIMIT: usize = 12;
+const MAX_OFFSET: usize = 0xFFFF;
+const HASH_BITS: u32 = 12;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Error {
+    // the block ends in the middle of a sequence, or before the expected size
+    Truncated,
+    // an offset before the start of the output, or more output than expected
+    Corrupt,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "compressed data is truncated"),
+            Error::Corrupt => write!(f, "compressed data is corrupt"),
+        }
+    }
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 4 + 16);
+    // where each hashed 4-byte sequence was seen last
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let (mut pos, mut anchor) = (0, 0);
+
+    while pos + MATCH_LIMIT < data.len() {
+        let seq = &data[pos..pos + MIN_MATCH];
+        let slot = &mut table[hash(seq)];
+        let candidate = std::mem::replace(slot, pos);
+        if candidate == usize::MAX || pos - candidate > MAX_OFFSET || data[candidate..candidate + MIN_MATCH] != *seq {
+            pos += 1;
+            continue;
+        }
+
+        let end = data.len() - LAST_LITERALS;
+        let mut len = MIN_MATCH;
+        while pos + len < end && data[candidate + len] == data[pos + len] {
+            len += 1;
+        }
+        sequence(&mut out, &data[anchor..pos], Some((pos - candidate, len)));
+        pos += len;
+        anchor = pos;
+    }
+
+    sequence(&mut out, &data[anchor..], None);
+    out
+}
+
+/**
+ * Decompress a block holding `size` bytes.
+ */
+pub fn decompress(block: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(size);
+    let mut pos = 0;
+
+    loop {
+        let token = *block.get(pos).ok_or(Error::Truncated)?;
+        pos += 1;
+        let literals = length(block, &mut pos, token >> 4)?;
+        out.extend_from_slice(block.get(pos..pos + literals).ok_or(Error::Truncated)?);
+        pos += literals;
+        if out.len() > size {
+            return Err(Error::Corrupt);
+        }
+        if pos == block.len() {
+            break;
+        }
+
+        let offset = block.get(pos..pos + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize).ok_or(Error::Truncated)?;
+        pos += 2;
+        let len = length(block, &mut pos, token & 0xF)? + MIN_MATCH;
+        if offset == 0 || offset > out.len() || out.len() + len > size {
+            return Err(Error::Corrupt);
+        }
+        // byte by byte, a match can overlap what it produces
+        let start = out.len() - offset;
+        for n in 0..len {
+            out.push(out[start + n]);
+        }
+    }
+
+    if out.len() < size {
+        return Err(Error::Truncated);
+    }
+    Ok(out)
+}
+
+fn hash(seq: &[u8]) -> usize {
+    let value = u32::from_le_bytes([seq[0], seq[1], seq[2], seq[3]]);
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+fn sequence(out: &mut Vec<u8>, literals: &[u8], found: Option<(usize, usize)>) {
+    let extra = found.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push((literals.len().min(15) as u8) << 4 | extra.min(15) as u8);
+    if literals.len() >= 15 {
+        put_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = found {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if extra >= 15 {
+            put_length(out, extra - 15);
+        }
+    }
+}
+
+// the part of a length past the 15 in the token: bytes of 255 while there's more
+fn put_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn length(block: &[u8], pos: &mut usize, nibble: u8) -> Result<usize, Error> {
+    let mut len = nibble as usize;
+    if nibble == 15 {
+        loop {
+            let byte = *block.get(*pos).ok_or(Error::Truncated)?;
+            *pos += 1;
+            len += byte as usize;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
@@ -0,0 +1,373 @@
+use crate::coverage::Coverage;
+use crate::hardware::{Chip8, Error, State, BIG_FONT_ADDR, FONT_ADDR};
+use crate::macros::{InputEvent, Playback};
+use crate::trace::History;
+use crate::wav::AudioTrack;
+
+// roughly 500 instructions per second at 60Hz
+pub const CYCLES_PER_FRAME: u32 = 8;
+
+// called with the core's state before every instruction
+pub type TraceHook = Box<dyn FnMut(&Chip8) + Send>;
+
+/**
+ * A `Chip8` together with its frame scheduling: every frame runs a fixed number of
+ * cycles and then ticks the timers once. The frontend decides how fast frames are run.
+ *
+ * ```
+ * use chip8::machine::Machine;
+ *
+ * let mut machine = Machine::new();
+ * // V0 = 0x05, V0 += 0x03, loop forever
+ * machine.load_rom(&[0x60, 0x05, 0x70, 0x03, 0x12, 0x04]);
+ * machine.run_frame().unwrap();
+ *
+ * assert_eq!(machine.chip8.v[0], 0x08);
+ * assert_eq!(machine.chip8.pc, 0x204);
+ * assert_eq!(machine.frame(), 1);
+ * ```
+ */
+pub struct Machine {
+    pub chip8: Chip8,
+    pub cycles_per_frame: u32,
+    // most `DXYN`s run in a frame, like the VIP which couldn't draw many sprites between
+    // two display refreshes. games that draw more flicker, as they did on the real thing
+    pub draw_budget: Option<u32>,
+    frame_draws: u32,
+    pub(crate) frame: u64,
+    // cycles already run in the current frame
+    pub(crate) cycle: u32,
+    // totals since the machine was created, for performance stats
+    cycles: u64,
+    draws: u64,
+    // e.g. to write a trace, see `chip8::trace`
+    pub trace_hook: Option<TraceHook>,
+    // recent instructions for post-mortems, recorded even without a trace hook
+    pub history: History,
+    // executed addresses and branch outcomes, only tracked when set
+    pub coverage: Option<Coverage>,
+    // the beep frame by frame, only recorded when set
+    pub audio: Option<AudioTrack>,
+    // instructions a program may run without drawing or waiting before it is taken to be
+    // stuck in a loop, see `runaway`
+    pub runaway_limit: Option<u64>,
+    // instructions run since the last draw or wait
+    since_progress: u64,
+    // where the program passed `runaway_limit`, until it draws or waits again
+    pub runaway: Option<usize>,
+}
+
+impl Machine {
+    /**
+     * Create a machine with the fonts already loaded.
+     */
+    pub fn new() -> Self {
+        let mut chip8 = Chip8::new();
+        chip8.load_fonts().expect("the fonts fit below the program");
+
+        Machine {
+            chip8,
+            cycles_per_frame: CYCLES_PER_FRAME,
+            draw_budget: None,
+            frame_draws: 0,
+            frame: 0,
+            cycle: 0,
+            cycles: 0,
+            draws: 0,
+            trace_hook: None,
+            history: History::new(),
+            coverage: None,
+            audio: None,
+            runaway_limit: None,
+            since_progress: 0,
+            runaway: None,
+        }
+    }
+
+    /**
+     * Load a program at 0x200 and point the PC at it. Panics if it doesn't fit, ROMs from
+     * outside go through `load_program`.
+     */
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        if let Err(e) = self.load_program(rom, false) {
+            panic!("{}", e);
+        }
+    }
+
+    /**
+     * Load a program at 0x200 and point the PC at it, unless it doesn't fit in RAM or would
+     * overwrite a font. With `relocate_font` a font in the way is moved back to its usual
+     * place below 0x200 instead, and true is returned.
+     *
+     * ```
+     * use chip8::hardware::{Error, FONT_ADDR};
+     * use chip8::machine::Machine;
+     *
+     * let mut machine = Machine::new();
+     * assert_eq!(machine.load_program(&[0x12, 0x00].repeat(0x800), false), Err(Error::LoadOutOfBounds { addr: 0x200, len: 0x1000 }));
+     *
+     * // a font left where a larger program goes
+     * machine.chip8.font_addr = 0x300;
+     * machine.chip8.load_fonts().unwrap();
+     * let rom = [0x12, 0x00].repeat(0x100);
+     * assert_eq!(machine.load_program(&rom, false), Err(Error::FontOverlap { start: 0x200, end: 0x3FF, font: 0x300 }));
+     * assert_eq!(machine.load_program(&rom, true), Ok(true));
+     * assert_eq!(machine.chip8.font_addr, FONT_ADDR);
+     * assert_eq!(machine.chip8.get_ram()[0x300..0x302], [0x12, 0x00]);
+     * ```
+     */
+    pub fn load_program(&mut self, rom: &[u8], relocate_font: bool) -> Result<bool, Error> {
+        let program = 0x200..0x200 + rom.len();
+        if program.end > self.chip8.get_ram().len() {
+            return Err(Error::LoadOutOfBounds { addr: program.start, len: rom.len() });
+        }
+
+        let in_the_way = self.chip8.font_ranges().into_iter().find(|font| font.start < program.end && program.start < font.end);
+        if let Some(font) = &in_the_way {
+            if !relocate_font {
+                return Err(Error::FontOverlap { start: program.start, end: program.end - 1, font: font.start });
+            }
+            self.chip8.font_addr = FONT_ADDR;
+            self.chip8.big_font_addr = BIG_FONT_ADDR;
+            self.chip8.load_fonts()?;
+        }
+
+        self.chip8.load_ram(rom, program.start)?;
+        self.chip8.start();
+        Ok(in_the_way.is_some())
+    }
+
+    /**
+     * Run a single cycle. Once a frame worth of cycles has run the timers tick and
+     * true is returned. A draw over the `draw_budget` ends the frame early instead. With the
+     * `vblank` quirk every draw ends the frame, one sprite a frame like on the VIP.
+     *
+     * ```
+     * use chip8::machine::Machine;
+     *
+     * let mut machine = Machine::new();
+     * machine.draw_budget = Some(1);
+     * // draw twice, loop forever
+     * machine.load_rom(&[0xD0, 0x05, 0xD0, 0x05, 0x12, 0x04]);
+     *
+     * machine.run_frame().unwrap();
+     * assert_eq!((machine.chip8.pc, machine.frame()), (0x202, 1));
+     * machine.run_frame().unwrap();
+     * assert_eq!(machine.draws(), 2);
+     *
+     * // the same with the display wait, the second draw goes in the second frame too
+     * let mut machine = Machine::new();
+     * machine.chip8.quirks.vblank = true;
+     * machine.load_rom(&[0xD0, 0x05, 0xD0, 0x05, 0x12, 0x04]);
+     *
+     * machine.run_frame().unwrap();
+     * assert_eq!((machine.chip8.pc, machine.draws()), (0x202, 1));
+     * machine.run_frame().unwrap();
+     * assert_eq!((machine.chip8.pc, machine.draws()), (0x204, 2));
+     * ```
+     *
+     * A program that runs more than `runaway_limit` instructions without drawing, clearing
+     * the screen, reading a timer or the keys, or waiting for a key is probably stuck.
+     * Its frames are throttled to `CYCLES_PER_FRAME` cycles until it does one of those again:
+     *
+     * ```
+     * use chip8::machine::{Machine, CYCLES_PER_FRAME};
+     *
+     * let mut machine = Machine::new();
+     * machine.cycles_per_frame = 1000;
+     * machine.runaway_limit = Some(100);
+     * // V0 += 1, loop forever
+     * machine.load_rom(&[0x70, 0x01, 0x12, 0x00]);
+     *
+     * machine.run_frame().unwrap();
+     * assert_eq!(machine.runaway, Some(0x200));
+     * let cycles = machine.cycles();
+     * machine.run_frame().unwrap();
+     * assert_eq!(machine.cycles() - cycles, CYCLES_PER_FRAME as u64);
+     * ```
+     */
+    pub fn step(&mut self) -> Result<bool, Error> {
+        // halted by FX0A or 00FD, nothing runs and there is nothing to count, the frame
+        // only goes by
+        if matches!(self.chip8.state, State::Halted(_)) {
+            self.chip8.cycle()?;
+            self.cycle += 1;
+            self.cycles += 1;
+            return Ok(self.frame_full());
+        }
+
+        let pc = self.chip8.pc % self.chip8.get_ram().len();
+        let is_draw = self.chip8.get_ram()[pc] >> 4 == 0xD;
+        // out of sprites for this frame, the draw waits for the next one
+        if is_draw && self.draw_budget.is_some_and(|budget| self.frame_draws >= budget) {
+            self.end_frame();
+            return Ok(true);
+        }
+
+        if let Some(hook) = &mut self.trace_hook {
+            hook(&self.chip8);
+        }
+        let ram = self.chip8.get_ram();
+        if is_draw {
+            self.draws += 1;
+            self.frame_draws += 1;
+        }
+        let opcode = (ram[pc] as u16) << 8 | ram[(pc + 1) % ram.len()] as u16;
+        self.history.push(pc, opcode);
+
+        self.chip8.cycle()?;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(pc, opcode, self.chip8.pc);
+        }
+        self.cycle += 1;
+        self.cycles += 1;
+        self.check_runaway(pc, opcode);
+        // the VIP's display wait, the sprite was drawn at the start of the display interrupt
+        // and the program carries on after it
+        if is_draw && self.chip8.quirks.vblank {
+            self.end_frame();
+            return Ok(true);
+        }
+
+        Ok(self.frame_full())
+    }
+
+    /**
+     * End the frame if it has run all its cycles.
+     */
+    fn frame_full(&mut self) -> bool {
+        // a stuck program doesn't get to burn through a fast `cycles_per_frame`
+        let cycles_per_frame = match self.runaway {
+            Some(_) => self.cycles_per_frame.min(CYCLES_PER_FRAME),
+            None => self.cycles_per_frame,
+        };
+        if self.cycle < cycles_per_frame {
+            return false;
+        }
+        self.end_frame();
+        true
+    }
+
+    /**
+     * Whether the program is only waiting: halted by `FX0A` or `00FD`, or spinning in a
+     * `1NNN` that jumps to itself, the usual way to stop on a title or end screen. Nothing
+     * but the timers changes until a key is pressed, so the frontend can sleep. `step`
+     * itself carries on as always, the jump runs for the rest of the frame.
+     *
+     * ```
+     * use chip8::machine::{Machine, CYCLES_PER_FRAME};
+     *
+     * let mut machine = Machine::new();
+     * // V0 = 1, then jump to this very jump
+     * machine.load_rom(&[0x60, 0x01, 0x12, 0x02]);
+     * assert!(!machine.idle());
+     *
+     * machine.run_frame().unwrap();
+     * assert!(machine.idle());
+     * assert_eq!(machine.cycles(), CYCLES_PER_FRAME as u64);
+     * ```
+     */
+    pub fn idle(&self) -> bool {
+        let ram = self.chip8.get_ram();
+        let pc = self.chip8.pc % ram.len();
+        let opcode = (ram[pc] as u16) << 8 | ram[(pc + 1) % ram.len()] as u16;
+        matches!(self.chip8.state, State::Halted(_)) || opcode == 0x1000 | pc as u16
+    }
+
+    /**
+     * Count the instruction at `pc` towards `runaway_limit`, or start counting again if it
+     * drew or waited.
+     */
+    fn check_runaway(&mut self, pc: usize, opcode: u16) {
+        let progress = matches!(opcode >> 12, 0xD | 0xE)
+            || opcode == 0x00E0
+            || matches!(opcode & 0xF0FF, 0xF007 | 0xF00A)
+            || self.idle();
+        if progress {
+            self.since_progress = 0;
+            self.runaway = None;
+            return;
+        }
+
+        self.since_progress += 1;
+        if self.runaway.is_none() && self.runaway_limit.is_some_and(|limit| self.since_progress > limit) {
+            self.runaway = Some(pc);
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.cycle = 0;
+        self.frame_draws = 0;
+        if let Some(audio) = &mut self.audio {
+            audio.record(self.chip8.tim_snd > 0);
+        }
+        self.chip8.decrease_timers();
+        self.frame += 1;
+    }
+
+    /**
+     * Run cycles up to the end of the current frame, then tick the timers.
+     */
+    pub fn run_frame(&mut self) -> Result<(), Error> {
+        while !self.step()? {}
+
+        Ok(())
+    }
+
+    /**
+     * Run exactly `frames` frames, pressing and releasing keys as `input` says (frames
+     * counted from now, see `macros::Playback`), and return the screen's checksum. Together
+     * with `Chip8::seed` a ROM always ends up on the same screen, for snapshot tests.
+     *
+     * ```
+     * use chip8::machine::Machine;
+     * use chip8::macros::InputEvent;
+     *
+     * // V0 = random, then draw the font glyph of the key that is held, forever
+     * let rom = [0xC0, 0xFF, 0xF1, 0x0A, 0xF1, 0x29, 0xD0, 0x05, 0x12, 0x08];
+     * let input = [InputEvent { frame: 1, key: 7, down: true }, InputEvent { frame: 2, key: 7, down: false }];
+     * let run = || {
+     *     let mut machine = Machine::new();
+     *     machine.chip8.seed(42);
+     *     machine.load_rom(&rom);
+     *     machine.run_frames(10, &input).unwrap()
+     * };
+     * assert_eq!(run(), run());
+     * ```
+     */
+    pub fn run_frames(&mut self, frames: u64, input: &[InputEvent]) -> Result<u32, Error> {
+        let mut playback = Playback::start(self, input.to_vec());
+        for _ in 0..frames {
+            playback.apply(self);
+            self.run_frame()?;
+        }
+        Ok(self.chip8.get_vram().checksum())
+    }
+
+    /**
+     * Number of frames run so far.
+     */
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /**
+     * Number of instructions executed so far.
+     */
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /**
+     * Number of `DXYN` instructions executed so far.
+     */
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Machine::new()
+    }
+}
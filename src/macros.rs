@@ -0,0 +1,181 @@
+//! Recorded keypad input that can be replayed on demand.
+//!
+//! Macros are kept in numbered slots and stored as text, one event per line, after a line
+//! with the format version:
+//!
+//! ```text
+//! version 1
+//! # slot frame key down|up
+//! 1 0 5 down
+//! 1 4 5 up
+//! ```
+//!
+//! Frames are counted from the start of the recording. Files without a version line are
+//! read as version 1.
+
+use std::collections::BTreeMap;
+
+use crate::hardware::KEY_COUNT;
+use crate::machine::Machine;
+
+// bump when the line format changes, and teach `MacroSet::parse` to read the old one
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct InputEvent {
+    // frames since the start of the macro
+    pub frame: u64,
+    pub key: usize,
+    pub down: bool,
+}
+
+pub type Macro = Vec<InputEvent>;
+
+/**
+ * Collects key events while recording, relative to the frame recording started on.
+ */
+pub struct Recorder {
+    start: u64,
+    events: Macro,
+}
+
+impl Recorder {
+    pub fn start(machine: &Machine) -> Self {
+        Recorder {
+            start: machine.frame(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, machine: &Machine, key: usize, down: bool) {
+        self.events.push(InputEvent { frame: machine.frame() - self.start, key, down });
+    }
+
+    pub fn finish(self) -> Macro {
+        self.events
+    }
+}
+
+/**
+ * Feeds a macro into a machine, one frame at a time.
+ *
+ * ```
+ * use chip8::machine::Machine;
+ * use chip8::macros::{InputEvent, Playback};
+ *
+ * let mut machine = Machine::new();
+ * machine.load_rom(&[0x12, 0x00]);
+ *
+ * let events = vec![
+ *     InputEvent { frame: 0, key: 5, down: true },
+ *     InputEvent { frame: 2, key: 5, down: false },
+ * ];
+ * let mut playback = Playback::start(&machine, events);
+ *
+ * playback.apply(&mut machine);
+ * assert!(machine.chip8.keys[5]);
+ * machine.run_frame().unwrap();
+ * machine.run_frame().unwrap();
+ * assert!(!playback.apply(&mut machine));
+ * assert!(!machine.chip8.keys[5]);
+ * ```
+ */
+pub struct Playback {
+    start: u64,
+    events: Macro,
+    next: usize,
+}
+
+impl Playback {
+    pub fn start(machine: &Machine, events: Macro) -> Self {
+        Playback {
+            start: machine.frame(),
+            events,
+            next: 0,
+        }
+    }
+
+    /**
+     * Inject every event that is due by the machine's current frame. Returns false once
+     * the macro is done, after releasing any keys it left held down.
+     */
+    pub fn apply(&mut self, machine: &mut Machine) -> bool {
+        let now = machine.frame() - self.start;
+
+        while let Some(event) = self.events.get(self.next) {
+            if event.frame > now {
+                return true;
+            }
+            machine.chip8.set_input(event.key, event.down);
+            self.next += 1;
+        }
+
+        for event in &self.events {
+            machine.chip8.set_input(event.key, false);
+        }
+        false
+    }
+}
+
+/**
+ * Macros by slot number.
+ */
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct MacroSet {
+    pub slots: BTreeMap<usize, Macro>,
+}
+
+impl MacroSet {
+    pub fn parse(text: &str) -> Result<MacroSet, String> {
+        let mut set = MacroSet::default();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(version) = line.strip_prefix("version ") {
+                match version.trim().parse::<u32>() {
+                    Ok(v) if v <= VERSION => continue,
+                    Ok(v) => return Err(format!("macros are version {}, this build reads up to {}", v, VERSION)),
+                    Err(_) => return Err(format!("line {}: invalid version '{}'", n + 1, version)),
+                }
+            }
+
+            let err = || format!("line {}: expected '<slot> <frame> <key> down|up', got '{}'", n + 1, line);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [slot, frame, key, state] = fields.as_slice() else {
+                return Err(err());
+            };
+
+            let slot = slot.parse::<usize>().map_err(|_| err())?;
+            let frame = frame.parse::<u64>().map_err(|_| err())?;
+            let key = usize::from_str_radix(key, 16).map_err(|_| err())?;
+            let down = match *state {
+                "down" => true,
+                "up" => false,
+                _ => return Err(err()),
+            };
+            if key >= KEY_COUNT {
+                return Err(err());
+            }
+
+            set.slots.entry(slot).or_default().push(InputEvent { frame, key, down });
+        }
+
+        Ok(set)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = format!("version {}\n# slot frame key down|up\n", VERSION);
+
+        for (slot, events) in &self.slots {
+            for event in events {
+                out += &format!("{} {} {:X} {}\n", slot, event.frame, event.key, if event.down { "down" } else { "up" });
+            }
+        }
+
+        out
+    }
+}
@@ -1,63 +1,375 @@
+#[cfg(feature = "gui")]
 extern crate sdl2;
 
-mod hardware;
+#[cfg(feature = "gui")]
 mod render;
-mod font;
+#[cfg(feature = "gui")]
+mod actions;
+#[cfg(feature = "gui")]
+mod gui;
+#[cfg(any(feature = "gui", feature = "minifb"))]
+mod beep;
+#[cfg(any(feature = "gui", feature = "minifb"))]
+mod buzzer;
+#[cfg(feature = "gui")]
+mod display;
+#[cfg(feature = "wgpu")]
+mod wgpu_display;
+#[cfg(feature = "minifb")]
+mod minifb_frontend;
+#[cfg(feature = "gui-debug")]
+mod debug_ui;
+#[cfg(feature = "gui-debug")]
+mod ui_painter;
+#[cfg(feature = "gui")]
+mod input;
+#[cfg(feature = "gui")]
+mod keymap;
+#[cfg(feature = "gui")]
+mod controllers;
+#[cfg(feature = "gui")]
+mod picture_menu;
+#[cfg(feature = "gui")]
+mod slow_draw;
+#[cfg(any(feature = "gui", feature = "minifb"))]
+mod fixedstep;
+#[cfg(feature = "gui")]
+mod sprite_editor;
+#[cfg(feature = "gui")]
+mod repl;
+#[cfg(feature = "gui")]
+mod debug_session;
+#[cfg(feature = "gui")]
+mod split_screen;
+mod compat;
+mod config;
+mod crash_report;
+mod headless;
+mod i18n;
+mod log_file;
+mod octo;
+mod palette_tool;
+mod paths;
+mod play_log;
+mod recent;
+mod png;
+mod rom_settings;
+mod rom_tool;
+mod routing;
+mod save_ram;
+mod soak;
+mod state_tool;
+mod stats;
+#[cfg(any(feature = "gui", feature = "minifb"))]
+mod thumbnail;
 
-use crate::font::FONT_SET;
+use chip8::coverage::Coverage;
+use chip8::disasm;
+use chip8::machine::Machine;
+use chip8::patch;
+use chip8::peripheral::{Rtc, RTC_ADDR, RTC_LEN};
+use chip8::savestate;
+use chip8::trace::{self, TraceRecord};
+use chip8::wav::AudioTrack;
+use octo::OctoOptions;
 
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
 
-use std::collections::HashSet;
+use std::time;
 
-use std::{thread, time};
+//const ROM: &[u8] = include_bytes!("../IBM Logo.ch8");
+const ROM: &[u8] = include_bytes!("../chip8-test-suite.ch8");
 
 fn main() -> Result<(), String> {
-    let mut renderer = render::Render::new("Chip8", hardware::CHIP8_WIDTH * hardware::MULTIPLIER, hardware::CHIP8_HEIGHT * hardware::MULTIPLIER, true)?;
-    let mut chip8 = hardware::Chip8::new();
+    let mut config = config::Config::from_args(std::env::args().skip(1))?;
+    let mut log = log_file::Log::open(config.log_file.as_deref())?;
+    log.info(&format!("started with {:?}", std::env::args().skip(1).collect::<Vec<_>>()));
 
-    chip8.load_ram(&FONT_SET, 0x50);
-    //chip8.load_ram(include_bytes!("../IBM Logo.ch8"), 0x200);
-    chip8.load_ram(include_bytes!("../chip8-test-suite.ch8"), 0x200);
+    if let config::Command::CompareTrace(a, b) = &config.command {
+        return compare_traces(a, b);
+    }
 
-    //chip8.load_ram(&[0x05], 0x1FF);
+    match &config.command {
+        config::Command::Disasm(path) => return disassemble(path, config.cfg, config.format),
+        config::Command::StateDump(path) => return state_tool::dump(path, config.format, config.out.as_deref()),
+        config::Command::RomNormalize(path) => return rom_tool::normalize(path, config.pad, config.format, config.out.as_deref()),
+        config::Command::RomTutorial(family) => return rom_tool::tutorial(family, config.out.as_deref()),
+        config::Command::PaletteExtract(path) => return palette_tool::extract(path, config.format, config.out.as_deref()),
+        config::Command::Recent => {
+            print!("{}", recent::Recent::load(&config.recent_file)?.menu());
+            return Ok(());
+        }
+        config::Command::Favorite(path) => return toggle_favorite(path, &config.recent_file),
+        config::Command::PlayTime => {
+            print!("{}", play_log::PlayLog::load(&config.play_log)?.report());
+            return Ok(());
+        }
+        config::Command::StateExtractVram(path) => {
+            let out = config.out.as_deref().ok_or("extract-vram needs --out <file>")?;
+            return state_tool::extract_vram(path, &config.palette, out);
+        }
+        _ => {}
+    }
+
+    if let config::Command::Soak(path) = &config.command {
+        let rom = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        return soak::run(&rom, config.instances, time::Duration::from_secs_f64(config.hours * 3600.0));
+    }
 
-    chip8.start();
+    #[cfg(feature = "gui")]
+    if config.sprite_editor {
+        return sprite_editor::run(&config.palette, config.backend);
+    }
+    if let config::Command::Split(paths) = &config.command {
+        #[cfg(feature = "gui")]
+        return split_screen::run(&config, paths);
+        #[cfg(not(feature = "gui"))]
+        return Err(format!("can't show {} side by side, split needs a build with the SDL window", paths.join(", ")));
+    }
 
-    renderer.sound.resume();
+    let (rom_path, rom) = match &config.rom {
+        Some(choice) => {
+            let (path, rom) = open_rom(choice, &config.recent_file)?;
+            (Some(path), rom)
+        }
+        None => (None, ROM.to_vec()),
+    };
+    let rom = match &config.patch {
+        Some(path) => {
+            let data = fs::read(path).map_err(|e| format!("can't read patch {}: {}", path, e))?;
+            patch::apply(&rom, &data).map_err(|e| format!("can't apply patch {}: {}", path, e))?
+        }
+        None => rom,
+    };
 
-    let mut fixedstep = fixedstep::FixedStep::start(60.0);
+    // the command line wins over the ROM's own settings
+    let settings = rom_settings::RomSettings::load(&config.rom_settings, &rom)?;
+    config.scaling = config.scaling.or(settings.scaling);
+    if !config.speed_given {
+        config.speed = settings.speed.unwrap_or(config.speed);
+    }
+    config.breakpoints.extend(settings.breakpoints);
+    config.paused |= settings.paused;
+    config.load_state |= settings.load_state;
 
-    'running: loop {
-        while fixedstep.update() {
-            chip8.decrease_timers();
-            if chip8.tim_snd == 0 {
-                renderer.sound.pause();
+    // and over the options Octo saved with the program
+    if let Some((path, octo)) = rom_path.as_deref().map(OctoOptions::load).transpose()?.flatten() {
+        log.info(&format!("Octo options loaded from {}", path));
+        config.cycles_per_frame = config.cycles_per_frame.or(octo.tickrate);
+        if !config.palette_given {
+            config.palette.on = octo.fill.unwrap_or(config.palette.on);
+            config.palette.off = octo.background.unwrap_or(config.palette.off);
+            config.palette.plane2 = octo.fill2.unwrap_or(config.palette.plane2);
+            config.palette.both = octo.blend.unwrap_or(config.palette.both);
+        }
+        match (octo.xo_chip, config.platform) {
+            (Some(true), Some(platform)) if !platform.xo_chip() => {
+                log.warn(&format!("{} asks for XO-CHIP, which --platform {} leaves off", path, platform.name()))
             }
+            (Some(true), _) if config.strict => log.warn(&format!("{} asks for XO-CHIP, which --strict leaves off", path)),
+            (Some(true), _) => config.xo_chip = true,
+            _ => {}
+        }
+        if !config.quirks_given && config.platform.is_none() {
+            octo.apply(&mut config.quirks);
         }
+    }
+
+    let mut machine = Machine::new();
+    if let Some(platform) = config.platform {
+        platform.apply(&mut machine.chip8).map_err(|e| e.to_string())?;
+    }
+    machine.chip8.set_xo_chip(config.xo_chip);
+    machine.load_program(&rom, true).map_err(|e| format!("can't load ROM: {}", e))?;
+    log.info(&format!("loaded ROM, {} bytes, crc32 {:08x}{}", rom.len(), patch::crc32(&rom),
+        config.patch.as_ref().map_or(String::new(), |p| format!(", patched with {}", p))));
+
+    let save_path = config.save_ram.then(|| save_ram::path(&config.save_dir, &rom));
+    if let Some(path) = &save_path {
+        if 0x200 + rom.len() > save_ram::ADDR {
+            log.warn(&format!("ROM reaches into save RAM at {:03X}, it will be overwritten", save_ram::ADDR));
+        }
+        save_ram::load(&mut machine, path)?;
+        log.info(&format!("save RAM loaded from {}", path));
+    }
+    if config.load_state {
+        let path = state_path(&config.save_dir, &rom);
+        fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| savestate::load(&mut machine, &data).map_err(|e| e.to_string()))
+            .map_err(|e| config.lang.format("err.state_load", &[&path, &e]))?;
+        log.info(&format!("state loaded from {}", path));
+    }
 
-        for event in renderer.event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } => break 'running,
-                Event::KeyDown { keycode: Some(keycode), .. } => {
-                    chip8.set_input(keycode, true);
-                },
-                Event::KeyUp { keycode: Some(keycode), .. } => {
-                    chip8.set_input(keycode, false);
-                },
-                _ => {}
+    machine.chip8.hardened = config.harden;
+    machine.chip8.strict = config.strict;
+    machine.chip8.quirks = config.quirks;
+    machine.draw_budget = config.draw_budget;
+    if let Some(cycles) = config.cycles_per_frame {
+        machine.cycles_per_frame = cycles;
+    }
+    machine.runaway_limit = config.runaway;
+    machine.chip8.trace = config.print_instructions;
+    machine.chip8.random = config.rng.create();
+    if config.extensions {
+        machine.chip8.map_peripheral(RTC_ADDR..RTC_ADDR + RTC_LEN, Box::new(Rtc)).map_err(|e| format!("{:?}", e))?;
+    }
+
+    if let Some(path) = &config.trace {
+        let mut out = BufWriter::new(File::create(path).map_err(|e| format!("can't create trace {}: {}", path, e))?);
+        let mut failed = false;
+        machine.trace_hook = Some(Box::new(move |chip8| {
+            if !failed && writeln!(out, "{}", TraceRecord::from_chip8(chip8)).is_err() {
+                eprintln!("writing the trace failed, tracing stopped");
+                failed = true;
             }
+        }));
+    }
+
+    if config.coverage.is_some() {
+        machine.coverage = Some(Coverage::new(machine.chip8.get_ram().len()));
+    }
+    if config.record_audio.is_some() {
+        machine.audio = Some(AudioTrack::new());
+    }
+
+    let mut stats = stats::Stats::start();
+    if let Some(path) = &config.frame_times {
+        stats.log_frames(&machine, path)?;
+    }
+
+    // only time spent in the window counts as playing. the launch is stored right away so
+    // it is counted even if the run doesn't end cleanly
+    let mut play_log = (config.command == config::Command::Run && !config.headless)
+        .then(|| play_log::PlayLog::load(&config.play_log))
+        .transpose()?;
+    if let Some(play_log) = &mut play_log {
+        let record = play_log.launch(&rom);
+        if record.launches > 1 {
+            println!("{}", config.lang.format("msg.play_time", &[&record.launches, &play_log::hours(record.played)]));
         }
+        if let Err(e) = play_log.store(&config.play_log) {
+            eprintln!("{}", e);
+            log.warn(&e);
+        }
+    }
+    let play_started = time::Instant::now();
+
+    let result = match config.command {
+        config::Command::Verify => headless::verify(&config, &mut machine),
+        config::Command::Ab => headless::ab(&config, &mut machine, &rom),
+        config::Command::Check => headless::check(&config, &mut machine),
+        #[cfg(feature = "minifb")]
+        _ if !config.headless && config.backend == config::RenderBackend::Minifb => {
+            minifb_frontend::run(&config, &mut machine, &rom, &mut stats)
+        }
+        #[cfg(feature = "gui")]
+        _ if !config.headless => gui::run(&config, &mut machine, &rom, &mut stats, &mut log),
+        _ => headless::run(&config, &mut machine, &rom, &mut stats),
+    };
+
+    match &result {
+        Ok(()) => log.info(&format!("stopped after {} frames", machine.frame())),
+        Err(e) => log.error(&format!("stopped at PC {:03X}, frame {}: {}", machine.chip8.pc, machine.frame(), e)),
+    }
+
+    if let Some(play_log) = &mut play_log {
+        play_log.add_time(&rom, play_started.elapsed());
+        if let Err(e) = play_log.store(&config.play_log) {
+            eprintln!("{}", e);
+            log.warn(&e);
+        }
+    }
+    if let Some(path) = &config.compat_report {
+        compat::Entry::new(&rom, &machine, &stats.faults, &result).append(path)?;
+        println!("{}", config.lang.format("msg.compat_report", &[path]));
+        log.info(&format!("compatibility report added to {}", path));
+    }
+    stats.finish_frames()?;
+    if let Some(path) = &config.stats_json {
+        stats.write_json(&machine, path)?;
+    }
+    if let (Some(path), Some(coverage)) = (&config.coverage, &machine.coverage) {
+        fs::write(path, coverage.annotate(machine.chip8.get_ram(), rom.len()))
+            .map_err(|e| format!("can't write coverage {}: {}", path, e))?;
+    }
+    // at the rate frames were run, so the track is as long as the session sounded
+    if let (Some(path), Some(audio)) = (&config.record_audio, &machine.audio) {
+        fs::write(path, audio.wav(config.refresh_rate * config.speed))
+            .map_err(|e| format!("can't write audio {}: {}", path, e))?;
+        log.info(&format!("{} frames of audio written to {}", audio.frames(), path));
+    }
+    if let Some(path) = &save_path {
+        save_ram::store(&machine, path).inspect_err(|e| log.error(e))?;
+        log.info(&format!("save RAM stored to {}", path));
+    }
+
+    result
+}
 
-        chip8.cycle();
+/**
+ * Where F9 saves the state of `rom`, kept next to the save RAM.
+ */
+fn state_path(save_dir: &str, rom: &[u8]) -> String {
+    format!("{}/{:08x}.state", save_dir, patch::crc32(rom))
+}
 
-        if chip8.vram_changed {
-            renderer.update(chip8.get_vram())?;
-            chip8.vram_changed = false;
+/**
+ * Read the ROM `run` was given: a file, a number from the `recent` list or `--last`. The
+ * file goes to the top of the recent files.
+ */
+fn open_rom(choice: &str, recent_file: &str) -> Result<(String, Vec<u8>), String> {
+    let mut recent = recent::Recent::load(recent_file)?;
+    let path = match choice.parse::<usize>() {
+        _ if choice == "--last" => recent.last().ok_or("no ROM has been run yet")?.to_string(),
+        // a file called "3" still wins over the third entry
+        Ok(n) if !Path::new(choice).exists() => {
+            recent.entry(n).ok_or_else(|| format!("there is no ROM {} in the recent list", n))?.to_string()
         }
+        _ => fs::canonicalize(choice).map_err(|e| format!("can't read {}: {}", choice, e))?.to_string_lossy().into_owned(),
+    };
+
+    let rom = fs::read(&path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    recent.add(&path);
+    recent.store(recent_file)?;
+    Ok((path, rom))
+}
 
-        thread::sleep(time::Duration::from_millis(2));
+fn toggle_favorite(rom: &str, recent_file: &str) -> Result<(), String> {
+    let path = fs::canonicalize(rom).map_err(|e| format!("can't read {}: {}", rom, e))?;
+    let path = path.to_string_lossy();
+    let mut recent = recent::Recent::load(recent_file)?;
+    if recent.toggle_favorite(&path) {
+        println!("{} is a favorite now", path);
+    } else {
+        println!("{} is no longer a favorite", path);
+    }
+    recent.store(recent_file)
+}
+
+fn disassemble(path: &str, cfg: bool, format: config::Format) -> Result<(), String> {
+    let rom = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let analysis = disasm::analyze(&rom);
+    match format {
+        // the JSON has the blocks as well, --cfg doesn't change it
+        config::Format::Json => print!("{}", analysis.to_json(&rom)),
+        config::Format::Text if cfg => print!("{}", analysis.to_dot(&rom)),
+        config::Format::Text => print!("{}", analysis.listing(&rom)),
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+fn compare_traces(a: &str, b: &str) -> Result<(), String> {
+    let left = fs::read_to_string(a).map_err(|e| format!("can't read {}: {}", a, e))?;
+    let right = fs::read_to_string(b).map_err(|e| format!("can't read {}: {}", b, e))?;
+
+    match trace::compare(&left, &right)? {
+        Some(mismatch) => Err(mismatch.to_string()),
+        None => {
+            println!("traces match");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use minifb::{Key, ScaleMode, Window, WindowOptions};
+
+use chip8::debugger::Debugger;
+use chip8::hardware::{KEY_COUNT, MULTIPLIER};
+use chip8::machine::Machine;
+
+use crate::beep::BeepSchedule;
+use crate::buzzer;
+use crate::config::{AudioBackend, Config};
+use crate::crash_report;
+use crate::fixedstep::FixedStep;
+use crate::routing::Device;
+use crate::stats::Stats;
+use crate::thumbnail;
+
+// the same 1234/QWER/ASDF/ZXCV layout as `input::keypad_index`
+const KEYMAP: [(Key, usize); KEY_COUNT] = [
+    (Key::Key1, 0x1), (Key::Key2, 0x2), (Key::Key3, 0x3), (Key::Key4, 0xc),
+    (Key::Q, 0x4), (Key::W, 0x5), (Key::E, 0x6), (Key::R, 0xd),
+    (Key::A, 0x7), (Key::S, 0x8), (Key::D, 0x9), (Key::F, 0xe),
+    (Key::Z, 0xa), (Key::X, 0x0), (Key::C, 0xb), (Key::V, 0xf),
+];
+
+/**
+ * Run in a minifb window: the screen and the keyboard, nothing else. No overlays, hotkeys
+ * or controllers, for builds without SDL and for embedding. Escape or closing the window
+ * quits, a fault ends the run with a crash report like in headless mode.
+ */
+pub fn run(config: &Config, machine: &mut Machine, rom: &[u8], stats: &mut Stats) -> Result<(), String> {
+    let lang = config.lang;
+    let vram = machine.chip8.get_vram();
+    let options = WindowOptions { resize: true, scale_mode: ScaleMode::AspectRatioStretch, ..WindowOptions::default() };
+    let (width, height) = (vram.width() * MULTIPLIER as usize, vram.height() * MULTIPLIER as usize);
+    let mut window = Window::new("Chip8", width, height, options).map_err(|e| e.to_string())?;
+    // frames are paced by `FixedStep` below, not by minifb
+    window.set_target_fps(0);
+
+    // SDL audio needs an SDL window, fall back to the next best backend
+    let audio = match config.audio {
+        AudioBackend::Sdl if AudioBackend::Cpal.is_built() => AudioBackend::Cpal,
+        AudioBackend::Sdl => AudioBackend::Off,
+        audio => audio,
+    };
+    let mut buzzer = buzzer::open(audio)?;
+    let mut beep = BeepSchedule::new(Duration::from_secs_f64(config.min_beep_ms / 1000.0));
+
+    let mut debugger = Debugger::new();
+    let mut fixedstep = FixedStep::start(config.refresh_rate * config.speed);
+    let mut held = [false; KEY_COUNT];
+    let mut pixels: Vec<u32> = Vec::new();
+    let mut halt_reported = false;
+    let mut thumbnail = thumbnail::pending(config, rom);
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        // there are no controllers here, keys routed to one stay up
+        for (key, index) in KEYMAP.into_iter().filter(|(_, index)| config.routing.allows(Device::Keyboard, *index)) {
+            let down = window.is_key_down(key);
+            if down != held[index] {
+                machine.chip8.set_input(index, down);
+                held[index] = down;
+            }
+        }
+
+        let emulate_started = Instant::now();
+        while fixedstep.update() && debugger.fault.is_none() {
+            if let Some(msg) = debugger.run_frame(machine) {
+                println!("{}", msg);
+            }
+            if let Some(ticks) = machine.chip8.sound_request.take() {
+                beep.request(Duration::from_secs_f64(ticks as f64 / (config.refresh_rate * config.speed)));
+            }
+        }
+        stats.emulate += emulate_started.elapsed();
+        if let Some(fault) = debugger.fault {
+            stats.faults.push(fault.to_string());
+            println!("{}", debugger.post_mortem(machine));
+            let path = crash_report::write(&config.crash_dir, rom, machine, &debugger)?;
+            return Err(format!("{}, crash report written to {}", fault, path));
+        }
+        buzzer.set_beep(machine.chip8.tim_snd > 0 || beep.is_on());
+
+        if machine.chip8.exited() && !halt_reported {
+            println!("{}", lang.tr("msg.exited"));
+            window.set_title(lang.tr("window.halted"));
+            halt_reported = true;
+        }
+
+        if let Some(path) = thumbnail.take_if(|_| machine.frame() >= config.thumbnail_frame) {
+            if let Err(e) = thumbnail::store(config, machine, &path) {
+                println!("{}", e);
+            }
+        }
+
+        let render_started = Instant::now();
+        let (width, height) = (machine.chip8.get_vram().width(), machine.chip8.get_vram().height());
+        // the length changes along with the resolution
+        if machine.chip8.vram_changed || pixels.len() != width * height {
+            pixels = machine.chip8
+                .to_rgba(&config.palette.adjusted(&config.picture))
+                .chunks_exact(4)
+                .map(|px| u32::from_be_bytes([0, px[0], px[1], px[2]]))
+                .collect();
+            machine.chip8.vram_changed = false;
+            stats.frames_rendered += 1;
+        }
+        // also polls the keyboard, so it runs even when nothing changed
+        window.update_with_buffer(&pixels, width, height).map_err(|e| e.to_string())?;
+        stats.render += render_started.elapsed();
+
+        let sleep_started = Instant::now();
+        thread::sleep(fixedstep.until_next());
+        stats.sleep += sleep_started.elapsed();
+        stats.end_pass(machine, buzzer.samples())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+//! Reading text back off the screen, so test ROMs that report their results as glyphs can
+//! be checked automatically.
+//!
+//! Glyphs are matched exactly, including the blank pixels in their box. Besides the built-in
+//! hex font, extra glyphs (e.g. a test suite's check and cross marks) can be loaded from a
+//! text file where each glyph is a `= <char>` line followed by rows of `#` and `.`:
+//!
+//! ```text
+//! = x
+//! #...#
+//! .#.#.
+//! ..#..
+//! .#.#.
+//! #...#
+//! ```
+
+use crate::font::FONT_SET;
+use crate::framebuffer::Framebuffer;
+
+// horizontal gap, in pixels, between two glyphs on a line that is read as a space
+const SPACE_GAP: usize = 3;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Glyph {
+    pub ch: char,
+    pub width: usize,
+    pub height: usize,
+    // row-major, like `Framebuffer`
+    pub pixels: Vec<bool>,
+}
+
+impl Glyph {
+    /**
+     * Glyph from a sprite, one byte per row with the MSB as the leftmost pixel.
+     */
+    pub fn from_sprite(ch: char, width: usize, rows: &[u8]) -> Self {
+        let pixels = rows.iter().flat_map(|row| (0..width).map(move |x| row & (0x80 >> x) != 0)).collect();
+
+        Glyph { ch, width, height: rows.len(), pixels }
+    }
+
+    fn matches(&self, vram: &Framebuffer, x: usize, y: usize) -> bool {
+        (0..self.height).all(|gy| {
+            (0..self.width).all(|gx| vram.get(x + gx, y + gy) == Some(self.pixels[gy * self.width + gx]))
+        })
+    }
+}
+
+/**
+ * The 4x5 hex digits of the built-in font.
+ */
+pub fn font_glyphs() -> Vec<Glyph> {
+    FONT_SET
+        .chunks(5)
+        .zip("0123456789ABCDEF".chars())
+        .map(|(rows, ch)| Glyph::from_sprite(ch, 4, rows))
+        .collect()
+}
+
+/**
+ * Parse glyphs in the format described in the module docs.
+ */
+pub fn parse_glyphs(text: &str) -> Result<Vec<Glyph>, String> {
+    let mut glyphs: Vec<Glyph> = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if let Some(ch) = line.strip_prefix('=') {
+            let mut chars = ch.trim().chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => glyphs.push(Glyph { ch, width: 0, height: 0, pixels: Vec::new() }),
+                _ => return Err(format!("line {}: expected '= <char>'", n + 1)),
+            }
+            continue;
+        }
+
+        let glyph = glyphs.last_mut().ok_or(format!("line {}: pixels before the first '= <char>'", n + 1))?;
+        if glyph.height > 0 && line.len() != glyph.width {
+            return Err(format!("line {}: glyph '{}' rows differ in width", n + 1, glyph.ch));
+        }
+        for c in line.chars() {
+            match c {
+                '#' => glyph.pixels.push(true),
+                '.' => glyph.pixels.push(false),
+                _ => return Err(format!("line {}: unexpected '{}', use '#' and '.'", n + 1, c)),
+            }
+        }
+        glyph.width = line.len();
+        glyph.height += 1;
+    }
+
+    match glyphs.iter().find(|g| g.height == 0) {
+        Some(empty) => Err(format!("glyph '{}' has no pixels", empty.ch)),
+        None => Ok(glyphs),
+    }
+}
+
+/**
+ * Find every glyph on screen and return the text, one string per line of glyphs, top to
+ * bottom. Glyphs count as being on the same line when their top rows line up.
+ *
+ * ```
+ * use chip8::machine::Machine;
+ * use chip8::ocr;
+ *
+ * let mut machine = Machine::new();
+ * // draw "A" at (2, 1), "7" at (7, 1) and "0" at (20, 1)
+ * machine.load_rom(&[
+ *     0x60, 0x0A, 0xF0, 0x29, 0x61, 0x02, 0x62, 0x01, 0xD1, 0x25,
+ *     0x60, 0x07, 0xF0, 0x29, 0x61, 0x07, 0xD1, 0x25,
+ *     0x60, 0x00, 0xF0, 0x29, 0x61, 0x14, 0xD1, 0x25,
+ *     0x00, 0xFD,
+ * ]);
+ * machine.run_frame().unwrap();
+ * machine.run_frame().unwrap();
+ * machine.run_frame().unwrap();
+ *
+ * assert_eq!(ocr::read_lines(machine.chip8.get_vram(), &ocr::font_glyphs()), vec!["A7 0"]);
+ * ```
+ */
+pub fn read_lines(vram: &Framebuffer, glyphs: &[Glyph]) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for y in 0..vram.height() {
+        let mut line = String::new();
+        let mut x = 0;
+        // right edge of the last glyph on this line
+        let mut last_end = None;
+
+        while x < vram.width() {
+            match glyphs.iter().find(|g| g.matches(vram, x, y)) {
+                Some(glyph) => {
+                    if last_end.is_some_and(|end| x >= end + SPACE_GAP) {
+                        line.push(' ');
+                    }
+                    line.push(glyph.ch);
+                    last_end = Some(x + glyph.width);
+                    x += glyph.width;
+                }
+                None => x += 1,
+            }
+        }
+
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+
+    lines
+}
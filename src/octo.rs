@@ -0,0 +1,197 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use chip8::quirks::Quirks;
+
+// short quirk names of a `quirks:` line, see `OctoOptions::parse_octorc`, the same as
+// `Quirks`' names
+const SHORT_QUIRKS: [(&str, &str); 6] = [
+    ("shift", "shiftQuirks"),
+    ("loadstore", "loadStoreQuirks"),
+    ("jump", "jumpQuirks"),
+    ("vblank", "vBlankQuirks"),
+    ("logic", "logicQuirks"),
+    ("clip", "clipQuirks"),
+];
+
+/**
+ * The options Octo saves with a program, read from a file next to the ROM so games built
+ * with Octo run the way their author set them up. `<rom>.json` holds them the way Octo
+ * exports them, on their own or under `"options"`:
+ *
+ * ```text
+ * {"options": {"tickrate": 20, "fillColor": "#FFCC00", "backgroundColor": "#996600", "shiftQuirks": true}}
+ * ```
+ *
+ * `<rom>.octorc` has one `key: value` per line, with the same keys or a `quirks:` line
+ * listing the quirks that are on. A leading `#` is allowed, so the lines can be copied from
+ * the top of the program's source:
+ *
+ * ```text
+ * # tickrate: 20
+ * # quirks: shift, loadstore
+ * ```
+ *
+ * Keys that don't matter here, like `touchInputMode`, are skipped.
+ */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct OctoOptions {
+    // instructions per frame
+    pub tickrate: Option<u32>,
+    pub fill: Option<[u8; 3]>,
+    pub background: Option<[u8; 3]>,
+    // XO-CHIP's colors for the second bitplane and for both, see `Palette`
+    pub fill2: Option<[u8; 3]>,
+    pub blend: Option<[u8; 3]>,
+    // `enableXO`, see `Chip8::set_xo_chip`
+    pub xo_chip: Option<bool>,
+    // quirk switches by Octo's name, in the order they were found
+    pub quirks: Vec<(String, bool)>,
+}
+
+impl OctoOptions {
+    pub fn parse_json(text: &str) -> Result<OctoOptions, String> {
+        let mut options = OctoOptions::default();
+        for (key, value) in json_fields(text)? {
+            options.set(&key, &value)?;
+        }
+        Ok(options)
+    }
+
+    pub fn parse_octorc(text: &str) -> Result<OctoOptions, String> {
+        let mut options = OctoOptions::default();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim().trim_start_matches('#').trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let at_line = |e| format!("line {}: {}", n + 1, e);
+            let (key, value) = (key.trim(), value.trim());
+            if key != "quirks" {
+                options.set(key, value).map_err(at_line)?;
+                continue;
+            }
+            for name in value.split(|c: char| c == ',' || c.is_whitespace()).filter(|name| !name.is_empty()) {
+                let (_, quirk) = SHORT_QUIRKS
+                    .iter()
+                    .find(|(short, _)| *short == name)
+                    .ok_or_else(|| at_line(format!("unknown quirk '{}'", name)))?;
+                options.quirks.push((quirk.to_string(), true));
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("invalid {} '{}'", key, value);
+        match key {
+            "tickrate" => self.tickrate = Some(value.parse::<u32>().ok().filter(|n| *n > 0).ok_or_else(invalid)?),
+            "fillColor" => self.fill = Some(parse_color(value).ok_or_else(invalid)?),
+            "backgroundColor" => self.background = Some(parse_color(value).ok_or_else(invalid)?),
+            "fillColor2" => self.fill2 = Some(parse_color(value).ok_or_else(invalid)?),
+            "blendColor" => self.blend = Some(parse_color(value).ok_or_else(invalid)?),
+            "enableXO" => self.xo_chip = Some(value.parse::<bool>().map_err(|_| invalid())?),
+            _ if SHORT_QUIRKS.iter().any(|(_, quirk)| *quirk == key) => {
+                let on = value.parse::<bool>().map_err(|_| invalid())?;
+                self.quirks.push((key.to_string(), on));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /**
+     * The options next to the ROM at `path`, the `.json` before the `.octorc`. Having
+     * neither is fine.
+     */
+    pub fn load(path: &str) -> Result<Option<(String, OctoOptions)>, String> {
+        for extension in ["json", "octorc"] {
+            let file = Path::new(path).with_extension(extension).to_string_lossy().into_owned();
+            let text = match fs::read_to_string(&file) {
+                Ok(text) => text,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("can't read {}: {}", file, e)),
+            };
+            let options = if extension == "json" { OctoOptions::parse_json(&text) } else { OctoOptions::parse_octorc(&text) };
+            return options.map(|options| Some((file.clone(), options))).map_err(|e| format!("{}: {}", file, e));
+        }
+
+        Ok(None)
+    }
+
+    /**
+     * Turn the quirks the options switch on or off in `quirks`.
+     */
+    pub fn apply(&self, quirks: &mut Quirks) {
+        for (name, on) in &self.quirks {
+            if let Some((short, _)) = SHORT_QUIRKS.iter().find(|(_, quirk)| quirk == name) {
+                let _ = quirks.set(short, *on);
+            }
+        }
+    }
+}
+
+/**
+ * `#RRGGBB`, or the `#RGB` shorthand.
+ */
+fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.strip_prefix('#')?;
+    let digit = |i: usize| u8::from_str_radix(hex.get(i..i + 1)?, 16).ok();
+    match hex.len() {
+        3 => Some([digit(0)? * 0x11, digit(1)? * 0x11, digit(2)? * 0x11]),
+        6 => Some([digit(0)? << 4 | digit(1)?, digit(2)? << 4 | digit(3)?, digit(4)? << 4 | digit(5)?]),
+        _ => None,
+    }
+}
+
+/**
+ * Every `"key": value` in a JSON document whose value is a string, number or boolean,
+ * wherever it is nested. Strings are unquoted, but escapes are kept as they are, Octo's
+ * options don't need them.
+ */
+fn json_fields(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '"' => {
+                let mut escaped = false;
+                let end = loop {
+                    match chars.next() {
+                        Some((i, '"')) if !escaped => break i,
+                        Some((_, c)) => escaped = c == '\\' && !escaped,
+                        None => return Err("unterminated string".to_string()),
+                    }
+                };
+                tokens.push((true, &text[start + 1..end]));
+            }
+            '{' | '}' | '[' | ']' | ':' | ',' => tokens.push((false, &text[start..start + 1])),
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || "{}[]:,\"".contains(c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push((false, &text[start..end]));
+            }
+        }
+    }
+
+    let mut fields = Vec::new();
+    for window in tokens.windows(3) {
+        if let [(true, key), (false, ":"), (quoted, value)] = window {
+            if *quoted || !"{[".contains(*value) {
+                fields.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+    Ok(fields)
+}
@@ -0,0 +1,301 @@
+use std::ops::RangeInclusive;
+
+/**
+ * Colors used to turn the monochrome framebuffer into an image, as RGB triplets. XO-CHIP
+ * programs draw on two bitplanes, `on` is for pixels only set in the first.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub off: [u8; 3],
+    pub on: [u8; 3],
+    // pixels only set in the second bitplane, and in both
+    pub plane2: [u8; 3],
+    pub both: [u8; 3],
+}
+
+impl Palette {
+    // the classic green-on-black look the SDL frontend has always used
+    pub const GREEN: Palette = Palette {
+        off: [0x00, 0x00, 0x00],
+        on: [0x00, 0xFF, 0x00],
+        plane2: [0x00, 0x80, 0x80],
+        both: [0xC0, 0xFF, 0x40],
+    };
+
+    // plain white on black for maximum contrast
+    pub const HIGH_CONTRAST: Palette = Palette {
+        off: [0x00, 0x00, 0x00],
+        on: [0xFF, 0xFF, 0xFF],
+        plane2: [0x80, 0x80, 0x80],
+        both: [0xFF, 0xFF, 0x00],
+    };
+
+    // black on white, for projectors and washed-out screens
+    pub const INVERTED: Palette = Palette {
+        off: [0xFF, 0xFF, 0xFF],
+        on: [0x00, 0x00, 0x00],
+        plane2: [0x80, 0x80, 0x80],
+        both: [0x00, 0x00, 0xA0],
+    };
+
+    pub const AMBER: Palette = Palette {
+        off: [0x00, 0x00, 0x00],
+        on: [0xFF, 0xB0, 0x00],
+        plane2: [0x80, 0x40, 0x00],
+        both: [0xFF, 0xE0, 0x80],
+    };
+
+    // orange on dark blue (Okabe-Ito), distinguishable with protanopia and deuteranopia
+    pub const BLUE_ORANGE: Palette = Palette {
+        off: [0x00, 0x2B, 0x4C],
+        on: [0xE6, 0x9F, 0x00],
+        plane2: [0x56, 0xB4, 0xE9],
+        both: [0xF0, 0xE4, 0x42],
+    };
+
+    // pale pink on dark red, keeps away from the blue/yellow axis lost with tritanopia
+    pub const TRITAN: Palette = Palette {
+        off: [0x3A, 0x00, 0x00],
+        on: [0xFF, 0xE0, 0xE0],
+        plane2: [0xC0, 0x40, 0x40],
+        both: [0xFF, 0xFF, 0xFF],
+    };
+
+    /**
+     * A palette of two colors, with XO-CHIP's other two made up from them: the second plane
+     * halfway between `off` and `on`, both planes in `on`.
+     *
+     * ```
+     * use chip8::palette::Palette;
+     *
+     * let palette = Palette::new([0x00, 0x00, 0x00], [0xFF, 0x80, 0x00]);
+     * assert_eq!(palette.plane2, [0x80, 0x40, 0x00]);
+     * assert_eq!(palette.both, palette.on);
+     * ```
+     */
+    pub fn new(off: [u8; 3], on: [u8; 3]) -> Palette {
+        let plane2 = [0, 1, 2].map(|c| (off[c] as u16 + on[c] as u16).div_ceil(2) as u8);
+        Palette { off, on, plane2, both: on }
+    }
+
+    /**
+     * Look up a preset from `PRESETS` by name.
+     */
+    pub fn by_name(name: &str) -> Option<Palette> {
+        PRESETS.iter().find(|(n, _)| *n == name).map(|(_, p)| *p)
+    }
+
+    /**
+     * A palette file: an `off` and an `on` line with a `#RRGGBB` color each, `#` at the
+     * start of a line begins a comment. `plane2` and `both` lines for XO-CHIP's colors are
+     * optional, see `new` for what they are without.
+     *
+     * ```
+     * use chip8::palette::Palette;
+     *
+     * let text = "# from a photo of a VIP\noff #101010\non  #E0F0A0\n";
+     * let palette = Palette::parse(text).unwrap();
+     * assert_eq!(palette, Palette::new([0x10, 0x10, 0x10], [0xE0, 0xF0, 0xA0]));
+     * assert_eq!(Palette::parse(&palette.to_text()), Ok(palette));
+     *
+     * let xo = Palette::parse("off #000000\non #FFFFFF\nplane2 #FF0000\nboth #0000FF").unwrap();
+     * assert_eq!((xo.plane2, xo.both), ([0xFF, 0x00, 0x00], [0x00, 0x00, 0xFF]));
+     * assert_eq!(Palette::parse(&xo.to_text()), Ok(xo));
+     * ```
+     */
+    pub fn parse(text: &str) -> Result<Palette, String> {
+        let (mut off, mut on, mut plane2, mut both) = (None, None, None, None);
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let color = parse_color(value.trim()).ok_or_else(|| format!("line {}: invalid color '{}', expected #RRGGBB", n + 1, value.trim()))?;
+            match key {
+                "off" => off = Some(color),
+                "on" => on = Some(color),
+                "plane2" => plane2 = Some(color),
+                "both" => both = Some(color),
+                _ => return Err(format!("line {}: unknown key '{}', expected off, on, plane2 or both", n + 1, key)),
+            }
+        }
+
+        match (off, on) {
+            (Some(off), Some(on)) => {
+                let palette = Palette::new(off, on);
+                Ok(Palette { plane2: plane2.unwrap_or(palette.plane2), both: both.unwrap_or(palette.both), ..palette })
+            }
+            _ => Err("a palette needs both an off and an on color".to_string()),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let hex = |[r, g, b]: [u8; 3]| format!("#{:02X}{:02X}{:02X}", r, g, b);
+        let mut text = format!("off {}\non  {}\n", hex(self.off), hex(self.on));
+        // only the XO-CHIP colors that aren't the ones `new` would make up
+        let made_up = Palette::new(self.off, self.on);
+        if (self.plane2, self.both) != (made_up.plane2, made_up.both) {
+            text += &format!("plane2 {}\nboth {}\n", hex(self.plane2), hex(self.both));
+        }
+        text
+    }
+
+    /**
+     * All colors with `picture` applied.
+     */
+    pub fn adjusted(&self, picture: &Picture) -> Palette {
+        Palette {
+            off: picture.apply(self.off),
+            on: picture.apply(self.on),
+            plane2: picture.apply(self.plane2),
+            both: picture.apply(self.both),
+        }
+    }
+
+    /**
+     * Color for a pixel that is set (`true`) or not.
+     */
+    pub fn color(&self, px: bool) -> [u8; 3] {
+        if px {
+            self.on
+        } else {
+            self.off
+        }
+    }
+
+    /**
+     * Color for a pixel by the bitplanes it is set in, bit 0 for the first and bit 1 for
+     * the second.
+     */
+    pub fn plane_color(&self, planes: u8) -> [u8; 3] {
+        match planes & 3 {
+            0 => self.off,
+            1 => self.on,
+            2 => self.plane2,
+            _ => self.both,
+        }
+    }
+}
+
+// selectable palettes, in the order the frontend cycles through them
+pub const PRESETS: [(&str, Palette); 6] = [
+    ("green", Palette::GREEN),
+    ("amber", Palette::AMBER),
+    ("high-contrast", Palette::HIGH_CONTRAST),
+    ("inverted", Palette::INVERTED),
+    ("blue-orange", Palette::BLUE_ORANGE),
+    ("tritan", Palette::TRITAN),
+];
+
+fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let byte = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([byte(0)?, byte(2)?, byte(4)?])
+}
+
+// colors closer than this in every channel count as one, see `dominant_colors`
+const SAME_COLOR: u8 = 48;
+
+/**
+ * The `n` colors covering most of an image, most common first, each with the share of the
+ * pixels close to it. Similar colors are merged, so the blur around pixels in a photo or
+ * a filtered screenshot doesn't push out a color that matters. For a screenshot of a CHIP-8
+ * game the first is the background and the second the pixels that are on.
+ *
+ * ```
+ * use chip8::palette;
+ *
+ * let mut pixels = vec![[0x00, 0x20, 0x00]; 60];
+ * pixels.extend([[0x30, 0xF0, 0x30]; 30]);
+ * // a bit of blur, too close to the pixels to count on its own
+ * pixels.extend([[0x28, 0xE0, 0x28]; 10]);
+ *
+ * let colors = palette::dominant_colors(&pixels, 4);
+ * assert_eq!(colors.len(), 2);
+ * assert_eq!(colors[0], ([0x00, 0x20, 0x00], 0.6));
+ * assert_eq!(colors[1].0, [0x30, 0xF0, 0x30]);
+ * ```
+ */
+pub fn dominant_colors(pixels: &[[u8; 3]], n: usize) -> Vec<([u8; 3], f64)> {
+    // pixel count and channel sums per color, at 4 bits per channel
+    let mut buckets = vec![(0usize, [0usize; 3]); 1 << 12];
+    for px in pixels {
+        let bucket = &mut buckets[(px[0] as usize >> 4) << 8 | (px[1] as usize >> 4) << 4 | px[2] as usize >> 4];
+        bucket.0 += 1;
+        for (sum, channel) in bucket.1.iter_mut().zip(px) {
+            *sum += *channel as usize;
+        }
+    }
+    buckets.retain(|(count, _)| *count > 0);
+    buckets.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+
+    let mut colors: Vec<([u8; 3], usize)> = Vec::new();
+    for (count, sums) in buckets {
+        let color = sums.map(|sum| (sum / count) as u8);
+        match colors.iter_mut().find(|(picked, _)| (0..3).all(|c| picked[c].abs_diff(color[c]) < SAME_COLOR)) {
+            Some((_, total)) => *total += count,
+            None => colors.push((color, count)),
+        }
+    }
+
+    colors.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    colors.truncate(n);
+    colors.into_iter().map(|(color, count)| (color, count as f64 / pixels.len().max(1) as f64)).collect()
+}
+
+/**
+ * Corrections for the display the picture ends up on, applied to the palette's colors:
+ * gamma first, then contrast around middle grey, then brightness. Projectors tend to need
+ * more brightness, TVs with crushed blacks a lower gamma.
+ *
+ * ```
+ * use chip8::palette::Picture;
+ *
+ * let picture = Picture::default();
+ * assert_eq!(picture.apply([0x00, 0x80, 0xFF]), [0x00, 0x80, 0xFF]);
+ *
+ * let brighter = Picture { brightness: 0.25, ..Picture::default() };
+ * assert_eq!(brighter.apply([0x00, 0x80, 0xFF]), [0x40, 0xC0, 0xFF]);
+ *
+ * let flat = Picture { contrast: 0.0, ..Picture::default() };
+ * assert_eq!(flat.apply([0x00, 0xFF, 0x20]), [0x80, 0x80, 0x80]);
+ * ```
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Picture {
+    // added to every channel, -1.0 (black) to 1.0 (white)
+    pub brightness: f32,
+    // scales the distance from middle grey, 1.0 leaves it alone
+    pub contrast: f32,
+    // above 1.0 lifts the dark colors, below darkens them
+    pub gamma: f32,
+}
+
+impl Picture {
+    // what each setting can be set to
+    pub const BRIGHTNESS: RangeInclusive<f32> = -1.0..=1.0;
+    pub const CONTRAST: RangeInclusive<f32> = 0.0..=4.0;
+    pub const GAMMA: RangeInclusive<f32> = 0.1..=10.0;
+
+    pub fn apply(&self, color: [u8; 3]) -> [u8; 3] {
+        color.map(|c| {
+            let v = (c as f32 / 255.0).powf(1.0 / self.gamma);
+            let v = (v - 0.5) * self.contrast + 0.5 + self.brightness;
+            (v.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+    }
+}
+
+impl Default for Picture {
+    fn default() -> Self {
+        Picture { brightness: 0.0, contrast: 1.0, gamma: 1.0 }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::GREEN
+    }
+}
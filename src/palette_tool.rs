@@ -0,0 +1,44 @@
+use std::fs;
+
+use chip8::palette::{self, Palette};
+
+use crate::config::Format;
+use crate::png;
+
+// colors `palette extract` reports, two make the palette and the rest show what was left out
+const REPORTED: usize = 4;
+
+/**
+ * `palette extract`: find the dominant colors of a screenshot or photo of another emulator
+ * or of real hardware, see `palette::dominant_colors`, and turn the two most common into a
+ * palette, background first. It is printed and, with `out`, written as a palette file for
+ * `--palette`.
+ */
+pub fn extract(path: &str, format: Format, out: Option<&str>) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let (_, _, pixels) = png::decode(&data).map_err(|e| format!("{}: {}", path, e))?;
+    let colors = palette::dominant_colors(&pixels, REPORTED);
+    let [(off, _), (on, _), ..] = colors[..] else {
+        return Err(format!("{} has only one color, nothing to tell the pixels apart by", path));
+    };
+    let extracted = Palette::new(off, on);
+
+    let hex = |[r, g, b]: [u8; 3]| format!("#{:02X}{:02X}{:02X}", r, g, b);
+    match format {
+        Format::Text => {
+            for (color, share) in &colors {
+                println!("{}  {:5.1}%", hex(*color), share * 100.0);
+            }
+            print!("\n{}", extracted.to_text());
+        }
+        Format::Json => {
+            let colors: Vec<String> = colors.iter().map(|(color, share)| format!("{{\"color\": \"{}\", \"share\": {:.4}}}", hex(*color), share)).collect();
+            println!("{{\"off\": \"{}\", \"on\": \"{}\", \"colors\": [{}]}}", hex(off), hex(on), colors.join(", "));
+        }
+    }
+
+    match out {
+        Some(out) => fs::write(out, format!("# extracted from {}\n{}", path, extracted.to_text())).map_err(|e| format!("can't write {}: {}", out, e)),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt;
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: usize = 0x454F46; // "EOF"
+const BPS_MAGIC: &[u8] = b"BPS1";
+const BPS_FOOTER_LEN: usize = 12;
+// XO-CHIP's 64 KiB of RAM is the most any ROM can fill, a bigger BPS target is damage
+const MAX_TARGET: usize = 1 << 16;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PatchError {
+    // neither an IPS nor a BPS header
+    UnknownFormat,
+    // patch ended in the middle of a record
+    Truncated,
+    // a record reads or writes outside the ROM, or the result would be too big for one
+    OutOfRange,
+    // BPS checksum of the source ROM, the result or the patch itself didn't match
+    ChecksumMismatch(&'static str),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "not an IPS or BPS patch"),
+            PatchError::Truncated => write!(f, "patch is truncated"),
+            PatchError::OutOfRange => write!(f, "patch refers to data outside the ROM"),
+            PatchError::ChecksumMismatch(what) => write!(f, "{} checksum mismatch", what),
+        }
+    }
+}
+
+/**
+ * Apply an IPS or BPS patch to `rom`, detected from the patch header.
+ *
+ * ```
+ * use chip8::patch;
+ *
+ * // IPS: write 0xAB 0xCD at offset 1
+ * let ips = b"PATCH\x00\x00\x01\x00\x02\xAB\xCDEOF";
+ * assert_eq!(patch::apply(&[0x00, 0x00, 0x00, 0x00], ips).unwrap(), vec![0x00, 0xAB, 0xCD, 0x00]);
+ * ```
+ */
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+/**
+ * IPS: a list of (24 bit offset, 16 bit size, data) records, where a size of 0 marks an
+ * RLE record (16 bit count, 1 byte value). Records may grow the ROM. An optional 24 bit
+ * length after the `EOF` marker truncates the result.
+ */
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut reader = Reader::new(patch, IPS_MAGIC.len());
+    let mut out = rom.to_vec();
+
+    loop {
+        let offset = reader.be(3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+
+        let size = reader.be(2)?;
+        let data = if size == 0 {
+            let count = reader.be(2)?;
+            vec![reader.byte()?; count]
+        } else {
+            reader.bytes(size)?.to_vec()
+        };
+
+        if out.len() < offset + data.len() {
+            out.resize(offset + data.len(), 0);
+        }
+        out[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    if reader.remaining() >= 3 {
+        out.truncate(reader.be(3)?);
+    }
+
+    Ok(out)
+}
+
+/**
+ * BPS: a target size and a list of actions building the target from the source ROM, the
+ * patch itself and already written output, followed by CRC32s of source, target and patch.
+ *
+ * ```
+ * use chip8::patch::{self, PatchError};
+ *
+ * // a BPS patch made of `body` and the checksums of `source`, `target` and itself
+ * let bps = |body: &[u8], source: &[u8], target: &[u8]| {
+ *     let mut patch = b"BPS1".to_vec();
+ *     patch.extend_from_slice(body);
+ *     patch.extend_from_slice(&patch::crc32(source).to_le_bytes());
+ *     patch.extend_from_slice(&patch::crc32(target).to_le_bytes());
+ *     patch.extend_from_slice(&patch::crc32(&patch).to_le_bytes());
+ *     patch
+ * };
+ * let source = [1, 2, 3, 4];
+ * let target = [3, 4, 3, 4, 3, 4, 9];
+ * // sizes 4 and 7, no metadata, then SourceCopy 2 bytes from 2, TargetCopy 4 bytes from 0
+ * // (reading what it writes) and TargetRead a 9
+ * let body = [0x84, 0x87, 0x80, 0x86, 0x84, 0x8F, 0x80, 0x81, 0x09];
+ * let good = bps(&body, &source, &target);
+ * assert_eq!(patch::apply_bps(&source, &good).unwrap(), target);
+ *
+ * // each of the three checksums is checked
+ * assert_eq!(patch::apply_bps(&[1, 2, 3, 5], &good), Err(PatchError::ChecksumMismatch("source ROM")));
+ * let wrong_target = bps(&body, &source, &[0; 7]);
+ * assert_eq!(patch::apply_bps(&source, &wrong_target), Err(PatchError::ChecksumMismatch("patched ROM")));
+ * let mut corrupt = good.clone();
+ * corrupt[8] ^= 1;
+ * assert_eq!(patch::apply_bps(&source, &corrupt), Err(PatchError::ChecksumMismatch("patch")));
+ *
+ * // cut off in the middle of the TargetRead, with checksums that match what is left
+ * let cut = bps(&body[..8], &source, &target);
+ * assert_eq!(patch::apply_bps(&source, &cut), Err(PatchError::Truncated));
+ * assert_eq!(patch::apply_bps(&source, b"BPS1"), Err(PatchError::Truncated));
+ *
+ * // sizes are refused before anything is allocated or copied: a 4 MiB target, and a
+ * // TargetCopy of 100 bytes into a target of 2
+ * let huge = bps(&[0x84, 0x00, 0x00, 0x00, 0x81, 0x80], &source, &[]);
+ * assert_eq!(patch::apply_bps(&source, &huge), Err(PatchError::OutOfRange));
+ * let long = bps(&[0x84, 0x82, 0x80, 0x81, 0x09, 0x0F, 0x82, 0x80], &source, &[9, 9]);
+ * assert_eq!(patch::apply_bps(&source, &long), Err(PatchError::OutOfRange));
+ * ```
+ */
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER_LEN {
+        return Err(PatchError::Truncated);
+    }
+
+    let body_end = patch.len() - BPS_FOOTER_LEN;
+    let footer = &patch[body_end..];
+    let checksum = |n: usize| u32::from_le_bytes([footer[n], footer[n + 1], footer[n + 2], footer[n + 3]]);
+
+    if crc32(&patch[..patch.len() - 4]) != checksum(8) {
+        return Err(PatchError::ChecksumMismatch("patch"));
+    }
+    if crc32(rom) != checksum(0) {
+        return Err(PatchError::ChecksumMismatch("source ROM"));
+    }
+
+    let mut reader = Reader::new(&patch[..body_end], BPS_MAGIC.len());
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+
+    if source_size != rom.len() {
+        return Err(PatchError::ChecksumMismatch("source ROM"));
+    }
+    if target_size > MAX_TARGET {
+        return Err(PatchError::OutOfRange);
+    }
+
+    let mut out: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+
+    while reader.remaining() > 0 {
+        let data = reader.varint()?;
+        let length = (data >> 2) + 1;
+        // `length` is as untrusted as the rest, check it before copying anything
+        if length > target_size - out.len() {
+            return Err(PatchError::OutOfRange);
+        }
+
+        match data & 3 {
+            // SourceRead: copy from the source at the current output position
+            0 => {
+                let start = out.len();
+                let chunk = rom.get(start..start + length).ok_or(PatchError::OutOfRange)?;
+                out.extend_from_slice(chunk);
+            }
+            // TargetRead: copy literal bytes from the patch
+            1 => out.extend_from_slice(reader.bytes(length)?),
+            // SourceCopy: copy from a relative position in the source
+            2 => {
+                source_offset = relative(source_offset, reader.varint()?)?;
+                let chunk = rom
+                    .get(source_offset..)
+                    .and_then(|rest| rest.get(..length))
+                    .ok_or(PatchError::OutOfRange)?;
+                out.extend_from_slice(chunk);
+                source_offset += length;
+            }
+            // TargetCopy: copy from already written output, byte by byte since ranges may overlap
+            _ => {
+                target_offset = relative(target_offset, reader.varint()?)?;
+                for _ in 0..length {
+                    let byte = *out.get(target_offset).ok_or(PatchError::OutOfRange)?;
+                    out.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if out.len() != target_size {
+        return Err(PatchError::Truncated);
+    }
+    if crc32(&out) != checksum(4) {
+        return Err(PatchError::ChecksumMismatch("patched ROM"));
+    }
+
+    Ok(out)
+}
+
+/**
+ * Move `offset` by a BPS signed relative value: bit 0 is the sign, the rest the distance.
+ */
+fn relative(offset: usize, value: usize) -> Result<usize, PatchError> {
+    let distance = value >> 1;
+
+    if value & 1 != 0 {
+        offset.checked_sub(distance).ok_or(PatchError::OutOfRange)
+    } else {
+        offset.checked_add(distance).ok_or(PatchError::OutOfRange)
+    }
+}
+
+/**
+ * CRC-32 (IEEE, reflected), as used by BPS.
+ */
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Reader { data, pos }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], PatchError> {
+        let chunk = self.data.get(self.pos..self.pos + n).ok_or(PatchError::Truncated)?;
+        self.pos += n;
+        Ok(chunk)
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // big endian unsigned integer of `n` bytes
+    fn be(&mut self, n: usize) -> Result<usize, PatchError> {
+        Ok(self.bytes(n)?.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+    }
+
+    // BPS variable length integer
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let mut data: usize = 0;
+        let mut shift: usize = 1;
+
+        loop {
+            let x = self.byte()? as usize;
+            data += (x & 0x7F) * shift;
+            if x & 0x80 != 0 {
+                return Ok(data);
+            }
+            if shift > usize::MAX >> 14 {
+                return Err(PatchError::OutOfRange);
+            }
+            shift <<= 7;
+            data += shift;
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "rust8";
+
+/**
+ * Directory for settings the user edits, like the per-ROM settings:
+ * `$XDG_CONFIG_HOME/rust8` (`~/.config/rust8`), `%APPDATA%\rust8` or
+ * `~/Library/Application Support/rust8`.
+ */
+pub fn config_dir() -> Option<PathBuf> {
+    platform_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/**
+ * Directory for files the emulator writes, like saves and macros: `$XDG_DATA_HOME/rust8`
+ * (`~/.local/share/rust8`), `%APPDATA%\rust8` or `~/Library/Application Support/rust8`.
+ */
+pub fn data_dir() -> Option<PathBuf> {
+    platform_dir("XDG_DATA_HOME", ".local/share")
+}
+
+fn platform_dir(xdg_var: &str, xdg_default: &str) -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| Path::new(&home).join("Library/Application Support"))
+    } else {
+        env::var_os(xdg_var)
+            .filter(|dir| Path::new(dir).is_absolute())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(xdg_default)))
+    };
+
+    base.map(|dir| dir.join(APP_DIR))
+}
+
+/**
+ * Default location of `name` under `dir`. A `name` in the working directory wins, so
+ * portable setups and files from before the platform directories were used keep working,
+ * and so does a missing home directory.
+ */
+pub fn locate(dir: Option<PathBuf>, name: &str) -> String {
+    match dir {
+        Some(dir) if !Path::new(name).exists() => dir.join(name).to_string_lossy().into_owned(),
+        _ => name.to_string(),
+    }
+}
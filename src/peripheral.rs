@@ -0,0 +1,88 @@
+//! Memory-mapped peripherals, for prototyping hardware the original machine never had.
+//!
+//! A peripheral claims an address range with `Chip8::map_peripheral`. From then on the
+//! program's data reads and writes in that range are handed to the peripheral instead of RAM.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//!
+//! use chip8::machine::Machine;
+//! use chip8::peripheral::Peripheral;
+//!
+//! // a write-only serial port, everything written to it ends up in `out`
+//! struct Serial {
+//!     out: Arc<Mutex<Vec<u8>>>,
+//! }
+//!
+//! impl Peripheral for Serial {
+//!     fn read(&mut self, _offset: usize) -> u8 {
+//!         0
+//!     }
+//!
+//!     fn write(&mut self, _offset: usize, value: u8) {
+//!         self.out.lock().unwrap().push(value);
+//!     }
+//! }
+//!
+//! let out = Arc::new(Mutex::new(Vec::new()));
+//! let mut machine = Machine::new();
+//! machine.chip8.map_peripheral(0xF00..0xF01, Box::new(Serial { out: out.clone() })).unwrap();
+//!
+//! // V0 = 'A', I = 0xF00, store V0
+//! machine.load_rom(&[0x60, 0x41, 0xAF, 0x00, 0xF0, 0x55, 0x00, 0xFD]);
+//! machine.run_frame().unwrap();
+//!
+//! assert_eq!(*out.lock().unwrap(), b"A");
+//! assert_eq!(machine.chip8.get_ram()[0xF00], 0);
+//! ```
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// where the frontend maps `Rtc` when extensions are enabled
+pub const RTC_ADDR: usize = 0xFF0;
+pub const RTC_LEN: usize = 3;
+
+/**
+ * A device living in the CHIP-8 address space. Offsets are relative to the start of the
+ * range it was mapped at, so the same peripheral can be mapped anywhere.
+ */
+pub trait Peripheral: Send {
+    fn read(&mut self, offset: usize) -> u8;
+    fn write(&mut self, offset: usize, value: u8);
+}
+
+/**
+ * Real-time clock: reading offset 0, 1 and 2 gives the host's current hours, minutes and
+ * seconds (UTC) as plain binary values. Writes are ignored.
+ *
+ * ```
+ * use chip8::machine::Machine;
+ * use chip8::peripheral::{Rtc, RTC_ADDR, RTC_LEN};
+ *
+ * let mut machine = Machine::new();
+ * machine.chip8.map_peripheral(RTC_ADDR..RTC_ADDR + RTC_LEN, Box::new(Rtc)).unwrap();
+ *
+ * // I = 0xFF0, load V0-V2
+ * machine.load_rom(&[0xAF, 0xF0, 0xF2, 0x65, 0x00, 0xFD]);
+ * machine.run_frame().unwrap();
+ *
+ * let [h, m, s] = [machine.chip8.v[0], machine.chip8.v[1], machine.chip8.v[2]];
+ * assert!(h < 24 && m < 60 && s < 60);
+ * ```
+ */
+pub struct Rtc;
+
+impl Peripheral for Rtc {
+    fn read(&mut self, offset: usize) -> u8 {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+
+        match offset {
+            0 => (secs / 3600 % 24) as u8,
+            1 => (secs / 60 % 60) as u8,
+            2 => (secs % 60) as u8,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, _offset: usize, _value: u8) {}
+}
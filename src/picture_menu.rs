@@ -0,0 +1,64 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use chip8::palette::Picture;
+
+use crate::controllers::MenuInput;
+use crate::i18n::Lang;
+
+// message ids of the settings, in menu order, with how much Left and Right change them
+const SETTINGS: [(&str, f32); 3] = [("menu.brightness", 0.05), ("menu.contrast", 0.1), ("menu.gamma", 0.1)];
+
+/**
+ * Tunes the `Picture` while the game keeps running, so the effect can be judged on the
+ * display at hand. Up and Down pick a setting, Left and Right change it, Backspace puts it
+ * back to normal, Escape or Enter close the menu.
+ */
+#[derive(Default)]
+pub struct PictureMenu {
+    selected: usize,
+}
+
+impl PictureMenu {
+    pub fn handle(&mut self, event: &Event, picture: &mut Picture) -> MenuInput {
+        let Event::KeyDown { keycode: Some(keycode), .. } = event else {
+            return MenuInput::Open;
+        };
+        let (setting, range) = match self.selected {
+            0 => (&mut picture.brightness, Picture::BRIGHTNESS),
+            1 => (&mut picture.contrast, Picture::CONTRAST),
+            _ => (&mut picture.gamma, Picture::GAMMA),
+        };
+        let step = SETTINGS[self.selected].1;
+        match keycode {
+            Keycode::Escape | Keycode::Return | Keycode::KpEnter => return MenuInput::Close,
+            Keycode::Up => self.selected = self.selected.saturating_sub(1),
+            Keycode::Down => self.selected = (self.selected + 1).min(SETTINGS.len() - 1),
+            // rounded to the step, so going back and forth ends up where it started
+            Keycode::Left => *setting = ((*setting - step) / step).round() * step,
+            Keycode::Right => *setting = ((*setting + step) / step).round() * step,
+            Keycode::Backspace => {
+                let normal = Picture::default();
+                *setting = [normal.brightness, normal.contrast, normal.gamma][self.selected];
+            }
+            _ => {}
+        }
+        *setting = setting.clamp(*range.start(), *range.end());
+        MenuInput::Open
+    }
+
+    /**
+     * The menu as overlay text.
+     */
+    pub fn text(&self, picture: &Picture, lang: Lang) -> String {
+        let mut out = lang.tr("menu.picture").to_string();
+        let values = [format!("{:+.2}", picture.brightness), format!("{:.2}", picture.contrast), format!("{:.2}", picture.gamma)];
+        for (n, ((name, _), value)) in SETTINGS.iter().zip(values).enumerate() {
+            let marker = if n == self.selected { '>' } else { ' ' };
+            out.push_str(&format!("\n{} {:<12} {:>6}", marker, lang.tr(name), value));
+        }
+        out.push('\n');
+        out.push_str(lang.tr("menu.picture_keys"));
+        out
+    }
+}
@@ -0,0 +1,108 @@
+//! The machines CHIP-8 programs were written for, each a combination of the core's settings
+//! so a program can be run the way its author saw it without picking them one by one:
+//!
+//! ```text
+//! vip     COSMAC VIP: 64x32 only, the original instructions (`strict`), the vblank, logic
+//!         and clip quirks and the VIP's own font
+//! schip   SUPER-CHIP 1.1: 64x32 and 128x64, the shift, loadstore, jump and clip quirks
+//! xochip  XO-CHIP as Octo runs it: 64 KiB of RAM, two bitplanes, no quirks
+//! ```
+//!
+//! ```
+//! use chip8::font::VIP_FONT_SET;
+//! use chip8::machine::Machine;
+//! use chip8::platform::Platform;
+//! use chip8::quirks::Quirks;
+//!
+//! let mut machine = Machine::new();
+//! Platform::by_name("vip").unwrap().apply(&mut machine.chip8).unwrap();
+//! assert!(machine.chip8.strict && !machine.chip8.xo_chip());
+//! assert_eq!(machine.chip8.quirks, Quirks::VIP);
+//! assert_eq!(machine.chip8.get_ram()[0x50..0x50 + 80], VIP_FONT_SET);
+//!
+//! Platform::XoChip.apply(&mut machine.chip8).unwrap();
+//! assert!(!machine.chip8.strict && machine.chip8.xo_chip());
+//! assert!(!machine.chip8.quirks.clip);
+//! ```
+
+use crate::font::{FONT_SET, VIP_FONT_SET};
+use crate::hardware::{Chip8, Error};
+use crate::quirks::Quirks;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Platform {
+    Vip,
+    Schip,
+    XoChip,
+}
+
+pub const PLATFORMS: [(&str, Platform); 3] = [("vip", Platform::Vip), ("schip", Platform::Schip), ("xochip", Platform::XoChip)];
+
+impl Platform {
+    pub fn by_name(name: &str) -> Result<Platform, String> {
+        PLATFORMS.iter().find(|(n, _)| *n == name).map(|(_, p)| *p).ok_or_else(|| {
+            let names: Vec<&str> = PLATFORMS.iter().map(|(n, _)| *n).collect();
+            format!("unknown platform '{}', expected one of: {}", name, names.join(", "))
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        PLATFORMS.iter().find(|(_, p)| *p == self).map_or("", |(n, _)| *n)
+    }
+
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::Vip => Quirks::VIP,
+            Platform::Schip => Quirks::SCHIP,
+            Platform::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    /**
+     * Only the instructions of 1977, see `Chip8::strict`. This is what keeps the VIP at
+     * 64x32, `00FF` faults.
+     */
+    pub fn strict(self) -> bool {
+        self == Platform::Vip
+    }
+
+    pub fn xo_chip(self) -> bool {
+        self == Platform::XoChip
+    }
+
+    pub fn font_set(self) -> &'static [u8; 80] {
+        match self {
+            Platform::Vip => &VIP_FONT_SET,
+            Platform::Schip | Platform::XoChip => &FONT_SET,
+        }
+    }
+
+    /**
+     * The platform whose quirks come after `quirks` in `PLATFORMS`, the first one if they
+     * aren't any platform's. For switching a running program to the next set of quirks.
+     *
+     * ```
+     * use chip8::platform::Platform;
+     * use chip8::quirks::Quirks;
+     *
+     * assert_eq!(Platform::after(Quirks::VIP), Platform::Schip);
+     * assert_eq!(Platform::after(Quirks::XO_CHIP), Platform::Vip);
+     * assert_eq!(Platform::after(Quirks::default()), Platform::Vip);
+     * ```
+     */
+    pub fn after(quirks: Quirks) -> Platform {
+        let current = PLATFORMS.iter().position(|(_, p)| p.quirks() == quirks);
+        PLATFORMS[current.map_or(0, |n| (n + 1) % PLATFORMS.len())].1
+    }
+
+    /**
+     * Set `chip8` up as this platform and load its fonts, before the program is loaded.
+     */
+    pub fn apply(self, chip8: &mut Chip8) -> Result<(), Error> {
+        chip8.strict = self.strict();
+        chip8.set_xo_chip(self.xo_chip());
+        chip8.quirks = self.quirks();
+        chip8.font_set = self.font_set();
+        chip8.load_fonts()
+    }
+}
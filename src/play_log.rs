@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chip8::patch;
+
+use crate::log_file;
+
+pub const PLAY_LOG: &str = "play_time.txt";
+
+/**
+ * How much a ROM has been played in the window, over all runs.
+ */
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Record {
+    pub launches: u64,
+    pub played: Duration,
+    // seconds since the Unix epoch
+    pub last_played: u64,
+}
+
+/**
+ * Play time of every ROM, keyed by checksum like the save files. Stored as text, one ROM
+ * per line with its launches, seconds played and when it was last started:
+ *
+ * ```text
+ * 0a1b2c3d 12 3725 1714564800
+ * ```
+ */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PlayLog {
+    pub records: BTreeMap<u32, Record>,
+}
+
+impl PlayLog {
+    pub fn parse(text: &str) -> Result<PlayLog, String> {
+        let mut log = PlayLog::default();
+
+        for (n, line) in text.lines().enumerate() {
+            match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [] => {}
+                [crc, launches, played, last] => {
+                    let invalid = || format!("line {}: invalid entry '{}'", n + 1, line.trim());
+                    let record = Record {
+                        launches: launches.parse().map_err(|_| invalid())?,
+                        played: Duration::from_secs(played.parse().map_err(|_| invalid())?),
+                        last_played: last.parse().map_err(|_| invalid())?,
+                    };
+                    log.records.insert(u32::from_str_radix(crc, 16).map_err(|_| invalid())?, record);
+                }
+                _ => return Err(format!("line {}: invalid entry '{}'", n + 1, line.trim())),
+            }
+        }
+
+        Ok(log)
+    }
+
+    pub fn to_text(&self) -> String {
+        self.records
+            .iter()
+            .map(|(crc, r)| format!("{:08x} {} {} {}\n", crc, r.launches, r.played.as_secs(), r.last_played))
+            .collect()
+    }
+
+    /**
+     * The log saved at `path`, empty if there is none yet.
+     */
+    pub fn load(path: &str) -> Result<PlayLog, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => PlayLog::parse(&text).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(PlayLog::default()),
+            Err(e) => Err(format!("can't read {}: {}", path, e)),
+        }
+    }
+
+    pub fn store(&self, path: &str) -> Result<(), String> {
+        let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir)
+            .and_then(|_| fs::write(path, self.to_text()))
+            .map_err(|e| format!("can't write {}: {}", path, e))
+    }
+
+    /**
+     * Count a new run of `rom`, starting now. Returns its record so far.
+     */
+    pub fn launch(&mut self, rom: &[u8]) -> Record {
+        let record = self.records.entry(patch::crc32(rom)).or_default();
+        record.launches += 1;
+        record.last_played = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        *record
+    }
+
+    pub fn add_time(&mut self, rom: &[u8], played: Duration) {
+        self.records.entry(patch::crc32(rom)).or_default().played += played;
+    }
+
+    /**
+     * A table of all ROMs, the most recently played first.
+     */
+    pub fn report(&self) -> String {
+        let mut records: Vec<(&u32, &Record)> = self.records.iter().collect();
+        records.sort_by_key(|(_, r)| std::cmp::Reverse(r.last_played));
+
+        let mut out = format!("{:<8}  {:>8}  {:>9}  {}\n", "crc32", "launches", "played", "last played");
+        for (crc, r) in records {
+            out.push_str(&format!("{:08x}  {:>8}  {:>9}  {}\n", crc, r.launches, hours(r.played), log_file::format_time(r.last_played)));
+        }
+        out
+    }
+}
+
+/**
+ * A duration as `h:mm:ss`.
+ */
+pub fn hours(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
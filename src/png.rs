@@ -0,0 +1,293 @@
+use chip8::patch::crc32;
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+// largest stored (uncompressed) deflate block
+const MAX_BLOCK: usize = 0xFFFF;
+
+/**
+ * Encode RGBA pixels (4 bytes per pixel, row-major) as a PNG. The image data is stored
+ * uncompressed, which is plenty for CHIP-8 sized screens and needs no deflate implementation.
+ * `text` is stored as `tEXt` metadata, keyword and value, which image viewers can show.
+ */
+pub fn encode(width: usize, height: usize, rgba: &[u8], text: &[(&str, &str)]) -> Vec<u8> {
+    // every row starts with its filter type, 0 for none
+    let mut raw = Vec::with_capacity(height * (width * 4 + 1));
+    for row in rgba.chunks(width * 4).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // zlib stream: header, stored deflate blocks, Adler-32 of the raw data
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(MAX_BLOCK).collect();
+    for (n, block) in blocks.iter().enumerate() {
+        zlib.push((n + 1 == blocks.len()) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, default compression, filtering and no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut out = SIGNATURE.to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    for (keyword, value) in text {
+        chunk(&mut out, b"tEXt", &[keyword.as_bytes(), &[0], value.as_bytes()].concat());
+    }
+    chunk(&mut out, b"IDAT", &zlib);
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/**
+ * Decode a PNG into its width, height and RGB pixels, row-major; alpha is dropped.
+ * Handles what screenshots are saved as: 8 bits per channel, grey, RGB or a palette, with
+ * or without alpha, not interlaced.
+ */
+pub fn decode(data: &[u8]) -> Result<(usize, usize, Vec<[u8; 3]>), String> {
+    let mut rest = data.strip_prefix(SIGNATURE).ok_or("not a PNG file")?;
+    let (mut header, mut palette, mut zlib) = (None, Vec::new(), Vec::new());
+
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let body = rest.get(8..8 + len).ok_or("truncated PNG chunk")?;
+        match &rest[4..8] {
+            b"IHDR" => header = Some(body),
+            b"PLTE" => palette = body.chunks(3).map(|c| [c[0], c[1], c.get(2).copied().unwrap_or(0)]).collect(),
+            b"IDAT" => zlib.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = rest.get(12 + len..).unwrap_or_default();
+    }
+
+    let header = header.filter(|h| h.len() == 13).ok_or("PNG without a header")?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+    let channels = match color_type {
+        0 => 1,
+        2 => 3,
+        3 => 1,
+        4 => 2,
+        6 => 4,
+        _ => return Err(format!("unknown PNG color type {}", color_type)),
+    };
+    if depth != 8 || interlace != 0 {
+        return Err(format!("unsupported PNG: {} bits per channel{}, only 8 without interlacing", depth, if interlace != 0 { ", interlaced" } else { "" }));
+    }
+
+    // zlib header, then deflate; the Adler-32 at the end isn't checked
+    let raw = inflate(zlib.get(2..).ok_or("PNG without image data")?)?;
+    let stride = width * channels;
+    if raw.len() < height * (stride + 1) {
+        return Err("PNG image data is too short".to_string());
+    }
+
+    let mut pixels = Vec::with_capacity(width * height);
+    let mut prior = vec![0u8; stride];
+    for row in raw.chunks(stride + 1).take(height) {
+        let mut line = row[1..].to_vec();
+        unfilter(row[0], &mut line, &prior, channels)?;
+        for px in line.chunks(channels) {
+            pixels.push(match color_type {
+                0 | 4 => [px[0]; 3],
+                3 => *palette.get(px[0] as usize).ok_or("PNG palette index out of range")?,
+                _ => [px[0], px[1], px[2]],
+            });
+        }
+        prior = line;
+    }
+
+    Ok((width, height, pixels))
+}
+
+// undo a row's filter in place, `prior` is the unfiltered row above
+fn unfilter(filter: u8, line: &mut [u8], prior: &[u8], bpp: usize) -> Result<(), String> {
+    for i in 0..line.len() {
+        let left = if i >= bpp { line[i - bpp] } else { 0 };
+        let up = prior[i];
+        let up_left = if i >= bpp { prior[i - bpp] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => {
+                let p = left as i16 + up as i16 - up_left as i16;
+                let (pa, pb, pc) = ((p - left as i16).abs(), (p - up as i16).abs(), (p - up_left as i16).abs());
+                if pa <= pb && pa <= pc {
+                    left
+                } else if pb <= pc {
+                    up
+                } else {
+                    up_left
+                }
+            }
+            _ => return Err(format!("unknown PNG filter {}", filter)),
+        };
+        line[i] = line[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+// base lengths and distances of the deflate length and distance codes, with their extra bits
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// order the code length code lengths of a dynamic block come in
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct Bits<'a> {
+    data: &'a [u8],
+    // position in bits, least significant bit of each byte first
+    pos: usize,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, n: u8) -> Result<usize, String> {
+        let mut value = 0;
+        for i in 0..n {
+            let byte = self.data.get(self.pos / 8).ok_or("PNG image data ends early")?;
+            value |= ((byte >> (self.pos % 8)) as usize & 1) << i;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+}
+
+/**
+ * Canonical Huffman code, decoded a bit at a time: codes of each length in turn, the
+ * symbols sorted by code.
+ */
+struct Huffman {
+    counts: [usize; 16],
+    symbols: Vec<usize>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<usize> = (0..lengths.len()).filter(|s| lengths[*s] > 0).collect();
+        symbols.sort_by_key(|s| lengths[*s]);
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<usize, String> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for len in 1..16 {
+            code |= bits.take(1)?;
+            let count = self.counts[len];
+            if code < first + count {
+                return self.symbols.get(index + code - first).copied().ok_or_else(|| "invalid PNG image data".to_string());
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid PNG image data".to_string())
+    }
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut bits = Bits { data, pos: 0 };
+    let mut out = Vec::new();
+
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => {
+                let start = bits.pos.div_ceil(8);
+                let len = data.get(start..start + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize).ok_or("PNG image data ends early")?;
+                out.extend_from_slice(data.get(start + 4..start + 4 + len).ok_or("PNG image data ends early")?);
+                bits.pos = (start + 4 + len) * 8;
+            }
+            kind @ (1 | 2) => {
+                let (lengths, distances) = if kind == 1 { fixed_codes() } else { dynamic_codes(&mut bits)? };
+                loop {
+                    let symbol = lengths.decode(&mut bits)?;
+                    match symbol {
+                        0..=255 => out.push(symbol as u8),
+                        256 => break,
+                        _ => {
+                            let n = symbol - 257;
+                            let len = *LENGTH_BASE.get(n).ok_or("invalid PNG image data")? as usize + bits.take(LENGTH_EXTRA[n])?;
+                            let d = distances.decode(&mut bits)?;
+                            let dist = *DIST_BASE.get(d).ok_or("invalid PNG image data")? as usize + bits.take(DIST_EXTRA[d])?;
+                            let from = out.len().checked_sub(dist).ok_or("invalid PNG image data")?;
+                            // byte by byte, the copy may overlap what it writes
+                            for i in 0..len {
+                                out.push(out[from + i]);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => return Err("invalid PNG image data".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let (literals, distances, code_lengths) = (bits.take(5)? + 257, bits.take(5)? + 1, bits.take(4)? + 4);
+    let mut order = [0; 19];
+    for &n in &CODE_LENGTH_ORDER[..code_lengths] {
+        order[n] = bits.take(3)? as u8;
+    }
+    let code = Huffman::new(&order);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code.decode(bits)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => (*lengths.last().ok_or("invalid PNG image data")?, 3 + bits.take(2)?),
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat));
+    }
+    if lengths.len() > literals + distances {
+        return Err("invalid PNG image data".to_string());
+    }
+    Ok((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
@@ -0,0 +1,121 @@
+//! Where CHIP-8 interpreters of different eras disagree. The VIP's interpreter, CHIP-48 and
+//! SUPER-CHIP on the HP 48 and the modern ones each run some instructions their own way, and
+//! programs written for one can misbehave on another. `Chip8::quirks` picks the behavior:
+//!
+//! ```text
+//! shift      8XY6/8XYE shift VX in place, ignoring VY (CHIP-48, SUPER-CHIP)
+//! loadstore  FX55/FX65 leave I alone instead of adding X + 1 to it (SUPER-CHIP)
+//! jump       BNNN jumps to XNN + VX rather than NNN + V0, SUPER-CHIP's BXNN
+//! vblank     DXYN waits for the display, the frame ends after it (VIP), see `Machine::step`
+//! logic      8XY1/8XY2/8XY3 clear VF (VIP)
+//! clip       sprites are cut off at the screen edge instead of wrapping around (VIP, SUPER-CHIP)
+//! ```
+//!
+//! The names are the ones Octo uses on a `quirks:` line. By default only `clip` is on.
+//!
+//! ```
+//! use chip8::machine::Machine;
+//! use chip8::quirks::Quirks;
+//!
+//! // V1 = 6, V2 = 0x81, V1 = V2 >> 1
+//! let rom = [0x61, 0x06, 0x62, 0x81, 0x81, 0x26];
+//! let run = |quirks: Quirks| {
+//!     let mut machine = Machine::new();
+//!     machine.chip8.quirks = quirks;
+//!     machine.load_rom(&rom);
+//!     (0..3).for_each(|_| machine.chip8.cycle().unwrap());
+//!     machine.chip8.v[1]
+//! };
+//!
+//! assert_eq!(run(Quirks::default()), 0x40);
+//! assert_eq!(run(Quirks::parse("shift,clip").unwrap()), 3);
+//! ```
+
+use std::fmt;
+
+// names of the quirks, for `--quirks` and Octo's options
+pub const NAMES: [&str; 6] = ["shift", "loadstore", "jump", "vblank", "logic", "clip"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Quirks {
+    pub shift: bool,
+    pub load_store: bool,
+    pub jump: bool,
+    pub vblank: bool,
+    pub logic: bool,
+    pub clip: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks { shift: false, load_store: false, jump: false, vblank: false, logic: false, clip: true }
+    }
+}
+
+impl Quirks {
+    // how the COSMAC VIP's interpreter behaved, for `--strict`
+    pub const VIP: Quirks = Quirks { shift: false, load_store: false, jump: false, vblank: true, logic: true, clip: true };
+    // SUPER-CHIP 1.1 on the HP 48
+    pub const SCHIP: Quirks = Quirks { shift: true, load_store: true, jump: true, vblank: false, logic: false, clip: true };
+    // XO-CHIP as Octo runs it, sprites wrap around
+    pub const XO_CHIP: Quirks = Quirks { shift: false, load_store: false, jump: false, vblank: false, logic: false, clip: false };
+
+    /**
+     * A comma-separated list of the quirks that are on, `none` for none of them.
+     *
+     * ```
+     * use chip8::quirks::Quirks;
+     *
+     * let quirks = Quirks::parse("shift, loadstore").unwrap();
+     * assert!(quirks.shift && quirks.load_store && !quirks.clip);
+     * assert_eq!(quirks.to_string(), "shift,loadstore");
+     * assert_eq!(Quirks::parse("none").unwrap().to_string(), "none");
+     * assert!(Quirks::parse("wobble").is_err());
+     * ```
+     */
+    pub fn parse(list: &str) -> Result<Quirks, String> {
+        let mut quirks = Quirks { clip: false, ..Quirks::default() };
+        if list.trim() == "none" {
+            return Ok(quirks);
+        }
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            quirks.set(name, true)?;
+        }
+        Ok(quirks)
+    }
+
+    /**
+     * Turn the quirk called `name` on or off.
+     */
+    pub fn set(&mut self, name: &str, on: bool) -> Result<(), String> {
+        *self.field(name).ok_or_else(|| format!("unknown quirk '{}', expected one of {}", name, NAMES.join(", ")))? = on;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        let mut copy = *self;
+        copy.field(name).map(|on| *on)
+    }
+
+    fn field(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "shift" => Some(&mut self.shift),
+            "loadstore" => Some(&mut self.load_store),
+            "jump" => Some(&mut self.jump),
+            "vblank" => Some(&mut self.vblank),
+            "logic" => Some(&mut self.logic),
+            "clip" => Some(&mut self.clip),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let on: Vec<&str> = NAMES.iter().copied().filter(|name| self.get(name) == Some(true)).collect();
+        match on.is_empty() {
+            true => write!(f, "none"),
+            false => write!(f, "{}", on.join(",")),
+        }
+    }
+}
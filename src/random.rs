@@ -0,0 +1,197 @@
+//! Where `CXNN` gets its random numbers from.
+//!
+//! The core asks its `Chip8::random` for a byte and masks it with `NN`. Most programs only
+//! need something that looks random, but a few lean on the generator of the interpreter they
+//! were written for, so the algorithm can be swapped, or replaced with one of your own:
+//!
+//! ```
+//! use chip8::machine::Machine;
+//! use chip8::random::Random;
+//!
+//! // always the same "random" number, for a test that wants a known layout
+//! struct Fixed(u8);
+//!
+//! impl Random for Fixed {
+//!     fn next(&mut self, _ram: &[u8], _ticks: u8) -> u8 {
+//!         self.0
+//!     }
+//!
+//!     fn seed(&mut self, _seed: u64) {}
+//! }
+//!
+//! let mut machine = Machine::new();
+//! machine.chip8.random = Box::new(Fixed(0xA5));
+//! // V0 = random & 0x0F, loop forever
+//! machine.load_rom(&[0xC0, 0x0F, 0x12, 0x02]);
+//! machine.run_frame().unwrap();
+//!
+//! assert_eq!(machine.chip8.v[0], 0x05);
+//! ```
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/**
+ * A source of random bytes for `CXNN`. `ram` is the whole memory and `ticks` counts the
+ * timer ticks (frames) so far, wrapping, for generators tied to the machine they ran on.
+ */
+pub trait Random: Send {
+    fn next(&mut self, ram: &[u8], ticks: u8) -> u8;
+    /**
+     * Start over from `seed`: the same seed gives the same sequence.
+     */
+    fn seed(&mut self, seed: u64);
+}
+
+/**
+ * The generators that come with the core.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Algorithm {
+    Host,
+    Xorshift,
+    Vip,
+}
+
+// names accepted by --rng
+pub const ALGORITHMS: [(&str, Algorithm); 3] = [("host", Algorithm::Host), ("xorshift", Algorithm::Xorshift), ("vip", Algorithm::Vip)];
+
+impl Algorithm {
+    pub fn by_name(name: &str) -> Option<Algorithm> {
+        ALGORITHMS.iter().find(|(n, _)| *n == name).map(|(_, a)| *a)
+    }
+
+    /**
+     * A generator running this algorithm, seeded from the OS.
+     */
+    pub fn create(self) -> Box<dyn Random> {
+        match self {
+            Algorithm::Host => Box::new(Host::new()),
+            Algorithm::Xorshift => Box::new(Xorshift::new()),
+            Algorithm::Vip => Box::new(Vip::new()),
+        }
+    }
+}
+
+/**
+ * The host's generator, evenly spread and unpredictable. The default.
+ */
+pub struct Host(StdRng);
+
+impl Host {
+    pub fn new() -> Self {
+        Host(StdRng::from_entropy())
+    }
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        Host::new()
+    }
+}
+
+impl Random for Host {
+    fn next(&mut self, _ram: &[u8], _ticks: u8) -> u8 {
+        self.0.gen_range(0..=255)
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.0 = StdRng::seed_from_u64(seed);
+    }
+}
+
+/**
+ * 16-bit xorshift (7, 9, 8), the cheap generator many later interpreters use. It never
+ * produces a zero state, and the low byte of each state is the number.
+ *
+ * ```
+ * use chip8::random::{Random, Xorshift};
+ *
+ * let mut a = Xorshift::new();
+ * let mut b = Xorshift::new();
+ * a.seed(1);
+ * b.seed(1);
+ * let ram = [0; 16];
+ * let first: Vec<u8> = (0..8).map(|_| a.next(&ram, 0)).collect();
+ * assert_eq!(first, (0..8).map(|_| b.next(&ram, 0)).collect::<Vec<u8>>());
+ * ```
+ */
+pub struct Xorshift(u16);
+
+impl Xorshift {
+    pub fn new() -> Self {
+        Xorshift(rand::random::<u16>().max(1))
+    }
+}
+
+impl Default for Xorshift {
+    fn default() -> Self {
+        Xorshift::new()
+    }
+}
+
+impl Random for Xorshift {
+    fn next(&mut self, _ram: &[u8], _ticks: u8) -> u8 {
+        let mut x = self.0;
+        x ^= x << 7;
+        x ^= x >> 9;
+        x ^= x << 8;
+        self.0 = x;
+        x as u8
+    }
+
+    fn seed(&mut self, seed: u64) {
+        // fold the seed down, 0 would stay 0 forever
+        self.0 = ((seed ^ seed >> 16 ^ seed >> 32 ^ seed >> 48) as u16).max(1);
+    }
+}
+
+/**
+ * After the COSMAC VIP interpreter's generator: it walks a pointer through the interpreter's
+ * own page of memory, adds the byte found there to the last number, and mixes in the count
+ * the display interrupt keeps, so what a program gets depends on which frame it asks in.
+ * The page here is 0x000-0x0FF with the fonts rather than the VIP's code, so the numbers
+ * behave alike without being the same values. Like the original, two numbers drawn in the
+ * same frame are far from independent.
+ *
+ * ```
+ * use chip8::random::{Random, Vip};
+ *
+ * let mut vip = Vip::new();
+ * vip.seed(0);
+ * let ram = [0; 256];
+ * // nothing to pick up from memory, so only the tick count moves the numbers
+ * assert_eq!((vip.next(&ram, 3), vip.next(&ram, 3), vip.next(&ram, 5)), (3, 6, 11));
+ * ```
+ */
+pub struct Vip {
+    // where in the page the next byte comes from
+    pointer: u8,
+    last: u8,
+}
+
+impl Vip {
+    pub fn new() -> Self {
+        Vip { pointer: rand::random(), last: rand::random() }
+    }
+}
+
+impl Default for Vip {
+    fn default() -> Self {
+        Vip::new()
+    }
+}
+
+impl Random for Vip {
+    fn next(&mut self, ram: &[u8], ticks: u8) -> u8 {
+        self.pointer = self.pointer.wrapping_add(1);
+        let byte = ram.get(self.pointer as usize).copied().unwrap_or(0);
+        self.last = self.last.wrapping_add(byte).wrapping_add(ticks);
+        self.last
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.pointer = seed as u8;
+        self.last = (seed >> 8) as u8;
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+pub const RECENT_FILE: &str = "recent.txt";
+
+// ROMs remembered besides the favorites
+const MAX_RECENT: usize = 10;
+
+/**
+ * ROM files started with `run`, the most recent first, and the ones marked as favorites.
+ * Stored as text, one ROM per line:
+ *
+ * ```text
+ * favorite /home/me/roms/pong.ch8
+ * recent /home/me/roms/tetris.ch8
+ * recent /home/me/roms/pong.ch8
+ * ```
+ */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Recent {
+    pub favorites: Vec<String>,
+    pub recent: Vec<String>,
+}
+
+impl Recent {
+    pub fn parse(text: &str) -> Result<Recent, String> {
+        let mut list = Recent::default();
+
+        for (n, line) in text.lines().enumerate() {
+            match line.trim().split_once(' ') {
+                None if line.trim().is_empty() => {}
+                Some(("favorite", path)) => list.favorites.push(path.to_string()),
+                Some(("recent", path)) => list.recent.push(path.to_string()),
+                _ => return Err(format!("line {}: unknown entry '{}'", n + 1, line.trim())),
+            }
+        }
+
+        Ok(list)
+    }
+
+    pub fn to_text(&self) -> String {
+        let favorites = self.favorites.iter().map(|path| format!("favorite {}\n", path));
+        favorites.chain(self.recent.iter().map(|path| format!("recent {}\n", path))).collect()
+    }
+
+    /**
+     * The list saved at `path`, empty if there is none yet.
+     */
+    pub fn load(path: &str) -> Result<Recent, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Recent::parse(&text).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Recent::default()),
+            Err(e) => Err(format!("can't read {}: {}", path, e)),
+        }
+    }
+
+    pub fn store(&self, path: &str) -> Result<(), String> {
+        let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir)
+            .and_then(|_| fs::write(path, self.to_text()))
+            .map_err(|e| format!("can't write {}: {}", path, e))
+    }
+
+    /**
+     * Put `rom` at the top of the recent files.
+     */
+    pub fn add(&mut self, rom: &str) {
+        self.recent.retain(|path| path != rom);
+        self.recent.insert(0, rom.to_string());
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /**
+     * Mark `rom` as a favorite, or unmark it if it is one. Returns whether it is one now.
+     */
+    pub fn toggle_favorite(&mut self, rom: &str) -> bool {
+        let was = self.favorites.iter().any(|path| path == rom);
+        if was {
+            self.favorites.retain(|path| path != rom);
+        } else {
+            self.favorites.push(rom.to_string());
+        }
+        !was
+    }
+
+    /**
+     * The ROM started last, for `run --last`.
+     */
+    pub fn last(&self) -> Option<&str> {
+        self.recent.first().map(String::as_str)
+    }
+
+    /**
+     * Favorites first, then the recent files that aren't favorites, with a `*` for favorites
+     * and the number `run` can be given instead of the path.
+     */
+    pub fn menu(&self) -> String {
+        let recent = self.recent.iter().filter(|path| !self.favorites.contains(path));
+        self.favorites
+            .iter()
+            .map(|path| (true, path))
+            .chain(recent.map(|path| (false, path)))
+            .enumerate()
+            .map(|(n, (favorite, path))| format!("{:>2} {} {}\n", n + 1, if favorite { '*' } else { ' ' }, path))
+            .collect()
+    }
+
+    /**
+     * The ROM at position `n` (from 1) of the `menu`.
+     */
+    pub fn entry(&self, n: usize) -> Option<&str> {
+        let recent = self.recent.iter().filter(|path| !self.favorites.contains(path));
+        self.favorites.iter().chain(recent).nth(n.checked_sub(1)?).map(String::as_str)
+    }
+}
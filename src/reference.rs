@@ -0,0 +1,462 @@
+use std::fmt;
+
+use crate::hardware::{
+    Chip8, Error, HaltReason, State, CHIP8_HEIGHT, CHIP8_WIDTH, KEY_COUNT, SCHIP_HEIGHT, SCHIP_WIDTH, V_REG_COUNT,
+};
+use crate::machine::Machine;
+use crate::quirks::Quirks;
+
+/**
+ * Deliberately simple, independently written CHIP-8 interpreter following the textbook
+ * semantics (XOR drawing, VF written last, `FX55`/`FX65` advance I by X + 1, ...), or the
+ * core's `Quirks` where it has them. It is not used to run games, only as a golden model
+ * the real core is checked against.
+ */
+pub struct Reference {
+    pub pc: usize,
+    pub i: usize,
+    pub v: [u8; V_REG_COUNT],
+    pub stack: Vec<usize>,
+    pub tim_delay: u8,
+    pub tim_snd: u8,
+    pub ram: Vec<u8>,
+    pub vram: Vec<bool>,
+    // XO-CHIP's second bitplane, and the planes drawn to and cleared as a bitmask
+    pub vram2: Vec<bool>,
+    pub planes: u8,
+    // 64x32, or 128x64 after SCHIP's `00FF`
+    pub width: usize,
+    pub height: usize,
+    pub keys: [bool; KEY_COUNT],
+    pub halted: bool,
+    // FX0A target register, and the key pressed since the wait started
+    pub waiting: Option<usize>,
+    wait_key: Option<usize>,
+    font_addr: usize,
+    big_font_addr: usize,
+    // the core's, see `Chip8::quirks`
+    quirks: Quirks,
+    // `DXY0` draws SCHIP's 16x16 sprite, unless the core is strict
+    big_sprites: bool,
+    // the XO-CHIP instructions, with RAM as big as the core's
+    xo_chip: bool,
+}
+
+/**
+ * First point where the core and the reference disagree.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Divergence {
+    pub frame: u64,
+    // address and opcode of the instruction after which the states differ
+    pub pc: usize,
+    pub opcode: u16,
+    pub what: String,
+    pub core: String,
+    pub reference: String,
+}
+
+impl Divergence {
+    /**
+     * One JSON object, with the values as they are shown in the message.
+     */
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"frame\": {}, \"pc\": {}, \"opcode\": {}, \"what\": {:?}, \"core\": {:?}, \"reference\": {:?}}}",
+            self.frame, self.pc, self.opcode, self.what, self.core, self.reference
+        )
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "frame {}, after {:04X} at {:#05x}: {} differs, core {} vs reference {}",
+            self.frame, self.opcode, self.pc, self.what, self.core, self.reference)
+    }
+}
+
+impl Reference {
+    /**
+     * Start from a copy of the core's current state, memory included.
+     */
+    pub fn from_chip8(chip8: &Chip8) -> Self {
+        let pixels: Vec<bool> = chip8.get_vram().rows().flatten().copied().collect();
+        let pixels2: Vec<bool> = chip8.get_plane2().rows().flatten().copied().collect();
+
+        Reference {
+            pc: chip8.pc,
+            i: chip8.i,
+            v: chip8.v,
+            stack: chip8.stack.clone(),
+            tim_delay: chip8.tim_delay,
+            tim_snd: chip8.tim_snd,
+            ram: chip8.get_ram().to_vec(),
+            vram: pixels,
+            vram2: pixels2,
+            planes: chip8.planes,
+            width: chip8.get_vram().width(),
+            height: chip8.get_vram().height(),
+            keys: chip8.keys,
+            halted: chip8.exited(),
+            waiting: match chip8.state {
+                State::Halted(HaltReason::WaitKey(reg)) => Some(reg),
+                _ => None,
+            },
+            wait_key: None,
+            font_addr: chip8.font_addr,
+            big_font_addr: chip8.big_font_addr,
+            quirks: chip8.quirks,
+            big_sprites: !chip8.strict,
+            xo_chip: chip8.xo_chip(),
+        }
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.tim_delay = self.tim_delay.saturating_sub(1);
+        self.tim_snd = self.tim_snd.saturating_sub(1);
+    }
+
+    /**
+     * Execute one instruction. `random` is the value `CXNN` should produce before masking;
+     * the lockstep driver passes in whatever the core drew so RNG never causes divergence.
+     */
+    pub fn step(&mut self, random: u8) {
+        if self.halted {
+            return;
+        }
+        // FX0A completes once a key has been pressed and released again
+        if let Some(reg) = self.waiting {
+            match self.wait_key {
+                Some(key) if !self.keys[key] => {
+                    self.v[reg] = key as u8;
+                    self.waiting = None;
+                    self.wait_key = None;
+                }
+                Some(_) => return,
+                None => {
+                    self.wait_key = self.keys.iter().position(|k| *k);
+                    return;
+                }
+            }
+        }
+
+        let op = self.opcode_at(self.pc);
+        let x = ((op >> 8) & 0xF) as usize;
+        let y = ((op >> 4) & 0xF) as usize;
+        let n = (op & 0xF) as usize;
+        let nn = (op & 0xFF) as u8;
+        let nnn = (op & 0xFFF) as usize;
+
+        let mut next = self.pc + 2;
+        // what a skip adds to `next`, XO-CHIP's `F000 NNNN` is skipped as a whole
+        let skip = if self.xo_chip && self.opcode_at(next) == 0xF000 { 4 } else { 2 };
+
+        match op >> 12 {
+            0x0 => match op {
+                0x00E0 => {
+                    if self.planes & 1 != 0 {
+                        self.vram.fill(false);
+                    }
+                    if self.planes & 2 != 0 {
+                        self.vram2.fill(false);
+                    }
+                }
+                0x00EE => next = self.stack.pop().unwrap_or(0),
+                0x00C0..=0x00CF => self.scroll(0, n as isize),
+                0x00FB => self.scroll(4, 0),
+                0x00FC => self.scroll(-4, 0),
+                0x00FD => {
+                    self.halted = true;
+                    next = self.pc;
+                }
+                0x00FE => self.resize(CHIP8_WIDTH as usize, CHIP8_HEIGHT as usize),
+                0x00FF => self.resize(SCHIP_WIDTH as usize, SCHIP_HEIGHT as usize),
+                // 0NNN machine code routines are ignored
+                _ => {}
+            },
+            0x1 => next = nnn,
+            0x2 => {
+                self.stack.push(next);
+                next = nnn;
+            }
+            0x3 => if self.v[x] == nn { next += skip },
+            0x4 => if self.v[x] != nn { next += skip },
+            0x5 if self.xo_chip && (n == 2 || n == 3) => {
+                // VX to VY, counting down when X > Y
+                let regs: Vec<usize> = if x <= y { (x..=y).collect() } else { (y..=x).rev().collect() };
+                for (offset, r) in regs.into_iter().enumerate() {
+                    let addr = (self.i + offset) % self.ram.len();
+                    if n == 2 {
+                        self.ram[addr] = self.v[r];
+                    } else {
+                        self.v[r] = self.ram[addr];
+                    }
+                }
+            }
+            0x5 => if self.v[x] == self.v[y] { next += skip },
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => self.alu(x, y, n),
+            0x9 => if self.v[x] != self.v[y] { next += skip },
+            0xA => self.i = nnn,
+            0xB => next = nnn + self.v[if self.quirks.jump { x } else { 0 }] as usize,
+            0xC => self.v[x] = random & nn,
+            0xD => self.draw(x, y, n),
+            0xE => {
+                let pressed = self.keys[(self.v[x] & 0xF) as usize];
+                if (nn == 0x9E && pressed) || (nn == 0xA1 && !pressed) {
+                    next += skip;
+                }
+            }
+            _ => match nn {
+                0x00 if self.xo_chip => {
+                    self.i = self.opcode_at(next) as usize;
+                    next += 2;
+                }
+                0x01 if self.xo_chip => self.planes = x as u8,
+                0x07 => self.v[x] = self.tim_delay,
+                0x0A => {
+                    self.waiting = Some(x);
+                    self.wait_key = None;
+                }
+                0x15 => self.tim_delay = self.v[x],
+                0x18 => self.tim_snd = self.v[x],
+                0x1E => self.i += self.v[x] as usize,
+                0x29 => self.i = self.font_addr + (self.v[x] & 0xF) as usize * 5,
+                0x30 => self.i = self.big_font_addr + (self.v[x] & 0xF) as usize * 10,
+                0x33 => {
+                    let (val, len) = (self.v[x], self.ram.len());
+                    self.ram[self.i % len] = val / 100;
+                    self.ram[(self.i + 1) % len] = val / 10 % 10;
+                    self.ram[(self.i + 2) % len] = val % 10;
+                }
+                0x55 => {
+                    for r in 0..=x {
+                        let addr = (self.i + r) % self.ram.len();
+                        self.ram[addr] = self.v[r];
+                    }
+                    if !self.quirks.load_store {
+                        self.i += x + 1;
+                    }
+                }
+                0x65 => {
+                    for r in 0..=x {
+                        self.v[r] = self.ram[(self.i + r) % self.ram.len()];
+                    }
+                    if !self.quirks.load_store {
+                        self.i += x + 1;
+                    }
+                }
+                _ => {}
+            },
+        }
+
+        self.pc = next;
+    }
+
+    fn alu(&mut self, x: usize, y: usize, n: usize) {
+        let (vx, vy) = (self.v[x], self.v[y]);
+        let vf_reset = self.quirks.logic;
+        // what the shifts shift
+        let shifted = if self.quirks.shift { vx } else { vy };
+
+        let (result, flag) = match n {
+            0x0 => (vy, None),
+            0x1 => (vx | vy, vf_reset.then_some(0)),
+            0x2 => (vx & vy, vf_reset.then_some(0)),
+            0x3 => (vx ^ vy, vf_reset.then_some(0)),
+            0x4 => {
+                let (r, carry) = vx.overflowing_add(vy);
+                (r, Some(carry as u8))
+            }
+            0x5 => {
+                let (r, borrow) = vx.overflowing_sub(vy);
+                (r, Some(!borrow as u8))
+            }
+            0x6 => (shifted >> 1, Some(shifted & 1)),
+            0x7 => {
+                let (r, borrow) = vy.overflowing_sub(vx);
+                (r, Some(!borrow as u8))
+            }
+            0xE => (shifted << 1, Some(shifted >> 7)),
+            _ => return,
+        };
+
+        self.v[x] = result;
+        if let Some(flag) = flag {
+            self.v[0xF] = flag;
+        }
+    }
+
+    fn opcode_at(&self, addr: usize) -> u16 {
+        (self.ram[addr % self.ram.len()] as u16) << 8 | self.ram[(addr + 1) % self.ram.len()] as u16
+    }
+
+    // a blank screen of the new size, switching to the size it already has changes nothing
+    fn resize(&mut self, width: usize, height: usize) {
+        if (width, height) != (self.width, self.height) {
+            (self.width, self.height) = (width, height);
+            self.vram = vec![false; width * height];
+            self.vram2 = vec![false; width * height];
+        }
+    }
+
+    // by pixels of the current resolution, on the selected planes
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let moved = |screen: &[bool]| -> Vec<bool> {
+            (0..width * height)
+                .map(|n| {
+                    let (x, y) = (n % width - dx, n / width - dy);
+                    (0..width).contains(&x) && (0..height).contains(&y) && screen[(y * width + x) as usize]
+                })
+                .collect()
+        };
+        if self.planes & 1 != 0 {
+            self.vram = moved(&self.vram);
+        }
+        if self.planes & 2 != 0 {
+            self.vram2 = moved(&self.vram2);
+        }
+    }
+
+    fn draw(&mut self, x: usize, y: usize, n: usize) {
+        let x0 = self.v[x] as usize % self.width;
+        let y0 = self.v[y] as usize % self.height;
+        let mut collision = 0;
+        // rows of 16 bits for the big sprite, of 8 in the top byte otherwise
+        let (rows, cols) = if n == 0 && self.big_sprites { (16, 16) } else { (n, 8) };
+        // each selected plane takes the next sprite's worth of bytes
+        let mut start = self.i;
+
+        for plane in [1, 2] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+            for row in 0..rows {
+                let byte = |n: usize| self.ram[(start + n) % self.ram.len()] as u16;
+                let sprite = match cols {
+                    16 => byte(2 * row) << 8 | byte(2 * row + 1),
+                    _ => byte(row) << 8,
+                };
+                let screen = if plane == 1 { &mut self.vram } else { &mut self.vram2 };
+                for col in 0..cols {
+                    let (px, py) = (x0 + col, y0 + row);
+                    if (self.quirks.clip && (px >= self.width || py >= self.height)) || sprite & (0x8000 >> col) == 0 {
+                        continue;
+                    }
+                    let (px, py) = (px % self.width, py % self.height);
+                    let idx = py * self.width + px;
+                    if screen[idx] {
+                        collision = 1;
+                    }
+                    screen[idx] ^= true;
+                }
+            }
+            start += rows * cols / 8;
+        }
+
+        self.v[0xF] = collision;
+    }
+
+    /**
+     * Describe the first difference with the core's state, if any.
+     */
+    pub fn compare(&self, chip8: &Chip8) -> Option<(String, String, String)> {
+        let diff = |what: &str, core: String, reference: String| Some((what.to_string(), core, reference));
+
+        if chip8.pc != self.pc {
+            return diff("PC", format!("{:#05x}", chip8.pc), format!("{:#05x}", self.pc));
+        }
+        if chip8.i != self.i {
+            return diff("I", format!("{:#05x}", chip8.i), format!("{:#05x}", self.i));
+        }
+        if let Some(r) = (0..V_REG_COUNT).find(|r| chip8.v[*r] != self.v[*r]) {
+            return diff(&format!("V{:X}", r), format!("{:#04x}", chip8.v[r]), format!("{:#04x}", self.v[r]));
+        }
+        let core_waiting = match chip8.state {
+            State::Halted(HaltReason::WaitKey(reg)) => Some(reg),
+            _ => None,
+        };
+        if core_waiting != self.waiting {
+            return diff("key wait", format!("{:?}", core_waiting), format!("{:?}", self.waiting));
+        }
+        if chip8.stack != self.stack {
+            return diff("stack", format!("{:x?}", chip8.stack), format!("{:x?}", self.stack));
+        }
+        if chip8.tim_delay != self.tim_delay {
+            return diff("delay timer", chip8.tim_delay.to_string(), self.tim_delay.to_string());
+        }
+        if chip8.tim_snd != self.tim_snd {
+            return diff("sound timer", chip8.tim_snd.to_string(), self.tim_snd.to_string());
+        }
+        if chip8.planes != self.planes {
+            return diff("bitplanes", chip8.planes.to_string(), self.planes.to_string());
+        }
+        if chip8.get_ram().len() != self.ram.len() {
+            return diff("RAM size", chip8.get_ram().len().to_string(), self.ram.len().to_string());
+        }
+        if let Some(addr) = (0..self.ram.len()).find(|a| chip8.get_ram()[*a] != self.ram[*a]) {
+            return diff(&format!("RAM[{:#05x}]", addr),
+                format!("{:#04x}", chip8.get_ram()[addr]), format!("{:#04x}", self.ram[addr]));
+        }
+
+        let vram = chip8.get_vram();
+        if (vram.width(), vram.height()) != (self.width, self.height) {
+            return diff("resolution", format!("{}x{}", vram.width(), vram.height()), format!("{}x{}", self.width, self.height));
+        }
+        for (x, y, core) in vram.pixels() {
+            if core != self.vram[y * self.width + x] {
+                return diff(&format!("pixel ({}, {})", x, y), core.to_string(), self.vram[y * self.width + x].to_string());
+            }
+        }
+        for (x, y, core) in chip8.get_plane2().pixels() {
+            if core != self.vram2[y * self.width + x] {
+                return diff(&format!("plane 2 pixel ({}, {})", x, y), core.to_string(), self.vram2[y * self.width + x].to_string());
+            }
+        }
+
+        None
+    }
+}
+
+/**
+ * Run `machine` for up to `frames` frames with a `Reference` in lockstep, comparing the
+ * full state after every instruction. Returns the first divergence, if any.
+ *
+ * ```
+ * use chip8::machine::Machine;
+ * use chip8::reference;
+ *
+ * let mut machine = Machine::new();
+ * // V0 = 0x10, V0 += V0, exit
+ * machine.load_rom(&[0x60, 0x10, 0x80, 0x04, 0x00, 0xFD]);
+ *
+ * assert_eq!(reference::lockstep(&mut machine, 60).unwrap(), None);
+ * assert_eq!(machine.chip8.v[0], 0x20);
+ * ```
+ */
+pub fn lockstep(machine: &mut Machine, frames: u64) -> Result<Option<Divergence>, Error> {
+    let mut reference = Reference::from_chip8(&machine.chip8);
+
+    while machine.frame() < frames && !machine.chip8.exited() {
+        let pc = machine.chip8.pc;
+        let opcode = reference.opcode_at(pc);
+        reference.keys = machine.chip8.keys;
+
+        let frame_done = machine.step()?;
+
+        // CXNN: hand the reference the same random value the core drew
+        let random = if opcode >> 12 == 0xC { machine.chip8.v[((opcode >> 8) & 0xF) as usize] } else { 0 };
+        reference.step(random);
+        if frame_done {
+            reference.tick_timers();
+        }
+
+        if let Some((what, core, reference)) = reference.compare(&machine.chip8) {
+            return Ok(Some(Divergence { frame: machine.frame(), pc, opcode, what, core, reference }));
+        }
+    }
+
+    Ok(None)
+}
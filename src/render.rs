@@ -1,165 +1,677 @@
 extern crate sdl2;
 
-use crate::hardware;
-
-use sdl2::render::Canvas;
 use sdl2::rect::Rect;
 use sdl2::rect::Point;
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::surface::Surface;
+use sdl2::controller::GameController;
+
+
+use chip8::framebuffer::Framebuffer;
+use chip8::hardware::KEY_COUNT;
+use chip8::palette::{Palette, Picture};
+use chip8::text;
 
-use sdl2::audio::{AudioCallback, AudioSpecDesired, AudioDevice};
+use crate::buzzer::{self, Buzzer};
+use crate::config::{AudioBackend, RenderBackend, Scaling};
+use crate::display::{self, Display};
 
-use array2d::Array2D;
+use std::time::{Duration, Instant};
 
-pub struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32
+// rumble motor strength while the sound timer runs, out of 0xFFFF
+const RUMBLE_STRENGTH: u16 = 0xC000;
+
+/**
+ * What gets drawn on top of what: layers are composited in the order of `LAYERS`, each
+ * one can be hidden on its own.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Layer {
+    // the emulated display
+    Game,
+    // pixel grid, see `draw_grid`
+    Grid,
+    // sprite box and register readout
+    Debug,
+    // 4x4 keypad with the held keys lit, for streams and checking the key mapping
+    Keypad,
+    // border shown while the beep sounds, for muted systems and players who can't hear it
+    Flash,
+    // the last warnings and errors, bottom left, see `log_file::Log::recent`
+    Messages,
+    // centered message box, e.g. when the program exited
+    Menu,
+    // the Ctrl+P command palette, see `actions::CommandPalette`, and the remap wizard's prompt
+    Commands,
 }
 
-impl AudioCallback for SquareWave {
-    type Channel = f32;
+pub const LAYERS: [Layer; 8] = [
+    Layer::Game,
+    Layer::Grid,
+    Layer::Debug,
+    Layer::Keypad,
+    Layer::Flash,
+    Layer::Messages,
+    Layer::Menu,
+    Layer::Commands,
+];
 
-    fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+// keypad layer, in the COSMAC VIP's layout
+const KEYPAD: [[usize; 4]; 4] = [[0x1, 0x2, 0x3, 0xC], [0x4, 0x5, 0x6, 0xD], [0x7, 0x8, 0x9, 0xE], [0xA, 0x0, 0xB, 0xF]];
+// size of one keypad key in window pixels, and the gap around it
+const KEYPAD_CELL: u32 = 24;
+const KEYPAD_GAP: u32 = 4;
+
+/**
+ * Where the emulated screen sits in the window, recomputed from the VRAM size so a resolution
+ * switch never needs a new window. How it is scaled depends on `Scaling`.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Viewport {
+    left: i32,
+    top: i32,
+    width: u32,
+    height: u32,
+    // emulated resolution
+    cols: usize,
+    rows: usize,
+}
+
+impl Viewport {
+    /**
+     * Window position of the top left corner of emulated pixel X/Y.
+     */
+    fn point(&self, x: usize, y: usize) -> Point {
+        Point::new(
+            self.left + (x as u64 * self.width as u64 / self.cols.max(1) as u64) as i32,
+            self.top + (y as u64 * self.height as u64 / self.rows.max(1) as u64) as i32,
+        )
+    }
+
+    /**
+     * Emulated pixel under window position X/Y, the inverse of `point`.
+     */
+    fn cell(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        let (dx, dy) = (x - self.left, y - self.top);
+        if dx < 0 || dy < 0 {
+            return None;
         }
+
+        // the largest cell whose top left corner is at or before the position
+        let col = ((dx as u64 + 1) * self.cols as u64 - 1) / self.width.max(1) as u64;
+        let row = ((dy as u64 + 1) * self.rows as u64 - 1) / self.height.max(1) as u64;
+        ((col as usize) < self.cols && (row as usize) < self.rows).then_some((col as usize, row as usize))
+    }
+
+    /**
+     * Window area covered by `w` by `h` emulated pixels starting at X/Y. Neighbouring rects
+     * touch without gaps even when a pixel isn't a whole number of window pixels.
+     */
+    fn rect(&self, x: usize, y: usize, w: usize, h: usize) -> Rect {
+        let (from, to) = (self.point(x, y), self.point(x + w, y + h));
+        Rect::new(from.x, from.y, (to.x - from.x).max(1) as u32, (to.y - from.y).max(1) as u32)
     }
 }
 
+// width in window pixels of the beep flash border
+const FLASH_BORDER: u32 = 12;
+
+// overlay text is drawn at this multiple of the font size
+const TEXT_SCALE: u32 = 2;
+
+// period and strength of the background palette cycle
+const CYCLE_PERIOD_SECS: f32 = 4.0;
+const CYCLE_AMPLITUDE: f32 = 12.0;
+
+// with `phosphor`, how fast a pixel that went out fades, and how faint it gets before it is dark
+const PHOSPHOR_HALF_LIFE_SECS: f32 = 0.025;
+const PHOSPHOR_CUTOFF: f32 = 1.0 / 32.0;
+
 pub struct Render {
-    pub canvas: Canvas<sdl2::video::Window>,
+    // the SDL canvas or another backend, see `display::open`
+    pub display: Box<dyn Display>,
     pub event_pump: sdl2::EventPump,
     pub timer: sdl2::TimerSubsystem,
-    pub sound: AudioDevice<SquareWave>,
+    // kept for opening the buzzer, see `open_buzzer`
+    #[cfg(feature = "audio")]
+    context: sdl2::Sdl,
+    // silent until `open_buzzer`
+    buzzer: Box<dyn Buzzer>,
+    controller_subsystem: sdl2::GameControllerSubsystem,
+    // open controllers, rumbled along with the beep
+    controllers: Vec<GameController>,
     pub width: u32,
     pub height: u32,
-    pub draw_grid: bool,
+    // indexed like `LAYERS`
+    visible: [bool; LAYERS.len()],
+    // recomputed from the VRAM size on every update
+    view: Viewport,
+    pub scaling: Scaling,
+    pub palette: Palette,
+    // slowly pulse the "off" color so it stands apart from the window background
+    pub palette_cycle: bool,
+    // brightness, contrast and gamma of the game layer's colors
+    pub picture: Picture,
+    // let pixels that went out fade like a CRT's phosphor, which smooths sprite flicker
+    pub phosphor: bool,
+    // with `phosphor`, per pixel: how bright it still is and the color it was lit in
+    glow: Vec<(f32, [u8; 3])>,
+    glow_at: Instant,
+    // debug layer: outline as (x, y, width, height) in emulated pixels, and text
+    pub sprite_box: Option<(usize, usize, usize, usize)>,
+    // game layer: outline of the focused tile in split screen, see `split_screen`
+    pub tile_box: Option<(usize, usize, usize, usize)>,
+    pub debug_text: String,
+    // what the last stepped instruction changed, in another color below `debug_text`
+    pub debug_changes: String,
+    // message layer contents
+    pub messages: String,
+    // menu layer contents, nothing is drawn while `None`
+    pub menu: Option<String>,
+    // command palette contents, nothing is drawn while `None`
+    pub commands: Option<String>,
+    // last state passed to `set_beep`, drives the flash layer
+    beeping: bool,
+    // keypad keys held on the host, for the keypad layer
+    pub keys: [bool; KEY_COUNT],
+    // keypad key outlined on the keypad layer, the one the remap wizard asks for
+    pub highlight: Option<usize>,
+    started: Instant,
 }
 
 impl Render {
     pub fn new(title: &str,
             width: u32,
             height: u32,
-            draw_grid: bool
+            draw_grid: bool,
+            backend: RenderBackend
     ) -> Result<Render, String> {
 
      let context = sdl2::init()?;
      let video = context.video()?;
-     let window = video.window(title, width, height)
-         .position_centered()
-         .opengl()
-         .build()
-         .map_err(|e| e.to_string())?;
-
-     let canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+     let display = display::open(backend, video.window(title, width, height).position_centered())?;
      let event_pump = context.event_pump()?;
      let timer_subsystem = context.timer()?;
-     let audio_subsystem = context.audio()?;
-
-     let desired_spec = AudioSpecDesired {
-        freq: Some(44100),
-        channels: Some(1),  // mono
-        samples: None       // default sample size
-    };
-
-    let audio_device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
-        // initialize the audio callback
-        SquareWave {
-            phase_inc: 440.0 / spec.freq as f32,
-            phase: 0.0,
-            volume: 0.25
-        }
-    }).unwrap();
-     
+     // controllers connected at startup show up as `ControllerDeviceAdded` events too
+     let controller_subsystem = context.game_controller()?;
+
+     let buzzer = buzzer::open(AudioBackend::Off)?;
+
      Ok(Render {
-         canvas: canvas,
+         display,
          event_pump: event_pump,
          timer: timer_subsystem,
-         sound: audio_device,
+         #[cfg(feature = "audio")]
+         context,
+         buzzer,
+         controller_subsystem,
+         controllers: Vec::new(),
          width: width,
          height: height,
-         draw_grid: draw_grid,
+         visible: [true, draw_grid, false, false, false, false, true, true],
+         view: Viewport { left: 0, top: 0, width, height, cols: 1, rows: 1 },
+         scaling: Scaling::default(),
+         palette: Palette::default(),
+         palette_cycle: false,
+         picture: Picture::default(),
+         phosphor: false,
+         glow: Vec::new(),
+         glow_at: Instant::now(),
+         sprite_box: None,
+         tile_box: None,
+         debug_text: String::new(),
+         debug_changes: String::new(),
+         messages: String::new(),
+         menu: None,
+         commands: None,
+         beeping: false,
+         keys: [false; KEY_COUNT],
+         highlight: None,
+         started: Instant::now(),
      })
     }
     
     /**
-     * Update canvas with VRAM data.
+     * Play the beep through `backend` from now on. Windows that never beep, like the sprite
+     * editor's, don't open one.
+     */
+    pub fn open_buzzer(&mut self, backend: AudioBackend) -> Result<(), String> {
+        self.buzzer = match backend {
+            #[cfg(feature = "audio")]
+            AudioBackend::Sdl => buzzer::open_sdl(&self.context)?,
+            _ => buzzer::open(backend)?,
+        };
+        Ok(())
+    }
+
+    /**
+     * Turn the beep on or off. A no-op with the `off` backend.
+     */
+    pub fn set_beep(&mut self, on: bool) {
+        self.beeping = on;
+        self.buzzer.set_beep(on);
+    }
+
+    /**
+     * Start using the controller at `joystick_index`, from `Event::ControllerDeviceAdded`.
+     * Returns its name.
+     */
+    pub fn add_controller(&mut self, joystick_index: u32) -> Result<String, String> {
+        let controller = self.controller_subsystem.open(joystick_index).map_err(|e| e.to_string())?;
+        let name = controller.name();
+        self.controllers.push(controller);
+        Ok(name)
+    }
+
+    /**
+     * Player number of a controller by its instance id from a controller event: 1 for the
+     * first one connected that is still there, and so on.
+     */
+    pub fn player(&self, instance_id: u32) -> Option<usize> {
+        self.controllers.iter().position(|c| c.instance_id() == instance_id).map(|n| n + 1)
+    }
+
+    /**
+     * Forget a controller, by the instance id from `Event::ControllerDeviceRemoved`. Returns
+     * its name, `None` if it wasn't in use. The players after it move up by one.
+     */
+    pub fn remove_controller(&mut self, instance_id: u32) -> Option<String> {
+        let n = self.controllers.iter().position(|c| c.instance_id() == instance_id)?;
+        Some(self.controllers.remove(n).name())
+    }
+
+    /**
+     * Names of the controllers in use, by player number.
+     */
+    pub fn controller_names(&self) -> Vec<String> {
+        self.controllers.iter().map(|c| c.name()).collect()
+    }
+
+    /**
+     * Swap the player numbers of two controllers, 0-based.
+     */
+    pub fn swap_controllers(&mut self, a: usize, b: usize) {
+        if a < self.controllers.len() && b < self.controllers.len() {
+            self.controllers.swap(a, b);
+        }
+    }
+
+    /**
+     * Rumble every controller for `duration`, a zero duration stops it. Feedback for players
+     * who can't hear the beep; controllers without a motor are skipped.
      */
-    pub fn update(&mut self, chip8_vram: &Array2D<bool>) -> Result<(), String> {
-        for (y, row) in chip8_vram.rows_iter().enumerate() {
-            for (x, px) in row.enumerate() {
-                if *px {
-                    self.canvas.set_draw_color(Color::GREEN);
+    pub fn rumble(&mut self, duration: Duration) {
+        let strength = if duration.is_zero() { 0 } else { RUMBLE_STRENGTH };
+        for controller in &mut self.controllers {
+            // fails for controllers that can't rumble, nothing to do about that
+            let _ = controller.set_rumble(strength, strength, duration.as_millis() as u32);
+        }
+    }
+
+    pub fn is_visible(&self, layer: Layer) -> bool {
+        self.visible[layer as usize]
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.beeping
+    }
+
+    /**
+     * Sample frames the audio device has played so far, see `Buzzer::samples`.
+     */
+    pub fn audio_samples(&self) -> Option<u64> {
+        self.buzzer.samples()
+    }
+
+    pub fn set_visible(&mut self, layer: Layer, visible: bool) {
+        self.visible[layer as usize] = visible;
+    }
+
+    pub fn toggle(&mut self, layer: Layer) {
+        self.visible[layer as usize] ^= true;
+    }
+
+    /**
+     * Update the display with VRAM data and composite the visible layers on top. `plane2` is
+     * XO-CHIP's second bitplane, drawn in the palette's `plane2` and `both` colors.
+     */
+    pub fn update(&mut self, chip8_vram: &Framebuffer, plane2: Option<&Framebuffer>) -> Result<(), String> {
+        self.view = self.viewport(chip8_vram.width(), chip8_vram.height());
+        let view = self.view;
+        self.display.set_screen(Rect::new(view.left, view.top, view.width, view.height), view.rows);
+
+        // letterbox bars, if the screen doesn't fill the window
+        self.display.set_draw_color(Color::BLACK);
+        self.display.clear();
+
+        for layer in LAYERS {
+            if self.is_visible(layer) {
+                self.draw_layer(layer, chip8_vram, plane2)?;
+            }
+        }
+
+        self.display.present()?;
+        Ok(())
+    }
+
+    fn draw_layer(&mut self, layer: Layer, chip8_vram: &Framebuffer, plane2: Option<&Framebuffer>) -> Result<(), String> {
+        match layer {
+            Layer::Game => {
+                let [r, g, b] = self.background();
+                self.display.set_draw_color(Color::RGB(r, g, b));
+                self.display.fill_rect(Rect::new(self.view.left, self.view.top, self.view.width, self.view.height))?;
+                if self.phosphor {
+                    self.draw_glow(chip8_vram, plane2, [r, g, b])?;
+                }
+
+                let [r, g, b] = self.picture.apply(self.palette.on);
+                self.display.set_draw_color(Color::RGB(r, g, b));
+                let in_plane2 = |x, y| plane2.and_then(|plane| plane.get(x, y)) == Some(true);
+                for (x, y) in chip8_vram.iter_set_pixels().filter(|(x, y)| !in_plane2(*x, *y)) {
+                    self.draw_dot(x, y)?;
+                }
+                if let Some(plane2) = plane2 {
+                    for (planes, color) in [(2, self.palette.plane2), (3, self.palette.both)] {
+                        let [r, g, b] = self.picture.apply(color);
+                        self.display.set_draw_color(Color::RGB(r, g, b));
+                        for (x, y) in plane2.iter_set_pixels().filter(|(x, y)| (chip8_vram.get(*x, *y) == Some(true)) == (planes == 3)) {
+                            self.draw_dot(x, y)?;
+                        }
+                    }
+                }
+                match self.tile_box {
+                    Some(tile_box) => self.draw_box_outline(tile_box, Color::WHITE),
+                    None => Ok(()),
+                }
+            }
+            Layer::Grid => self.draw_grid(chip8_vram.width(), chip8_vram.height()),
+            Layer::Debug => {
+                if let Some(sprite_box) = self.sprite_box {
+                    self.draw_box_outline(sprite_box, Color::YELLOW)?;
                 }
-                else {
-                    self.canvas.set_draw_color(Color::BLACK);
+                let text = self.debug_text.clone();
+                self.draw_text(4, 4, TEXT_SCALE, &text, [0xFF, 0xFF, 0x00])?;
+                let below = 4 + (text::measure(&text).1 as u32 * TEXT_SCALE) as i32;
+                let changes = self.debug_changes.clone();
+                self.draw_text(4, below, TEXT_SCALE, &changes, [0x00, 0xFF, 0xFF])
+            }
+            Layer::Keypad => {
+                // bottom right corner, clear of the debug text
+                let size = 4 * KEYPAD_CELL + 5 * KEYPAD_GAP;
+                let (left, top) = (self.width.saturating_sub(size) as i32, self.height.saturating_sub(size) as i32);
+                self.display.set_draw_color(Color::BLACK);
+                self.display.fill_rect(Rect::new(left, top, size, size))?;
+
+                for (row, keys) in KEYPAD.iter().enumerate() {
+                    for (col, &key) in keys.iter().enumerate() {
+                        let x = left + (KEYPAD_GAP + col as u32 * (KEYPAD_CELL + KEYPAD_GAP)) as i32;
+                        let y = top + (KEYPAD_GAP + row as u32 * (KEYPAD_CELL + KEYPAD_GAP)) as i32;
+                        let cell = Rect::new(x, y, KEYPAD_CELL, KEYPAD_CELL);
+                        let label = if self.keys[key] {
+                            let [r, g, b] = self.palette.on;
+                            self.display.set_draw_color(Color::RGB(r, g, b));
+                            self.display.fill_rect(cell)?;
+                            [0x00, 0x00, 0x00]
+                        } else {
+                            self.display.set_draw_color(Color::GRAY);
+                            self.display.draw_rect(cell)?;
+                            [0xFF, 0xFF, 0xFF]
+                        };
+                        if self.highlight == Some(key) {
+                            self.display.set_draw_color(Color::YELLOW);
+                            self.display.draw_rect(Rect::new(x - 2, y - 2, KEYPAD_CELL + 4, KEYPAD_CELL + 4))?;
+                        }
+                        let inset = ((KEYPAD_CELL - text::GLYPH_SIZE as u32 * TEXT_SCALE) / 2) as i32;
+                        self.draw_text(x + inset, y + inset, TEXT_SCALE, &format!("{:X}", key), label)?;
+                    }
                 }
-                self.draw_dot(x as i32, y as i32)?;
+                Ok(())
+            }
+            Layer::Flash => {
+                if !self.beeping {
+                    return Ok(());
+                }
+                let [r, g, b] = self.palette.on;
+                self.display.set_draw_color(Color::RGB(r, g, b));
+                self.display.fill_rects(&[
+                    Rect::new(0, 0, self.width, FLASH_BORDER),
+                    Rect::new(0, self.height.saturating_sub(FLASH_BORDER) as i32, self.width, FLASH_BORDER),
+                    Rect::new(0, 0, FLASH_BORDER, self.height),
+                    Rect::new(self.width.saturating_sub(FLASH_BORDER) as i32, 0, FLASH_BORDER, self.height),
+                ])
+            }
+            Layer::Messages => {
+                // small print, messages can be long
+                let text = self.messages.clone();
+                let (w, h) = text::measure(&text);
+                let (w, h) = (w as u32 + 8, h as u32 + 8);
+                let top = self.height.saturating_sub(h) as i32;
+                self.display.set_draw_color(Color::BLACK);
+                self.display.fill_rect(Rect::new(0, top, w, h))?;
+                self.draw_text(4, top + 4, 1, &text, [0xFF, 0x80, 0x00])
             }
+            Layer::Menu => match self.menu.clone() {
+                Some(text) => self.draw_box(&text, None),
+                None => Ok(()),
+            },
+            // near the top, so whatever it is about to change stays in view
+            Layer::Commands => match self.commands.clone() {
+                Some(text) => self.draw_box(&text, Some(self.height as i32 / 8)),
+                None => Ok(()),
+            },
         }
-        if self.draw_grid
-        {
-            self.draw_grid()?;
+    }
+
+    /**
+     * Draw the pixels that went out, fading from their color to `background` a little more
+     * on every update, and note the color of the lit ones for when they go out.
+     */
+    fn draw_glow(&mut self, chip8_vram: &Framebuffer, plane2: Option<&Framebuffer>, background: [u8; 3]) -> Result<(), String> {
+        let (width, height) = (chip8_vram.width(), chip8_vram.height());
+        // switching resolution starts from a dark screen
+        if self.glow.len() != width * height {
+            self.glow = vec![(0.0, [0; 3]); width * height];
+        }
+        let fade = 0.5f32.powf(self.glow_at.elapsed().as_secs_f32() / PHOSPHOR_HALF_LIFE_SECS);
+        self.glow_at = Instant::now();
+
+        let colors = [self.palette.on, self.palette.plane2, self.palette.both].map(|color| self.picture.apply(color));
+        for y in 0..height {
+            for x in 0..width {
+                let planes = (chip8_vram.get(x, y) == Some(true)) as usize
+                    + 2 * (plane2.and_then(|plane| plane.get(x, y)) == Some(true)) as usize;
+                let (level, color) = &mut self.glow[y * width + x];
+                if planes > 0 {
+                    (*level, *color) = (1.0, colors[planes - 1]);
+                    continue;
+                }
+                *level = if *level * fade < PHOSPHOR_CUTOFF { 0.0 } else { *level * fade };
+                if *level == 0.0 {
+                    continue;
+                }
+                let (level, color) = (*level, *color);
+                let mix = |n: usize| (background[n] as f32 + (color[n] as f32 - background[n] as f32) * level) as u8;
+                self.display.set_draw_color(Color::RGB(mix(0), mix(1), mix(2)));
+                self.draw_dot(x, y)?;
+            }
         }
-        self.canvas.present();
         Ok(())
     }
 
     /**
-     * Will draw a grid for debugging. Every 8x4 block will be marked with red lines.
+     * Whether a pixel is still fading out with `phosphor`, the screen needs redrawing until
+     * none is.
+     */
+    pub fn fading(&self) -> bool {
+        self.phosphor && self.glow.iter().any(|(level, _)| *level > 0.0)
+    }
+
+    /**
+     * Draw `text` in a framed box, centered horizontally. `top` is where the text starts,
+     * it is centered vertically without one.
      */
-    pub fn draw_grid(&mut self) -> Result<(), String> {
-        let mut n = hardware::MULTIPLIER as usize;
+    fn draw_box(&mut self, text: &str, top: Option<i32>) -> Result<(), String> {
+        let (w, h) = text::measure(text);
+        let (w, h) = (w as u32 * TEXT_SCALE, h as u32 * TEXT_SCALE);
+        let x = (self.width.saturating_sub(w) / 2) as i32;
+        let y = top.unwrap_or((self.height.saturating_sub(h) / 2) as i32);
+        let margin = 8 * TEXT_SCALE;
+
+        self.display.set_draw_color(Color::BLACK);
+        self.display.fill_rect(Rect::new(x - margin as i32, y - margin as i32, w + 2 * margin, h + 2 * margin))?;
+        self.display.set_draw_color(Color::WHITE);
+        self.display.draw_rect(Rect::new(x - margin as i32, y - margin as i32, w + 2 * margin, h + 2 * margin))?;
+        self.draw_text(x, y, TEXT_SCALE, text, [0xFF, 0xFF, 0xFF])
+    }
 
-        while n < (hardware::CHIP8_WIDTH * hardware::MULTIPLIER) as usize {
-            let start = Point::new(n as i32, 0);
-            let end = Point::new(n as i32, (hardware::CHIP8_HEIGHT * hardware::MULTIPLIER) as i32);
-            if n % 160 == 0 {
-                self.canvas.set_draw_color(Color::RED);
+    /**
+     * Use a snapshot of the screen as the window icon, centered on a square background.
+     */
+    pub fn set_icon(&mut self, chip8_vram: &Framebuffer) -> Result<(), String> {
+        let (width, height) = (chip8_vram.width(), chip8_vram.height());
+        let size = width.max(height);
+        let (left, top) = ((size - width) / 2, (size - height) / 2);
+        let rgba = chip8_vram.to_rgba(&self.palette);
+        let [r, g, b] = self.palette.off;
+
+        let mut icon = Surface::new(size as u32, size as u32, PixelFormatEnum::RGBA32)?;
+        let pitch = icon.pitch() as usize;
+        icon.with_lock_mut(|pixels| {
+            for y in 0..size {
+                for x in 0..size {
+                    let px = &mut pixels[y * pitch + x * 4..][..4];
+                    if (left..left + width).contains(&x) && (top..top + height).contains(&y) {
+                        let src = ((y - top) * width + x - left) * 4;
+                        px.copy_from_slice(&rgba[src..src + 4]);
+                    } else {
+                        px.copy_from_slice(&[r, g, b, 0xFF]);
+                    }
+                }
             }
-            else {
-                self.canvas.set_draw_color(Color::GRAY);
+        });
+
+        self.display.window_mut().set_icon(icon);
+        Ok(())
+    }
+
+    /**
+     * The palette's "off" color, shifted along a slow sine wave when `palette_cycle` is set,
+     * with `picture` applied.
+     */
+    fn background(&self) -> [u8; 3] {
+        if !self.palette_cycle {
+            return self.picture.apply(self.palette.off);
+        }
+
+        let phase = self.started.elapsed().as_secs_f32() / CYCLE_PERIOD_SECS;
+        let delta = (phase * std::f32::consts::TAU).sin().abs() * CYCLE_AMPLITUDE;
+
+        // darken bright backgrounds, brighten dark ones
+        let [r, g, b] = self.palette.off;
+        let shift = |c: u8| {
+            if c > 0x80 {
+                (c as f32 - delta) as u8
+            } else {
+                (c as f32 + delta) as u8
             }
-            self.canvas.draw_line(start, end)?;
-            n += hardware::MULTIPLIER as usize;
+        };
+        self.picture.apply([shift(r), shift(g), shift(b)])
+    }
+
+    /**
+     * Draw `text` at X/Y in window coordinates, each font pixel `scale` window pixels wide.
+     * Call before `present()`.
+     */
+    pub fn draw_text(&mut self, x: i32, y: i32, scale: u32, text: &str, [r, g, b]: [u8; 3]) -> Result<(), String> {
+        let rendered = text::render(text);
+        self.display.set_draw_color(Color::RGB(r, g, b));
+
+        for (tx, ty) in rendered.iter_set_pixels() {
+            self.display.fill_rect(Rect::new(x + (tx as u32 * scale) as i32, y + (ty as u32 * scale) as i32, scale, scale))?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Will draw a grid for debugging, one cell per emulated pixel. Every 8x4 block will be
+     * marked with red lines.
+     */
+    pub fn draw_grid(&mut self, cols: usize, rows: usize) -> Result<(), String> {
+        let view = self.view;
+
+        for col in 1..cols {
+            self.display.set_draw_color(if col % 8 == 0 { Color::RED } else { Color::GRAY });
+            self.display.draw_line(view.point(col, 0), view.point(col, rows))?;
+        }
+
+        for row in 1..rows {
+            self.display.set_draw_color(if row % 4 == 0 { Color::RED } else { Color::GRAY });
+            self.display.draw_line(view.point(0, row), view.point(cols, row))?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Outline an area in emulated pixels, e.g. the one the next `DXYN` will draw to, see
+     * `debugger::next_sprite`.
+     */
+    fn draw_box_outline(&mut self, (x, y, w, h): (usize, usize, usize, usize), color: Color) -> Result<(), String> {
+        if w == 0 || h == 0 {
+            return Ok(());
         }
 
-        n = hardware::MULTIPLIER as usize;
+        self.display.set_draw_color(color);
+        self.display.draw_rect(self.view.rect(x, y, w, h))
+    }
 
-        while n < (hardware::CHIP8_HEIGHT * hardware::MULTIPLIER) as usize {
-            let start = Point::new(0 as i32, n as i32);
-            let end = Point::new((hardware::CHIP8_WIDTH * hardware::MULTIPLIER) as i32, n as i32);
-            if n % 80 == 0 {
-                self.canvas.set_draw_color(Color::RED);
+    fn viewport(&self, cols: usize, rows: usize) -> Viewport {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        let (width, height) = match self.scaling {
+            Scaling::PixelPerfect => {
+                let cell = (self.width / cols as u32).min(self.height / rows as u32).max(1);
+                (cols as u32 * cell, rows as u32 * cell)
             }
-            else {
-                self.canvas.set_draw_color(Color::GRAY);
+            Scaling::Fit => {
+                let scale = (self.width as f64 / cols as f64).min(self.height as f64 / rows as f64);
+                ((cols as f64 * scale) as u32, (rows as f64 * scale) as u32)
             }
-            self.canvas.draw_line(start, end)?;
-            n += hardware::MULTIPLIER as usize;
+            Scaling::Stretch => (self.width, self.height),
+        };
+
+        Viewport {
+            left: (self.width.saturating_sub(width) / 2) as i32,
+            top: (self.height.saturating_sub(height) / 2) as i32,
+            width,
+            height,
+            cols,
+            rows,
         }
-        Ok(())
+    }
+
+    /**
+     * Emulated pixel under window position X/Y, or None outside the screen (e.g. on the
+     * letterbox bars). Follows the scaling and letterboxing of the last `update`, so mouse
+     * handling in frontends and tools agrees with what is drawn.
+     */
+    pub fn screen_to_cell(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        self.view.cell(x, y)
+    }
+
+    /**
+     * Window area of emulated pixel X/Y, the inverse of `screen_to_cell`.
+     */
+    pub fn cell_to_screen(&self, x: usize, y: usize) -> Option<Rect> {
+        (x < self.view.cols && y < self.view.rows).then(|| self.view.rect(x, y, 1, 1))
     }
 
     /**
      * Will draw a single pixel at X/Y.
      */
-    fn draw_dot(&mut self, x_in: i32, y_in: i32) -> Result<(), String> {
-        let point = Point::new(x_in, y_in);
-        self.canvas.fill_rect(Rect::new(
-            point.x * hardware::MULTIPLIER as i32,
-            point.y * hardware::MULTIPLIER as i32,
-            hardware::MULTIPLIER,
-            hardware::MULTIPLIER,
-        ))?;
+    fn draw_dot(&mut self, x_in: usize, y_in: usize) -> Result<(), String> {
+        if let Some(rect) = self.cell_to_screen(x_in, y_in) {
+            self.display.fill_rect(rect)?;
+        }
 
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,39 @@
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/**
+ * Reads debugger commands from stdin on a background thread, so the SDL loop can keep
+ * running and pick them up with `lines()` once per iteration.
+ */
+pub struct Repl {
+    rx: Receiver<String>,
+}
+
+impl Repl {
+    pub fn start() -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                match line {
+                    Ok(line) => {
+                        if tx.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Repl { rx }
+    }
+
+    /**
+     * Commands entered since the last call.
+     */
+    pub fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.rx.try_iter()
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::io::ErrorKind;
+
+use chip8::patch;
+
+use crate::config::{self, Scaling};
+
+pub const ROM_SETTINGS: &str = "roms.txt";
+
+/**
+ * Settings for a single ROM, for variants that need different handling than the command
+ * line defaults. The file has one line per ROM: its crc32 (as in the macro and save file
+ * names) followed by `key=value` pairs. `#` starts a comment:
+ *
+ * ```text
+ * # 64x48 variant, its pixels were taller than wide
+ * 1a2b3c4d scaling=stretch
+ * ```
+ *
+ * The rest set up how a run starts, for debugging the same spot again and again or for
+ * demos. They act like the options of the same name:
+ *
+ * ```text
+ * 5e6f7a8b speed=2.0 break=0x2f0,0x31c paused load_state
+ * ```
+ */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RomSettings {
+    pub scaling: Option<Scaling>,
+    pub speed: Option<f64>,
+    pub breakpoints: Vec<usize>,
+    pub paused: bool,
+    pub load_state: bool,
+}
+
+impl RomSettings {
+    /**
+     * Settings for the ROM with checksum `crc`. Every line is checked, not just the ROM's,
+     * so mistakes show up right away.
+     */
+    pub fn parse(text: &str, crc: u32) -> Result<RomSettings, String> {
+        let mut found = RomSettings::default();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut fields = line.split_whitespace();
+            let Some(key) = fields.next() else {
+                continue;
+            };
+
+            let rom_crc = u32::from_str_radix(key, 16).map_err(|_| format!("line {}: invalid crc32 '{}'", n + 1, key))?;
+            let mut settings = RomSettings::default();
+            for field in fields {
+                let at_line = |e| format!("line {}: {}", n + 1, e);
+                match field.split_once('=') {
+                    Some(("scaling", name)) => settings.scaling = Some(Scaling::by_name(name).map_err(at_line)?),
+                    Some(("speed", factor)) => settings.speed = Some(config::parse_speed(factor).map_err(at_line)?),
+                    Some(("break", addrs)) => {
+                        for addr in addrs.split(',') {
+                            settings.breakpoints.push(config::parse_addr(addr).map_err(at_line)?);
+                        }
+                    }
+                    None if field == "paused" => settings.paused = true,
+                    None if field == "load_state" => settings.load_state = true,
+                    _ => return Err(format!("line {}: unknown setting '{}'", n + 1, field)),
+                }
+            }
+
+            if rom_crc == crc {
+                found = settings;
+            }
+        }
+
+        Ok(found)
+    }
+
+    /**
+     * Settings for `rom` from the file at `path`. Having no file is fine.
+     */
+    pub fn load(path: &str, rom: &[u8]) -> Result<RomSettings, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => RomSettings::parse(&text, patch::crc32(rom)).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(RomSettings::default()),
+            Err(e) => Err(format!("can't read {}: {}", path, e)),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs;
+
+use chip8::disasm::{self, ROM_ADDR};
+use chip8::tutorial;
+
+use crate::config::Format;
+
+// load address of ROMs written for the ETI 660, see `Normalized::hints`
+const ETI_660_ADDR: usize = 0x600;
+
+/**
+ * A ROM with its trailing zeros stripped, and what `rom normalize` found out about it.
+ * Stripping is always safe: RAM past the ROM starts out zeroed, so the program sees the
+ * same memory either way. An odd length means the last instruction is cut in half, or the
+ * file picked up a stray byte somewhere, `pad` adds a zero to make it even again.
+ *
+ * ```text
+ * 12 04 F0 90 12 04 00 00 00  ->  12 04 F0 90 12 04
+ * 60 05 70 01 12 02 05        ->  60 05 70 01 12 02 05 00 (with --pad)
+ * ```
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Normalized {
+    pub rom: Vec<u8>,
+    // size of the file as it was
+    pub size: usize,
+    pub trimmed: usize,
+    // whether the result has an odd length, before padding
+    pub odd: bool,
+    pub padded: bool,
+}
+
+impl Normalized {
+    pub fn new(rom: &[u8], pad: bool) -> Normalized {
+        let len = rom.iter().rposition(|b| *b != 0).map_or(0, |last| last + 1);
+        let odd = len % 2 == 1;
+        let mut out = rom[..len].to_vec();
+        if odd && pad {
+            out.push(0);
+        }
+        Normalized { rom: out, size: rom.len(), trimmed: rom.len() - len, odd, padded: odd && pad }
+    }
+
+    /**
+     * Guesses about where the program starts and what it was made for, for ROMs that do
+     * nothing or fault right away.
+     */
+    pub fn hints(&self) -> Vec<String> {
+        let rom = &self.rom;
+        let mut hints = Vec::new();
+        if rom.len() < 2 {
+            hints.push("too short to hold an instruction".to_string());
+            return hints;
+        }
+
+        let leading = rom.iter().take_while(|b| **b == 0).count();
+        if leading >= 2 {
+            hints.push(format!("starts with {} zero bytes, maybe a header or padding in front of the program", leading));
+        }
+
+        let entry = (rom[0] as u16) << 8 | rom[1] as u16;
+        let target = (entry & 0xFFF) as usize;
+        if matches!(entry >> 12, 0x1 | 0x2) && (ETI_660_ADDR..ETI_660_ADDR + rom.len()).contains(&target) && target >= ROM_ADDR + rom.len() {
+            hints.push(format!("the first instruction goes to {:#05x}, past the end: probably made to load at {:#05x} (ETI 660)", target, ETI_660_ADDR));
+        }
+
+        let analysis = disasm::analyze(rom);
+        let end = ROM_ADDR + rom.len();
+        let mut outside: Vec<usize> = analysis.successors.values().flatten().copied().filter(|a| *a < ROM_ADDR || *a >= end).collect();
+        outside.sort_unstable();
+        outside.dedup();
+        if !outside.is_empty() {
+            let targets: Vec<String> = outside.iter().map(|a| format!("{:#05x}", a)).collect();
+            hints.push(format!("jumps out of the ROM to {}", targets.join(", ")));
+        }
+
+        let code = analysis.code.len() * 2;
+        if code * 4 < rom.len() {
+            hints.push(format!("only {} of {} bytes are reachable code, the rest is data or never runs", code, rom.len()));
+        }
+        if hints.is_empty() {
+            hints.push("looks like a plain program loaded at 0x200".to_string());
+        }
+        hints
+    }
+
+    fn to_text(&self, path: &str) -> String {
+        let mut out = format!("{}: {} bytes", path, self.size);
+        if self.trimmed > 0 {
+            out.push_str(&format!(", {} trailing zero bytes stripped", self.trimmed));
+        }
+        if self.padded {
+            out.push_str(", padded to an even length");
+        }
+        out.push_str(&format!(", {} bytes left\n", self.rom.len()));
+        if self.odd && !self.padded {
+            out.push_str("warning: odd length, the last instruction is cut short; --pad adds a zero byte\n");
+        }
+        if let [hi, lo, ..] = self.rom[..] {
+            let opcode = (hi as u16) << 8 | lo as u16;
+            out.push_str(&format!("entry {:#05x}: {:04X}  {}\n", ROM_ADDR, opcode, disasm::mnemonic(opcode)));
+        }
+        for hint in self.hints() {
+            out.push_str(&format!("hint: {}\n", hint));
+        }
+        out
+    }
+
+    fn to_json(&self, path: &str) -> String {
+        let entry = match self.rom[..] {
+            [hi, lo, ..] => ((hi as u16) << 8 | lo as u16).to_string(),
+            _ => "null".to_string(),
+        };
+        let hints: Vec<String> = self.hints().iter().map(|hint| format!("{:?}", hint)).collect();
+
+        format!(
+            "{{\n  \"file\": {:?},\n  \"size\": {},\n  \"trimmed\": {},\n  \"odd\": {},\n  \"padded\": {},\n  \"length\": {},\n  \"entry\": {},\n  \"hints\": [{}]\n}}\n",
+            path,
+            self.size,
+            self.trimmed,
+            self.odd,
+            self.padded,
+            self.rom.len(),
+            entry,
+            hints.join(", "),
+        )
+    }
+}
+
+/**
+ * `rom normalize`: report on the ROM at `path` and, with `out`, write it there with its
+ * trailing zeros stripped. `out` may be `path` itself.
+ */
+pub fn normalize(path: &str, pad: bool, format: Format, out: Option<&str>) -> Result<(), String> {
+    let rom = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let normalized = Normalized::new(&rom, pad);
+    match format {
+        Format::Text => print!("{}", normalized.to_text(path)),
+        Format::Json => print!("{}", normalized.to_json(path)),
+    }
+
+    match out {
+        Some(out) => fs::write(out, &normalized.rom).map_err(|e| format!("can't write {}: {}", out, e)),
+        None => Ok(()),
+    }
+}
+
+/**
+ * `rom tutorial`: describe the tutorial ROM for an opcode `family` and, with `out`, write
+ * it there.
+ */
+pub fn tutorial(family: &str, out: Option<&str>) -> Result<(), String> {
+    let tutorial = tutorial::by_name(family)?;
+    print!("{}", tutorial.describe());
+    match out {
+        Some(out) => fs::write(out, tutorial.rom()).map_err(|e| format!("can't write {}: {}", out, e)),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,87 @@
+use chip8::hardware::KEY_COUNT;
+
+/**
+ * Where a keypad key press comes from.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Device {
+    Keyboard,
+    // by player number, 1 for the first controller connected
+    Controller(usize),
+}
+
+impl Device {
+    /**
+     * `keyboard`, or `pad<n>` for the nth controller.
+     */
+    pub fn by_name(name: &str) -> Result<Device, String> {
+        match name {
+            "keyboard" => Ok(Device::Keyboard),
+            _ => name
+                .strip_prefix("pad")
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .map(Device::Controller)
+                .ok_or_else(|| format!("unknown input device '{}', expected keyboard or pad<n>", name)),
+        }
+    }
+}
+
+/**
+ * Which device may press which keypad keys, so two players can share the keypad without
+ * getting in each other's way: with `0-7=pad1,8-f=pad2` the second controller's buttons
+ * can't press the first player's keys, even where both controllers use the same bindings.
+ * A route is a set of keys in hex, single digits or ranges, and the device that owns them:
+ *
+ * ```text
+ * 14=pad1,cd=pad2
+ * 0-7=keyboard,8-f=pad1
+ * ```
+ *
+ * Keys without a route can be pressed from anywhere.
+ */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Routing {
+    routes: [Option<Device>; KEY_COUNT],
+}
+
+impl Routing {
+    pub fn parse(spec: &str) -> Result<Routing, String> {
+        let mut routing = Routing::default();
+
+        for route in spec.split(',').map(str::trim).filter(|route| !route.is_empty()) {
+            let (keys, device) = route.split_once('=').ok_or_else(|| format!("invalid route '{}', expected <keys>=<device>", route))?;
+            let device = Device::by_name(device.trim())?;
+            let invalid = || format!("invalid keys '{}' in route '{}'", keys, route);
+            let hex = |c: char| c.to_digit(16).map(|d| d as usize).ok_or_else(invalid);
+            if keys.trim().is_empty() {
+                return Err(invalid());
+            }
+
+            let mut chars = keys.trim().chars().peekable();
+            while let Some(c) = chars.next() {
+                let first = hex(c)?;
+                let last = match chars.next_if_eq(&'-') {
+                    Some(_) => hex(chars.next().ok_or_else(invalid)?)?,
+                    None => first,
+                };
+                if first > last {
+                    return Err(invalid());
+                }
+                for key in first..=last {
+                    routing.routes[key] = Some(device);
+                }
+            }
+        }
+
+        Ok(routing)
+    }
+
+    /**
+     * Whether `device` may press `key`.
+     */
+    #[cfg(any(feature = "gui", feature = "minifb"))]
+    pub fn allows(&self, device: Device, key: usize) -> bool {
+        self.routes.get(key).copied().flatten().is_none_or(|owner| owner == device)
+    }
+}
@@ -0,0 +1,38 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use chip8::machine::Machine;
+use chip8::patch;
+
+// RAM kept across sessions with --save-ram
+pub const ADDR: usize = 0xE00;
+pub const LEN: usize = 0x100;
+
+pub const SAVE_DIR: &str = "saves";
+
+/**
+ * Save file for a ROM, keyed by its checksum like the input macros.
+ */
+pub fn path(dir: &str, rom: &[u8]) -> String {
+    format!("{}/{:08x}.sav", dir, patch::crc32(rom))
+}
+
+/**
+ * Copy a previous session's save RAM into memory. Having no save yet is fine.
+ */
+pub fn load(machine: &mut Machine, path: &str) -> Result<(), String> {
+    match fs::read(path) {
+        Ok(data) => machine.chip8.load_ram(&data[..data.len().min(LEN)], ADDR).map_err(|e| format!("can't load save RAM {}: {}", path, e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("can't read save RAM {}: {}", path, e)),
+    }
+}
+
+pub fn store(machine: &Machine, path: &str) -> Result<(), String> {
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(path, &machine.chip8.get_ram()[ADDR..ADDR + LEN]))
+        .map_err(|e| format!("can't write save RAM {}: {}", path, e))
+}
@@ -0,0 +1,445 @@
+//! Save states: a snapshot of a `Machine` that can be restored later.
+//!
+//! A state starts with a 10 byte header: the magic `C8ST`, the format version (u16) and a
+//! CRC-32 of the body. Since version 2 the body is compressed: its size (u32) comes after
+//! the header, followed by the body as an LZ4 block (see `lz4`), and the CRC is of the body
+//! as it was before compressing. A state that was cut short or damaged is refused with an
+//! error saying so. States from older builds are migrated to the current layout before they
+//! are loaded, states from newer builds are refused instead of being loaded as garbage.
+//!
+//! The body (version 3) is little endian: PC, I (u16), stack depth (u8) and entries (u16),
+//! V0-VF, delay and sound timer, the first 4 KiB of RAM, display width and height (u16)
+//! followed by the pixels packed 8 to a byte, the held keys (u16 bitmask), the halt state
+//! (u8 kind, u8 register), the key FX0A saw pressed (0xFF for none), the two font addresses
+//! (u16), the frame number (u64), the cycle within that frame (u32), whether XO-CHIP mode
+//! is on (u8) and the selected bitplanes (u8). In XO-CHIP mode the rest of its 64 KiB of
+//! RAM and the second bitplane, packed like the first, follow. Version 2 bodies are the
+//! same up to the cycle, and so are version 1 bodies.
+//!
+//! Peripherals aren't part of the state, the frontend maps them again. Neither is the
+//! instruction history, it is cleared on load.
+//!
+//! ```
+//! use chip8::machine::Machine;
+//! use chip8::savestate;
+//!
+//! let mut machine = Machine::new();
+//! // V0 += 1, loop forever
+//! machine.load_rom(&[0x70, 0x01, 0x12, 0x00]);
+//! machine.run_frame().unwrap();
+//! let state = savestate::save(&machine);
+//!
+//! machine.run_frame().unwrap();
+//! savestate::load(&mut machine, &state).unwrap();
+//! assert_eq!(machine.chip8.v[0], 4);
+//! assert_eq!(machine.frame(), 1);
+//! ```
+
+use std::fmt;
+
+use crate::framebuffer::Framebuffer;
+use crate::hardware::{HaltReason, State, KEY_COUNT, RAM_SIZE, STACK_SIZE, V_REG_COUNT, XO_RAM_SIZE};
+use crate::lz4;
+use crate::machine::Machine;
+use crate::patch::crc32;
+use crate::random::Host;
+
+pub const MAGIC: &[u8] = b"C8ST";
+// bump on every change to the body layout, and add a step to `MIGRATIONS`
+pub const VERSION: u16 = 3;
+
+// upgrades a body by one version, from `OLDEST_VERSION + n` to the next for entry n
+type Migration = fn(Vec<u8>) -> Result<Vec<u8>, StateError>;
+const MIGRATIONS: &[Migration] = &[
+    // 1 to 2: the layout stayed, only the way it is stored changed
+    Ok,
+    // 2 to 3: XO-CHIP mode, off, with the first bitplane selected
+    |mut body| {
+        body.extend_from_slice(&[0, 1]);
+        Ok(body)
+    },
+];
+const OLDEST_VERSION: u16 = VERSION - MIGRATIONS.len() as u16;
+const HEADER_LEN: usize = 10;
+// the first version with a compressed body
+const COMPRESSED_SINCE: u16 = 2;
+// bodies are a few KiB, a bigger size is damage rather than a state
+const MAX_BODY: usize = 1 << 20;
+
+// marks "no key" in the FX0A wait
+const NO_KEY: u8 = 0xFF;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum StateError {
+    // doesn't start with the magic
+    NotAState,
+    // written by a newer build
+    TooNew(u16),
+    // older than anything `migrate` still knows how to upgrade
+    TooOld(u16),
+    ChecksumMismatch,
+    // ended in the middle of a field, or of the compressed body
+    Truncated,
+    // the compressed body doesn't decompress to the size it should have
+    Corrupt,
+    // a field holds a value the core can't be in, e.g. a stack deeper than `STACK_SIZE`
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::NotAState => write!(f, "not a save state"),
+            StateError::TooNew(v) => write!(f, "save state is version {}, this build reads up to {}", v, VERSION),
+            StateError::TooOld(v) => write!(f, "save state version {} is no longer supported", v),
+            StateError::ChecksumMismatch => write!(f, "save state is corrupt (checksum mismatch)"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Corrupt => write!(f, "save state is corrupt (bad compressed data)"),
+            StateError::Invalid(what) => write!(f, "save state has an invalid {}", what),
+        }
+    }
+}
+
+/**
+ * Snapshot the machine in the current format. In XO-CHIP mode that includes the RAM past
+ * 4 KiB and the second bitplane.
+ *
+ * ```
+ * use chip8::machine::Machine;
+ * use chip8::savestate;
+ *
+ * let mut machine = Machine::new();
+ * machine.chip8.set_xo_chip(true);
+ * // I = 0xFFF0, V0 = 0x2A, store it, select plane 2, clear it, loop forever
+ * machine.load_rom(&[0xF0, 0x00, 0xFF, 0xF0, 0x60, 0x2A, 0xF0, 0x55, 0xF2, 0x01, 0x00, 0xE0, 0x12, 0x0C]);
+ * machine.run_frame().unwrap();
+ * let state = savestate::save(&machine);
+ *
+ * let mut restored = Machine::new();
+ * savestate::load(&mut restored, &state).unwrap();
+ * assert!(restored.chip8.xo_chip());
+ * assert_eq!(restored.chip8.get_ram()[0xFFF0], 0x2A);
+ * assert_eq!(restored.chip8.planes, 2);
+ * ```
+ */
+pub fn save(machine: &Machine) -> Vec<u8> {
+    let body = body(machine);
+    let mut out = MAGIC.to_vec();
+    put_u16(&mut out, VERSION);
+    out.extend_from_slice(&crc32(&body).to_le_bytes());
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend(lz4::compress(&body));
+    out
+}
+
+// the machine in the current body layout, uncompressed
+fn body(machine: &Machine) -> Vec<u8> {
+    let chip8 = &machine.chip8;
+    let mut body = Vec::new();
+
+    put_u16(&mut body, chip8.pc as u16);
+    put_u16(&mut body, chip8.i as u16);
+    body.push(chip8.stack.len() as u8);
+    for addr in &chip8.stack {
+        put_u16(&mut body, *addr as u16);
+    }
+    body.extend_from_slice(&chip8.v);
+    body.push(chip8.tim_delay);
+    body.push(chip8.tim_snd);
+    body.extend_from_slice(&chip8.get_ram()[..RAM_SIZE]);
+
+    let vram = chip8.get_vram();
+    put_u16(&mut body, vram.width() as u16);
+    put_u16(&mut body, vram.height() as u16);
+    put_pixels(&mut body, vram);
+
+    put_u16(&mut body, chip8.keys.iter().enumerate().fold(0, |mask, (n, down)| mask | ((*down as u16) << n)));
+    let (kind, reg) = match chip8.state {
+        State::Running => (0, 0),
+        State::Halted(HaltReason::Exit) => (1, 0),
+        State::Halted(HaltReason::WaitKey(reg)) => (2, reg as u8),
+    };
+    body.extend_from_slice(&[kind, reg, chip8.wait_key.map_or(NO_KEY, |k| k as u8)]);
+    put_u16(&mut body, chip8.font_addr as u16);
+    put_u16(&mut body, chip8.big_font_addr as u16);
+
+    body.extend_from_slice(&machine.frame().to_le_bytes());
+    body.extend_from_slice(&machine.cycle.to_le_bytes());
+
+    body.extend_from_slice(&[chip8.xo_chip() as u8, chip8.planes]);
+    if chip8.xo_chip() {
+        body.extend_from_slice(&chip8.get_ram()[RAM_SIZE..]);
+        put_pixels(&mut body, chip8.get_plane2());
+    }
+    body
+}
+
+/**
+ * A short fingerprint of the whole machine, frame number included: the CRC-32 a state saved
+ * now would carry in its header. Runs of the same ROM with the same settings and input are
+ * deterministic, so a verifier replaying a run has to reach the same checksum at the same
+ * frame. Settings like `cycles_per_frame` aren't part of it, but a run with different ones
+ * won't get there.
+ *
+ * ```
+ * use chip8::machine::Machine;
+ * use chip8::savestate;
+ *
+ * let run = |cycles| {
+ *     let mut machine = Machine::new();
+ *     machine.cycles_per_frame = cycles;
+ *     // V0 += 1, loop forever
+ *     machine.load_rom(&[0x70, 0x01, 0x12, 0x00]);
+ *     machine.run_frames(10, &[]).unwrap();
+ *     savestate::checksum(&machine)
+ * };
+ * assert_eq!(run(8), run(8));
+ * assert_ne!(run(8), run(10));
+ * ```
+ */
+pub fn checksum(machine: &Machine) -> u32 {
+    crc32(&body(machine))
+}
+
+/**
+ * Restore a state written by `save`, in this or an older format. The machine is only
+ * touched if the whole state is valid.
+ *
+ * ```
+ * use chip8::lz4;
+ * use chip8::machine::Machine;
+ * use chip8::savestate::{self, StateError};
+ *
+ * let mut machine = Machine::new();
+ * // V0 += 1, loop forever
+ * machine.load_rom(&[0x70, 0x01, 0x12, 0x00]);
+ * machine.run_frame().unwrap();
+ * let state = savestate::save(&machine);
+ * // 4 KiB of RAM that is empty but for the fonts and the program
+ * assert!(state.len() < 384);
+ *
+ * assert_eq!(savestate::load(&mut machine, &state[..state.len() - 10]), Err(StateError::Truncated));
+ * let mut damaged = state.clone();
+ * damaged[40] ^= 0x55;
+ * assert!(savestate::load(&mut machine, &damaged).is_err());
+ *
+ * // version 1: the body without the XO-CHIP mode and bitplanes, not compressed
+ * let size = u32::from_le_bytes(state[10..14].try_into().unwrap()) as usize;
+ * let body = lz4::decompress(&state[14..], size).unwrap();
+ * let body = &body[..body.len() - 2];
+ * let mut old = b"C8ST\x01\x00".to_vec();
+ * old.extend_from_slice(&chip8::patch::crc32(body).to_le_bytes());
+ * old.extend_from_slice(body);
+ * savestate::load(&mut machine, &old).unwrap();
+ * assert_eq!(machine.frame(), 1);
+ * ```
+ *
+ * The random number generator belongs to the session like the `draw_hook` does, `CXNN`
+ * carries on with it:
+ *
+ * ```
+ * use chip8::machine::Machine;
+ * use chip8::random::Algorithm;
+ * use chip8::savestate;
+ *
+ * let start = || {
+ *     let mut machine = Machine::new();
+ *     machine.chip8.random = Algorithm::Xorshift.create();
+ *     machine.chip8.seed(7);
+ *     // V0 = random, loop forever
+ *     machine.load_rom(&[0xC0, 0xFF, 0x12, 0x00]);
+ *     machine
+ * };
+ * let roll = |machine: &mut Machine| {
+ *     (0..6).map(|_| {
+ *         machine.chip8.cycle().unwrap();
+ *         machine.chip8.cycle().unwrap();
+ *         machine.chip8.v[0]
+ *     }).collect::<Vec<u8>>()
+ * };
+ *
+ * let mut straight = start();
+ * let expected = [roll(&mut straight), roll(&mut straight)].concat();
+ *
+ * let mut machine = start();
+ * let first = roll(&mut machine);
+ * let state = savestate::save(&machine);
+ * savestate::load(&mut machine, &state).unwrap();
+ * assert_eq!([first, roll(&mut machine)].concat(), expected);
+ * ```
+ */
+pub fn load(machine: &mut Machine, data: &[u8]) -> Result<(), StateError> {
+    if !data.starts_with(MAGIC) {
+        return Err(StateError::NotAState);
+    }
+    if data.len() < HEADER_LEN {
+        return Err(StateError::Truncated);
+    }
+
+    let version = u16::from_le_bytes([data[4], data[5]]);
+    if version > VERSION {
+        return Err(StateError::TooNew(version));
+    }
+    let body = match version {
+        COMPRESSED_SINCE.. => decompress(&data[HEADER_LEN..])?,
+        _ => data[HEADER_LEN..].to_vec(),
+    };
+    if crc32(&body) != u32::from_le_bytes([data[6], data[7], data[8], data[9]]) {
+        return Err(StateError::ChecksumMismatch);
+    }
+
+    let body = migrate(version, body)?;
+    let mut r = Reader { data: &body, pos: 0 };
+    let mut restored = Machine::new();
+    let chip8 = &mut restored.chip8;
+
+    chip8.pc = r.u16()? as usize;
+    chip8.i = r.u16()? as usize;
+    let depth = r.u8()? as usize;
+    if depth > STACK_SIZE {
+        return Err(StateError::Invalid("stack depth"));
+    }
+    chip8.stack = (0..depth).map(|_| r.u16().map(usize::from)).collect::<Result<_, _>>()?;
+    chip8.sp = depth;
+    chip8.v.copy_from_slice(r.bytes(V_REG_COUNT)?);
+    chip8.tim_delay = r.u8()?;
+    chip8.tim_snd = r.u8()?;
+    chip8.load_ram(r.bytes(RAM_SIZE)?, 0).map_err(|_| StateError::Invalid("RAM"))?;
+
+    let (width, height) = (r.u16()? as usize, r.u16()? as usize);
+    if width == 0 || height == 0 {
+        return Err(StateError::Invalid("display size"));
+    }
+    chip8.vram = r.pixels(width, height)?;
+    chip8.vram_changed = true;
+    chip8.resolution_changed = true;
+
+    let keys = r.u16()?;
+    for (n, key) in chip8.keys.iter_mut().enumerate() {
+        *key = keys & (1 << n) != 0;
+    }
+    let (kind, reg) = (r.u8()?, r.u8()? as usize);
+    chip8.state = match kind {
+        0 => State::Running,
+        1 => State::Halted(HaltReason::Exit),
+        2 if reg < V_REG_COUNT => State::Halted(HaltReason::WaitKey(reg)),
+        _ => return Err(StateError::Invalid("halt state")),
+    };
+    chip8.wait_key = match r.u8()? {
+        NO_KEY => None,
+        key if (key as usize) < KEY_COUNT => Some(key as usize),
+        _ => return Err(StateError::Invalid("key")),
+    };
+    chip8.font_addr = r.u16()? as usize;
+    chip8.big_font_addr = r.u16()? as usize;
+
+    restored.frame = u64::from_le_bytes(r.bytes(8)?.try_into().unwrap());
+    restored.cycle = u32::from_le_bytes(r.bytes(4)?.try_into().unwrap());
+
+    let chip8 = &mut restored.chip8;
+    let (xo_chip, planes) = (r.u8()?, r.u8()?);
+    if xo_chip > 1 {
+        return Err(StateError::Invalid("mode"));
+    }
+    if planes > 3 {
+        return Err(StateError::Invalid("bitplanes"));
+    }
+    chip8.planes = planes;
+    chip8.vram2 = Framebuffer::new(width, height);
+    if xo_chip == 1 {
+        chip8.set_xo_chip(true);
+        chip8.load_ram(r.bytes(XO_RAM_SIZE - RAM_SIZE)?, RAM_SIZE).map_err(|_| StateError::Invalid("RAM"))?;
+        chip8.vram2 = r.pixels(width, height)?;
+    }
+    if chip8.pc >= chip8.get_ram().len() - 1 {
+        return Err(StateError::Invalid("PC"));
+    }
+    if r.pos != body.len() {
+        return Err(StateError::Invalid("length"));
+    }
+
+    // what belongs to the session rather than the state
+    restored.chip8.trace = machine.chip8.trace;
+    restored.chip8.hardened = machine.chip8.hardened;
+    restored.chip8.strict = machine.chip8.strict;
+    restored.chip8.quirks = machine.chip8.quirks;
+    restored.chip8.font_set = machine.chip8.font_set;
+    restored.chip8.random = std::mem::replace(&mut machine.chip8.random, Box::new(Host::new()));
+    restored.chip8.draw_hook = machine.chip8.draw_hook.take();
+    restored.chip8.peripherals = std::mem::take(&mut machine.chip8.peripherals);
+    machine.chip8 = restored.chip8;
+    machine.frame = restored.frame;
+    machine.cycle = restored.cycle;
+    // the instructions that led here aren't the ones in the history
+    machine.history.clear();
+    Ok(())
+}
+
+/**
+ * The body of a compressed state: its size, then the LZ4 block.
+ */
+fn decompress(data: &[u8]) -> Result<Vec<u8>, StateError> {
+    let size = data.get(..4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize).ok_or(StateError::Truncated)?;
+    if size > MAX_BODY {
+        return Err(StateError::Invalid("body size"));
+    }
+    lz4::decompress(&data[4..], size).map_err(|e| match e {
+        lz4::Error::Truncated => StateError::Truncated,
+        lz4::Error::Corrupt => StateError::Corrupt,
+    })
+}
+
+/**
+ * Upgrade a body from `version` to the current layout, one version at a time.
+ */
+fn migrate(version: u16, mut body: Vec<u8>) -> Result<Vec<u8>, StateError> {
+    if version < OLDEST_VERSION {
+        return Err(StateError::TooOld(version));
+    }
+    for upgrade in &MIGRATIONS[(version - OLDEST_VERSION) as usize..] {
+        body = upgrade(body)?;
+    }
+    Ok(body)
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+// 8 pixels to a byte, leftmost in the high bit
+fn put_pixels(out: &mut Vec<u8>, screen: &Framebuffer) {
+    for chunk in screen.rows().flatten().copied().collect::<Vec<bool>>().chunks(8) {
+        out.push(chunk.iter().enumerate().fold(0, |byte, (n, px)| byte | ((*px as u8) << (7 - n))));
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or(StateError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn pixels(&mut self, width: usize, height: usize) -> Result<Framebuffer, StateError> {
+        let packed = self.bytes((width * height).div_ceil(8))?;
+        let mut screen = Framebuffer::new(width, height);
+        for n in 0..width * height {
+            screen.set(n % width, n / width, packed[n / 8] & (0x80 >> (n % 8)) != 0).map_err(|_| StateError::Invalid("display size"))?;
+        }
+        Ok(screen)
+    }
+}
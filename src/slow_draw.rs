@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chip8::framebuffer::Framebuffer;
+use chip8::hardware::DrawRow;
+use chip8::machine::Machine;
+
+// how long each sprite row stays the newest one on screen
+const ROW_TIME: Duration = Duration::from_millis(150);
+
+/**
+ * Slow motion for `DXYN`, for showing how CHIP-8 graphics work: after a frame that drew
+ * sprites the screen goes back to how it was before the frame and the sprite rows appear
+ * one by one, each with the address and byte it came from, see `current`. The program
+ * waits until all of them are shown.
+ */
+pub struct SlowDraw {
+    // rows the core drew in the current frame, filled by `Chip8::draw_hook`
+    drawn: Arc<Mutex<Vec<DrawRow>>>,
+    // the screen before the frame, kept in case the frame draws
+    before: Option<Framebuffer>,
+    // what is shown instead of the real screen while rows are being revealed
+    screen: Option<Framebuffer>,
+    queue: VecDeque<DrawRow>,
+    // the row revealed last, while there is one
+    pub current: Option<DrawRow>,
+    // when `current` was revealed, `None` to reveal the next row right away
+    shown_at: Option<Instant>,
+}
+
+impl SlowDraw {
+    pub fn attach(machine: &mut Machine) -> SlowDraw {
+        let drawn = Arc::new(Mutex::new(Vec::new()));
+        let sink = drawn.clone();
+        machine.chip8.draw_hook = Some(Box::new(move |row: &DrawRow| sink.lock().unwrap().push(*row)));
+        SlowDraw { drawn, before: None, screen: None, queue: VecDeque::new(), current: None, shown_at: None }
+    }
+
+    pub fn detach(machine: &mut Machine) {
+        machine.chip8.draw_hook = None;
+    }
+
+    /**
+     * Whether rows are still being revealed, the machine shouldn't run meanwhile.
+     */
+    pub fn busy(&self) -> bool {
+        self.screen.is_some()
+    }
+
+    pub fn before_frame(&mut self, machine: &Machine) {
+        self.before = Some(machine.chip8.get_vram().clone());
+    }
+
+    /**
+     * Start revealing the rows the frame drew, if any. True if it did.
+     */
+    pub fn after_frame(&mut self) -> bool {
+        let rows: Vec<DrawRow> = self.drawn.lock().unwrap().drain(..).collect();
+        if rows.is_empty() {
+            return false;
+        }
+        self.screen = self.before.take();
+        self.queue = rows.into();
+        self.current = None;
+        self.shown_at = None;
+        true
+    }
+
+    /**
+     * Reveal the next row once the last one has been shown long enough, copying its pixels
+     * from `vram`, the real screen. True if what is shown changed.
+     */
+    pub fn advance(&mut self, vram: &Framebuffer) -> bool {
+        let Some(screen) = &mut self.screen else {
+            return false;
+        };
+        if self.shown_at.is_some_and(|shown| shown.elapsed() < ROW_TIME) {
+            return false;
+        }
+        // a resolution change in the frame, there is nothing sensible to reveal
+        if (screen.width(), screen.height()) != (vram.width(), vram.height()) {
+            self.queue.clear();
+        }
+
+        self.shown_at = Some(Instant::now());
+        self.current = self.queue.pop_front();
+        match self.current {
+            Some(row) => {
+                for x in (row.x..row.x + 8).take_while(|x| *x < vram.width()) {
+                    let _ = screen.set(x, row.y, vram.get(x, row.y).unwrap_or(false));
+                }
+            }
+            // done, the real screen takes over with whatever else the frame did
+            None => self.screen = None,
+        }
+        true
+    }
+
+    pub fn screen(&self) -> Option<&Framebuffer> {
+        self.screen.as_ref()
+    }
+}
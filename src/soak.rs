@@ -0,0 +1,125 @@
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use chip8::hardware::KEY_COUNT;
+use chip8::machine::Machine;
+
+// how often progress and memory use are reported
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+// resident memory growth past the first report that counts as a leak
+const MAX_GROWTH_KB: u64 = 32 * 1024;
+
+/**
+ * Endurance test: run `instances` headless machines flat out for `duration`, pressing random
+ * keys, restarting the ROM whenever it exits. Fails on any panic or core error, or when
+ * the process' memory keeps growing.
+ */
+pub fn run(rom: &[u8], instances: usize, duration: Duration) -> Result<(), String> {
+    let deadline = Instant::now() + duration;
+    let frames = Arc::new(AtomicU64::new(0));
+    // set when one instance fails, so the rest don't keep running until the deadline
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // a ROM that doesn't load is reported once, not by every instance
+    start(rom)?;
+    println!("soaking {} instances for {:.1}h", instances, duration.as_secs_f64() / 3600.0);
+
+    let workers: Vec<_> = (0..instances)
+        .map(|n| {
+            let rom = rom.to_vec();
+            let (frames, stop) = (frames.clone(), stop.clone());
+            thread::Builder::new()
+                .name(format!("soak-{}", n))
+                .spawn(move || soak_one(&rom, deadline, &frames, &stop))
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Result<_, _>>()?;
+
+    let mut baseline = None;
+    let mut peak = 0;
+    while Instant::now() < deadline && !workers.iter().any(|w| w.is_finished()) {
+        thread::sleep(REPORT_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+
+        let rss = resident_kb();
+        if let Some(rss) = rss {
+            baseline.get_or_insert(rss);
+            peak = peak.max(rss);
+        }
+        println!("{} frames, {} KiB resident", frames.load(Ordering::Relaxed),
+            rss.map_or("?".to_string(), |kb| kb.to_string()));
+    }
+
+    stop.store(true, Ordering::Relaxed);
+
+    let mut failures = Vec::new();
+    for (n, worker) in workers.into_iter().enumerate() {
+        match worker.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => failures.push(format!("instance {}: {}", n, e)),
+            Err(panic) => {
+                let msg = panic
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                failures.push(format!("instance {} panicked: {}", n, msg));
+            }
+        }
+    }
+
+    if let Some(base) = baseline {
+        if peak > base + MAX_GROWTH_KB {
+            failures.push(format!("memory grew from {} KiB to {} KiB", base, peak));
+        }
+    }
+
+    if failures.is_empty() {
+        println!("soak passed, {} frames", frames.load(Ordering::Relaxed));
+        Ok(())
+    } else {
+        Err(failures.join("\n"))
+    }
+}
+
+fn soak_one(rom: &[u8], deadline: Instant, frames: &AtomicU64, stop: &AtomicBool) -> Result<(), String> {
+    let mut rng = rand::thread_rng();
+    let mut machine = start(rom)?;
+
+    while Instant::now() < deadline && !stop.load(Ordering::Relaxed) {
+        if rng.gen_ratio(1, 8) {
+            machine.chip8.set_input(rng.gen_range(0..KEY_COUNT), rng.gen());
+        }
+
+        machine
+            .run_frame()
+            .map_err(|e| format!("{:?} at PC {:#05x}, frame {}", e, machine.chip8.pc, machine.frame()))?;
+        frames.fetch_add(1, Ordering::Relaxed);
+
+        if machine.chip8.exited() {
+            machine = start(rom)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn start(rom: &[u8]) -> Result<Machine, String> {
+    let mut machine = Machine::new();
+    machine.load_program(rom, true).map_err(|e| format!("can't load ROM: {}", e))?;
+    Ok(machine)
+}
+
+/**
+ * Resident set size of this process, Linux only.
+ */
+fn resident_kb() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // statm counts pages, assume the usual 4 KiB
+    Some(pages * 4)
+}
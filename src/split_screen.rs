@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+
+use chip8::framebuffer::Framebuffer;
+use chip8::hardware::{CHIP8_HEIGHT, CHIP8_WIDTH, KEY_COUNT, MULTIPLIER};
+use chip8::machine::Machine;
+
+use crate::config::Config;
+use crate::fixedstep::FixedStep;
+use crate::keymap::{Binding, KeyMap};
+use crate::render::{self, Render};
+use crate::routing::Device;
+
+// tiles per row, a third and fourth tile go below the first two
+const COLS: usize = 2;
+
+/**
+ * One of the games on screen.
+ */
+struct Tile {
+    machine: Machine,
+    // file name of the ROM, for the window title
+    name: String,
+    // the fault that stopped it, the other tiles keep running
+    fault: Option<String>,
+}
+
+/**
+ * Run 2 to `config::SPLIT_MAX` ROMs side by side in one window, each on its own machine with the
+ * options from the command line. Two tiles sit next to each other, three or four in a 2x2
+ * grid:
+ *
+ * ```text
+ * +---------+---------+
+ * | pong    | tetris  |
+ * +---------+---------+
+ * | brix    |         |
+ * +---------+---------+
+ * ```
+ *
+ * The keypad goes to the focused tile, outlined, and only it beeps. Tab moves the focus on,
+ * clicking a tile focuses it. Escape or closing the window quits. A tile that faults stops
+ * with a message and the others carry on.
+ */
+pub fn run(config: &Config, paths: &[String]) -> Result<(), String> {
+    let mut tiles = paths.iter().map(|path| open(config, path)).collect::<Result<Vec<Tile>, String>>()?;
+    let rows = tiles.len().div_ceil(COLS);
+
+    // as wide as the single screen window, as high as the tiles need
+    let width = CHIP8_WIDTH * MULTIPLIER;
+    let height = width * (rows as u32 * CHIP8_HEIGHT) / (COLS as u32 * CHIP8_WIDTH);
+    let mut renderer = Render::new("Chip8", width, height, false, config.backend)?;
+    renderer.open_buzzer(config.audio)?;
+    renderer.palette = config.palette;
+    renderer.picture = config.picture;
+    renderer.scaling = config.scaling.unwrap_or_default();
+
+    let lang = config.lang;
+    let keymap = KeyMap::load(&config.keys_file)?;
+    let mut fixedstep = FixedStep::start(config.refresh_rate * config.speed);
+    let mut focus = 0;
+    let mut title_focus = None;
+
+    'running: loop {
+        while fixedstep.update() {
+            for tile in tiles.iter_mut().filter(|tile| tile.fault.is_none()) {
+                if let Err(e) = tile.machine.run_frame() {
+                    println!("{}: {}", tile.name, e);
+                    tile.fault = Some(e.to_string());
+                }
+            }
+        }
+
+        for event in renderer.event_pump.poll_iter().collect::<Vec<_>>() {
+            let next = match event {
+                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => break 'running,
+                Event::KeyDown { keycode: Some(Keycode::Tab), repeat: false, .. } => Some((focus + 1) % tiles.len()),
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                    renderer.screen_to_cell(x, y).map(|(x, y)| tile_at(&tiles, x, y)).filter(|n| *n < tiles.len())
+                }
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } | Event::KeyUp { keycode: Some(keycode), repeat: false, .. } => {
+                    let down = matches!(event, Event::KeyDown { .. });
+                    if let Some(key) = keymap.lookup(Binding::Key(keycode)).filter(|k| config.routing.allows(Device::Keyboard, *k)) {
+                        tiles[focus].machine.chip8.set_input(key, down);
+                        renderer.keys[key] = down;
+                    }
+                    None
+                }
+                _ => None,
+            };
+            // keys held for the tile that had the focus are let go
+            if let Some(next) = next.filter(|next| *next != focus) {
+                for key in (0..KEY_COUNT).filter(|key| renderer.keys[*key]) {
+                    tiles[focus].machine.chip8.set_input(key, false);
+                }
+                renderer.keys = [false; KEY_COUNT];
+                focus = next;
+            }
+        }
+
+        if title_focus != Some(focus) {
+            let title = lang.format("window.split", &[&tiles[focus].name, &(focus + 1), &tiles.len()]);
+            renderer.display.window_mut().set_title(&title).map_err(|e| e.to_string())?;
+            title_focus = Some(focus);
+        }
+        renderer.set_beep(tiles[focus].machine.chip8.tim_snd > 0);
+
+        let screens: Vec<&Framebuffer> = tiles.iter().map(|tile| tile.machine.chip8.get_vram()).collect();
+        let (screen, (cell_w, cell_h)) = tile(&screens);
+        renderer.tile_box = Some(((focus % COLS) * cell_w, (focus / COLS) * cell_h, cell_w, cell_h));
+        let faults: Vec<String> = tiles.iter().filter_map(|tile| tile.fault.as_ref().map(|e| format!("{}: {}", tile.name, e))).collect();
+        renderer.messages = faults.join("\n");
+        renderer.set_visible(render::Layer::Messages, !renderer.messages.is_empty());
+        renderer.update(&screen, None)?;
+
+        thread::sleep(fixedstep.until_next());
+    }
+
+    Ok(())
+}
+
+fn open(config: &Config, path: &str) -> Result<Tile, String> {
+    let rom = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let mut machine = Machine::new();
+    if let Some(platform) = config.platform {
+        platform.apply(&mut machine.chip8).map_err(|e| e.to_string())?;
+    }
+    machine.chip8.set_xo_chip(config.xo_chip);
+    machine.load_program(&rom, true).map_err(|e| format!("can't load {}: {}", path, e))?;
+    machine.chip8.strict = config.strict;
+    machine.chip8.quirks = config.quirks;
+    machine.draw_budget = config.draw_budget;
+    if let Some(cycles) = config.cycles_per_frame {
+        machine.cycles_per_frame = cycles;
+    }
+    machine.chip8.random = config.rng.create();
+
+    let name = Path::new(path).file_name().map_or(path.to_string(), |name| name.to_string_lossy().into_owned());
+    Ok(Tile { machine, name, fault: None })
+}
+
+/**
+ * The screens in a grid `COLS` wide, each in a cell the size of the largest one, and the
+ * size of a cell. A 64x32 screen among 128x64 ones is shown at twice the size.
+ */
+fn tile(screens: &[&Framebuffer]) -> (Framebuffer, (usize, usize)) {
+    let cell_w = screens.iter().map(|s| s.width()).max().unwrap_or(CHIP8_WIDTH as usize);
+    let cell_h = screens.iter().map(|s| s.height()).max().unwrap_or(CHIP8_HEIGHT as usize);
+    let rows = screens.len().div_ceil(COLS);
+    let mut out = Framebuffer::new(COLS * cell_w, rows * cell_h);
+
+    for (n, screen) in screens.iter().enumerate() {
+        let scale = (cell_w / screen.width()).min(cell_h / screen.height()).max(1);
+        let (left, top) = ((n % COLS) * cell_w, (n / COLS) * cell_h);
+        for (x, y) in screen.iter_set_pixels() {
+            for (dx, dy) in (0..scale).flat_map(|dx| (0..scale).map(move |dy| (dx, dy))) {
+                let _ = out.set(left + x * scale + dx, top + y * scale + dy, true);
+            }
+        }
+    }
+
+    (out, (cell_w, cell_h))
+}
+
+// the tile under pixel X/Y of the tiled screen
+fn tile_at(tiles: &[Tile], x: usize, y: usize) -> usize {
+    let cell_w = tiles.iter().map(|t| t.machine.chip8.get_vram().width()).max().unwrap_or(1);
+    let cell_h = tiles.iter().map(|t| t.machine.chip8.get_vram().height()).max().unwrap_or(1);
+    y / cell_h * COLS + x / cell_w
+}
@@ -0,0 +1,123 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+use chip8::palette::Palette;
+
+use crate::config::RenderBackend;
+use crate::render::Render;
+
+// on-screen size of a single sprite pixel
+const CELL: u32 = 40;
+// DXYN draws at most 15 rows
+const MAX_ROWS: usize = 15;
+const DEFAULT_ROWS: usize = 8;
+
+/**
+ * An 8xN sprite being edited, one byte per row, MSB is the leftmost pixel.
+ */
+pub struct SpriteEditor {
+    rows: Vec<u8>,
+}
+
+impl SpriteEditor {
+    pub fn new(height: usize) -> Self {
+        SpriteEditor {
+            rows: vec![0; height.clamp(1, MAX_ROWS)],
+        }
+    }
+
+    pub fn toggle(&mut self, x: usize, y: usize) {
+        if x < 8 && y < self.rows.len() {
+            self.rows[y] ^= 0x80 >> x;
+        }
+    }
+
+    pub fn resize(&mut self, height: usize) {
+        self.rows.resize(height.clamp(1, MAX_ROWS), 0);
+    }
+
+    pub fn clear(&mut self) {
+        self.rows.fill(0);
+    }
+
+    /**
+     * Sprite bytes as plain hex, e.g. `3C 42 42 3C`.
+     */
+    pub fn to_hex(&self) -> String {
+        self.rows.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")
+    }
+
+    /**
+     * Sprite as an Octo label with data, ready to paste into a `.8o` source.
+     */
+    pub fn to_octo(&self, label: &str) -> String {
+        let bytes: Vec<String> = self.rows.iter().map(|b| format!("0x{:02X}", b)).collect();
+        format!(": {}\n\t{}\n", label, bytes.join(" "))
+    }
+
+    fn draw(&self, renderer: &mut Render, palette: &Palette) -> Result<(), String> {
+        let [r, g, b] = palette.off;
+        renderer.display.set_draw_color(Color::RGB(r, g, b));
+        renderer.display.clear();
+
+        for (y, row) in self.rows.iter().enumerate() {
+            for x in 0..8 {
+                let [r, g, b] = palette.color(row & (0x80 >> x) != 0);
+                let cell = Rect::new((x as u32 * CELL) as i32, (y as u32 * CELL) as i32, CELL, CELL);
+
+                renderer.display.set_draw_color(Color::RGB(r, g, b));
+                renderer.display.fill_rect(cell)?;
+                renderer.display.set_draw_color(Color::GRAY);
+                renderer.display.draw_rect(cell)?;
+            }
+        }
+
+        renderer.display.present()?;
+        Ok(())
+    }
+}
+
+/**
+ * Run the sprite editor window until it is closed.
+ * Left click toggles a pixel, Up/Down change the height, Delete clears,
+ * C copies the sprite to the clipboard in Octo syntax (and prints it), Escape quits.
+ */
+pub fn run(palette: &Palette, backend: RenderBackend) -> Result<(), String> {
+    let mut renderer = Render::new("Chip8 sprite editor", 8 * CELL, MAX_ROWS as u32 * CELL, false, backend)?;
+    let mut editor = SpriteEditor::new(DEFAULT_ROWS);
+
+    editor.draw(&mut renderer, palette)?;
+
+    loop {
+        let event = renderer.event_pump.wait_event();
+
+        match event {
+            Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => break,
+            Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                editor.toggle(x as usize / CELL as usize, y as usize / CELL as usize);
+            }
+            Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+                editor.resize(editor.rows.len() - 1);
+            }
+            Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+                editor.resize(editor.rows.len() + 1);
+            }
+            Event::KeyDown { keycode: Some(Keycode::Delete), .. } => editor.clear(),
+            Event::KeyDown { keycode: Some(Keycode::C), .. } => {
+                let octo = editor.to_octo("sprite");
+                renderer.display.window().subsystem().clipboard().set_clipboard_text(&octo)?;
+                print!("{}", octo);
+            }
+            _ => continue,
+        }
+
+        renderer.display.window_mut().set_title(&format!("Chip8 sprite editor - {}", editor.to_hex()))
+            .map_err(|e| e.to_string())?;
+        editor.draw(&mut renderer, palette)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::fmt::Write;
+use std::fs;
+
+use chip8::hardware::{HaltReason, State};
+use chip8::machine::Machine;
+use chip8::palette::Palette;
+use chip8::savestate;
+
+use crate::config::Format;
+use crate::png;
+
+/**
+ * Restore the state in the file at `path` into a fresh machine.
+ */
+fn load(path: &str) -> Result<Machine, String> {
+    let data = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let mut machine = Machine::new();
+    savestate::load(&mut machine, &data).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(machine)
+}
+
+fn state_name(state: State) -> String {
+    match state {
+        State::Running => "running".to_string(),
+        State::Halted(HaltReason::Exit) => "exited".to_string(),
+        State::Halted(HaltReason::WaitKey(reg)) => format!("waiting for a key into V{:X}", reg),
+    }
+}
+
+fn to_text(machine: &Machine) -> String {
+    let chip8 = &machine.chip8;
+    let vram = chip8.get_vram();
+    let mut out = String::new();
+
+    let _ = writeln!(out, "frame {}, {}, checksum {:08x}", machine.frame(), state_name(chip8.state), savestate::checksum(machine));
+    let _ = writeln!(out, "PC {:03X}  I {:03X}  DT {:02X}  ST {:02X}", chip8.pc, chip8.i, chip8.tim_delay, chip8.tim_snd);
+    for (n, v) in chip8.v.iter().enumerate() {
+        let _ = write!(out, "V{:X} {:02X}{}", n, v, if n % 8 == 7 { "\n" } else { "  " });
+    }
+    let stack: Vec<String> = chip8.stack.iter().map(|addr| format!("{:03X}", addr)).collect();
+    let _ = writeln!(out, "stack [{}]", stack.join(" "));
+    let keys: Vec<String> = (0..chip8.keys.len()).filter(|k| chip8.keys[*k]).map(|k| format!("{:X}", k)).collect();
+    let _ = writeln!(out, "keys held [{}]", keys.join(" "));
+
+    let _ = writeln!(out, "\ndisplay {}x{}", vram.width(), vram.height());
+    for row in vram.rows() {
+        out.extend(row.iter().map(|px| if *px { '#' } else { '.' }));
+        out.push('\n');
+    }
+
+    out.push_str("\nRAM\n");
+    for (n, line) in chip8.get_ram().chunks(16).enumerate() {
+        let bytes: Vec<String> = line.iter().map(|b| format!("{:02X}", b)).collect();
+        let _ = writeln!(out, "{:03X}: {}", n * 16, bytes.join(" "));
+    }
+    out
+}
+
+fn to_json(machine: &Machine) -> String {
+    let chip8 = &machine.chip8;
+    let vram = chip8.get_vram();
+    let list = |items: Vec<String>| items.join(", ");
+
+    let display: Vec<String> = vram
+        .rows()
+        .map(|row| format!("\"{}\"", row.iter().map(|px| if *px { '#' } else { '.' }).collect::<String>()))
+        .collect();
+    let ram: String = chip8.get_ram().iter().map(|b| format!("{:02x}", b)).collect();
+
+    format!(
+        "{{\n  \"version\": {},\n  \"frame\": {},\n  \"checksum\": \"{:08x}\",\n  \"state\": \"{}\",\n  \"pc\": {},\n  \"i\": {},\n  \"stack\": [{}],\n  \"v\": [{}],\n  \"delay_timer\": {},\n  \"sound_timer\": {},\n  \"keys\": [{}],\n  \"font_addr\": {},\n  \"big_font_addr\": {},\n  \"display\": {{\n    \"width\": {},\n    \"height\": {},\n    \"rows\": [\n      {}\n    ]\n  }},\n  \"ram\": \"{}\"\n}}\n",
+        savestate::VERSION,
+        machine.frame(),
+        savestate::checksum(machine),
+        state_name(chip8.state),
+        chip8.pc,
+        chip8.i,
+        list(chip8.stack.iter().map(|addr| addr.to_string()).collect()),
+        list(chip8.v.iter().map(|v| v.to_string()).collect()),
+        chip8.tim_delay,
+        chip8.tim_snd,
+        list(chip8.keys.iter().map(|k| k.to_string()).collect()),
+        chip8.font_addr,
+        chip8.big_font_addr,
+        vram.width(),
+        vram.height(),
+        display.join(",\n      "),
+        ram,
+    )
+}
+
+/**
+ * `state dump`: print the registers, display and RAM of the state at `path`, or write them
+ * to `out`. JSON keeps addresses and registers as numbers and RAM as one hex string.
+ */
+pub fn dump(path: &str, format: Format, out: Option<&str>) -> Result<(), String> {
+    let machine = load(path)?;
+    let text = match format {
+        Format::Text => to_text(&machine),
+        Format::Json => to_json(&machine),
+    };
+
+    match out {
+        Some(out) => fs::write(out, text).map_err(|e| format!("can't write {}: {}", out, e)),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+/**
+ * `state extract-vram`: write the display of the state at `path` as a PNG in `palette`'s
+ * colors, one image pixel per CHIP-8 pixel and both XO-CHIP bitplanes composed. The frame and state checksum go in its metadata,
+ * see `savestate::checksum`.
+ */
+pub fn extract_vram(path: &str, palette: &Palette, out: &str) -> Result<(), String> {
+    let machine = load(path)?;
+    let vram = machine.chip8.get_vram();
+    let frame = machine.frame().to_string();
+    let checksum = format!("{:08x}", savestate::checksum(&machine));
+    let text = [("Software", "rust8"), ("Frame", frame.as_str()), ("State checksum", checksum.as_str())];
+    let image = png::encode(vram.width(), vram.height(), &machine.chip8.to_rgba(palette), &text);
+    fs::write(out, image).map_err(|e| format!("can't write {}: {}", out, e))
+}
@@ -0,0 +1,139 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use chip8::machine::Machine;
+
+/**
+ * Performance counters for a run. The emulated counts come from the `Machine`, the rest
+ * is filled in by the frontend.
+ */
+pub struct Stats {
+    started: Instant,
+    // screen updates actually presented
+    pub frames_rendered: u64,
+    // time spent running frames, drawing and sleeping in the main loop
+    pub emulate: Duration,
+    pub render: Duration,
+    pub sleep: Duration,
+    // faults hit during the run, also the ones skipped past in the debugger
+    pub faults: Vec<String>,
+    // `--frame-times`, a row per pass of the main loop
+    frame_log: Option<FrameLog>,
+}
+
+/**
+ * The CSV of `--frame-times`, with the counters as they were at the end of the last row.
+ */
+struct FrameLog {
+    path: String,
+    out: BufWriter<File>,
+    cycles: u64,
+    emulate: Duration,
+    render: Duration,
+    sleep: Duration,
+    samples: Option<u64>,
+}
+
+impl Stats {
+    pub fn start() -> Self {
+        Stats {
+            started: Instant::now(),
+            frames_rendered: 0,
+            emulate: Duration::ZERO,
+            render: Duration::ZERO,
+            sleep: Duration::ZERO,
+            faults: Vec::new(),
+            frame_log: None,
+        }
+    }
+
+    /**
+     * Write a row to `path` on every `end_pass` from now on, what each counter went up by
+     * during the pass:
+     *
+     * ```text
+     * frame,cycles,emulate_us,render_us,sleep_us,audio_samples
+     * 1,10,85,1210,15360,
+     * 2,10,42,230,16180,1024
+     * ```
+     *
+     * `frame` is the emulated frame at the end of the pass, `audio_samples` the sample
+     * frames the audio device pulled, empty without one.
+     */
+    pub fn log_frames(&mut self, machine: &Machine, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("can't write frame times {}: {}", path, e))?;
+        let mut out = BufWriter::new(file);
+        writeln!(out, "frame,cycles,emulate_us,render_us,sleep_us,audio_samples")
+            .map_err(|e| format!("can't write frame times {}: {}", path, e))?;
+        self.frame_log = Some(FrameLog {
+            path: path.to_string(),
+            out,
+            cycles: machine.cycles(),
+            emulate: self.emulate,
+            render: self.render,
+            sleep: self.sleep,
+            samples: None,
+        });
+        Ok(())
+    }
+
+    /**
+     * End a pass of the main loop, adding its row to the `--frame-times` CSV if there is one.
+     */
+    pub fn end_pass(&mut self, machine: &Machine, samples: Option<u64>) -> Result<(), String> {
+        let Some(log) = &mut self.frame_log else {
+            return Ok(());
+        };
+        let micros = |now: Duration, then: Duration| (now - then).as_micros();
+        // the device may have been opened after the log, its first reading counts from zero
+        let pulled = samples.map_or(String::new(), |now| (now - log.samples.unwrap_or(0)).to_string());
+
+        writeln!(
+            log.out,
+            "{},{},{},{},{},{}",
+            machine.frame(),
+            machine.cycles() - log.cycles,
+            micros(self.emulate, log.emulate),
+            micros(self.render, log.render),
+            micros(self.sleep, log.sleep),
+            pulled,
+        )
+        .map_err(|e| format!("can't write frame times {}: {}", log.path, e))?;
+
+        log.cycles = machine.cycles();
+        (log.emulate, log.render, log.sleep, log.samples) = (self.emulate, self.render, self.sleep, samples);
+        Ok(())
+    }
+
+    /**
+     * Flush the `--frame-times` CSV, if there is one.
+     */
+    pub fn finish_frames(&mut self) -> Result<(), String> {
+        match &mut self.frame_log {
+            Some(log) => log.out.flush().map_err(|e| format!("can't write frame times {}: {}", log.path, e)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn to_json(&self, machine: &Machine) -> String {
+        let elapsed = self.started.elapsed().as_secs_f64();
+
+        format!(
+            "{{\n  \"cycles\": {},\n  \"frames\": {},\n  \"frames_rendered\": {},\n  \"draw_calls\": {},\n  \"emulate_secs\": {:.3},\n  \"render_secs\": {:.3},\n  \"sleep_secs\": {:.3},\n  \"elapsed_secs\": {:.3},\n  \"cycles_per_sec\": {:.1}\n}}\n",
+            machine.cycles(),
+            machine.frame(),
+            self.frames_rendered,
+            machine.draws(),
+            self.emulate.as_secs_f64(),
+            self.render.as_secs_f64(),
+            self.sleep.as_secs_f64(),
+            elapsed,
+            machine.cycles() as f64 / elapsed.max(f64::EPSILON),
+        )
+    }
+
+    pub fn write_json(&self, machine: &Machine, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_json(machine)).map_err(|e| format!("can't write stats {}: {}", path, e))
+    }
+}
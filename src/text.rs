@@ -0,0 +1,98 @@
+//! ASCII text in an 8x8 bitmap font, for overlays, menus and help screens drawn on top of
+//! the emulated display. Text is rendered into a `Framebuffer` so any frontend can show it.
+
+use crate::font::TEXT_FONT;
+use crate::framebuffer::Framebuffer;
+
+// width and height of a character cell, in pixels
+pub const GLYPH_SIZE: usize = 8;
+
+/**
+ * Rows of a character, LSB leftmost. Characters outside printable ASCII show as `?`,
+ * `measure` and `draw` spell out umlauts first, see `spell_out`.
+ */
+pub fn glyph(c: char) -> &'static [u8; 8] {
+    let index = match c {
+        ' '..='~' => c as usize - 0x20,
+        _ => '?' as usize - 0x20,
+    };
+    &TEXT_FONT[index]
+}
+
+/**
+ * `text` with the letters German has beyond ASCII written the way German is without them:
+ * `ä` as `ae`, `ß` as `ss`. The font has no umlauts, this way translations can still spell
+ * them properly and read right everywhere else.
+ *
+ * ```
+ * use chip8::text;
+ *
+ * assert_eq!(text::spell_out("Zustandsprüfsumme öffnen, Größe"), "Zustandspruefsumme oeffnen, Groesse");
+ * assert_eq!(text::measure("Rücksprung"), text::measure("Ruecksprung"));
+ * ```
+ */
+pub fn spell_out(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            'ä' => out.push_str("ae"),
+            'ö' => out.push_str("oe"),
+            'ü' => out.push_str("ue"),
+            'Ä' => out.push_str("Ae"),
+            'Ö' => out.push_str("Oe"),
+            'Ü' => out.push_str("Ue"),
+            'ß' => out.push_str("ss"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/**
+ * Size in pixels of `text` once rendered, lines are split on `\n`.
+ */
+pub fn measure(text: &str) -> (usize, usize) {
+    let text = spell_out(text);
+    let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+    (columns * GLYPH_SIZE, text.lines().count() * GLYPH_SIZE)
+}
+
+/**
+ * Set the pixels of `text` with its top-left corner at X/Y. Only set pixels are written,
+ * so whatever is underneath shows through, and anything outside `fb` is clipped.
+ */
+pub fn draw(fb: &mut Framebuffer, x: usize, y: usize, text: &str) {
+    let text = spell_out(text);
+    for (line_no, line) in text.lines().enumerate() {
+        for (col, c) in line.chars().enumerate() {
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for bit in 0..GLYPH_SIZE {
+                    if bits & (1 << bit) != 0 {
+                        // out of range pixels are simply not drawn
+                        let _ = fb.set(x + col * GLYPH_SIZE + bit, y + line_no * GLYPH_SIZE + row, true);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Render `text` into a framebuffer of exactly its size.
+ *
+ * ```
+ * use chip8::text;
+ *
+ * let fb = text::render("Hi\n!");
+ * assert_eq!((fb.width(), fb.height()), (16, 16));
+ * // left leg of the "H"
+ * assert_eq!(fb.get(0, 0), Some(true));
+ * assert_eq!(fb.get(0, 7), Some(false));
+ * ```
+ */
+pub fn render(text: &str) -> Framebuffer {
+    let (width, height) = measure(text);
+    let mut fb = Framebuffer::new(width, height);
+    draw(&mut fb, 0, 0, text);
+    fb
+}
@@ -0,0 +1,40 @@
+use std::fs;
+use std::path::Path;
+
+use chip8::machine::Machine;
+use chip8::patch;
+
+use crate::config::Config;
+use crate::png;
+
+/**
+ * Thumbnail of a ROM, keyed by its checksum like the save RAM.
+ */
+pub fn path(dir: &str, rom: &[u8]) -> String {
+    format!("{}/{:08x}.png", dir, patch::crc32(rom))
+}
+
+/**
+ * Where this session should store a thumbnail, if it should: only for ROMs run from a file,
+ * only while they don't have one yet, and not with `--thumbnail-frame 0`.
+ */
+pub fn pending(config: &Config, rom: &[u8]) -> Option<String> {
+    let path = path(&config.thumbnail_dir, rom);
+    (config.rom.is_some() && config.thumbnail_frame > 0 && !Path::new(&path).exists()).then_some(path)
+}
+
+/**
+ * Write the display as a PNG in the configured colors, one image pixel per CHIP-8 pixel.
+ */
+pub fn store(config: &Config, machine: &Machine, path: &str) -> Result<(), String> {
+    let vram = machine.chip8.get_vram();
+    let palette = config.palette.adjusted(&config.picture);
+    let frame = machine.frame().to_string();
+    let text = [("Software", "rust8"), ("Frame", frame.as_str())];
+    let image = png::encode(vram.width(), vram.height(), &machine.chip8.to_rgba(&palette), &text);
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+
+    fs::create_dir_all(dir)
+        .and_then(|_| fs::write(path, image))
+        .map_err(|e| format!("can't write thumbnail {}: {}", path, e))
+}
@@ -0,0 +1,247 @@
+//! Execution traces, one line per instruction describing the state *before* it executes:
+//!
+//! ```text
+//! PC:0200 OP:6010 V0:00 V1:00 V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:00 I:0000 DT:00 ST:00
+//! ```
+//!
+//! All values are upper case hex. This `KEY:VALUE` layout is what most emulators with a
+//! trace/log option can be configured to emit, so traces from other emulators can be
+//! compared directly. When parsing, fields are matched by key and only `PC` is required;
+//! fields missing from either trace are simply not compared.
+
+use std::fmt;
+
+use crate::hardware::{Chip8, V_REG_COUNT};
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct TraceRecord {
+    pub pc: usize,
+    pub opcode: Option<u16>,
+    pub v: [Option<u8>; V_REG_COUNT],
+    pub i: Option<usize>,
+    pub dt: Option<u8>,
+    pub st: Option<u8>,
+}
+
+impl TraceRecord {
+    /**
+     * Record the core's state before it executes the instruction at PC.
+     */
+    pub fn from_chip8(chip8: &Chip8) -> Self {
+        let ram = chip8.get_ram();
+        let opcode = (ram[chip8.pc % ram.len()] as u16) << 8 | ram[(chip8.pc + 1) % ram.len()] as u16;
+
+        TraceRecord {
+            pc: chip8.pc,
+            opcode: Some(opcode),
+            v: chip8.v.map(Some),
+            i: Some(chip8.i),
+            dt: Some(chip8.tim_delay),
+            st: Some(chip8.tim_snd),
+        }
+    }
+
+    /**
+     * Parse a trace line. Unknown keys are ignored.
+     *
+     * ```
+     * use chip8::trace::TraceRecord;
+     *
+     * let record = TraceRecord::parse("PC:0202 OP:7001 V0:05 I:0050").unwrap();
+     * assert_eq!(record.pc, 0x202);
+     * assert_eq!(record.v[0], Some(0x05));
+     * assert_eq!(record.dt, None);
+     * ```
+     */
+    pub fn parse(line: &str) -> Result<TraceRecord, String> {
+        let mut record = TraceRecord::default();
+        let mut has_pc = false;
+
+        for field in line.split_whitespace() {
+            let (key, value) = match field.split_once(':') {
+                Some(kv) => kv,
+                None => continue,
+            };
+            let num = usize::from_str_radix(value, 16).map_err(|_| format!("invalid value in '{}'", field))?;
+
+            match key.to_ascii_uppercase().as_str() {
+                "PC" => {
+                    record.pc = num;
+                    has_pc = true;
+                }
+                "OP" => record.opcode = Some(num as u16),
+                "I" => record.i = Some(num),
+                "DT" => record.dt = Some(num as u8),
+                "ST" => record.st = Some(num as u8),
+                reg if reg.len() == 2 && reg.starts_with('V') => {
+                    if let Ok(n) = usize::from_str_radix(&reg[1..], 16) {
+                        record.v[n] = Some(num as u8);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if has_pc {
+            Ok(record)
+        } else {
+            Err(format!("no PC in trace line '{}'", line))
+        }
+    }
+
+    /**
+     * First field present in both records with a different value, as (name, ours, theirs).
+     */
+    pub fn diff(&self, other: &TraceRecord) -> Option<(String, String, String)> {
+        fn cmp<T: PartialEq + fmt::UpperHex>(name: &str, a: Option<T>, b: Option<T>) -> Option<(String, String, String)> {
+            match (a, b) {
+                (Some(a), Some(b)) if a != b => Some((name.to_string(), format!("{:X}", a), format!("{:X}", b))),
+                _ => None,
+            }
+        }
+
+        cmp("PC", Some(self.pc), Some(other.pc))
+            .or_else(|| cmp("OP", self.opcode, other.opcode))
+            .or_else(|| (0..V_REG_COUNT).find_map(|n| cmp(&format!("V{:X}", n), self.v[n], other.v[n])))
+            .or_else(|| cmp("I", self.i, other.i))
+            .or_else(|| cmp("DT", self.dt, other.dt))
+            .or_else(|| cmp("ST", self.st, other.st))
+    }
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PC:{:04X}", self.pc)?;
+        if let Some(op) = self.opcode {
+            write!(f, " OP:{:04X}", op)?;
+        }
+        for (n, v) in self.v.iter().enumerate() {
+            if let Some(v) = v {
+                write!(f, " V{:X}:{:02X}", n, v)?;
+            }
+        }
+        if let Some(i) = self.i {
+            write!(f, " I:{:04X}", i)?;
+        }
+        if let Some(dt) = self.dt {
+            write!(f, " DT:{:02X}", dt)?;
+        }
+        if let Some(st) = self.st {
+            write!(f, " ST:{:02X}", st)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Where two traces first disagree.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct TraceMismatch {
+    // 1-based index of the instruction, blank lines aren't counted
+    pub record: usize,
+    pub field: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for TraceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "instruction {}: {} differs ({} vs {})", self.record, self.field, self.left, self.right)
+    }
+}
+
+/**
+ * Compare two traces line by line. Blank lines are skipped; when one trace is shorter
+ * only the common part is compared.
+ *
+ * ```
+ * use chip8::trace;
+ *
+ * let a = "PC:0200 OP:6005 V0:00\nPC:0202 OP:7001 V0:05\n";
+ * let b = "PC:0200 OP:6005 V0:00\nPC:0202 OP:7001 V0:06\n";
+ * let mismatch = trace::compare(a, b).unwrap().unwrap();
+ * assert_eq!((mismatch.record, mismatch.field.as_str()), (2, "V0"));
+ * ```
+ */
+pub fn compare(left: &str, right: &str) -> Result<Option<TraceMismatch>, String> {
+    let left_lines = left.lines().filter(|l| !l.trim().is_empty());
+    let right_lines = right.lines().filter(|l| !l.trim().is_empty());
+
+    for (n, (l, r)) in left_lines.zip(right_lines).enumerate() {
+        let (l, r) = (TraceRecord::parse(l)?, TraceRecord::parse(r)?);
+        if let Some((field, left, right)) = l.diff(&r) {
+            return Ok(Some(TraceMismatch { record: n + 1, field, left, right }));
+        }
+    }
+
+    Ok(None)
+}
+
+// instructions kept by `History`
+pub const HISTORY_CAPACITY: usize = 1024;
+
+/**
+ * The last `HISTORY_CAPACITY` executed instructions as (PC, opcode), kept by every `Machine`
+ * whether tracing is on or not. Each entry is packed into a `u32`, so recording one is a
+ * store and an increment.
+ *
+ * ```
+ * use chip8::machine::Machine;
+ *
+ * let mut machine = Machine::new();
+ * // V0 += 1, loop forever
+ * machine.load_rom(&[0x70, 0x01, 0x12, 0x00]);
+ * machine.run_frame().unwrap();
+ *
+ * let recent: Vec<(usize, u16)> = machine.history.iter().collect();
+ * assert_eq!(recent.len(), 8);
+ * assert_eq!(recent[..2], [(0x200, 0x7001), (0x202, 0x1200)]);
+ * ```
+ */
+#[derive(Clone)]
+pub struct History {
+    entries: Box<[u32; HISTORY_CAPACITY]>,
+    // total pushed, the next entry goes to `pushed % HISTORY_CAPACITY`
+    pushed: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History { entries: Box::new([0; HISTORY_CAPACITY]), pushed: 0 }
+    }
+
+    pub fn push(&mut self, pc: usize, opcode: u16) {
+        self.entries[self.pushed % HISTORY_CAPACITY] = (pc as u32) << 16 | opcode as u32;
+        self.pushed += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.pushed.min(HISTORY_CAPACITY)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pushed == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.pushed = 0;
+    }
+
+    /**
+     * The recorded instructions as (PC, opcode), oldest first.
+     */
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u16)> + '_ {
+        let start = self.pushed - self.len();
+        (start..self.pushed).map(|n| {
+            let entry = self.entries[n % HISTORY_CAPACITY];
+            ((entry >> 16) as usize, entry as u16)
+        })
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new()
+    }
+}
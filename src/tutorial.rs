@@ -0,0 +1,239 @@
+//! Small ROMs that teach one opcode family by running it on known inputs and checking the
+//! results, for learning CHIP-8 and for a quick self-test of an interpreter.
+//!
+//! Each family is a few rows on the screen, one per opcode, labeled with a hex digit from
+//! the font: `8xyn` has a row for `8XY0` labeled `0`, one for `8XY1` labeled `1` and so on.
+//! Every case in a row draws a tick when the registers came out as expected and a cross
+//! when they didn't, and `V7` counts the crosses. `Tutorial::describe` lists what each
+//! mark stands for. There is no assembler in this crate, so the opcodes are emitted
+//! directly; `disasm` shows them as assembly again.
+//!
+//! ```
+//! use chip8::machine::Machine;
+//! use chip8::tutorial::{self, FAMILIES};
+//!
+//! for (name, _, _) in FAMILIES {
+//!     let mut machine = Machine::new();
+//!     let rom = tutorial::by_name(name).unwrap().rom();
+//!     machine.load_rom(&rom);
+//!     machine.run_frames(60, &[]).unwrap();
+//!     // done: at the loop the ROM ends with, and without crosses
+//!     assert_eq!(machine.chip8.pc, 0x200 + rom.len() - 2);
+//!     assert_eq!(machine.chip8.v[7], 0, "failures in {}", name);
+//! }
+//! ```
+
+use crate::disasm::ROM_ADDR;
+
+// the ROM starts with a jump over its data, sprites come right after it
+const OK_ADDR: u16 = ROM_ADDR as u16 + 2;
+const FAIL_ADDR: u16 = OK_ADDR + 5;
+// where `FX33` writes its digits
+const SCRATCH_ADDR: u16 = FAIL_ADDR + 5;
+const CODE_ADDR: u16 = SCRATCH_ADDR + 4;
+
+// a tick and a cross, 4x5 pixels like the font's digits
+const OK_SPRITE: [u8; 5] = [0x00, 0x10, 0x20, 0xA0, 0x40];
+const FAIL_SPRITE: [u8; 5] = [0x90, 0x60, 0x60, 0x90, 0x00];
+
+// a row is a label and its cases, each 4 pixels wide with a gap of 1
+pub const CASES_PER_ROW: usize = 3;
+const ROW_WIDTH: u8 = 5 * (CASES_PER_ROW as u8 + 1) + 2;
+const ROW_HEIGHT: u8 = 7;
+// rows next to each other before the next line, all fit in 64x32
+const ROWS_PER_LINE: usize = 3;
+
+/**
+ * One check: `code` runs the opcode under test on known inputs, after which each register
+ * in `expect` must hold its value.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Case {
+    pub code: Vec<u16>,
+    pub expect: Vec<(usize, u8)>,
+    // the case in words, e.g. `V1=0xFF V2=0x01 -> V1=0x00 VF=1`
+    pub about: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Row {
+    // the hex digit shown in front of the row
+    pub label: u8,
+    pub opcode: &'static str,
+    pub cases: Vec<Case>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tutorial {
+    pub family: &'static str,
+    pub about: &'static str,
+    pub rows: Vec<Row>,
+}
+
+// name, what the family does, and its rows
+pub type Family = (&'static str, &'static str, fn() -> Vec<Row>);
+
+pub const FAMILIES: [Family; 4] = [
+    ("8xyn", "arithmetic and logic between VX and VY, VF is the carry, borrow or shifted out bit", alu),
+    ("7xnn", "add a constant to VX, without touching VF", add),
+    ("skip", "skip the next instruction on a comparison", skip),
+    ("fx33", "store VX as three decimal digits at I", bcd),
+];
+
+pub fn by_name(name: &str) -> Result<Tutorial, String> {
+    let (family, about, rows) = FAMILIES.iter().find(|(n, _, _)| n.eq_ignore_ascii_case(name)).ok_or_else(|| {
+        let names: Vec<&str> = FAMILIES.iter().map(|(n, _, _)| *n).collect();
+        format!("unknown opcode family '{}', expected one of: {}", name, names.join(", "))
+    })?;
+    Ok(Tutorial { family, about, rows: rows() })
+}
+
+impl Tutorial {
+    /**
+     * The ROM, to load at 0x200. It clears the screen, draws every row, and loops forever
+     * when done.
+     */
+    pub fn rom(&self) -> Vec<u8> {
+        let mut code = vec![0x00E0, 0x6700];
+        for (n, row) in self.rows.iter().enumerate() {
+            let (x, y) = ((n % ROWS_PER_LINE) as u8 * ROW_WIDTH, (n / ROWS_PER_LINE) as u8 * ROW_HEIGHT);
+            code.extend([0x6400 | x as u16, 0x6500 | y as u16, 0x6600 | row.label as u16, 0xF629, 0xD455]);
+            for (i, case) in row.cases.iter().enumerate() {
+                code.extend(&case.code);
+                // SE past a jump to the cross for every register, otherwise on to the tick
+                let checks = case.expect.len() as u16;
+                let at = CODE_ADDR + 2 * code.len() as u16;
+                let fail = at + 4 * checks + 4;
+                let draw = fail + 4;
+                for (reg, value) in &case.expect {
+                    code.extend([0x3000 | (*reg as u16) << 8 | *value as u16, 0x1000 | fail]);
+                }
+                code.extend([0xA000 | OK_ADDR, 0x1000 | draw, 0xA000 | FAIL_ADDR, 0x7701]);
+                code.extend([0x6400 | (x + 5 * (i as u8 + 1)) as u16, 0x6500 | y as u16, 0xD455]);
+            }
+        }
+        let end = CODE_ADDR + 2 * code.len() as u16;
+        code.push(0x1000 | end);
+
+        let mut rom = vec![0x10 | (CODE_ADDR >> 8) as u8, CODE_ADDR as u8];
+        rom.extend(OK_SPRITE);
+        rom.extend(FAIL_SPRITE);
+        rom.extend([0; 4]);
+        rom.extend(code.iter().flat_map(|op| op.to_be_bytes()));
+        rom
+    }
+
+    /**
+     * What the ROM shows, row by row, for reading along while it runs.
+     */
+    pub fn describe(&self) -> String {
+        let mut out = format!("{}: {}\n", self.family, self.about);
+        out.push_str("each case draws a tick when it passed and a cross when it failed, V7 counts the crosses\n");
+        for row in &self.rows {
+            out.push_str(&format!("\n{:X}  {}\n", row.label, row.opcode));
+            for (i, case) in row.cases.iter().enumerate() {
+                out.push_str(&format!("  {}. {}\n", i + 1, case.about));
+            }
+        }
+        out
+    }
+}
+
+fn alu() -> Vec<Row> {
+    // (N, name, result, flag: None where VF is left alone)
+    type Op = (u8, &'static str, fn(u8, u8) -> u8, Option<fn(u8, u8) -> u8>);
+    const OPS: [Op; 9] = [
+        (0x0, "8XY0  LD VX, VY", |_, y| y, None),
+        (0x1, "8XY1  OR VX, VY", |x, y| x | y, None),
+        (0x2, "8XY2  AND VX, VY", |x, y| x & y, None),
+        (0x3, "8XY3  XOR VX, VY", |x, y| x ^ y, None),
+        (0x4, "8XY4  ADD VX, VY, VF = carry", |x, y| x.wrapping_add(y), Some(|x, y| x.checked_add(y).is_none() as u8)),
+        (0x5, "8XY5  SUB VX, VY, VF = no borrow", |x, y| x.wrapping_sub(y), Some(|x, y| (x >= y) as u8)),
+        (0x6, "8XY6  VX = VY >> 1, VF = bit shifted out", |_, y| y >> 1, Some(|_, y| y & 1)),
+        (0x7, "8XY7  VX = VY - VX, VF = no borrow", |x, y| y.wrapping_sub(x), Some(|x, y| (y >= x) as u8)),
+        (0xE, "8XYE  VX = VY << 1, VF = bit shifted out", |_, y| y << 1, Some(|_, y| y >> 7)),
+    ];
+    const INPUTS: [(u8, u8); CASES_PER_ROW] = [(0x12, 0x34), (0xFF, 0x01), (0x81, 0xC3)];
+
+    OPS.iter()
+        .map(|(n, opcode, result, flag)| {
+            let cases = INPUTS
+                .iter()
+                .map(|&(x, y)| {
+                    let mut expect = vec![(1, result(x, y))];
+                    let mut about = format!("V1={:#04x} V2={:#04x} -> V1={:#04x}", x, y, result(x, y));
+                    if let Some(flag) = flag {
+                        expect.push((3, flag(x, y)));
+                        about.push_str(&format!(" VF={}", flag(x, y)));
+                    }
+                    // VF goes to V3 right away, before the checks can change it
+                    let code = vec![0x6100 | x as u16, 0x6200 | y as u16, 0x8120 | *n as u16, 0x83F0];
+                    Case { code, expect, about }
+                })
+                .collect();
+            Row { label: *n, opcode, cases }
+        })
+        .collect()
+}
+
+fn add() -> Vec<Row> {
+    const INPUTS: [(u8, u8); CASES_PER_ROW] = [(0x10, 0x05), (0xFF, 0x02), (0x80, 0x80)];
+    let cases = INPUTS
+        .iter()
+        .map(|&(x, nn)| {
+            let sum = x.wrapping_add(nn);
+            // VF starts out as 0x55 and has to stay that way, even on overflow
+            let code = vec![0x6F55, 0x6100 | x as u16, 0x7100 | nn as u16, 0x83F0];
+            let about = format!("V1={:#04x} + {:#04x} -> V1={:#04x} VF unchanged", x, nn, sum);
+            Case { code, expect: vec![(1, sum), (3, 0x55)], about }
+        })
+        .collect();
+    vec![Row { label: 0x7, opcode: "7XNN  ADD VX, NN", cases }]
+}
+
+fn skip() -> Vec<Row> {
+    // (opcode nibble, name, compares with V2 rather than NN, skips when equal)
+    const OPS: [(u8, &str, bool, bool); 4] = [
+        (0x3, "3XNN  SE VX, NN", false, true),
+        (0x4, "4XNN  SNE VX, NN", false, false),
+        (0x5, "5XY0  SE VX, VY", true, true),
+        (0x9, "9XY0  SNE VX, VY", true, false),
+    ];
+    const INPUTS: [(u8, u8); CASES_PER_ROW] = [(0x42, 0x42), (0x42, 0x24), (0x00, 0xFF)];
+
+    OPS.iter()
+        .map(|&(nib, opcode, registers, on_equal)| {
+            let cases = INPUTS
+                .iter()
+                .map(|&(x, other)| {
+                    let op = match registers {
+                        true => (nib as u16) << 12 | 0x120,
+                        false => (nib as u16) << 12 | 0x100 | other as u16,
+                    };
+                    // V3 is set to 1 by the instruction that is skipped or not
+                    let code = vec![0x6100 | x as u16, 0x6200 | other as u16, 0x6300, op, 0x6301];
+                    let skipped = (x == other) == on_equal;
+                    let what = if registers { "V2" } else { "NN" };
+                    let about = format!("V1={:#04x} {}={:#04x} -> {}", x, what, other, if skipped { "skips" } else { "doesn't skip" });
+                    Case { code, expect: vec![(3, !skipped as u8)], about }
+                })
+                .collect();
+            Row { label: nib, opcode, cases }
+        })
+        .collect()
+}
+
+fn bcd() -> Vec<Row> {
+    const INPUTS: [u8; CASES_PER_ROW] = [0, 42, 255];
+    let cases = INPUTS
+        .iter()
+        .map(|&x| {
+            let digits = [x / 100, x / 10 % 10, x % 10];
+            // the digits are read back into V0-V2 with FX65
+            let code = vec![0x6100 | x as u16, 0xA000 | SCRATCH_ADDR, 0xF133, 0xF265];
+            let about = format!("V1={} -> {} {} {}", x, digits[0], digits[1], digits[2]);
+            Case { code, expect: digits.iter().copied().enumerate().collect(), about }
+        })
+        .collect();
+    vec![Row { label: 0x3, opcode: "FX33  LD B, VX", cases }]
+}
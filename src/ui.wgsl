@@ -0,0 +1,61 @@
+// egui meshes for the debugger window, see `ui_painter`. Vertex colors and textures are
+// premultiplied sRGB, blending happens in gamma space like egui expects.
+
+struct Locals {
+    // window size in points
+    screen_size: vec2<f32>,
+    // uniform buffers are at least 16 bytes on some backends
+    padding: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> locals: Locals;
+@group(1) @binding(0) var tex: texture_2d<f32>;
+@group(1) @binding(1) var tex_sampler: sampler;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    // sRGB, 0 to 1
+    @location(1) color: vec4<f32>,
+};
+
+fn linear_from_gamma(srgb: vec3<f32>) -> vec3<f32> {
+    let lower = srgb / 12.92;
+    let higher = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(higher, lower, srgb < vec3<f32>(0.04045));
+}
+
+fn gamma_from_linear(rgb: vec3<f32>) -> vec3<f32> {
+    let lower = rgb * 12.92;
+    let higher = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, rgb < vec3<f32>(0.0031308));
+}
+
+@vertex
+fn vs_main(@location(0) pos: vec2<f32>, @location(1) uv: vec2<f32>, @location(2) color: u32) -> VertexOut {
+    var out: VertexOut;
+    out.position = vec4<f32>(2.0 * pos.x / locals.screen_size.x - 1.0, 1.0 - 2.0 * pos.y / locals.screen_size.y, 0.0, 1.0);
+    out.uv = uv;
+    out.color = vec4<f32>(
+        f32(color & 255u),
+        f32((color >> 8u) & 255u),
+        f32((color >> 16u) & 255u),
+        f32((color >> 24u) & 255u),
+    ) / 255.0;
+    return out;
+}
+
+// for an sRGB surface, which converts the output back to gamma itself
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let tex_linear = textureSample(tex, tex_sampler, in.uv);
+    let color = in.color * vec4<f32>(gamma_from_linear(tex_linear.rgb), tex_linear.a);
+    return vec4<f32>(linear_from_gamma(color.rgb), color.a);
+}
+
+// for a surface without sRGB conversion
+@fragment
+fn fs_main_gamma(in: VertexOut) -> @location(0) vec4<f32> {
+    let tex_linear = textureSample(tex, tex_sampler, in.uv);
+    return in.color * vec4<f32>(gamma_from_linear(tex_linear.rgb), tex_linear.a);
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+
+use egui::epaint::{ImageData, Primitive};
+use egui::{TextureFilter, TextureId, TexturesDelta};
+use sdl2::video::Window;
+use wgpu::util::DeviceExt;
+
+use crate::wgpu_display;
+
+// bytes per vertex: position and uv (2 f32 each) and an sRGBA color (u32)
+const VERTEX_SIZE: u64 = 5 * 4;
+
+/**
+ * Draws egui output into an SDL window with wgpu, for the debugger window in `debug_ui`.
+ */
+pub struct Painter {
+    // declared before `window` so it is dropped first, it must not outlive the window
+    surface: wgpu::Surface,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface_config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    // window size in points, group 0
+    locals: wgpu::Buffer,
+    locals_group: wgpu::BindGroup,
+    texture_layout: wgpu::BindGroupLayout,
+    // egui's font atlas and the images the panels show, group 1
+    textures: HashMap<TextureId, (wgpu::Texture, wgpu::BindGroup)>,
+    window: Window,
+}
+
+impl Painter {
+    pub fn new(window: Window) -> Result<Self, String> {
+        let (surface, device, queue, surface_config) = wgpu_display::connect(&window)?;
+
+        let locals = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ui locals"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let locals_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let locals_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &locals_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: locals.as_entire_binding() }],
+        });
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("ui"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("ui.wgsl").into()),
+        });
+        // egui colors are premultiplied
+        let blend = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::OneMinusDstAlpha,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("ui"),
+            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&locals_layout, &texture_layout],
+                push_constant_ranges: &[],
+            })),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: VERTEX_SIZE,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Uint32],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: if surface_config.format.describe().srgb { "fs_main" } else { "fs_main_gamma" },
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_config.format,
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Ok(Painter {
+            surface,
+            device,
+            queue,
+            surface_config,
+            pipeline,
+            locals,
+            locals_group,
+            texture_layout,
+            textures: HashMap::new(),
+            window,
+        })
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /**
+     * Window pixels per egui point, more than 1 on HiDPI screens.
+     */
+    pub fn pixels_per_point(&self) -> f32 {
+        self.window.drawable_size().0 as f32 / self.window.size().0.max(1) as f32
+    }
+
+    /**
+     * Draw one frame of egui output: `textures` as returned by `Context::run`, `primitives`
+     * from `Context::tessellate`.
+     */
+    pub fn paint(&mut self, textures: TexturesDelta, primitives: &[egui::ClippedPrimitive], pixels_per_point: f32) -> Result<(), String> {
+        for (id, delta) in &textures.set {
+            self.update_texture(*id, delta);
+        }
+        self.fit_surface();
+
+        let (width, height) = (self.surface_config.width, self.surface_config.height);
+        let screen = [width as f32 / pixels_per_point, height as f32 / pixels_per_point, 0.0, 0.0];
+        let locals: Vec<u8> = screen.iter().flat_map(|f| f.to_le_bytes()).collect();
+        self.queue.write_buffer(&self.locals, 0, &locals);
+
+        // every mesh in one vertex and one index buffer, drawn by range
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut draws = Vec::new();
+        for primitive in primitives {
+            let Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+            let base_vertex = (vertices.len() as u64 / VERTEX_SIZE) as i32;
+            let first = indices.len() as u32 / 4;
+            for vertex in &mesh.vertices {
+                for value in [vertex.pos.x, vertex.pos.y, vertex.uv.x, vertex.uv.y] {
+                    vertices.extend_from_slice(&value.to_le_bytes());
+                }
+                vertices.extend_from_slice(&vertex.color.to_array());
+            }
+            for index in &mesh.indices {
+                indices.extend_from_slice(&index.to_le_bytes());
+            }
+            draws.push((primitive.clip_rect, mesh.texture_id, first..indices.len() as u32 / 4, base_vertex));
+        }
+
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.device, &self.surface_config);
+                return Ok(());
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ui vertices"),
+            contents: &vertices,
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("ui indices"),
+            contents: &indices,
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: true },
+                })],
+                depth_stencil_attachment: None,
+            });
+            if !draws.is_empty() {
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &self.locals_group, &[]);
+                pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            }
+            for (clip, texture, range, base_vertex) in draws {
+                let Some((_, bind_group)) = self.textures.get(&texture) else {
+                    continue;
+                };
+                // clip rectangle in surface pixels, skipping meshes that are clipped away
+                let left = (clip.min.x * pixels_per_point).round().clamp(0.0, width as f32) as u32;
+                let top = (clip.min.y * pixels_per_point).round().clamp(0.0, height as f32) as u32;
+                let right = (clip.max.x * pixels_per_point).round().clamp(left as f32, width as f32) as u32;
+                let bottom = (clip.max.y * pixels_per_point).round().clamp(top as f32, height as f32) as u32;
+                if right == left || bottom == top {
+                    continue;
+                }
+                pass.set_scissor_rect(left, top, right - left, bottom - top);
+                pass.set_bind_group(1, bind_group, &[]);
+                pass.draw_indexed(range, base_vertex, 0..1);
+            }
+        }
+        self.queue.submit(Some(encoder.finish()));
+        output.present();
+
+        for id in &textures.free {
+            self.textures.remove(id);
+        }
+        Ok(())
+    }
+
+    /**
+     * Create a texture or update part of it.
+     */
+    fn update_texture(&mut self, id: TextureId, delta: &egui::epaint::ImageDelta) {
+        let [width, height] = delta.image.size();
+        let pixels: Vec<u8> = match &delta.image {
+            ImageData::Color(image) => image.pixels.iter().flat_map(|c| c.to_array()).collect(),
+            ImageData::Font(image) => image.srgba_pixels(None).flat_map(|c| c.to_array()).collect(),
+        };
+        let size = wgpu::Extent3d { width: width as u32, height: height as u32, depth_or_array_layers: 1 };
+
+        let origin = match delta.pos {
+            Some([x, y]) => wgpu::Origin3d { x: x as u32, y: y as u32, z: 0 },
+            None => {
+                let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("ui texture"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                });
+                let filter = |filter| match filter {
+                    TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+                    TextureFilter::Linear => wgpu::FilterMode::Linear,
+                };
+                let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
+                    mag_filter: filter(delta.options.magnification),
+                    min_filter: filter(delta.options.minification),
+                    ..wgpu::SamplerDescriptor::default()
+                });
+                let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &self.texture_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&texture.create_view(&wgpu::TextureViewDescriptor::default())),
+                        },
+                        wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
+                    ],
+                });
+                self.textures.insert(id, (texture, bind_group));
+                wgpu::Origin3d::ZERO
+            }
+        };
+
+        let Some((texture, _)) = self.textures.get(&id) else {
+            return;
+        };
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture { texture, mip_level: 0, origin, aspect: wgpu::TextureAspect::All },
+            &pixels,
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: NonZeroU32::new(4 * width as u32), rows_per_image: None },
+            size,
+        );
+    }
+
+    /**
+     * Follow the window to a screen with a different scale factor.
+     */
+    fn fit_surface(&mut self) {
+        let (width, height) = self.window.drawable_size();
+        if (width.max(1), height.max(1)) != (self.surface_config.width, self.surface_config.height) {
+            self.surface_config.width = width.max(1);
+            self.surface_config.height = height.max(1);
+            self.surface.configure(&self.device, &self.surface_config);
+        }
+    }
+}
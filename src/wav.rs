@@ -0,0 +1,106 @@
+//! The beep of a session as a WAV file, to go along with a screen capture. The track is
+//! recorded in emulated frames rather than wall time, so it stays in step with the frames
+//! however fast or slow the session ran, and pauses leave no gaps.
+//!
+//! The sound is the frontend's: a 440 Hz square wave at a quarter of full scale, written as
+//! 16-bit mono PCM. A frame beeps when the sound timer was running during it.
+//!
+//! ```
+//! use chip8::machine::Machine;
+//! use chip8::wav::{AudioTrack, SAMPLE_RATE};
+//!
+//! let mut machine = Machine::new();
+//! machine.audio = Some(AudioTrack::new());
+//! // V0 = 30, sound timer = V0, loop forever
+//! machine.load_rom(&[0x60, 0x1E, 0xF0, 0x18, 0x12, 0x04]);
+//! machine.run_frames(60, &[]).unwrap();
+//!
+//! let track = machine.audio.as_ref().unwrap();
+//! assert_eq!(track.frames(), 60);
+//! assert_eq!(track.beeping(), 30);
+//!
+//! // one second at 60 frames a second, after the 44 byte header
+//! let wav = track.wav(60.0);
+//! assert_eq!(&wav[..4], b"RIFF");
+//! assert_eq!(wav.len(), 44 + 2 * SAMPLE_RATE as usize);
+//! // loud in the first half, silent in the second
+//! assert_ne!(&wav[44..46], &[0, 0]);
+//! assert!(wav[44 + SAMPLE_RATE as usize..].iter().all(|b| *b == 0));
+//! ```
+
+pub const SAMPLE_RATE: u32 = 44100;
+// pitch and loudness of the beep, the same as the frontend's
+const PITCH_HZ: f64 = 440.0;
+const VOLUME: f64 = 0.25;
+
+/**
+ * Whether the beep sounded, frame by frame, see `Machine::audio`.
+ */
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct AudioTrack {
+    beeps: Vec<bool>,
+}
+
+impl AudioTrack {
+    pub fn new() -> Self {
+        AudioTrack::default()
+    }
+
+    pub fn record(&mut self, beeping: bool) {
+        self.beeps.push(beeping);
+    }
+
+    pub fn frames(&self) -> usize {
+        self.beeps.len()
+    }
+
+    /**
+     * Number of frames the beep sounded in.
+     */
+    pub fn beeping(&self) -> usize {
+        self.beeps.iter().filter(|b| **b).count()
+    }
+
+    /**
+     * The track as a WAV file, at `frame_rate` frames a second.
+     */
+    pub fn wav(&self, frame_rate: f64) -> Vec<u8> {
+        // frame n covers the samples from round(n * rate / fps) on, so the length doesn't
+        // drift when a frame isn't a whole number of samples
+        let samples_at = |frame: usize| (frame as f64 * SAMPLE_RATE as f64 / frame_rate).round() as usize;
+        let total = samples_at(self.beeps.len());
+        let amplitude = (VOLUME * i16::MAX as f64) as i16;
+
+        let mut data = Vec::with_capacity(2 * total);
+        for (n, beeping) in self.beeps.iter().enumerate() {
+            for sample in samples_at(n)..samples_at(n + 1) {
+                // the wave keeps running while silent, like the frontend's
+                let phase = (sample as f64 * PITCH_HZ / SAMPLE_RATE as f64).fract();
+                let value = match (beeping, phase < 0.5) {
+                    (false, _) => 0,
+                    (true, true) => amplitude,
+                    (true, false) => -amplitude,
+                };
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        let mut wav = Vec::with_capacity(44 + data.len());
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, mono
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        // bytes per second and per sample, bits per sample
+        wav.extend_from_slice(&(2 * SAMPLE_RATE).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        wav
+    }
+}
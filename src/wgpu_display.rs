@@ -0,0 +1,342 @@
+use std::num::NonZeroU32;
+
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::video::Window;
+
+use crate::display::Display;
+
+// pixel format of the frame texture, the window contents are drawn in sRGB
+const FRAME_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// size of `Params` in crt.wgsl, in f32s
+const PARAMS_LEN: usize = 12;
+
+/**
+ * wgpu backend for `Render`. Everything is drawn into a buffer at the window size, which is
+ * uploaded as a texture and drawn to the window through the CRT filter in `crt.wgsl`. The
+ * surface is as large as the window's drawable area, so on HiDPI screens the filter runs at
+ * the screen's own resolution instead of being scaled up afterwards.
+ */
+pub struct WgpuDisplay {
+    // declared before `window` so it is dropped first, it must not outlive the window
+    surface: wgpu::Surface,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface_config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    frame: wgpu::Texture,
+    params: wgpu::Buffer,
+    window: Window,
+    // window contents as RGBA, uploaded to `frame` on present
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    color: [u8; 4],
+    // emulated screen area and its rows, see `Display::set_screen`
+    screen: (Rect, usize),
+}
+
+impl WgpuDisplay {
+    pub fn new(window: Window) -> Result<Self, String> {
+        let (width, height) = window.size();
+        let (surface, device, queue, surface_config) = connect(&window)?;
+        let format = surface_config.format;
+
+        let frame = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("frame"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FRAME_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        // nearest, the pixels stay sharp when the surface is larger than the frame
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("params"),
+            size: (PARAMS_LEN * 4) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&frame.create_view(&wgpu::TextureViewDescriptor::default())),
+                },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: params.as_entire_binding() },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("crt"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("crt.wgsl").into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("crt"),
+            layout: Some(&device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            })),
+            vertex: wgpu::VertexState { module: &shader, entry_point: "vs_main", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState { format, blend: None, write_mask: wgpu::ColorWrites::ALL })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Ok(WgpuDisplay {
+            surface,
+            device,
+            queue,
+            surface_config,
+            pipeline,
+            bind_group,
+            frame,
+            params,
+            window,
+            pixels: vec![0; (width * height * 4) as usize],
+            width,
+            height,
+            color: [0, 0, 0, 0xFF],
+            screen: (Rect::new(0, 0, width, height), height as usize),
+        })
+    }
+
+    /**
+     * Set the pixel at X/Y in window coordinates, if it is inside the window.
+     */
+    fn plot(&mut self, x: i32, y: i32) {
+        if (0..self.width as i32).contains(&x) && (0..self.height as i32).contains(&y) {
+            let offset = (y as usize * self.width as usize + x as usize) * 4;
+            self.pixels[offset..offset + 4].copy_from_slice(&self.color);
+        }
+    }
+
+    /**
+     * Follow the window to a screen with a different scale factor.
+     */
+    fn fit_surface(&mut self) {
+        let (width, height) = self.window.drawable_size();
+        if (width.max(1), height.max(1)) != (self.surface_config.width, self.surface_config.height) {
+            self.surface_config.width = width.max(1);
+            self.surface_config.height = height.max(1);
+            self.surface.configure(&self.device, &self.surface_config);
+        }
+    }
+}
+
+/**
+ * Open a wgpu device that can draw to `window`, with the surface configured for the
+ * window's drawable size. The surface must be dropped before the window.
+ */
+pub fn connect(window: &Window) -> Result<(wgpu::Surface, wgpu::Device, wgpu::Queue, wgpu::SurfaceConfiguration), String> {
+    let instance = wgpu::Instance::new(wgpu::Backends::all());
+    // sound as long as the caller drops the surface before the window
+    let surface = unsafe { instance.create_surface(window) };
+
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::LowPower,
+        force_fallback_adapter: false,
+        compatible_surface: Some(&surface),
+    }))
+    .ok_or("no graphics adapter for the window")?;
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: None,
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+        },
+        None,
+    ))
+    .map_err(|e| e.to_string())?;
+
+    let format = *surface.get_supported_formats(&adapter).first().ok_or("the window can't be drawn to with wgpu")?;
+    let (width, height) = window.drawable_size();
+    let surface_config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format,
+        width: width.max(1),
+        height: height.max(1),
+        present_mode: wgpu::PresentMode::Fifo,
+    };
+    surface.configure(&device, &surface_config);
+
+    Ok((surface, device, queue, surface_config))
+}
+
+impl Display for WgpuDisplay {
+    fn window(&self) -> &Window {
+        &self.window
+    }
+
+    fn window_mut(&mut self) -> &mut Window {
+        &mut self.window
+    }
+
+    fn set_draw_color(&mut self, color: Color) {
+        // drawing replaces what is underneath, like the SDL canvas' default blend mode
+        self.color = [color.r, color.g, color.b, 0xFF];
+    }
+
+    fn clear(&mut self) {
+        let color = self.color;
+        for px in self.pixels.chunks_exact_mut(4) {
+            px.copy_from_slice(&color);
+        }
+    }
+
+    fn fill_rect(&mut self, rect: Rect) -> Result<(), String> {
+        let Some(rect) = rect.intersection(Rect::new(0, 0, self.width, self.height)) else {
+            return Ok(());
+        };
+        for y in rect.top()..rect.bottom() {
+            let row = y as usize * self.width as usize;
+            for px in self.pixels[(row + rect.left() as usize) * 4..(row + rect.right() as usize) * 4].chunks_exact_mut(4) {
+                px.copy_from_slice(&self.color);
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_rect(&mut self, rect: Rect) -> Result<(), String> {
+        let (right, bottom) = (rect.right() - 1, rect.bottom() - 1);
+        self.draw_line(Point::new(rect.left(), rect.top()), Point::new(right, rect.top()))?;
+        self.draw_line(Point::new(rect.left(), bottom), Point::new(right, bottom))?;
+        self.draw_line(Point::new(rect.left(), rect.top()), Point::new(rect.left(), bottom))?;
+        self.draw_line(Point::new(right, rect.top()), Point::new(right, bottom))
+    }
+
+    fn draw_line(&mut self, from: Point, to: Point) -> Result<(), String> {
+        // Bresenham, both ends included like SDL
+        let (dx, dy) = ((to.x() - from.x()).abs(), -(to.y() - from.y()).abs());
+        let (sx, sy) = ((to.x() - from.x()).signum(), (to.y() - from.y()).signum());
+        let (mut x, mut y, mut err) = (from.x(), from.y(), dx + dy);
+        loop {
+            self.plot(x, y);
+            if (x, y) == (to.x(), to.y()) {
+                return Ok(());
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    fn present(&mut self) -> Result<(), String> {
+        self.fit_surface();
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.frame,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.pixels,
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: NonZeroU32::new(self.width * 4), rows_per_image: None },
+            wgpu::Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 },
+        );
+
+        let (area, rows) = self.screen;
+        let params: [f32; PARAMS_LEN] = [
+            self.width as f32,
+            self.height as f32,
+            self.surface_config.width as f32,
+            self.surface_config.height as f32,
+            area.x() as f32,
+            area.y() as f32,
+            area.width() as f32,
+            area.height() as f32,
+            rows as f32,
+            0.0,
+            0.0,
+            0.0,
+        ];
+        let bytes: Vec<u8> = params.iter().flat_map(|p| p.to_ne_bytes()).collect();
+        self.queue.write_buffer(&self.params, 0, &bytes);
+
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            // e.g. after the window moved to another screen, draw the next frame instead
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.device, &self.surface_config);
+                return Ok(());
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: true },
+                })],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            // a single triangle covering the whole surface, see `vs_main`
+            pass.draw(0..3, 0..1);
+        }
+        self.queue.submit(Some(encoder.finish()));
+        output.present();
+        Ok(())
+    }
+
+    fn set_screen(&mut self, area: Rect, rows: usize) {
+        self.screen = (area, rows);
+    }
+}
@@ -0,0 +1,43 @@
+//! Where `DXYN` puts sprites near the edges of the screen: the starting position wraps
+//! around, the sprite itself is clipped.
+
+use chip8::machine::Machine;
+
+// V0 = x, V1 = y, I = the "0" glyph, draw it, loop forever
+fn draw_zero_at(x: u8, y: u8) -> Machine {
+    let mut machine = Machine::new();
+    machine.load_rom(&[0x60, x, 0x61, y, 0xA0, 0x50, 0xD0, 0x15, 0x12, 0x08]);
+    machine.run_frame().unwrap();
+    machine
+}
+
+fn lit(machine: &Machine) -> Vec<(usize, usize)> {
+    machine.chip8.get_vram().iter_set_pixels().collect()
+}
+
+#[test]
+fn sprite_inside_the_screen_is_drawn_whole() {
+    // the "0" glyph is 4 wide and 5 tall with a hollow middle: 14 pixels
+    assert_eq!(lit(&draw_zero_at(10, 10)).len(), 14);
+}
+
+#[test]
+fn sprite_is_clipped_at_the_right_and_bottom_edges() {
+    let pixels = lit(&draw_zero_at(62, 30));
+
+    assert!(pixels.iter().all(|(x, y)| *x >= 62 && *y >= 30));
+    // the 2x2 top left corner of the glyph, which is all that fits
+    assert_eq!(pixels, [(62, 30), (63, 30), (62, 31)]);
+}
+
+#[test]
+fn starting_position_wraps_around() {
+    assert_eq!(lit(&draw_zero_at(64 + 10, 32 + 10)), lit(&draw_zero_at(10, 10)));
+}
+
+#[test]
+fn last_column_and_row_are_drawable() {
+    let pixels = lit(&draw_zero_at(63, 31));
+
+    assert_eq!(pixels, [(63, 31)]);
+}
@@ -0,0 +1,727 @@
+//! A corpus of tiny hand-assembled ROMs, one or a few opcodes each, that between them use
+//! every instruction the core knows: `cargo test` checks the whole interpreter without any
+//! ROM files. `corpus_covers_every_opcode` fails when an instruction is missing, so a new
+//! opcode needs a ROM here too.
+//!
+//! Most ROMs end in a jump to itself; `run` steps the core until it gets there and the
+//! tests look at the registers, memory and screen it left behind.
+
+use std::collections::BTreeSet;
+
+use chip8::coverage::{Branch, Coverage};
+use chip8::hardware::{HaltReason, State};
+use chip8::machine::Machine;
+use chip8::quirks::Quirks;
+use chip8::reference;
+
+const CLS: &[u8] = &[
+    0x60, 0x00, // V0 = 0
+    0xF0, 0x29, // I = glyph of V0
+    0xD0, 0x05, // draw it
+    0x00, 0xE0, // clear the screen
+    0x12, 0x08, // loop
+];
+
+const CALL_RET: &[u8] = &[
+    0x22, 0x06, // call 0x206
+    0x61, 0x01, // V1 = 1, after the return
+    0x12, 0x04, // loop
+    0x62, 0x02, // 0x206: V2 = 2
+    0x00, 0xEE, // return
+];
+
+const JP: &[u8] = &[
+    0x12, 0x06, // jump to 0x206
+    0x60, 0x01, // skipped over
+    0x12, 0x04, //
+    0x61, 0x02, // 0x206: V1 = 2
+    0x12, 0x08, // loop
+];
+
+// the core has no machine code to run, 0NNN jumps like 1NNN
+const SYS: &[u8] = &[
+    0x02, 0x06, // machine code at 0x206
+    0x60, 0x01, // skipped over
+    0x12, 0x04, //
+    0x61, 0x02, // 0x206: V1 = 2
+    0x12, 0x08, // loop
+];
+
+const JP_V0: &[u8] = &[
+    0x60, 0x04, // V0 = 4
+    0xB2, 0x04, // jump to 0x204 + V0
+    0x61, 0x01, // 0x204: skipped over
+    0x12, 0x06, //
+    0x62, 0x02, // 0x208: V2 = 2
+    0x12, 0x0A, // loop
+];
+
+const EXIT: &[u8] = &[
+    0x60, 0x01, // V0 = 1
+    0x00, 0xFD, // exit
+    0x61, 0x01, // never runs
+];
+
+// every skip once taken and once not, the instruction after it sets a marker register
+const SKIPS: &[u8] = &[
+    0x61, 0x05, // V1 = 5
+    0x62, 0x05, // V2 = 5
+    0x63, 0x06, // V3 = 6
+    0x31, 0x05, // SE V1, 5: skips
+    0x6A, 0x01, //
+    0x31, 0x06, // SE V1, 6: doesn't
+    0x6B, 0x01, //
+    0x41, 0x06, // SNE V1, 6: skips
+    0x6C, 0x01, //
+    0x41, 0x05, // SNE V1, 5: doesn't
+    0x6D, 0x01, //
+    0x51, 0x20, // SE V1, V2: skips
+    0x6E, 0x01, //
+    0x51, 0x30, // SE V1, V3: doesn't
+    0x64, 0x01, //
+    0x91, 0x30, // SNE V1, V3: skips
+    0x65, 0x01, //
+    0x91, 0x20, // SNE V1, V2: doesn't
+    0x66, 0x01, //
+    0x12, 0x26, // loop
+];
+
+const LD_ADD: &[u8] = &[
+    0x6F, 0x07, // VF = 7
+    0x6A, 0x42, // VA = 0x42
+    0x7A, 0x01, // VA += 1
+    0x6B, 0xFF, // VB = 0xFF
+    0x7B, 0x02, // VB += 2, wraps without touching VF
+    0x12, 0x0A, // loop
+];
+
+const LOGIC: &[u8] = &[
+    0x62, 0x0F, // V2 = 0x0F
+    0x6A, 0x3C, //
+    0x8A, 0x20, // VA = V2
+    0x6B, 0x3C, //
+    0x8B, 0x21, // VB = 0x3C | V2
+    0x6C, 0x3C, //
+    0x8C, 0x22, // VC = 0x3C & V2
+    0x6D, 0x3C, //
+    0x8D, 0x23, // VD = 0x3C ^ V2
+    0x12, 0x12, // loop
+];
+
+// each result in its own register, VF copied out right after
+const ARITHMETIC: &[u8] = &[
+    0x6A, 0xFF, //
+    0x6B, 0x02, //
+    0x8A, 0xB4, // VA = 0xFF + 2, carries
+    0x80, 0xF0, // V0 = VF
+    0x6A, 0x10, //
+    0x8A, 0xB4, // VA = 0x10 + 2
+    0x81, 0xF0, // V1 = VF
+    0x6C, 0x05, //
+    0x6D, 0x07, //
+    0x8C, 0xD5, // VC = 5 - 7, borrows
+    0x82, 0xF0, // V2 = VF
+    0x6D, 0x05, //
+    0x6E, 0x07, //
+    0x8D, 0xE7, // VD = 7 - 5
+    0x83, 0xF0, // V3 = VF
+    0x6E, 0x07, //
+    0x64, 0x05, //
+    0x8E, 0x47, // VE = 5 - 7, borrows
+    0x85, 0xF0, // V5 = VF
+    0x66, 0x07, //
+    0x67, 0x05, //
+    0x86, 0x75, // V6 = 7 - 5
+    0x88, 0xF0, // V8 = VF
+    0x12, 0x2E, // loop
+];
+
+// the core shifts VY into VX, like the COSMAC VIP
+const SHIFTS: &[u8] = &[
+    0x62, 0x05, //
+    0x81, 0x26, // V1 = 5 >> 1
+    0x83, 0xF0, // V3 = VF
+    0x62, 0x81, //
+    0x84, 0x2E, // V4 = 0x81 << 1
+    0x85, 0xF0, // V5 = VF
+    0x62, 0x40, //
+    0x86, 0x2E, // V6 = 0x40 << 1
+    0x87, 0xF0, // V7 = VF
+    0x62, 0x04, //
+    0x88, 0x26, // V8 = 4 >> 1
+    0x89, 0xF0, // V9 = VF
+    0x12, 0x18, // loop
+];
+
+const ADD_I: &[u8] = &[
+    0xA1, 0x23, // I = 0x123
+    0x60, 0x05, //
+    0xF0, 0x1E, // I += 5
+    0x12, 0x06, // loop
+];
+
+const FONTS: &[u8] = &[
+    0x61, 0x07, //
+    0xF1, 0x29, // I = small glyph of 7
+    0xF1, 0x30, // I = big glyph of 7
+    0x12, 0x06, // loop
+];
+
+// the big F, drawn at the top left
+const BIG_HEX: &[u8] = &[
+    0x60, 0x0F, //
+    0xF0, 0x30, // I = big glyph of F
+    0x61, 0x00, //
+    0xD1, 0x1A, // draw its 10 rows at 0, 0
+    0x12, 0x08, // loop
+];
+
+const RANDOM: &[u8] = &[
+    0xC0, 0x00, // V0 = random & 0
+    0xC1, 0x0F, // V1 = random & 0x0F
+    0x12, 0x04, // loop
+];
+
+// the same glyph twice: the second draw erases the first and reports the collision
+const COLLISION: &[u8] = &[
+    0x60, 0x00, //
+    0xF0, 0x29, // I = glyph of 0
+    0xD0, 0x05, // draw it at 0, 0
+    0x81, 0xF0, // V1 = VF
+    0xD0, 0x05, // draw it again
+    0x82, 0xF0, // V2 = VF
+    0x12, 0x0C, // loop
+];
+
+// run by hand, with keys pressed in between
+const KEY_SKIPS: &[u8] = &[
+    0x60, 0x05, // V0 = 5
+    0xE0, 0x9E, // skip if 5 is down
+    0x61, 0x01, //
+    0xE0, 0xA1, // skip if 5 is up
+    0x62, 0x01, //
+    0x12, 0x0A, // loop
+];
+
+const TIMERS: &[u8] = &[
+    0x60, 0x05, //
+    0xF0, 0x15, // delay timer = 5
+    0xF1, 0x07, // V1 = delay timer
+    0x62, 0x03, //
+    0xF2, 0x18, // sound timer = 3
+    0x12, 0x0A, // loop
+];
+
+// run by hand, it waits for a key
+const WAIT_KEY: &[u8] = &[
+    0xF3, 0x0A, // V3 = key
+    0x64, 0x01, // V4 = 1
+    0x12, 0x04, // loop
+];
+
+const BCD: &[u8] = &[
+    0x60, 0xFE, // V0 = 254
+    0xA3, 0x00, //
+    0xF0, 0x33, // digits at 0x300
+    0x61, 0x07, // V1 = 7
+    0xA3, 0x10, //
+    0xF1, 0x33, // digits at 0x310
+    0x12, 0x0C, // loop
+];
+
+// V2 = 2 so that I ends up at 0x303 whether it advances by X + 1 or VX + 1
+const STORE_LOAD: &[u8] = &[
+    0x60, 0x0A, //
+    0x61, 0x0B, //
+    0x62, 0x0C, //
+    0xA3, 0x00, //
+    0xF2, 0x55, // V0-V2 to 0x300
+    0x60, 0x00, //
+    0x61, 0x00, //
+    0x62, 0x00, //
+    0xA3, 0x00, //
+    0xF2, 0x65, // V0-V2 back from 0x300
+    0x12, 0x14, // loop
+];
+
+// a 16x16 sprite in the bottom right corner, only its top left quarter fits
+const HIRES: &[u8] = &[
+    0x00, 0xFF, // 128x64
+    0x60, 0x70, // V0 = 112
+    0x61, 0x38, // V1 = 56
+    0xA2, 0x0C, // I = 0x20C
+    0xD0, 0x10, // draw the big sprite
+    0x12, 0x0A, // loop
+    0xF0, 0x0F, 0xF0, 0x0F, // 0x20C: rows of 4 pixels on the left and 4 on the right
+    0xF0, 0x0F, 0xF0, 0x0F, //
+    0xF0, 0x0F, 0xF0, 0x0F, //
+    0xF0, 0x0F, 0xF0, 0x0F, // the 8 rows below the edge are zeros from RAM
+];
+
+const LORES: &[u8] = &[
+    0x00, 0xFF, // 128x64
+    0x60, 0x00, //
+    0xF0, 0x29, // I = glyph of 0
+    0xD0, 0x05, // draw it
+    0x00, 0xFE, // back to 64x32
+    0x12, 0x0A, // loop
+];
+
+// the glyph of 0 scrolled down 2 and right 4 in lores pixels
+const SCROLL: &[u8] = &[
+    0x60, 0x00, //
+    0xF0, 0x29, // I = glyph of 0
+    0xD0, 0x05, // draw it at 0/0
+    0x00, 0xC2, // down 2
+    0x00, 0xFB, // right 4
+    0x00, 0xFB, // right 4
+    0x00, 0xFC, // left 4
+    0x12, 0x0E, // loop
+];
+
+// the same in hires pixels, from the right edge
+const HIRES_SCROLL: &[u8] = &[
+    0x00, 0xFF, // 128x64
+    0x60, 0x7C, // V0 = 124
+    0x61, 0x00, //
+    0xF1, 0x29, // I = glyph of 0
+    0xD0, 0x15, // draw it at 124/0
+    0x00, 0xCF, // down 15
+    0x00, 0xFC, // left 4
+    0x12, 0x0E, // loop
+];
+
+// shifts whose result depends on the shift quirk
+const SHIFT_VX: &[u8] = &[
+    0x61, 0x05, // V1 = 5
+    0x62, 0x81, // V2 = 0x81
+    0x81, 0x26, // V1 = V2 >> 1, or V1 >> 1
+    0x83, 0xF0, // V3 = VF
+    0x82, 0x1E, // V2 = V1 << 1, or V2 << 1
+    0x12, 0x0A, // loop
+];
+
+// VF survives an OR unless the logic quirk clears it
+const LOGIC_VF: &[u8] = &[
+    0x6F, 0x05, // VF = 5
+    0x81, 0x21, // V1 |= V2
+    0x12, 0x04, // loop
+];
+
+// the glyph of 0 at the right edge, its right half past it
+const EDGE: &[u8] = &[
+    0x60, 0x3E, // V0 = 62
+    0x61, 0x00, //
+    0xF1, 0x29, // I = glyph of 0
+    0xD0, 0x15, // draw it at 62/0
+    0x12, 0x08, // loop
+];
+
+// XO-CHIP: the glyph of 0 on the second plane only, then a row on both, one byte each
+const PLANES: &[u8] = &[
+    0xF2, 0x01, // plane 2
+    0x60, 0x00, //
+    0xF0, 0x29, // I = glyph of 0
+    0xD0, 0x05, // draw it
+    0xF3, 0x01, // planes 1 and 2
+    0xA2, 0x12, // I = 0x212
+    0x61, 0x08, // V1 = 8
+    0xD1, 0x01, // draw a row at 8/0
+    0x12, 0x10, // loop
+    0xF0, 0x0F, // 0x212: 4 pixels on plane 1, the 4 after them on plane 2
+];
+
+// XO-CHIP: a skip over a long load skips all 4 bytes of it, registers to and from the upper RAM
+const LONG: &[u8] = &[
+    0x60, 0x01, // V0 = 1
+    0x30, 0x01, // SE V0, 1: skips
+    0xF0, 0x00, 0x03, 0x00, // I = 0x300
+    0xF0, 0x00, 0xE0, 0x00, // I = 0xE000
+    0x61, 0x05, // V1 = 5
+    0x62, 0x07, // V2 = 7
+    0x51, 0x22, // V1-V2 to I
+    0x52, 0x13, // V2-V1 back from I, swapping them
+    0xF0, 0x02, // audio pattern, ignored
+    0xF0, 0x3A, // pitch, ignored
+    0x12, 0x18, // loop
+];
+
+const CORPUS: [&[u8]; 30] = [
+    CLS, CALL_RET, JP, SYS, JP_V0, EXIT, SKIPS, LD_ADD, LOGIC, ARITHMETIC, SHIFTS, ADD_I, FONTS, BIG_HEX, RANDOM, COLLISION,
+    KEY_SKIPS, TIMERS, WAIT_KEY, BCD, STORE_LOAD, HIRES, LORES, SCROLL, HIRES_SCROLL, SHIFT_VX, LOGIC_VF, EDGE, PLANES,
+    LONG,
+];
+
+fn machine(rom: &[u8]) -> Machine {
+    let mut machine = Machine::new();
+    machine.load_rom(rom);
+    machine
+}
+
+// `rom` with only the quirks in `list` on
+fn quirky(rom: &[u8], list: &str) -> Machine {
+    let mut machine = machine(rom);
+    machine.chip8.quirks = Quirks::parse(list).unwrap();
+    machine
+}
+
+fn xo_machine(rom: &[u8]) -> Machine {
+    let mut machine = Machine::new();
+    machine.chip8.set_xo_chip(true);
+    machine.load_rom(rom);
+    machine
+}
+
+fn run(rom: &[u8]) -> Machine {
+    finish(machine(rom))
+}
+
+// step until the PC stays put: a jump to itself, an exit or a wait
+fn finish(mut machine: Machine) -> Machine {
+    for _ in 0..1000 {
+        let pc = machine.chip8.pc;
+        machine.chip8.cycle().unwrap();
+        if machine.chip8.pc == pc {
+            return machine;
+        }
+    }
+    panic!("the ROM never came to a stop");
+}
+
+// the instruction pattern of `opcode`, as the opcode tables write it
+fn pattern(opcode: u16) -> String {
+    let nn = opcode & 0xFF;
+    match opcode >> 12 {
+        0x0 if opcode & 0xFFF0 == 0x00C0 => "00CN".to_string(),
+        0x0 if matches!(opcode, 0x00E0 | 0x00EE | 0x00FB..=0x00FF) => format!("{:04X}", opcode),
+        0x0 | 0x1 | 0x2 | 0xA | 0xB => format!("{:X}NNN", opcode >> 12),
+        0x3 | 0x4 | 0x6 | 0x7 | 0xC => format!("{:X}XNN", opcode >> 12),
+        0x5 | 0x9 => format!("{:X}XY{:X}", opcode >> 12, opcode & 0xF),
+        0x8 => format!("8XY{:X}", opcode & 0xF),
+        0xD if opcode & 0xF == 0 => "DXY0".to_string(),
+        0xD => "DXYN".to_string(),
+        0xF if matches!(opcode, 0xF000 | 0xF002) => format!("{:04X}", opcode),
+        0xF if nn == 0x01 => "FN01".to_string(),
+        _ => format!("{:X}X{:02X}", opcode >> 12, nn),
+    }
+}
+
+#[test]
+fn corpus_covers_every_opcode() {
+    let used: BTreeSet<String> =
+        CORPUS.iter().flat_map(|rom| rom.chunks_exact(2)).map(|op| pattern(u16::from_be_bytes([op[0], op[1]]))).collect();
+    let all = [
+        "00CN", "00E0", "00EE", "00FB", "00FC", "00FD", "00FE", "00FF", "0NNN", "1NNN", "2NNN", "3XNN", "4XNN", "5XY0", "6XNN", "7XNN", "8XY0",
+        "8XY1", "8XY2", "8XY3", "8XY4", "8XY5", "8XY6", "8XY7", "8XYE", "9XY0", "ANNN", "BNNN", "CXNN", "DXYN", "DXY0",
+        "EX9E", "EXA1", "FX07", "FX0A", "FX15", "FX18", "FX1E", "FX29", "FX30", "FX33", "FX55", "FX65",
+        "5XY2", "5XY3", "F000", "FN01", "F002", "FX3A",
+    ];
+    let missing: Vec<&str> = all.iter().copied().filter(|p| !used.contains(*p)).collect();
+    assert!(missing.is_empty(), "no ROM uses {:?}", missing);
+}
+
+#[test]
+fn clear_screen() {
+    let machine = run(CLS);
+    assert_eq!(machine.chip8.get_vram().iter_set_pixels().count(), 0);
+    assert!(machine.chip8.vram_changed);
+}
+
+#[test]
+fn call_and_return() {
+    let machine = run(CALL_RET);
+    assert_eq!((machine.chip8.v[1], machine.chip8.v[2]), (1, 2));
+    assert!(machine.chip8.stack.is_empty());
+    assert_eq!(machine.chip8.pc, 0x204);
+}
+
+#[test]
+fn jumps() {
+    for rom in [JP, SYS] {
+        let machine = run(rom);
+        assert_eq!((machine.chip8.v[0], machine.chip8.v[1]), (0, 2));
+    }
+
+    let machine = run(JP_V0);
+    assert_eq!((machine.chip8.v[1], machine.chip8.v[2]), (0, 2));
+}
+
+#[test]
+fn exit() {
+    let machine = run(EXIT);
+    assert!(machine.chip8.exited());
+    assert_eq!((machine.chip8.v[0], machine.chip8.v[1]), (1, 0));
+}
+
+#[test]
+fn skips() {
+    let v = run(SKIPS).chip8.v;
+    // taken skips leave their marker at 0
+    assert_eq!([v[0xA], v[0xC], v[0xE], v[0x5]], [0; 4]);
+    assert_eq!([v[0xB], v[0xD], v[0x4], v[0x6]], [1; 4]);
+}
+
+#[test]
+fn load_and_add() {
+    let v = run(LD_ADD).chip8.v;
+    assert_eq!((v[0xA], v[0xB], v[0xF]), (0x43, 0x01, 0x07));
+}
+
+#[test]
+fn logic() {
+    let v = run(LOGIC).chip8.v;
+    assert_eq!([v[0xA], v[0xB], v[0xC], v[0xD]], [0x0F, 0x3F, 0x0C, 0x33]);
+}
+
+#[test]
+fn arithmetic() {
+    let v = run(ARITHMETIC).chip8.v;
+    // (result, VF) of 8XY4 carrying and not, 8XY5 borrowing, 8XY7 not and borrowing, 8XY5 not
+    assert_eq!((v[0xA], v[0x0], v[0x1]), (0x12, 1, 0));
+    assert_eq!((v[0xC], v[0x2]), (0xFE, 0));
+    assert_eq!((v[0xD], v[0x3]), (0x02, 1));
+    assert_eq!((v[0xE], v[0x5]), (0xFE, 0));
+    assert_eq!((v[0x6], v[0x8]), (0x02, 1));
+}
+
+#[test]
+fn shifts() {
+    let v = run(SHIFTS).chip8.v;
+    assert_eq!((v[1], v[3]), (0x02, 1));
+    assert_eq!((v[4], v[5]), (0x02, 1));
+    assert_eq!((v[6], v[7]), (0x80, 0));
+    assert_eq!((v[8], v[9]), (0x02, 0));
+    assert_eq!(v[2], 0x04);
+}
+
+#[test]
+fn index_register() {
+    assert_eq!(run(ADD_I).chip8.i, 0x128);
+
+    let mut machine = machine(FONTS);
+    machine.chip8.cycle().unwrap();
+    machine.chip8.cycle().unwrap();
+    assert_eq!(machine.chip8.i, machine.chip8.font_addr + 7 * 5);
+    let machine = run(FONTS);
+    assert_eq!(machine.chip8.i, machine.chip8.big_font_addr + 7 * 10);
+}
+
+#[test]
+fn big_hex_digit() {
+    let mut machine = machine(BIG_HEX);
+    assert_eq!(reference::lockstep(&mut machine, 1).unwrap(), None);
+    let font = machine.chip8.font_ranges()[1].clone();
+    assert_eq!(machine.chip8.i, font.start + 0xF * 10);
+    assert_eq!(machine.chip8.i + 10, font.end);
+
+    // two full rows, then the two columns of the stem
+    let vram = machine.chip8.get_vram();
+    assert!((0..8).all(|x| vram.get(x, 0) == Some(true) && vram.get(x, 1) == Some(true)));
+    assert_eq!((0..8).filter(|x| vram.get(*x, 9) == Some(true)).count(), 2);
+}
+
+#[test]
+fn random_is_masked() {
+    let mut values = BTreeSet::new();
+    for seed in 0..32 {
+        let mut machine = machine(RANDOM);
+        machine.chip8.seed(seed);
+        let v = finish(machine).chip8.v;
+        assert_eq!(v[0], 0);
+        assert!(v[1] <= 0x0F);
+        values.insert(v[1]);
+    }
+    assert!(values.len() > 1);
+}
+
+#[test]
+fn draw_collision() {
+    let machine = run(COLLISION);
+    assert_eq!((machine.chip8.v[1], machine.chip8.v[2]), (0, 1));
+    assert_eq!(machine.chip8.get_vram().iter_set_pixels().count(), 0);
+}
+
+#[test]
+fn key_skips() {
+    // V1 and V2 stay 0 where the instruction before them skipped
+    let skips = |keys: &[usize]| {
+        let mut machine = machine(KEY_SKIPS);
+        for key in keys {
+            machine.chip8.set_input(*key, true);
+        }
+        assert_eq!(reference::lockstep(&mut machine, 1).unwrap(), None);
+        assert_eq!(machine.chip8.pc, 0x20A);
+        // held keys stay down while the program reads them
+        assert!(keys.iter().all(|key| machine.chip8.keys[*key]));
+        (machine.chip8.v[1] == 0, machine.chip8.v[2] == 0)
+    };
+
+    // 5 down: EX9E skips, EXA1 doesn't
+    assert_eq!(skips(&[5]), (true, false));
+    assert_eq!(skips(&[5, 6]), (true, false));
+    // 5 up: EXA1 skips, EX9E doesn't, whether another key is down or none is
+    assert_eq!(skips(&[]), (false, true));
+    assert_eq!(skips(&[6]), (false, true));
+}
+
+#[test]
+fn timers() {
+    let machine = run(TIMERS);
+    assert_eq!(machine.chip8.v[1], 5);
+    assert_eq!((machine.chip8.tim_delay, machine.chip8.tim_snd), (5, 3));
+    assert_eq!(machine.chip8.sound_request, Some(3));
+}
+
+#[test]
+fn wait_for_key() {
+    let mut machine = run(WAIT_KEY);
+    assert_eq!(machine.chip8.state, State::Halted(HaltReason::WaitKey(3)));
+
+    // the key counts once it is released
+    machine.chip8.set_input(0xB, true);
+    machine.chip8.set_input(0xB, false);
+    assert_eq!(machine.chip8.state, State::Running);
+    machine.chip8.cycle().unwrap();
+    assert_eq!((machine.chip8.v[3], machine.chip8.v[4]), (0xB, 1));
+}
+
+#[test]
+fn decimal_digits() {
+    let machine = run(BCD);
+    let ram = machine.chip8.get_ram();
+    assert_eq!(ram[0x300..0x303], [2, 5, 4]);
+    assert_eq!(ram[0x310..0x313], [0, 0, 7]);
+}
+
+#[test]
+fn store_and_load() {
+    let machine = run(STORE_LOAD);
+    assert_eq!(machine.chip8.get_ram()[0x300..0x304], [0x0A, 0x0B, 0x0C, 0x00]);
+    assert_eq!(machine.chip8.v[..3], [0x0A, 0x0B, 0x0C]);
+    assert_eq!(machine.chip8.i, 0x303);
+}
+
+#[test]
+fn hires_big_sprite() {
+    let done = run(HIRES);
+    let vram = done.chip8.get_vram();
+    assert_eq!((vram.width(), vram.height()), (128, 64));
+    assert!(done.chip8.resolution_changed);
+    // 8 visible rows of 4 + 4 pixels, the right half 8 pixels further on
+    assert_eq!(vram.iter_set_pixels().count(), 64);
+    assert_eq!((vram.get(112, 56), vram.get(116, 56), vram.get(124, 63)), (Some(true), Some(false), Some(true)));
+    assert_eq!(done.chip8.v[0xF], 0);
+
+    // the reference interpreter draws it the same way
+    assert_eq!(reference::lockstep(&mut machine(HIRES), 2).unwrap(), None);
+}
+
+#[test]
+fn back_to_lores() {
+    let machine = run(LORES);
+    let vram = machine.chip8.get_vram();
+    assert_eq!((vram.width(), vram.height()), (64, 32));
+    assert_eq!(vram.iter_set_pixels().count(), 0);
+}
+
+#[test]
+fn scroll() {
+    let done = run(SCROLL);
+    let vram = done.chip8.get_vram();
+    assert_eq!(vram.iter_set_pixels().count(), 14);
+    assert_eq!((vram.get(4, 2), vram.get(7, 6), vram.get(0, 0)), (Some(true), Some(true), Some(false)));
+    assert!(done.chip8.vram_changed);
+
+    assert_eq!(reference::lockstep(&mut machine(SCROLL), 2).unwrap(), None);
+}
+
+#[test]
+fn hires_scroll() {
+    let done = run(HIRES_SCROLL);
+    let vram = done.chip8.get_vram();
+    assert_eq!(vram.iter_set_pixels().count(), 14);
+    assert_eq!((vram.get(120, 15), vram.get(123, 19), vram.get(124, 0)), (Some(true), Some(true), Some(false)));
+
+    assert_eq!(reference::lockstep(&mut machine(HIRES_SCROLL), 2).unwrap(), None);
+}
+
+#[test]
+fn shift_quirk() {
+    let v = run(SHIFT_VX).chip8.v;
+    assert_eq!((v[1], v[3], v[2], v[0xF]), (0x40, 1, 0x80, 0));
+    let v = finish(quirky(SHIFT_VX, "shift")).chip8.v;
+    assert_eq!((v[1], v[3], v[2], v[0xF]), (0x02, 1, 0x02, 1));
+
+    assert_eq!(reference::lockstep(&mut quirky(SHIFT_VX, "shift"), 2).unwrap(), None);
+}
+
+#[test]
+fn load_store_quirk() {
+    let done = finish(quirky(STORE_LOAD, "loadstore"));
+    assert_eq!(done.chip8.v[..3], [0x0A, 0x0B, 0x0C]);
+    assert_eq!(done.chip8.i, 0x300);
+
+    assert_eq!(reference::lockstep(&mut quirky(STORE_LOAD, "loadstore"), 2).unwrap(), None);
+}
+
+#[test]
+fn jump_quirk() {
+    // B204 adds V2, which is 0, and lands on V1 = 1
+    let done = finish(quirky(JP_V0, "jump"));
+    assert_eq!((done.chip8.v[1], done.chip8.v[2], done.chip8.pc), (1, 0, 0x206));
+
+    assert_eq!(reference::lockstep(&mut quirky(JP_V0, "jump"), 2).unwrap(), None);
+}
+
+#[test]
+fn logic_quirk() {
+    assert_eq!(run(LOGIC_VF).chip8.v[0xF], 5);
+    assert_eq!(finish(quirky(LOGIC_VF, "logic")).chip8.v[0xF], 0);
+
+    assert_eq!(reference::lockstep(&mut quirky(LOGIC_VF, "logic"), 2).unwrap(), None);
+}
+
+#[test]
+fn clip_quirk() {
+    // cut off at the edge by default, wrapped around to the left without the quirk
+    let done = run(EDGE);
+    assert_eq!(done.chip8.get_vram().iter_set_pixels().count(), 7);
+    let done = finish(quirky(EDGE, "none"));
+    assert_eq!(done.chip8.get_vram().iter_set_pixels().count(), 14);
+    assert_eq!((done.chip8.get_vram().get(0, 0), done.chip8.get_vram().get(1, 1)), (Some(true), Some(true)));
+
+    assert_eq!(reference::lockstep(&mut quirky(EDGE, "none"), 2).unwrap(), None);
+}
+
+#[test]
+fn xo_planes() {
+    let done = finish(xo_machine(PLANES));
+    assert_eq!(done.chip8.planes, 3);
+    assert_eq!(done.chip8.get_vram().iter_set_pixels().count(), 4);
+    // the 14 pixels of the 0 and the 4 from the row
+    let plane2 = done.chip8.get_plane2();
+    assert_eq!(plane2.iter_set_pixels().count(), 18);
+    assert_eq!((done.chip8.get_vram().get(8, 0), plane2.get(8, 0), plane2.get(12, 0)), (Some(true), Some(false), Some(true)));
+    assert_eq!(done.chip8.v[0xF], 0);
+
+    assert_eq!(reference::lockstep(&mut xo_machine(PLANES), 2).unwrap(), None);
+}
+
+#[test]
+fn xo_long_load() {
+    let done = finish(xo_machine(LONG));
+    assert_eq!(done.chip8.pc, 0x218);
+    assert_eq!(done.chip8.i, 0xE000);
+    assert_eq!(&done.chip8.get_ram()[0xE000..0xE002], &[5, 7]);
+    assert_eq!((done.chip8.v[1], done.chip8.v[2]), (7, 5));
+
+    assert_eq!(reference::lockstep(&mut xo_machine(LONG), 2).unwrap(), None);
+
+    // coverage sees the skip over all 4 bytes as taken
+    let mut covered = xo_machine(LONG);
+    covered.coverage = Some(Coverage::new(covered.chip8.get_ram().len()));
+    covered.run_frame().unwrap();
+    let coverage = covered.coverage.as_ref().unwrap();
+    assert_eq!(coverage.branches[&0x202], Branch { taken: 1, not_taken: 0 });
+    assert_eq!((coverage.hits(0x204), coverage.hits(0x208)), (0, 1));
+}
@@ -0,0 +1,246 @@
+//! Golden-image tests: each program runs for a fixed number of frames with a fixed seed and
+//! scripted input, and its screen has to match the checksum recorded when it was known to
+//! be right. A changed checksum means the screen changed, look at it before updating.
+//!
+//! The programs are small title-screen-like ROMs written for these tests. Well-known ROMs
+//! can be checked too where they are available: set `CHIP8_SNAPSHOT_DIR` to a directory
+//! with the ROM files and a `snapshots.txt` listing `<file> <frames> <crc32>` per line.
+
+use std::fs;
+
+use chip8::machine::Machine;
+use chip8::macros::InputEvent;
+
+const SEED: u64 = 0x5eed;
+
+fn snapshot(rom: &[u8], frames: u64, input: &[InputEvent]) -> u32 {
+    let mut machine = Machine::new();
+    machine.chip8.seed(SEED);
+    machine.load_rom(rom);
+    machine.run_frames(frames, input).unwrap()
+}
+
+// loop forever
+const BLANK: &[u8] = &[0x12, 0x00];
+
+#[test]
+fn blank_screen() {
+    assert_eq!(snapshot(BLANK, 10, &[]), 0x61a0_9bb4);
+}
+
+#[test]
+fn font_glyph() {
+    // V1 = 0, I = glyph of V1, draw it at the top left, loop
+    let rom = [0x61, 0x00, 0xF1, 0x29, 0xD2, 0x25, 0x12, 0x06];
+    assert_eq!(snapshot(&rom, 10, &[]), 0xd912_2aed);
+}
+
+#[test]
+fn hex_digits_in_a_row() {
+    let rom = [
+        0x60, 0x00, // V0 = digit
+        0x61, 0x00, // V1 = x
+        0xF0, 0x29, // I = glyph of V0
+        0xD1, 0x25, // draw at V1, V2 = 0
+        0x70, 0x01, // next digit
+        0x71, 0x04, // 4 pixels further
+        0x30, 0x10, // done after F
+        0x12, 0x04, //
+        0x12, 0x10, // loop
+    ];
+    assert_eq!(snapshot(&rom, 30, &[]), 0x325d_5fac);
+}
+
+#[test]
+fn checkerboard() {
+    let rom = [
+        0xA2, 0x20, // I = tile
+        0x60, 0x00, // V0 = x
+        0x61, 0x00, // V1 = y
+        0xD0, 0x18, // draw the tile
+        0x70, 0x08, // next column
+        0x30, 0x40, //
+        0x12, 0x06, //
+        0x60, 0x00, // next row
+        0x71, 0x08, //
+        0x31, 0x20, //
+        0x12, 0x06, //
+        0x12, 0x16, // loop
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // padding up to 0x220
+        0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, // tile
+    ];
+    assert_eq!(snapshot(&rom, 60, &[]), 0x2eb3_3cb6);
+}
+
+#[test]
+fn border() {
+    let rom = [
+        0xA2, 0x30, // I = horizontal line
+        0x60, 0x00, // V0 = x
+        0x61, 0x00, // V1 = top
+        0x62, 0x1F, // V2 = bottom
+        0xD0, 0x11, // top edge
+        0xD0, 0x21, // bottom edge
+        0x70, 0x08, // next 8 pixels
+        0x30, 0x40, //
+        0x12, 0x08, //
+        0xA2, 0x31, // I = left edge
+        0x60, 0x00, //
+        0x61, 0x01, //
+        0xD0, 0x1F, // rows 1-15
+        0x61, 0x10, //
+        0xD0, 0x1F, // rows 16-30
+        0xA2, 0x40, // I = right edge
+        0x60, 0x38, //
+        0x61, 0x01, //
+        0xD0, 0x1F, //
+        0x61, 0x10, //
+        0xD0, 0x1F, //
+        0x12, 0x2A, // loop
+        0x00, 0x00, 0x00, 0x00, // padding up to 0x230
+        0xFF, // horizontal line
+        0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80,
+        0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01,
+    ];
+    assert_eq!(snapshot(&rom, 30, &[]), 0xf698_db6a);
+}
+
+// 64 dots at random places
+const STARFIELD: &[u8] = &[
+    0xA2, 0x10, // I = dot
+    0xC0, 0x3F, // V0 = random x
+    0xC1, 0x1F, // V1 = random y
+    0xD0, 0x11, // draw it
+    0x72, 0x01, // count it
+    0x32, 0x40, //
+    0x12, 0x02, //
+    0x12, 0x0E, // loop
+    0x80, // dot
+];
+
+#[test]
+fn random_starfield() {
+    assert_eq!(snapshot(STARFIELD, 60, &[]), 0x0299_830b);
+}
+
+#[test]
+fn random_starfield_depends_on_the_seed() {
+    let mut machine = Machine::new();
+    machine.chip8.seed(SEED + 1);
+    machine.load_rom(STARFIELD);
+    assert_ne!(machine.run_frames(60, &[]).unwrap(), snapshot(STARFIELD, 60, &[]));
+}
+
+// wait for a key, then show its hex digit
+const PRESS_ANY_KEY: &[u8] = &[0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06];
+
+#[test]
+fn waits_for_a_key() {
+    assert_eq!(snapshot(PRESS_ANY_KEY, 10, &[]), snapshot(BLANK, 10, &[]));
+}
+
+#[test]
+fn shows_the_key_pressed() {
+    let input = [InputEvent { frame: 2, key: 0xA, down: true }, InputEvent { frame: 4, key: 0xA, down: false }];
+    assert_eq!(snapshot(PRESS_ANY_KEY, 10, &input), 0x048d_abf8);
+}
+
+// wait half a second on the delay timer, then show an 8
+const DELAYED: &[u8] = &[
+    0x60, 0x1E, // V0 = 30 frames
+    0xF0, 0x15, // DT = V0
+    0xF0, 0x07, // V0 = DT
+    0x30, 0x00, // until it ran out
+    0x12, 0x04, //
+    0x61, 0x08, //
+    0xF1, 0x29, // I = glyph of 8
+    0xD2, 0x25, //
+    0x12, 0x10, // loop
+];
+
+#[test]
+fn delay_timer_not_run_out() {
+    assert_eq!(snapshot(DELAYED, 20, &[]), snapshot(BLANK, 20, &[]));
+}
+
+#[test]
+fn delay_timer_run_out() {
+    assert_eq!(snapshot(DELAYED, 40, &[]), 0xca10_280d);
+}
+
+#[test]
+fn title_text() {
+    let rom = [
+        0x60, 0x12, // V0 = x
+        0x61, 0x0D, // V1 = y
+        0xA2, 0x30, 0xD0, 0x15, 0x70, 0x06, // C
+        0xA2, 0x35, 0xD0, 0x15, 0x70, 0x06, // H
+        0xA2, 0x3A, 0xD0, 0x15, 0x70, 0x06, // I
+        0xA2, 0x3F, 0xD0, 0x15, 0x70, 0x06, // P
+        0x62, 0x08, 0xF2, 0x29, 0xD0, 0x15, // 8 from the font
+        0x12, 0x22, // loop
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // padding up to 0x230
+        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+        0x90, 0x90, 0xF0, 0x90, 0x90, // H
+        0xE0, 0x40, 0x40, 0x40, 0xE0, // I
+        0xF0, 0x90, 0xF0, 0x80, 0x80, // P
+    ];
+    assert_eq!(snapshot(&rom, 30, &[]), 0x891c_cae0);
+}
+
+#[test]
+fn cleared_screen() {
+    // draw a glyph, then clear the screen
+    let rom = [0xF1, 0x29, 0xD2, 0x25, 0x00, 0xE0, 0x12, 0x06];
+    assert_eq!(snapshot(&rom, 10, &[]), snapshot(BLANK, 10, &[]));
+}
+
+#[test]
+fn digits_from_a_subroutine() {
+    let rom = [
+        0x60, 0x01, // V0 = 1
+        0x22, 0x0A, // draw it
+        0x60, 0x02, // V0 = 2
+        0x22, 0x0A, // draw it
+        0x12, 0x08, // loop
+        0xF0, 0x29, // I = glyph of V0
+        0xD1, 0x25, // draw at V1, V2
+        0x71, 0x05, // move right
+        0x00, 0xEE, // return
+    ];
+    assert_eq!(snapshot(&rom, 10, &[]), 0x350e_6d3f);
+}
+
+#[test]
+fn decimal_score() {
+    let rom = [
+        0x63, 0x7B, // V3 = 123
+        0xA3, 0x00, // I = 0x300
+        0xF3, 0x33, // its decimal digits at I
+        0xF2, 0x65, // V0-V2 = the digits
+        0x63, 0x00, // V3 = x
+        0xF0, 0x29, 0xD3, 0x45, 0x73, 0x05, // hundreds
+        0xF1, 0x29, 0xD3, 0x45, 0x73, 0x05, // tens
+        0xF2, 0x29, 0xD3, 0x45, // ones
+        0x12, 0x1A, // loop
+    ];
+    assert_eq!(snapshot(&rom, 10, &[]), 0x2207_8934);
+}
+
+#[test]
+fn rom_corpus() {
+    let Ok(dir) = std::env::var("CHIP8_SNAPSHOT_DIR") else {
+        return;
+    };
+    let list = fs::read_to_string(format!("{}/snapshots.txt", dir)).unwrap();
+
+    for line in list.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#')) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [file, frames, crc] = fields[..] else {
+            panic!("expected '<file> <frames> <crc32>', got '{}'", line);
+        };
+        let rom = fs::read(format!("{}/{}", dir, file)).unwrap();
+        let expected = u32::from_str_radix(crc, 16).unwrap();
+        assert_eq!(snapshot(&rom, frames.parse().unwrap(), &[]), expected, "{} after {} frames", file, frames);
+    }
+}
@@ -0,0 +1,162 @@
+//! Frame accuracy of the timers: beeps must start and stop within one frame of where the
+//! ROM asks for them, and line up with the drawing they accompany.
+
+use chip8::coverage::Coverage;
+use chip8::machine::{Machine, CYCLES_PER_FRAME};
+
+// allowed error, in frames
+const TOLERANCE: i64 = 1;
+
+fn machine(rom: &[u8]) -> Machine {
+    let mut machine = Machine::new();
+    machine.load_rom(rom);
+    machine
+}
+
+/**
+ * Run `frames` frames and collect (first frame, length in frames) of every beep, plus
+ * the frames in which the screen was drawn to. A frame counts as beeping if the sound
+ * timer was running at any point during it.
+ */
+fn run(machine: &mut Machine, frames: u64) -> (Vec<(i64, i64)>, Vec<i64>) {
+    let mut beeps = Vec::new();
+    let mut draws = Vec::new();
+    let mut beep_start: Option<i64> = None;
+
+    for frame in 0..frames as i64 {
+        let mut beeping = false;
+        loop {
+            let frame_done = machine.step().unwrap();
+            beeping |= machine.chip8.tim_snd > 0;
+            if frame_done {
+                break;
+            }
+        }
+
+        if machine.chip8.vram_changed {
+            draws.push(frame);
+            machine.chip8.vram_changed = false;
+        }
+
+        match (beeping, beep_start) {
+            (true, None) => beep_start = Some(frame),
+            (false, Some(start)) => {
+                beeps.push((start, frame - start));
+                beep_start = None;
+            }
+            _ => {}
+        }
+    }
+
+    (beeps, draws)
+}
+
+fn assert_near(what: &str, actual: i64, expected: i64) {
+    assert!((actual - expected).abs() <= TOLERANCE, "{}: got frame {}, expected {} ±{}", what, actual, expected, TOLERANCE);
+}
+
+#[test]
+fn metronome_beeps_every_30_frames() {
+    let mut machine = machine(&[
+        0x60, 0x04, // V0 = 4
+        0x61, 0x1E, // V1 = 30
+        0xF0, 0x18, // ST = V0
+        0xF1, 0x15, // DT = V1
+        0xF2, 0x07, // V2 = DT
+        0x32, 0x00, // skip if V2 == 0
+        0x12, 0x08, // wait for DT
+        0x12, 0x04, // next tick
+    ]);
+
+    let (beeps, _) = run(&mut machine, 125);
+
+    assert_eq!(beeps.len(), 5, "beeps: {:?}", beeps);
+    for (n, (start, len)) in beeps.iter().enumerate() {
+        assert_near(&format!("beep {} start", n), *start, n as i64 * 30);
+        assert_near(&format!("beep {} length", n), *len, 4);
+    }
+}
+
+#[test]
+fn beep_lines_up_with_draw() {
+    let mut machine = machine(&[
+        0x60, 0x3C, // V0 = 60
+        0xF0, 0x15, // DT = V0
+        0xF1, 0x07, // V1 = DT
+        0x31, 0x00, // skip if V1 == 0
+        0x12, 0x04, // wait for DT
+        0x62, 0x06, // V2 = 6
+        0xF2, 0x18, // ST = V2
+        0x63, 0x00, // V3 = 0
+        0xF3, 0x29, // I = glyph "0"
+        0xD3, 0x35, // draw it
+        0x12, 0x00, // again
+    ]);
+
+    let (beeps, draws) = run(&mut machine, 200);
+
+    assert_eq!(beeps.len(), 3, "beeps: {:?}", beeps);
+    assert_eq!(draws.len(), 3, "draws: {:?}", draws);
+    for ((start, len), draw) in beeps.iter().zip(&draws) {
+        assert_near("beep vs draw", *start, *draw);
+        assert_near("beep length", *len, 6);
+    }
+    assert_near("first draw", draws[0], 60);
+}
+
+#[test]
+fn short_beep_is_not_lost() {
+    let mut machine = machine(&[
+        0x60, 0x01, // V0 = 1
+        0xF0, 0x18, // ST = V0
+        0x12, 0x04, // loop forever
+    ]);
+
+    let (beeps, _) = run(&mut machine, 10);
+
+    assert_eq!(beeps.len(), 1, "beeps: {:?}", beeps);
+    assert_near("beep start", beeps[0].0, 0);
+}
+
+#[test]
+fn vblank_draws_once_per_frame() {
+    let rom = [
+        0x70, 0x01, // V0 += 1
+        0xD1, 0x15, // draw
+        0xD1, 0x15, // and erase
+        0x12, 0x00, // again
+    ];
+
+    // two turns of the loop fit in the 8 cycles of a frame
+    let mut free = machine(&rom);
+    run(&mut free, 60);
+    assert_eq!(free.draws(), 4 * 60);
+    assert_eq!(free.chip8.v[0], 2 * 60);
+
+    let mut waiting = machine(&rom);
+    waiting.chip8.quirks.vblank = true;
+    run(&mut waiting, 60);
+    assert_eq!(waiting.draws(), 60);
+    assert_eq!(waiting.chip8.v[0], 30);
+}
+
+#[test]
+fn waiting_for_a_key_counts_nothing() {
+    let rom = [
+        0xF0, 0x0A, // V0 = key
+        0xD0, 0x05, // draw
+        0x12, 0x02, // and again
+    ];
+
+    // the draw after the wait is never reached, it must not look like it ran either
+    let mut waiting = machine(&rom);
+    waiting.coverage = Some(Coverage::new(waiting.chip8.get_ram().len()));
+    waiting.draw_budget = Some(1);
+    run(&mut waiting, 10);
+    assert_eq!(waiting.frame(), 10);
+    assert_eq!(waiting.cycles(), 10 * CYCLES_PER_FRAME as u64);
+    assert_eq!(waiting.draws(), 0);
+    assert_eq!(waiting.history.len(), 1);
+    assert_eq!(waiting.coverage.as_ref().unwrap().hits(0x202), 0);
+}
+